@@ -211,8 +211,16 @@ fn main() {
         PeerSocket::Connect(remote_node) => {
             debug!("Peerd running in CONNECT mode");
             debug!("Connecting to {}", &remote_node.addr());
-            peerd::run_from_connect(service_config, remote_node, local_socket, local_node)
-                .expect("Error running peerd runtime");
+            peerd::run_from_connect(
+                service_config,
+                remote_node,
+                local_socket,
+                local_node,
+                opts.local_init(),
+                opts.connect_timeout(),
+                opts.reconnect_backoff(),
+            )
+            .expect("Error running peerd runtime");
             unreachable!()
         }
     };
@@ -228,14 +236,18 @@ fn main() {
         remote_node_addr: full internet2 remote node address
         local_socket: None
         connect: true */
-    peerd::run_from_listener(
+    // the forked process serves this connection only, it exits on failure
+    if let Err(err) = peerd::run_from_listener(
         service_config,
         connection,
         remote_node_addr,
         local_socket,
         local_node,
-    )
-    .expect("Error running peerd runtime");
+        opts.local_init(),
+    ) {
+        error!("peerd stopped: {}", err);
+        std::process::exit(1);
+    }
 
     unreachable!()
 }
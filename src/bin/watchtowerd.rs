@@ -0,0 +1,43 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+#![recursion_limit = "256"]
+// Coding conventions
+#![deny(
+    non_upper_case_globals,
+    non_camel_case_types,
+    non_snake_case,
+    unused_mut,
+    unused_imports,
+    dead_code,
+    missing_docs
+)]
+
+//! Main executable for watchtowerd: standalone Farcaster swap watchtower.
+
+#[macro_use]
+extern crate log;
+
+use clap::Parser;
+
+use farcaster_node::watchtowerd::{self, Opts};
+use farcaster_node::ServiceConfig;
+
+fn main() {
+    let mut opts = Opts::parse();
+    trace!("Command-line arguments: {:?}", &opts);
+    opts.process();
+    trace!("Processed arguments: {:?}", &opts);
+
+    let service_config: ServiceConfig = opts.shared.clone().into();
+    trace!("Daemon configuration: {:#?}", &service_config);
+    debug!("MSG RPC socket {}", &service_config.msg_endpoint);
+    debug!("CTL RPC socket {}", &service_config.ctl_endpoint);
+
+    debug!("Starting runtime ...");
+    watchtowerd::run(service_config, opts).expect("Error running watchtowerd runtime");
+    unreachable!()
+}
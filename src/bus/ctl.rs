@@ -16,6 +16,8 @@ use farcaster_core::{
     blockchain::Blockchain,
     swap::btcxmr::{Deal, DealParameters, Parameters},
     swap::SwapId,
+    transaction::TxLabel,
+    Uuid,
 };
 
 use bitcoin::secp256k1::SecretKey;
@@ -25,10 +27,12 @@ use strict_encoding::{NetworkDecode, NetworkEncode};
 
 use crate::bus::p2p::{PeerMsg, TakerCommit};
 use crate::bus::{
-    AddressSecretKey, CheckpointEntry, DealStatusPair, Failure, OptionDetails, Outcome, Progress,
+    AddressSecretKey, AutoFundingRecord, ChatRecord, CheckpointEntry, DealExpiry, DealRange,
+    DealStatusPair, Failure, MakerAddresses, NodeEvent, OpenDeal, OptionDetails, Outcome,
+    OutcomeRecord, Progress, QueuedSweep, WebhookDelivery,
 };
 use crate::swapd::CheckpointSwapd;
-use crate::syncerd::{Health, SweepAddressAddendum};
+use crate::syncerd::{Health, SweepAddressAddendum, SweepDestination};
 use crate::{Error, ServiceId};
 
 use super::p2p::Commit;
@@ -75,7 +79,7 @@ pub enum CtlMsg {
     Params(Params),
 
     #[display("peerd_unreachable({0})")]
-    PeerdUnreachable(ServiceId),
+    PeerdUnreachable(PeerdUnreachable),
 
     #[display("peerd_reconnected({0})")]
     PeerdReconnected(ServiceId),
@@ -128,8 +132,8 @@ pub enum CtlMsg {
     #[from]
     SweepAddress(SweepAddressAddendum),
 
-    #[display("set_address_secret_key")]
-    SetAddressSecretKey(AddressSecretKey),
+    #[display("set_address_secret_key({1})")]
+    SetAddressSecretKey(AddressSecretKey, Network),
 
     #[display("get_balance")]
     GetBalance(AddressSecretKey),
@@ -173,14 +177,159 @@ pub enum CtlMsg {
     #[display("failed peer message")]
     FailedPeerMessage(PeerMsg),
 
-    #[display("connect failed")]
-    ConnectFailed,
+    /// A message sent from swapd to its peerd once the swap no longer needs
+    /// messages from the counterparty, peerd stops reconnecting when none of
+    /// its swaps need the counterparty anymore.
+    #[display("release_peer({0})")]
+    ReleasePeer(SwapId),
+
+    /// Request from a client to swapd to send a chat message to the
+    /// counterparty of the swap
+    #[display("send_chat_message(..)")]
+    SendChatMessage(String),
+
+    #[display("set_chat_record({0})")]
+    SetChatRecord(ChatRecord),
+
+    #[display("set_auto_funding_record({0})")]
+    SetAutoFundingRecord(AutoFundingRecord),
+
+    #[display("set_outcome_record({0})")]
+    SetOutcomeRecord(OutcomeRecord),
+
+    /// Sent by the client to swapd with a base64 psbt funding the swap, signed
+    /// by an external wallet
+    #[display("import_funding_psbt(..)")]
+    ImportFundingPsbt(String),
+
+    #[display("connect_failed({0})")]
+    ConnectFailed(String),
 
     #[display("health_check({0} {1})")]
     HealthCheck(Blockchain, Network),
 
     #[display("health_result({0})")]
     HealthResult(Health),
+
+    /// Request from a client to farcasterd to simulate a swap of the deal
+    /// parameters with the current fee estimates, without publishing a deal
+    #[display("simulate({0})")]
+    Simulate(DealParameters),
+
+    /// Request from a client to swapd to broadcast one of its pre-signed
+    /// transactions, overriding the automatic logic of the swap
+    #[display("force_broadcast({0})")]
+    ForceBroadcast(ForceBroadcast),
+
+    /// Sent by swapd to farcasterd, and forwarded to databased, to queue the
+    /// bitcoin sweep of the swap for the next batch sweep
+    #[display("queue_sweep({0})")]
+    QueueSweep(QueuedSweep),
+
+    /// Triggers farcasterd to sweep the queued bitcoin sweeps in a batch
+    #[display("batch_sweep()")]
+    BatchSweep,
+
+    /// Sent by farcasterd to databased to remove the queued sweeps of the
+    /// source addresses once they are swept
+    #[display("remove_queued_sweeps(..)")]
+    RemoveQueuedSweeps(Vec<bitcoin::Address>),
+
+    /// Triggers the market maker to re-create, revoke and re-price its deals
+    #[display("market_maker_tick()")]
+    MarketMakerTick,
+
+    /// Sets the market price used by the market maker, in XMR for one BTC
+    #[display("set_market_price({0})")]
+    SetMarketPrice(monero::Amount),
+
+    /// Sent by farcasterd to databased to persist a made deal until it is
+    /// taken, revoked or expires
+    #[display("set_open_deal({0})")]
+    SetOpenDeal(OpenDeal),
+
+    /// Sent by farcasterd to databased once a made deal is no longer open
+    #[display("remove_open_deal({0})")]
+    RemoveOpenDeal(Deal),
+
+    /// Sent by farcasterd to databased to persist the addresses of the maker
+    /// of a taken deal
+    #[display("set_maker_addresses({0})")]
+    SetMakerAddresses(MakerAddresses),
+
+    /// Triggers farcasterd to revoke the expired deals
+    #[display("expire_deals()")]
+    ExpireDeals,
+
+    /// Triggers farcasterd to check its launched services and restart or
+    /// restore the crashed ones
+    #[display("supervise_services()")]
+    SuperviseServices,
+
+    /// Triggers swapd to check whether the swap is stalled before the
+    /// arbitrating lock for longer than the timeout of its state
+    #[display("check_pre_lock_timeout()")]
+    CheckPreLockTimeout,
+
+    /// Sent by farcasterd to itself to retrieve the block height of the
+    /// arbitrating blockchain the deals may expire at
+    #[display("get_block_height({0})")]
+    GetBlockHeight(Network),
+
+    /// Sent by farcasterd to databased to persist a webhook notification until
+    /// it is delivered
+    #[display("queue_webhook_delivery({0})")]
+    QueueWebhookDelivery(WebhookDelivery),
+
+    /// Sent over the bridge once a webhook notification is delivered or its
+    /// attempts are exhausted, forwarded by farcasterd to databased
+    #[display("remove_webhook_delivery({0})")]
+    RemoveWebhookDelivery(Uuid),
+
+    /// Sent to databased to append an event to the event log of the node
+    #[display("log_event({0})")]
+    LogEvent(NodeEvent),
+
+    /// A message sent from a client to farcasterd to checkpoint the running
+    /// swaps and terminate the node, even if a critical action of a swap is
+    /// imminent when forced
+    #[display("shutdown(force: {0})")]
+    Shutdown(bool),
+
+    /// Sent by farcasterd to swapd on shutdown to checkpoint its current state
+    #[display("checkpoint_swap()")]
+    CheckpointSwap,
+
+    /// Sent by swapd to farcasterd once its state is checkpointed on shutdown
+    #[display("swap_checkpointed()")]
+    SwapCheckpointed,
+
+    /// Sent by farcasterd to swapd to receive its current state report, when
+    /// auto-funding waits for the swap state
+    #[display("report_state()")]
+    ReportState,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Display, NetworkEncode, NetworkDecode)]
+#[display("{label}, confirm: {confirm}")]
+pub struct ForceBroadcast {
+    pub label: TxLabel,
+    /// Without confirmation swapd only reports what would be broadcasted and
+    /// the safety warnings
+    pub confirm: bool,
+}
+
+/// Parse the label of a transaction that can be force-broadcasted
+pub fn parse_broadcast_label(s: &str) -> Result<TxLabel, Error> {
+    match s {
+        "cancel" | "Cancel" => Ok(TxLabel::Cancel),
+        "refund" | "Refund" => Ok(TxLabel::Refund),
+        "punish" | "Punish" => Ok(TxLabel::Punish),
+        _ => Err(Error::Farcaster(format!(
+            "Unknown transaction {}, expected cancel, refund, or punish",
+            s
+        ))),
+    }
 }
 
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -196,8 +345,19 @@ pub enum ProgressStack {
 pub struct ProtoDeal {
     pub deal_parameters: DealParameters,
     pub public_addr: InetSocketAddr,
+    /// Additional addresses the maker is reachable on, advertised along the
+    /// deal
+    pub alternate_addrs: Vec<InetSocketAddr>,
     pub arbitrating_addr: bitcoin::Address,
     pub accordant_addr: monero::Address,
+    /// Destinations the swept Monero is split between besides
+    /// `accordant_addr`, the node's default is used if empty
+    pub accordant_split: Vec<SweepDestination>,
+    /// Once passed the deal is revoked, it never expires if None
+    pub expiry: Option<DealExpiry>,
+    /// Amounts takers can take from the deal if it is a range deal, the deal
+    /// is taken as a whole if None
+    pub range: Option<DealRange>,
 }
 
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -206,6 +366,12 @@ pub struct PubDeal {
     pub deal: Deal,
     pub bitcoin_address: bitcoin::Address,
     pub monero_address: monero::Address,
+    /// Destinations the swept Monero is split between besides
+    /// `monero_address`, the node's default is used if empty
+    pub monero_split: Vec<SweepDestination>,
+    /// Addresses advertised by the maker besides the deal's one, tried in
+    /// order if connecting to the deal's address fails
+    pub alternate_addrs: Vec<InetSocketAddr>,
 }
 
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -258,6 +424,7 @@ pub struct InitTakerSwap {
     pub key_manager: WrappedKeyManager,
     pub target_bitcoin_address: bitcoin::Address,
     pub target_monero_address: monero::Address,
+    pub target_monero_split: Vec<SweepDestination>,
 }
 
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -269,6 +436,7 @@ pub struct InitMakerSwap {
     pub key_manager: WrappedKeyManager,
     pub target_bitcoin_address: bitcoin::Address,
     pub target_monero_address: monero::Address,
+    pub target_monero_split: Vec<SweepDestination>,
     pub commit: Commit,
 }
 
@@ -406,6 +574,13 @@ pub enum Tx {
     Punish(Transaction),
 }
 
+#[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
+#[display("{peerd}, {reason}")]
+pub struct PeerdUnreachable {
+    pub peerd: ServiceId,
+    pub reason: String,
+}
+
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
 #[display("taker_commited")]
 pub struct TakerCommitted {
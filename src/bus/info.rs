@@ -4,25 +4,35 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::time::Duration;
 
 use amplify::ToYamlString;
 use farcaster_core::role::{SwapRole, TradeRole};
 use farcaster_core::trade::DealId;
-use farcaster_core::{blockchain::Blockchain, swap::btcxmr::Deal, swap::SwapId};
+use farcaster_core::transaction::TxLabel;
+use farcaster_core::{
+    blockchain::{Blockchain, Network},
+    swap::btcxmr::Deal,
+    swap::SwapId,
+};
 use internet2::addr::{InetSocketAddr, NodeAddr, NodeId};
 #[cfg(feature = "serde")]
 use serde_with::{DisplayFromStr, DurationSeconds};
 use strict_encoding::{NetworkDecode, NetworkEncode};
 
 use crate::bus::{
-    AddressSecretKey, CheckpointEntry, DealStatusPair, Failure, List, OptionDetails, Progress,
+    AddressSecretKey, AutoFundingRecord, ChatRecord, CheckpointEntry, DealStatusPair, EventFilter,
+    Failure, List, MakerAddresses, NodeEvent, OpenDeal, OptionDetails, OutcomeRecord, Progress,
+    StateTimestamp, WebhookDelivery,
 };
+#[cfg(feature = "cli")]
 use crate::cli::DealSelector;
 use crate::farcasterd::stats::Stats;
 use crate::swapd::StateReport;
 use crate::syncerd::runtime::SyncerdTask;
+use crate::syncerd::SweepBitcoinBatch;
 use crate::Error;
 
 use super::ctl::FundingInfo;
@@ -40,26 +50,26 @@ pub enum InfoMsg {
     #[display("list_peers()")]
     ListPeers,
 
-    #[display("list_swaps()")]
-    ListSwaps,
+    #[display("list_swaps({0:?})")]
+    ListSwaps(Option<Network>),
 
     #[display("list_tasks()")]
     ListTasks,
 
-    #[display("list_deals({0})")]
-    ListDeals(DealStatusSelector),
+    #[display("list_deals({0}, {1:?})")]
+    ListDeals(DealStatusSelector, Option<Network>),
 
     #[display("list_listens()")]
     ListListens,
 
-    #[display("retrieve_all_checkpoint_info")]
-    RetrieveAllCheckpointInfo,
+    #[display("retrieve_all_checkpoint_info({0:?})")]
+    RetrieveAllCheckpointInfo(Option<Network>),
 
     #[display("get_address_secret_key({0})")]
     GetAddressSecretKey(Address),
 
-    #[display("get_addresses({0})")]
-    GetAddresses(Blockchain),
+    #[display("get_addresses({0}, {1:?})")]
+    GetAddresses(Blockchain, Option<Network>),
 
     #[display("needs_funding({0})")]
     NeedsFunding(Blockchain),
@@ -67,6 +77,48 @@ pub enum InfoMsg {
     #[display("get_checkpoint_entry({0})")]
     GetCheckpointEntry(SwapId),
 
+    #[display("get_chat_messages({0})")]
+    GetChatMessages(SwapId),
+
+    #[display("get_funding_psbt()")]
+    GetFundingPsbt,
+
+    #[display("get_auto_funding_records()")]
+    GetAutoFundingRecords,
+
+    #[display("get_watchtower_package({0})")]
+    GetWatchtowerPackage(SwapId),
+
+    #[display("get_outcome_record({0})")]
+    GetOutcomeRecord(SwapId),
+
+    #[display("get_swap_txs()")]
+    GetSwapTxs,
+
+    /// Request from farcasterd to databased for the queued bitcoin sweeps
+    /// along with the secret keys of their source addresses
+    #[display("get_sweep_batch()")]
+    GetSweepBatch,
+
+    /// Request from farcasterd to databased for the persisted open deals
+    #[display("retrieve_open_deals()")]
+    RetrieveOpenDeals,
+
+    /// Request from farcasterd to databased for the persisted addresses of
+    /// the makers of the taken deals
+    #[display("retrieve_maker_addresses()")]
+    RetrieveMakerAddresses,
+
+    /// Request from farcasterd to databased for the webhook notifications
+    /// pending delivery
+    #[display("get_webhook_deliveries()")]
+    GetWebhookDeliveries,
+
+    /// Request from a client to databased for the events of the event log
+    /// selected by the filter
+    #[display("get_events({0})")]
+    GetEvents(EventFilter),
+
     // Progress functionalities
     // ----------------
     // Returns a SwapProgress message
@@ -174,11 +226,47 @@ pub enum InfoMsg {
     #[display("checkpoint_entry({0})")]
     CheckpointEntry(CheckpointEntry),
     // - End GetCheckpointEntry section
+    #[display(inner)]
+    ChatMessageList(List<ChatRecord>),
+
+    #[display(inner)]
+    FundingPsbt(String),
+
+    #[display(inner)]
+    AutoFundingRecordList(List<AutoFundingRecord>),
+
+    #[display("{0}")]
+    SweepBatch(SweepBitcoinBatch),
+
+    #[display("open_deals(..)")]
+    OpenDeals(Vec<OpenDeal>),
+
+    #[display("maker_addresses(..)")]
+    MakerAddressesList(Vec<MakerAddresses>),
+
+    #[display("webhook_deliveries(..)")]
+    WebhookDeliveries(Vec<WebhookDelivery>),
+
+    #[display(inner)]
+    EventList(List<NodeEvent>),
+
+    #[display(inner)]
+    WatchtowerPackage(String),
+
+    #[display(inner)]
+    OutcomeRecord(OutcomeRecord),
+
     #[display("{0}")]
     FundingInfos(FundingInfos),
 
     #[display("{0}")]
     AddressBalance(AddressBalance),
+
+    #[display(inner)]
+    SimulationReport(SimulationReport),
+
+    #[display(inner)]
+    SwapTxList(List<SwapTx>),
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -216,6 +304,8 @@ pub struct MoneroAddressSwapIdPair {
 pub struct MadeDeal {
     pub message: String,
     pub deal_info: DealInfo,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub alternate_addrs: Vec<InetSocketAddr>,
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -267,6 +357,8 @@ pub struct NodeInfo {
     pub deals: Vec<Deal>,
     #[serde(alias = "statistics")]
     pub stats: Stats,
+    /// Number of crashes of the launched services, by service
+    pub crashes: BTreeMap<String, u64>,
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -291,6 +383,8 @@ pub struct PeerInfo {
     pub messages_received: usize,
     pub forked_from_listener: bool,
     pub awaits_pong: bool,
+    pub swap_channels: usize,
+    pub unacked_messages: usize,
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -314,6 +408,7 @@ pub struct SwapInfo {
     pub local_swap_role: SwapRole,
     pub connected_counterparty_node_id: Option<NodeId>,
     pub state: StateReport,
+    pub timeline: Vec<StateTimestamp>,
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -400,6 +495,86 @@ pub struct AddressBalance {
     pub balance: u64,
 }
 
+/// Expected fees, block windows, and parameter incompatibilities of a swap,
+/// computed before publishing a deal
+#[cfg_attr(feature = "serde", serde_as)]
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(SimulationReport::to_yaml_string)]
+pub struct SimulationReport {
+    /// Current fee estimates of the Bitcoin syncer
+    pub high_priority_sats_per_kvbyte: u64,
+    pub low_priority_sats_per_kvbyte: u64,
+    /// Fee of the lock transaction at the current high priority estimate,
+    /// paid on top of the arbitrating amount
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub lock_fee: bitcoin::Amount,
+    /// Fees of the pre-signed transactions, set by the deal's fee strategy
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub buy_fee: bitcoin::Amount,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub cancel_fee: bitcoin::Amount,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub refund_fee: bitcoin::Amount,
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub punish_fee: bitcoin::Amount,
+    /// Confirmations of the lock transaction in which buy is safe
+    pub safe_buy_window: BlockWindow,
+    /// Confirmations of the lock transaction after which cancel is valid
+    pub cancel_valid_after: u32,
+    /// Confirmations of the cancel transaction in which refund is safe
+    pub safe_refund_window: BlockWindow,
+    /// Confirmations of the cancel transaction after which punish is valid
+    pub punish_valid_after: u32,
+    /// Smallest amounts worth swapping at the deal's price after fees
+    #[serde(with = "bitcoin::util::amount::serde::as_btc")]
+    pub min_arbitrating_amount: bitcoin::Amount,
+    #[serde_as(as = "DisplayFromStr")]
+    pub min_accordant_amount: monero::Amount,
+    /// Parameters that would make the swap fail or unsafe, empty if the deal
+    /// can be published
+    pub incompatibilities: Vec<String>,
+}
+
+/// A pre-signed transaction held by a running swap
+#[cfg_attr(feature = "serde", serde_as)]
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display("{label}: {txid}")]
+pub struct SwapTx {
+    pub label: TxLabel,
+    #[serde_as(as = "DisplayFromStr")]
+    pub txid: bitcoin::Txid,
+    /// The consensus encoded transaction, in hex
+    pub hex: String,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display("{from}..={to}")]
+pub struct BlockWindow {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl BlockWindow {
+    pub fn is_empty(&self) -> bool {
+        self.from > self.to
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
 pub enum DealStatusSelector {
     #[display("Open")]
@@ -412,6 +587,7 @@ pub enum DealStatusSelector {
     All,
 }
 
+#[cfg(feature = "cli")]
 impl From<DealSelector> for DealStatusSelector {
     fn from(deal_selector: DealSelector) -> DealStatusSelector {
         match deal_selector {
@@ -467,6 +643,8 @@ impl ToYamlString for NodeInfo {}
 #[cfg(feature = "serde")]
 impl ToYamlString for PeerInfo {}
 #[cfg(feature = "serde")]
+impl ToYamlString for SimulationReport {}
+#[cfg(feature = "serde")]
 impl ToYamlString for SwapInfo {}
 #[cfg(feature = "serde")]
 impl ToYamlString for SyncerInfo {}
@@ -5,6 +5,7 @@
 // https://opensource.org/licenses/MIT.
 
 use farcaster_core::{
+    blockchain::Network,
     protocol::message::Abort,
     swap::btcxmr::message::{
         BuyProcedureSignature, CommitAliceParameters, CommitBobParameters, CoreArbitratingSetup,
@@ -49,6 +50,10 @@ pub enum PeerMsg {
     #[display("buy procedure signature")]
     BuyProcedureSignature(BuyProcedureSignature),
 
+    #[api(type = 16)]
+    #[display("init({0})")]
+    Init(Init),
+
     #[api(type = 18)]
     #[display("ping({0})")]
     Ping(u16),
@@ -76,6 +81,26 @@ pub enum PeerMsg {
     #[api(type = 33802)]
     #[display("msg_receipt {0}")]
     MsgReceipt(Receipt),
+
+    #[api(type = 33803)]
+    #[display("sequenced {0}")]
+    Sequenced(Sequenced),
+
+    #[api(type = 33804)]
+    #[display("reconnect_done()")]
+    ReconnectDone,
+
+    #[api(type = 33805)]
+    #[display("chat_message({0})")]
+    ChatMessage(ChatMessage),
+
+    #[api(type = 33806)]
+    #[display("deal_rejected({0})")]
+    DealRejected(DealRejected),
+
+    #[api(type = 33807)]
+    #[display("connect_done()")]
+    ConnectDone,
 }
 
 impl PeerMsg {
@@ -92,13 +117,19 @@ impl PeerMsg {
             PeerMsg::CoreArbitratingSetup(CoreArbitratingSetup { swap_id, .. }) => *swap_id,
             PeerMsg::BuyProcedureSignature(BuyProcedureSignature { swap_id, .. }) => *swap_id,
             PeerMsg::MsgReceipt(Receipt { swap_id, .. }) => *swap_id,
+            PeerMsg::Sequenced(Sequenced { swap_id, .. }) => *swap_id,
+            PeerMsg::ChatMessage(ChatMessage { swap_id, .. }) => *swap_id,
+            PeerMsg::DealRejected(DealRejected { swap_id, .. }) => *swap_id,
             PeerMsg::Ping(_)
             | PeerMsg::Pong(_)
             | PeerMsg::PingPeer
             | PeerMsg::PeerReceiverRuntimeShutdown
+            | PeerMsg::ReconnectDone
+            | PeerMsg::ConnectDone
+            | PeerMsg::Init(_)
             | PeerMsg::Identity(_) => {
                 unreachable!(
-                    "Ping, Pong, PingPeer, PeerdShutdown and Identity do not contain swapid"
+                    "Ping, Pong, PingPeer, PeerdShutdown, ReconnectDone, ConnectDone, Init and Identity do not contain swapid"
                 )
             }
         }
@@ -107,16 +138,11 @@ impl PeerMsg {
     pub fn on_receiver_whitelist(&self) -> bool {
         matches!(
             self,
-            PeerMsg::MakerCommit(_)
-                | PeerMsg::TakerCommit(_)
-                | PeerMsg::Reveal(_)
-                | PeerMsg::RefundProcedureSignatures(_)
-                | PeerMsg::CoreArbitratingSetup(_)
-                | PeerMsg::BuyProcedureSignature(_)
-                | PeerMsg::Ping(_)
+            PeerMsg::Ping(_)
                 | PeerMsg::Pong(_)
                 | PeerMsg::MsgReceipt(_)
-                | PeerMsg::DealNotFound(_)
+                | PeerMsg::Sequenced(_)
+                | PeerMsg::ChatMessage(_)
         )
     }
 
@@ -130,10 +156,110 @@ impl PeerMsg {
                 | PeerMsg::CoreArbitratingSetup(_)
                 | PeerMsg::BuyProcedureSignature(_)
                 | PeerMsg::DealNotFound(_)
+                | PeerMsg::DealRejected(_)
         )
     }
 }
 
+/// Maximum length in bytes of the text of a chat message.
+pub const MAX_CHAT_MESSAGE_LEN: usize = 1024;
+
+/// Maximum number of chat messages stored per swap, the messages over it are
+/// dropped.
+pub const MAX_CHAT_MESSAGES: u64 = 256;
+
+/// Free text message exchanged by the traders of a swap, outside of the swap
+/// protocol.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{swap_id}")]
+pub struct ChatMessage {
+    pub swap_id: SwapId,
+    pub text: String,
+}
+
+/// Rejection of a taken deal, with the reason the deal cannot be swapped.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{swap_id}: {reason}")]
+pub struct DealRejected {
+    pub swap_id: SwapId,
+    pub reason: String,
+}
+
+/// Version of the peer-to-peer swap protocol spoken by this node.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Features supported by this node, as a bit field. No optional feature is
+/// defined yet, the field lets future versions negotiate them.
+pub const SUPPORTED_FEATURES: u64 = 0;
+/// Features this node requires its counterparty to support.
+pub const REQUIRED_FEATURES: u64 = 0;
+
+/// Handshake message exchanged by both peers right after connecting, before
+/// any swap message, to detect incompatible nodes early.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[display("v{protocol_version}, {networks:?}, features {features:#x}")]
+pub struct Init {
+    pub protocol_version: u16,
+    pub networks: Vec<Network>,
+    pub features: u64,
+    pub required_features: u64,
+}
+
+impl Init {
+    pub fn new(networks: Vec<Network>) -> Self {
+        Init {
+            protocol_version: PROTOCOL_VERSION,
+            networks,
+            features: SUPPORTED_FEATURES,
+            required_features: REQUIRED_FEATURES,
+        }
+    }
+
+    /// Check the remote peer's init against ours, returns the reason for
+    /// rejecting the connection if both nodes cannot swap together.
+    pub fn check_compatibility(&self, remote: &Init) -> Result<(), String> {
+        if remote.protocol_version != self.protocol_version {
+            return Err(format!(
+                "remote peer speaks protocol version {}, local node speaks version {}",
+                remote.protocol_version, self.protocol_version
+            ));
+        }
+        let unsupported = remote.required_features & !self.features;
+        if unsupported != 0 {
+            return Err(format!(
+                "remote peer requires features {:#x} not supported by the local node",
+                unsupported
+            ));
+        }
+        let missing = self.required_features & !remote.features;
+        if missing != 0 {
+            return Err(format!(
+                "remote peer does not support required features {:#x}",
+                missing
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check both nodes are able to swap on the network of the deal being
+    /// negotiated, returns the reason for rejecting the deal otherwise.
+    pub fn check_network(&self, remote: &Init, network: Network) -> Result<(), String> {
+        if !self.networks.contains(&network) {
+            return Err(format!(
+                "local node does not swap on {}, it supports {:?}",
+                network, self.networks
+            ));
+        }
+        if !remote.networks.contains(&network) {
+            return Err(format!(
+                "remote peer does not swap on {}, it supports {:?}",
+                network, remote.networks
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
 pub enum Reveal {
     #[display("Alice")]
@@ -195,9 +321,37 @@ impl Commit {
     }
 }
 
+/// Acknowledges the delivery of all the messages of a swap channel up to and
+/// including sequence number `seq`.
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
-#[display("receipt")]
+#[display("receipt({seq})")]
 pub struct Receipt {
     pub swap_id: SwapId,
     pub msg_type: internet2::TypeId,
+    pub seq: u64,
+}
+
+/// A protocol message wrapped with its position in the swap channel. Sequence
+/// numbers are scoped to a peer connection and restart at zero after a
+/// reconnect.
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode)]
+#[display("{seq}")]
+pub struct Sequenced {
+    pub swap_id: SwapId,
+    pub seq: u64,
+    pub payload: Vec<u8>,
+}
+
+impl Sequenced {
+    pub fn with(seq: u64, msg: &PeerMsg) -> Result<Self, strict_encoding::Error> {
+        Ok(Sequenced {
+            swap_id: msg.swap_id(),
+            seq,
+            payload: msg.strict_serialize()?,
+        })
+    }
+
+    pub fn msg(&self) -> Result<PeerMsg, strict_encoding::Error> {
+        PeerMsg::strict_deserialize(&self.payload)
+    }
 }
@@ -5,24 +5,32 @@
 // https://opensource.org/licenses/MIT.
 
 use std::{
+    convert::TryInto,
     fmt::{self, Debug, Display, Formatter},
     str::FromStr,
 };
 
 use farcaster_core::{
-    blockchain::Network,
+    blockchain::{Blockchain, Network},
+    consensus,
     role::TradeRole,
-    swap::{btcxmr::Deal, SwapId},
+    swap::{
+        btcxmr::{Deal, DealParameters},
+        SwapId,
+    },
+    trade::DEAL_PREFIX,
+    Uuid,
 };
 
 use amplify::{ToYamlString, Wrapper};
-use internet2::addr::NodeId;
+use internet2::addr::{InetSocketAddr, NodeId};
 use microservices::rpc;
 use serde_with::DisplayFromStr;
-use strict_encoding::{NetworkDecode, NetworkEncode};
+use strict_encoding::{NetworkDecode, NetworkEncode, StrictDecode, StrictEncode};
 
 use crate::swapd::StateReport;
-use crate::syncerd::Health;
+use crate::syncerd::{Health, SweepDestination};
+use crate::Error;
 
 #[derive(Clone, PartialEq, Eq, Debug, Display, NetworkEncode, NetworkDecode)]
 #[display("{swap_id}, {deal}")]
@@ -100,6 +108,9 @@ pub enum DealStatus {
     InProgress,
     #[display("Ended({0})")]
     Ended(Outcome),
+    /// All the liquidity of a range deal is taken by child deals
+    #[display("Exhausted")]
+    Exhausted,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
@@ -133,6 +144,459 @@ pub enum Outcome {
     FailurePunish,
     #[display("Failure Abort")]
     FailureAbort,
+    #[display("Failure Timeout")]
+    FailureTimeout,
+}
+
+/// What led a swap to its failure outcome
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub enum OutcomeReason {
+    #[display("User Abort")]
+    UserAbort,
+    #[display("Counterparty Abort")]
+    CounterpartyAbort,
+    #[display("Timeout")]
+    Timeout,
+    #[display("Peer Unreachable")]
+    PeerUnreachable,
+    #[display("Funding Canceled")]
+    FundingCanceled,
+    #[display("Invalid Message")]
+    InvalidMessage,
+}
+
+/// A state of the swap and the time at which it was entered
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display("{state} at {timestamp}")]
+pub struct StateTimestamp {
+    pub state: String,
+    /// Unix timestamp in seconds at which the state was entered
+    pub timestamp: u64,
+}
+
+/// The outcome of an ended swap, as stored by databased
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(OutcomeRecord::to_yaml_string)]
+pub struct OutcomeRecord {
+    pub swap_id: SwapId,
+    pub deal: Deal,
+    pub local_trade_role: TradeRole,
+    pub outcome: Outcome,
+    /// Only set for failure outcomes, if the reason is known
+    pub reason: Option<OutcomeReason>,
+    /// The last state of the swap before it ended
+    pub final_state: String,
+    /// The states of the swap in the order they were entered, kept when the
+    /// swap is restored from a checkpoint
+    pub timeline: Vec<StateTimestamp>,
+}
+
+#[cfg(feature = "serde")]
+impl ToYamlString for OutcomeRecord {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub enum ChatDirection {
+    #[display("Sent")]
+    Sent,
+    #[display("Received")]
+    Received,
+}
+
+/// A chat message exchanged with the counterparty of a swap, as stored by
+/// databased
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(ChatRecord::to_yaml_string)]
+pub struct ChatRecord {
+    pub swap_id: SwapId,
+    /// Unix timestamp in seconds at which the message was sent or received
+    pub timestamp: u64,
+    pub direction: ChatDirection,
+    pub text: String,
+}
+
+#[cfg(feature = "serde")]
+impl ToYamlString for ChatRecord {}
+
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+pub enum AutoFundingOutcome {
+    /// The payment was sent in the transaction with the given id
+    #[display("paid({0})")]
+    Paid(String),
+    /// The payment was not sent because of the auto-funding policy
+    #[display("refused({0})")]
+    Refused(String),
+    /// The wallet failed to send the payment
+    #[display("failed({0})")]
+    Failed(String),
+}
+
+/// An automatic funding attempt made by farcasterd, as stored by databased
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(AutoFundingRecord::to_yaml_string)]
+pub struct AutoFundingRecord {
+    pub swap_id: SwapId,
+    /// Unix timestamp in seconds of the attempt
+    pub timestamp: u64,
+    pub blockchain: Blockchain,
+    pub network: Network,
+    pub address: String,
+    /// Amount in the smallest unit of the blockchain, satoshi or piconero
+    pub amount: u64,
+    pub outcome: AutoFundingOutcome,
+}
+
+#[cfg(feature = "serde")]
+impl ToYamlString for AutoFundingRecord {}
+
+/// A bitcoin sweep of a finished swap queued to be batched with others, as
+/// stored by databased. The secret key of the source address is retrieved from
+/// the stored bitcoin addresses when the batch is swept.
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(QueuedSweep::to_yaml_string)]
+pub struct QueuedSweep {
+    pub swap_id: SwapId,
+    pub source_address: bitcoin::Address,
+    pub destination_address: bitcoin::Address,
+}
+
+#[cfg(feature = "serde")]
+impl ToYamlString for QueuedSweep {}
+
+/// Expiry of a made deal, the deal is revoked once it passed
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+pub enum DealExpiry {
+    /// Unix timestamp in seconds
+    #[display("timestamp {0}")]
+    Timestamp(u64),
+    /// Block height of the arbitrating blockchain
+    #[display("block height {0}")]
+    BlockHeight(u64),
+}
+
+impl DealExpiry {
+    /// Whether the expiry passed at the given unix timestamp and known block
+    /// height of the arbitrating blockchain
+    pub fn is_passed(&self, now: u64, height: Option<u64>) -> bool {
+        match self {
+            DealExpiry::Timestamp(timestamp) => now >= *timestamp,
+            DealExpiry::BlockHeight(expiry_height) => {
+                height.map_or(false, |height| height >= *expiry_height)
+            }
+        }
+    }
+}
+
+/// A deal as shared with takers, along with the information the maker
+/// advertises besides the deal. The string keeps the `Deal:` encoding, the
+/// additional information being appended to the encoded deal, so that nodes
+/// not aware of it still read the deal.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AdvertisedDeal {
+    pub deal: Deal,
+    /// Addresses the maker is reachable on besides the deal's one
+    pub alternate_addrs: Vec<InetSocketAddr>,
+}
+
+impl From<Deal> for AdvertisedDeal {
+    fn from(deal: Deal) -> Self {
+        AdvertisedDeal {
+            deal,
+            alternate_addrs: vec![],
+        }
+    }
+}
+
+impl Display for AdvertisedDeal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.alternate_addrs.is_empty() {
+            return Display::fmt(&self.deal, f);
+        }
+        let mut data = consensus::serialize(&self.deal);
+        self.alternate_addrs
+            .strict_encode(&mut data)
+            .expect("Encoding in memory works");
+        let encoded = base58_monero::encode_check(&data).expect("Encoding in base58 check works");
+        write!(f, "{}{}", DEAL_PREFIX, encoded)
+    }
+}
+
+impl FromStr for AdvertisedDeal {
+    type Err = consensus::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .strip_prefix(DEAL_PREFIX)
+            .ok_or(consensus::Error::IncorrectMagicBytes)?;
+        let data = base58_monero::decode_check(encoded).map_err(consensus::Error::new)?;
+        let (deal, consumed) = consensus::deserialize_partial::<Deal>(&data)?;
+        let alternate_addrs = if consumed < data.len() {
+            Vec::<InetSocketAddr>::strict_deserialize(&data[consumed..])
+                .map_err(consensus::Error::new)?
+        } else {
+            vec![]
+        };
+        Ok(AdvertisedDeal {
+            deal,
+            alternate_addrs,
+        })
+    }
+}
+
+/// Addresses of the maker of a swap taken by the node, the first one being
+/// the address the deal was taken through, as stored by databased to
+/// reconnect to the maker after a restart
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[display("{swap_id}")]
+pub struct MakerAddresses {
+    pub swap_id: SwapId,
+    pub addrs: Vec<InetSocketAddr>,
+}
+
+/// Range of bitcoin amounts takers can take from a range deal, at the price of
+/// the deal. Each take spawns a child swap of a child deal with the chosen
+/// amount, the rest of the liquidity stays open.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[display("{min} to {remaining}")]
+pub struct DealRange {
+    /// Minimum amount of a take
+    pub min: bitcoin::Amount,
+    /// Liquidity left, the maximum amount of a take
+    pub remaining: bitcoin::Amount,
+}
+
+impl DealRange {
+    /// Parameters of a child deal taking the bitcoin amount from a range deal,
+    /// the monero amount follows the price of the range deal
+    pub fn child_parameters(
+        parent: &DealParameters,
+        arbitrating_amount: bitcoin::Amount,
+    ) -> Result<DealParameters, Error> {
+        if parent.arbitrating_amount.as_sat() == 0 {
+            return Err(Error::Farcaster("Invalid range deal amount".to_string()));
+        }
+        let accordant_amount = parent.accordant_amount.as_pico() as u128
+            * arbitrating_amount.as_sat() as u128
+            / parent.arbitrating_amount.as_sat() as u128;
+        Ok(DealParameters {
+            uuid: Uuid::new().into(),
+            arbitrating_amount,
+            accordant_amount: monero::Amount::from_pico(accordant_amount.try_into().map_err(
+                |_| Error::Farcaster("Monero amount of the child deal overflows".to_string()),
+            )?),
+            ..parent.clone()
+        })
+    }
+
+    /// Whether the deal is a child deal taken from the range deal: same maker
+    /// and swap parameters, amounts at the price of the range deal
+    pub fn is_child(parent: &Deal, child: &Deal) -> bool {
+        let (p, c) = (&parent.parameters, &child.parameters);
+        parent.node_id == child.node_id
+            && parent.peer_address == child.peer_address
+            && p.uuid != c.uuid
+            && p.network == c.network
+            && p.arbitrating_blockchain == c.arbitrating_blockchain
+            && p.accordant_blockchain == c.accordant_blockchain
+            && p.cancel_timelock == c.cancel_timelock
+            && p.punish_timelock == c.punish_timelock
+            && p.fee_strategy == c.fee_strategy
+            && p.maker_role == c.maker_role
+            && DealRange::child_parameters(p, c.arbitrating_amount).map_or(false, |expected| {
+                expected.accordant_amount == c.accordant_amount
+            })
+    }
+
+    /// Check the bitcoin amount can be taken from the range
+    pub fn check(&self, amount: bitcoin::Amount) -> Result<(), Error> {
+        if amount < self.min || amount > self.remaining {
+            Err(Error::Farcaster(format!(
+                "Amount {} outside of the range {}",
+                amount, self
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An open deal made by the node, as stored by databased to be made again
+/// when farcasterd restarts
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[display("{deal}")]
+pub struct OpenDeal {
+    pub deal: Deal,
+    pub arbitrating_addr: bitcoin::Address,
+    pub accordant_addr: monero::Address,
+    pub accordant_split: Vec<SweepDestination>,
+    pub alternate_addrs: Vec<InetSocketAddr>,
+    pub expiry: Option<DealExpiry>,
+    /// Liquidity left if the deal is a range deal
+    pub range: Option<DealRange>,
+    /// Whether the deal is made by the market maker, which keeps managing it
+    pub market_maker: bool,
+}
+
+/// A webhook notification pending delivery, as stored by databased until it
+/// is delivered or its delivery attempts are exhausted
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[display("{id} to {url}")]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub url: String,
+    /// JSON body of the notification
+    pub body: String,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate", rename_all = "snake_case")
+)]
+pub enum EventKind {
+    #[display("deal_made")]
+    DealMade,
+    #[display("deal_taken")]
+    DealTaken,
+    #[display("deal_revoked")]
+    DealRevoked,
+    #[display("swap_launched")]
+    SwapLaunched,
+    #[display("state_transition")]
+    StateTransition,
+    #[display("tx_broadcast")]
+    TxBroadcast,
+    #[display("funding")]
+    Funding,
+    #[display("swap_ended")]
+    SwapEnded,
+    #[display("peer_connected")]
+    PeerConnected,
+    #[display("peer_disconnected")]
+    PeerDisconnected,
+    #[display("error")]
+    Error,
+}
+
+impl FromStr for EventKind {
+    type Err = Error;
+    fn from_str(input: &str) -> Result<EventKind, Self::Err> {
+        match input.to_lowercase().replace('-', "_").as_str() {
+            "deal_made" => Ok(EventKind::DealMade),
+            "deal_taken" => Ok(EventKind::DealTaken),
+            "deal_revoked" => Ok(EventKind::DealRevoked),
+            "swap_launched" => Ok(EventKind::SwapLaunched),
+            "state_transition" => Ok(EventKind::StateTransition),
+            "tx_broadcast" => Ok(EventKind::TxBroadcast),
+            "funding" => Ok(EventKind::Funding),
+            "swap_ended" => Ok(EventKind::SwapEnded),
+            "peer_connected" => Ok(EventKind::PeerConnected),
+            "peer_disconnected" => Ok(EventKind::PeerDisconnected),
+            "error" => Ok(EventKind::Error),
+            _ => Err(Error::Farcaster(format!("Unknown event kind {}", input))),
+        }
+    }
+}
+
+/// An entry of the event log of the node, as stored by databased
+#[derive(Clone, Debug, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(NodeEvent::to_yaml_string)]
+pub struct NodeEvent {
+    /// Sequence number of the event in the log, set by databased
+    pub id: u64,
+    /// Unix timestamp in seconds at which the event occurred
+    pub timestamp: u64,
+    pub kind: EventKind,
+    pub swap_id: Option<SwapId>,
+    pub details: String,
+}
+
+#[cfg(feature = "serde")]
+impl ToYamlString for NodeEvent {}
+
+impl NodeEvent {
+    /// A new event occurring now, to be logged by databased
+    pub fn new(kind: EventKind, swap_id: Option<SwapId>, details: impl ToString) -> Self {
+        NodeEvent {
+            id: 0,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            kind,
+            swap_id,
+            details: details.to_string(),
+        }
+    }
+}
+
+/// Selection of the events of the event log, an unset field selects all the
+/// events
+#[derive(Clone, Debug, Default, Eq, PartialEq, Display, NetworkEncode, NetworkDecode)]
+#[display(Debug)]
+pub struct EventFilter {
+    /// Events that occurred at or after this unix timestamp in seconds
+    pub since: Option<u64>,
+    /// Events logged after the event with this sequence number
+    pub after: Option<u64>,
+    pub swap_id: Option<SwapId>,
+    pub kind: Option<EventKind>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &NodeEvent) -> bool {
+        self.since.map_or(true, |since| event.timestamp >= since)
+            && self.after.map_or(true, |after| event.id > after)
+            && self
+                .swap_id
+                .map_or(true, |swap_id| event.swap_id == Some(swap_id))
+            && self.kind.map_or(true, |kind| event.kind == kind)
+    }
 }
 
 #[derive(Clone, Debug, Display, NetworkEncode, NetworkDecode)]
@@ -295,3 +759,67 @@ pub struct ReducedHealthReport {
 impl ToYamlString for HealthReport {}
 #[cfg(feature = "serde")]
 impl ToYamlString for ReducedHealthReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use farcaster_core::bitcoin::{fee::SatPerVByte, timelock::CSVTimelock};
+    use farcaster_core::blockchain::FeeStrategy;
+    use farcaster_core::role::SwapRole;
+
+    #[test]
+    fn range_deal_children() {
+        let btc = |amount: &str| bitcoin::Amount::from_str(amount).unwrap();
+        let parent = DealParameters {
+            uuid: Uuid::new().into(),
+            network: Network::Testnet,
+            arbitrating_blockchain: Blockchain::Bitcoin,
+            accordant_blockchain: Blockchain::Monero,
+            arbitrating_amount: btc("0.1 BTC"),
+            accordant_amount: monero::Amount::from_str("15 XMR").unwrap(),
+            cancel_timelock: CSVTimelock::new(4),
+            punish_timelock: CSVTimelock::new(5),
+            fee_strategy: FeeStrategy::Fixed(SatPerVByte::from_sat(1)),
+            maker_role: SwapRole::Bob,
+        };
+        let child = DealRange::child_parameters(&parent, btc("0.02 BTC")).unwrap();
+        assert_eq!(
+            child.accordant_amount,
+            monero::Amount::from_str("3 XMR").unwrap()
+        );
+        assert_ne!(child.uuid, parent.uuid);
+
+        let range = DealRange {
+            min: btc("0.01 BTC"),
+            remaining: btc("0.05 BTC"),
+        };
+        assert!(range.check(btc("0.01 BTC")).is_ok());
+        assert!(range.check(btc("0.05 BTC")).is_ok());
+        assert!(range.check(btc("0.005 BTC")).is_err());
+        assert!(range.check(btc("0.06 BTC")).is_err());
+    }
+
+    #[test]
+    fn advertised_deal_string() {
+        let deal = Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap();
+        // without additional information the advertised deal is the deal
+        let advertised = AdvertisedDeal::from(deal.clone());
+        assert_eq!(advertised.to_string(), deal.to_string());
+        assert_eq!(
+            AdvertisedDeal::from_str(&deal.to_string()).unwrap(),
+            advertised
+        );
+
+        let advertised = AdvertisedDeal {
+            deal: deal.clone(),
+            alternate_addrs: vec![
+                InetSocketAddr::from_str("10.0.0.1:7067").unwrap(),
+                InetSocketAddr::from_str("[::1]:7067").unwrap(),
+            ],
+        };
+        let encoded = advertised.to_string();
+        assert_eq!(AdvertisedDeal::from_str(&encoded).unwrap(), advertised);
+        // nodes unaware of the additional information still read the deal
+        assert_eq!(Deal::from_str(&encoded).unwrap(), deal);
+    }
+}
@@ -23,12 +23,13 @@ use farcaster_core::{
 
 use super::Command;
 use crate::bus::{
-    ctl::{self, CtlMsg},
+    ctl::{self, CtlMsg, ForceBroadcast},
     info::{Address, InfoMsg},
     AddressSecretKey,
 };
 use crate::bus::{
-    BusMsg, Failure, FailureCode, HealthCheckSelector, HealthReport, ReducedHealthReport,
+    AdvertisedDeal, BusMsg, DealExpiry, DealRange, EventFilter, Failure, FailureCode,
+    HealthCheckSelector, HealthReport, ReducedHealthReport,
 };
 use crate::cli::opts::CheckpointSelector;
 use crate::client::Client;
@@ -95,11 +96,18 @@ impl Exec for Command {
                                     node_addr
                                 )));
                             }
+                            // the swap may have ended, report its outcome
                             ServiceId::Swap(swap_id) => {
-                                return Err(Error::Farcaster(format!(
-                                    "No running swap with id {}",
-                                    swap_id
-                                )));
+                                runtime.request_info(
+                                    ServiceId::Database,
+                                    InfoMsg::GetOutcomeRecord(swap_id),
+                                )?;
+                                runtime.report_response_or_fail().map_err(|_| {
+                                    Error::Farcaster(format!(
+                                        "No running or ended swap with id {}",
+                                        swap_id
+                                    ))
+                                })?;
                             }
                             ServiceId::Syncer(blockchain, network) => {
                                 return Err(Error::Farcaster(format!(
@@ -128,14 +136,17 @@ impl Exec for Command {
                 runtime.report_response_or_fail()?;
             }
 
-            Command::ListSwaps => {
-                runtime.request_info(ServiceId::Farcasterd, InfoMsg::ListSwaps)?;
+            Command::ListSwaps { network } => {
+                runtime.request_info(ServiceId::Farcasterd, InfoMsg::ListSwaps(network))?;
                 runtime.report_response_or_fail()?;
             }
 
             // TODO: only list deals matching list of DealIds
-            Command::ListDeals { select } => {
-                runtime.request_info(ServiceId::Farcasterd, InfoMsg::ListDeals(select.into()))?;
+            Command::ListDeals { select, network } => {
+                runtime.request_info(
+                    ServiceId::Farcasterd,
+                    InfoMsg::ListDeals(select.into(), network),
+                )?;
                 runtime.report_response_or_fail()?;
             }
 
@@ -152,18 +163,18 @@ impl Exec for Command {
                 runtime.report_response_or_fail()?;
             }
 
-            Command::ListCheckpoints { select } => {
+            Command::ListCheckpoints { select, network } => {
                 match select {
                     CheckpointSelector::All => {
                         runtime.request_info(
                             ServiceId::Database,
-                            InfoMsg::RetrieveAllCheckpointInfo,
+                            InfoMsg::RetrieveAllCheckpointInfo(network),
                         )?;
                     }
                     CheckpointSelector::AvailableForRestore => {
                         runtime.request_info(
                             ServiceId::Database,
-                            InfoMsg::RetrieveAllCheckpointInfo,
+                            InfoMsg::RetrieveAllCheckpointInfo(network),
                         )?;
                         if let BusMsg::Info(InfoMsg::CheckpointList(list)) =
                             runtime.report_failure()?
@@ -337,12 +348,17 @@ impl Exec for Command {
                 accordant_amount,
                 arbitrating_addr,
                 accordant_addr,
+                accordant_split,
                 cancel_timelock,
                 punish_timelock,
                 fee_strategy,
                 maker_role,
                 public_ip_addr,
                 public_port,
+                alternate_addrs,
+                expires_at,
+                expires_at_height,
+                min_arbitrating_amount,
             } => {
                 // Monero local address types are mainnet address types
                 if network != accordant_addr.network.into() && network != Network::Local {
@@ -400,14 +416,48 @@ impl Exec for Command {
                 let proto_deal = ctl::ProtoDeal {
                     deal_parameters,
                     public_addr,
+                    alternate_addrs,
                     arbitrating_addr,
                     accordant_addr,
+                    accordant_split,
+                    expiry: expires_at
+                        .map(DealExpiry::Timestamp)
+                        .or_else(|| expires_at_height.map(DealExpiry::BlockHeight)),
+                    range: min_arbitrating_amount.map(|min| DealRange {
+                        min,
+                        remaining: arbitrating_amount,
+                    }),
                 };
                 runtime.request_ctl(ServiceId::Farcasterd, CtlMsg::MakeDeal(proto_deal))?;
                 // report success or failure of the request to cli
                 runtime.report_response_or_fail()?;
             }
 
+            Command::Simulate {
+                network,
+                arbitrating_amount,
+                accordant_amount,
+                maker_role,
+                cancel_timelock,
+                punish_timelock,
+                fee_strategy,
+            } => {
+                let deal_parameters = DealParameters {
+                    uuid: Uuid::new().into(),
+                    network,
+                    arbitrating_blockchain: Blockchain::Bitcoin,
+                    accordant_blockchain: Blockchain::Monero,
+                    arbitrating_amount,
+                    accordant_amount,
+                    cancel_timelock,
+                    punish_timelock,
+                    fee_strategy,
+                    maker_role,
+                };
+                runtime.request_ctl(ServiceId::Farcasterd, CtlMsg::Simulate(deal_parameters))?;
+                runtime.report_response_or_fail()?;
+            }
+
             Command::DealInfo { deal } => {
                 println!("\n Trading {}\n", deal_buy_information(&deal.parameters));
                 println!("{}", serde_yaml::to_string(&deal).expect("already parsed"));
@@ -417,8 +467,31 @@ impl Exec for Command {
                 deal,
                 bitcoin_address,
                 monero_address,
+                monero_split,
                 without_validation,
+                arbitrating_amount,
+                alternate_addrs: extra_addrs,
             } => {
+                let AdvertisedDeal {
+                    deal,
+                    mut alternate_addrs,
+                } = deal;
+                for addr in extra_addrs {
+                    if !alternate_addrs.contains(&addr) {
+                        alternate_addrs.push(addr);
+                    }
+                }
+                // taking part of a range deal is taking a child deal at the same price
+                let deal = match arbitrating_amount {
+                    Some(amount) => match DealRange::child_parameters(&deal.parameters, amount) {
+                        Ok(parameters) => parameters.to_v1(deal.node_id, deal.peer_address),
+                        Err(err) => {
+                            eprintln!("Error: {}", err);
+                            return Ok(());
+                        }
+                    },
+                    None => deal,
+                };
                 let Deal {
                     version: _,
                     parameters: deal_parameters,
@@ -477,7 +550,11 @@ impl Exec for Command {
                         "\nWant to buy {}?\n\nCarefully validate the deal!\n",
                         deal_buy_information(&deal.parameters)
                     );
-                    println!("Trade counterparty: {}@{}\n", &node_id, peer_address);
+                    println!("Trade counterparty: {}@{}", &node_id, peer_address);
+                    for addr in &alternate_addrs {
+                        println!("Alternate address: {}@{}", &node_id, addr);
+                    }
+                    println!();
                     println!("{}", serde_yaml::to_string(&deal).expect("already parsed"));
                 }
                 if without_validation || take_deal() {
@@ -488,6 +565,8 @@ impl Exec for Command {
                             deal,
                             bitcoin_address,
                             monero_address,
+                            monero_split,
+                            alternate_addrs,
                         }),
                     )?;
                     // report success of failure of the request to cli
@@ -500,11 +579,76 @@ impl Exec for Command {
                 runtime.report_response_or_fail()?;
             }
 
+            Command::MarketPrice { price } => {
+                runtime.request_ctl(ServiceId::Farcasterd, CtlMsg::SetMarketPrice(price))?;
+                runtime.report_response_or_fail()?;
+            }
+
             Command::AbortSwap { swap_id } => {
                 runtime.request_ctl(ServiceId::Swap(swap_id), CtlMsg::AbortSwap)?;
                 runtime.report_response_or_fail()?;
             }
 
+            Command::SendMessage { swap_id, message } => {
+                runtime.request_ctl(ServiceId::Swap(swap_id), CtlMsg::SendChatMessage(message))?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::Messages { swap_id } => {
+                runtime.request_info(ServiceId::Database, InfoMsg::GetChatMessages(swap_id))?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::Events {
+                since,
+                swap_id,
+                kind,
+            } => {
+                runtime.request_info(
+                    ServiceId::Database,
+                    InfoMsg::GetEvents(EventFilter {
+                        since,
+                        after: None,
+                        swap_id,
+                        kind,
+                    }),
+                )?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::FundingPsbt { swap_id } => {
+                runtime.request_info(ServiceId::Swap(swap_id), InfoMsg::GetFundingPsbt)?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::WatchtowerPackage { swap_id } => {
+                runtime
+                    .request_info(ServiceId::Database, InfoMsg::GetWatchtowerPackage(swap_id))?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::SwapTxs { swap_id } => {
+                runtime.request_info(ServiceId::Swap(swap_id), InfoMsg::GetSwapTxs)?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::BroadcastTx {
+                swap_id,
+                tx,
+                confirm,
+            } => {
+                runtime.request_ctl(
+                    ServiceId::Swap(swap_id),
+                    CtlMsg::ForceBroadcast(ForceBroadcast { label: tx, confirm }),
+                )?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::ImportFundingPsbt { swap_id, psbt } => {
+                runtime.request_ctl(ServiceId::Swap(swap_id), CtlMsg::ImportFundingPsbt(psbt))?;
+                runtime.report_response_or_fail()?;
+            }
+
             Command::Progress { swapid, follow } => {
                 if follow {
                     // subscribe to progress event and loop until Finish event is received or user
@@ -533,8 +677,19 @@ impl Exec for Command {
                 runtime.report_response_or_fail()?;
             }
 
-            Command::ListFundingAddresses { blockchain } => {
-                runtime.request_info(ServiceId::Database, InfoMsg::GetAddresses(blockchain))?;
+            Command::AutoFundingLog => {
+                runtime.request_info(ServiceId::Database, InfoMsg::GetAutoFundingRecords)?;
+                runtime.report_response_or_fail()?;
+            }
+
+            Command::ListFundingAddresses {
+                blockchain,
+                network,
+            } => {
+                runtime.request_info(
+                    ServiceId::Database,
+                    InfoMsg::GetAddresses(blockchain, network),
+                )?;
                 runtime.report_response_or_fail()?;
             }
 
@@ -602,6 +757,7 @@ impl Exec for Command {
                             source_spend_key: secret_key_info.spend,
                             source_view_key: secret_key_info.view,
                             destination_address,
+                            split: vec![],
                             minimum_balance: monero::Amount::from_pico(0),
                             from_height: secret_key_info.creation_height,
                         })),
@@ -612,6 +768,11 @@ impl Exec for Command {
                 }
             }
 
+            Command::Shutdown { force } => {
+                runtime.request_ctl(ServiceId::Farcasterd, CtlMsg::Shutdown(force))?;
+                runtime.report_response_or_fail()?;
+            }
+
             Command::Completion { shell } => {
                 let mut app = super::Opts::command();
                 let name = app.get_name().to_string();
@@ -6,6 +6,7 @@
 
 use bitcoin::Address as BtcAddress;
 use clap_complete::shells::Shell;
+use internet2::addr::InetSocketAddr;
 use monero::Address as XmrAddress;
 use std::net::IpAddr;
 use std::str::FromStr;
@@ -15,10 +16,13 @@ use farcaster_core::{
     blockchain::{Blockchain, FeeStrategy, Network},
     role::SwapRole,
     swap::{btcxmr::Deal, SwapId},
+    transaction::TxLabel,
 };
 
+use crate::bus::ctl::parse_broadcast_label;
 use crate::bus::info::Address;
-use crate::bus::HealthCheckSelector;
+use crate::bus::{AdvertisedDeal, EventKind, HealthCheckSelector};
+use crate::syncerd::SweepDestination;
 
 /// Command-line tool for working with Farcaster node
 #[derive(Parser, Clone, PartialEq, Eq, Debug)]
@@ -47,7 +51,7 @@ pub enum Command {
     #[display("info<{subject:?}>")]
     Info {
         /// Remote peer address, swap id, or blockchain and network. If absent, returns information
-        /// about the node itself
+        /// about the node itself. For an ended swap, returns its outcome
         subject: Vec<String>,
     },
 
@@ -56,7 +60,11 @@ pub enum Command {
 
     /// Lists running swaps
     #[clap(aliases = &["ls"])]
-    ListSwaps,
+    ListSwaps {
+        /// Only list the swaps on this network
+        #[clap(long)]
+        network: Option<Network>,
+    },
 
     /// Lists deals created by daemon
     #[clap(aliases = &["ld"])]
@@ -68,6 +76,10 @@ pub enum Command {
             possible_values = &["open", "Open", "inprogress", "in_progress", "ended", "Ended", "all", "All"],
         )]
         select: DealSelector,
+
+        /// Only list the deals on this network
+        #[clap(long)]
+        network: Option<Network>,
     },
 
     /// Gives information on an open deal
@@ -78,6 +90,14 @@ pub enum Command {
         deal: Deal,
     },
 
+    /// Set the market price the automated market maker prices its deals from
+    /// when using the spread pricing strategy without a price file.
+    #[display("market-price<{price}>")]
+    MarketPrice {
+        /// The amount of XMR for 1 BTC, e.g. "150 XMR".
+        price: monero::Amount,
+    },
+
     /// Lists listeners created by daemon
     #[clap(aliases = &["ll"])]
     ListListens,
@@ -102,6 +122,10 @@ pub enum Command {
             possible_values = &["all", "All", "available", "Available", "available-for-restore"],
         )]
         select: CheckpointSelector,
+
+        /// Only list the checkpoints of the swaps on this network
+        #[clap(long)]
+        network: Option<Network>,
     },
 
     /// Checks the health of the syncers
@@ -146,6 +170,12 @@ pub enum Command {
         #[clap(long = "xmr-addr")]
         accordant_addr: XmrAddress,
 
+        /// Additional destination of the swept Monero, as ADDRESS:PERCENT% or ADDRESS:AMOUNT in
+        /// XMR, the remainder goes to the Monero address. Can be repeated, defaults to the node's
+        /// `sweep_split` configuration.
+        #[clap(long = "xmr-split")]
+        accordant_split: Vec<SweepDestination>,
+
         /// Network to use to execute the swap between the chosen blockchains.
         #[clap(
             short,
@@ -209,6 +239,70 @@ pub enum Command {
         /// {farcasterd.bind_ip}:{farcasterd.bind_port}
         #[clap(short = 'p', long, default_value = "7067")]
         public_port: u16,
+
+        /// Additional address, as IP:PORT, to advertise along the deal, e.g. to be reachable
+        /// on both IPv4 and IPv6. Can be repeated, takers try them in order.
+        #[clap(long = "alt-addr")]
+        alternate_addrs: Vec<InetSocketAddr>,
+
+        /// Unix timestamp, in seconds, at which the deal expires and is revoked. Takers
+        /// committing after the expiry are told the deal is not found.
+        #[clap(long, conflicts_with = "expires-at-height")]
+        expires_at: Option<u64>,
+
+        /// Bitcoin block height at which the deal expires and is revoked.
+        #[clap(long)]
+        expires_at_height: Option<u64>,
+
+        /// Make a range deal: takers can take any bitcoin amount between this minimum and the
+        /// amount of the deal, at the same price. Each take runs its own swap and the rest of
+        /// the deal stays open.
+        #[clap(long = "min-btc-amount")]
+        min_arbitrating_amount: Option<bitcoin::Amount>,
+    },
+
+    /// Simulates a swap of the deal parameters without publishing a deal. Reports the expected
+    /// fees with the current fee estimates, the block windows for a safe buy and refund, the
+    /// minimum amounts worth swapping, and the parameters incompatible with the swap
+    /// configuration.
+    ///
+    /// Example usage:
+    ///
+    /// simulate --btc-amount "0.0000135 BTC" --xmr-amount "0.001 XMR" --cancel-timelock 10
+    /// --punish-timelock 20
+    Simulate {
+        /// Network to use to execute the swap between the chosen blockchains.
+        #[clap(
+            short,
+            long,
+            default_value = "testnet",
+            possible_values = &["Testnet", "testnet", "Mainnet", "mainnet", "Local", "local"]
+        )]
+        network: Network,
+
+        /// Amount of arbitrating assets to exchanged.
+        #[clap(long = "btc-amount")]
+        arbitrating_amount: bitcoin::Amount,
+
+        /// Amount of accordant assets to exchanged.
+        #[clap(long = "xmr-amount")]
+        accordant_amount: monero::Amount,
+
+        /// The future maker swap role, either Alice of Bob.
+        #[clap(short = 'r', long, default_value = "Bob", possible_values = &["Alice", "Bob"])]
+        maker_role: SwapRole,
+
+        /// The cancel timelock parameter of the arbitrating blockchain.
+        #[clap(long, default_value = "4")]
+        cancel_timelock: CSVTimelock,
+
+        /// The punish timelock parameter of the arbitrating blockchain.
+        #[clap(long, default_value = "5")]
+        punish_timelock: CSVTimelock,
+
+        /// The chosen fee strategy for the arbitrating transactions.
+        #[clap(long, default_value = "1 satoshi/vByte")]
+        fee_strategy: FeeStrategy<SatPerVByte>,
     },
 
     /// Taker accepts deal and connects to maker's daemon to start the trade.
@@ -221,13 +315,29 @@ pub enum Command {
         #[clap(long = "xmr-addr")]
         monero_address: XmrAddress,
 
-        /// An encoded deal.
+        /// Additional destination of the swept Monero, as ADDRESS:PERCENT% or ADDRESS:AMOUNT in
+        /// XMR, the remainder goes to the Monero address. Can be repeated, defaults to the node's
+        /// `sweep_split` configuration.
+        #[clap(long = "xmr-split")]
+        monero_split: Vec<SweepDestination>,
+
+        /// An encoded deal, as shared by the maker.
         #[clap(short = 'D', long = "deal")]
-        deal: Deal,
+        deal: AdvertisedDeal,
 
         /// Accept the deal without validation.
         #[clap(short, long)]
         without_validation: bool,
+
+        /// Bitcoin amount to take from a range deal, the monero amount follows the price of the
+        /// deal. Takes the whole deal if not set.
+        #[clap(long = "btc-amount")]
+        arbitrating_amount: Option<bitcoin::Amount>,
+
+        /// Additional address of the maker, as IP:PORT, besides the ones advertised with the
+        /// deal. Can be repeated, tried in order if the deal's address cannot be reached.
+        #[clap(long = "alt-addr")]
+        alternate_addrs: Vec<InetSocketAddr>,
     },
 
     /// Revoke deal accepts a deal and revokes it within the runtime.
@@ -244,6 +354,98 @@ pub enum Command {
         swap_id: SwapId,
     },
 
+    /// Send a chat message to the counterparty of a swap.
+    #[display("send-message<{swap_id}>")]
+    SendMessage {
+        /// The swap the message is about
+        swap_id: SwapId,
+
+        /// The text of the message
+        message: String,
+    },
+
+    /// Lists the chat messages exchanged with the counterparty of a swap, kept
+    /// until the swap is cleaned up.
+    #[display("messages<{swap_id}>")]
+    Messages {
+        /// The swap the messages are about
+        swap_id: SwapId,
+    },
+
+    /// Lists the events of the node: deals made, taken, and revoked, swaps
+    /// launched and ended, state transitions, transactions broadcasted,
+    /// funding, peer connections, and errors.
+    #[display("events")]
+    Events {
+        /// Only list the events that occurred at or after this unix timestamp
+        /// in seconds
+        #[clap(long)]
+        since: Option<u64>,
+
+        /// Only list the events of this swap
+        #[clap(long)]
+        swap_id: Option<SwapId>,
+
+        /// Only list the events of this kind: deal_made, deal_taken,
+        /// deal_revoked, swap_launched, state_transition, tx_broadcast,
+        /// funding, swap_ended, peer_connected, peer_disconnected, or error
+        #[clap(long)]
+        kind: Option<EventKind>,
+    },
+
+    /// Export the funding of a swap as an unsigned PSBT, to be completed and
+    /// signed by an external wallet.
+    #[display("funding-psbt<{swap_id}>")]
+    FundingPsbt {
+        /// The swap awaiting funding
+        swap_id: SwapId,
+    },
+
+    /// Export the watchtower package of a swap: its pre-signed cancel, refund,
+    /// or punish transactions and timelocks, to be given to `watchtowerd`.
+    #[display("watchtower-package<{swap_id}>")]
+    WatchtowerPackage {
+        /// The swap to protect
+        swap_id: SwapId,
+    },
+
+    /// List the pre-signed transactions held by a running swap, with their
+    /// hex encoding.
+    #[display("swap-txs<{swap_id}>")]
+    SwapTxs {
+        /// The running swap
+        swap_id: SwapId,
+    },
+
+    /// Broadcast the pre-signed cancel, refund, or punish transaction of a
+    /// running swap, overriding the automatic logic of the swap. Without
+    /// --confirm, only reports what would be broadcasted and whether a race
+    /// with the counterparty is possible.
+    #[display("broadcast-tx<{swap_id}, {tx}>")]
+    BroadcastTx {
+        /// The running swap
+        swap_id: SwapId,
+
+        /// The transaction to broadcast: cancel, refund, or punish
+        #[clap(parse(try_from_str = parse_broadcast_label))]
+        tx: TxLabel,
+
+        /// Broadcast the transaction, even if the safety checks warn
+        #[clap(long)]
+        confirm: bool,
+    },
+
+    /// Import a PSBT funding a swap, signed by an external wallet, and
+    /// broadcast it.
+    #[display("import-funding-psbt<{swap_id}>")]
+    ImportFundingPsbt {
+        /// The swap awaiting funding
+        swap_id: SwapId,
+
+        /// The signed and finalized PSBT, base64 encoded
+        psbt: String,
+    },
+
     /// Request swap progress report.
     #[display("progress<{swapid}>")]
     Progress {
@@ -262,11 +464,20 @@ pub enum Command {
         blockchain: Blockchain,
     },
 
+    /// Lists the automatic funding attempts made by the daemon, with their
+    /// outcome.
+    #[display("auto-funding-log")]
+    AutoFundingLog,
+
     /// Returns previously created funding addresses for blockchain.
     #[display("list-funding-address<{blockchain}>")]
     ListFundingAddresses {
         /// Retrieve funding addresses for a particular blockchain.
         blockchain: Blockchain,
+
+        /// Only retrieve the funding addresses on this network
+        #[clap(long)]
+        network: Option<Network>,
     },
 
     /// Attempts to sweep any funds on a given bitcoin funding address
@@ -294,6 +505,16 @@ pub enum Command {
         address: Address,
     },
 
+    /// Checkpoint the running swaps and shut the node down. Reports the swaps
+    /// with upcoming cancel or punish deadlines and refuses to shut down if a
+    /// critical action is imminent, unless forced.
+    #[display("shutdown<force: {force}>")]
+    Shutdown {
+        /// Shut down even if a critical action of a swap is imminent
+        #[clap(long)]
+        force: bool,
+    },
+
     /// Output shell completion code for the specified shell (bash, zsh or fish)
     ///
     /// The shell code must be evaluated to provide interactive completion of swap-cli commands.
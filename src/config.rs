@@ -4,8 +4,11 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use crate::syncerd::SweepDestination;
 use crate::{AccordantBlockchain, ArbitratingBlockchain, Error};
-use farcaster_core::blockchain::Network;
+use farcaster_core::bitcoin::fee::SatPerVByte;
+use farcaster_core::blockchain::{FeeStrategy, Network};
+use farcaster_core::role::SwapRole;
 use internet2::addr::InetSocketAddr;
 use std::fs::File;
 use std::io::prelude::*;
@@ -13,6 +16,7 @@ use std::path::Path;
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
+use serde_with::DisplayFromStr;
 
 pub const FARCASTER_MAINNET_ELECTRUM_SERVER: &str = "ssl://blockstream.info:700";
 pub const FARCASTER_MAINNET_MONERO_DAEMON: &str = "http://node.community.rino.io:18081";
@@ -27,6 +31,20 @@ pub const FARCASTER_BIND_IP: &str = "0.0.0.0";
 
 pub const GRPC_BIND_IP_ADDRESS: &str = "127.0.0.1";
 
+pub const PEERD_RECONNECT_INITIAL_DELAY: u64 = 1;
+pub const PEERD_RECONNECT_MAX_DELAY: u64 = 60;
+pub const PEERD_CONNECT_TIMEOUT: u64 = 10;
+
+pub const BATCH_SWEEP_INTERVAL: u64 = 6 * 60 * 60;
+pub const BATCH_SWEEP_THRESHOLD: usize = 20;
+
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 10;
+
+pub const MARKET_MAKER_INTERVAL: u64 = 60;
+pub const MARKET_MAKER_REPRICE_THRESHOLD: f64 = 1.0;
+pub const MARKET_MAKER_CANCEL_TIMELOCK: u32 = 4;
+pub const MARKET_MAKER_PUNISH_TIMELOCK: u32 = 5;
+
 pub const SWAP_MAINNET_BITCOIN_SAFETY: u8 = 7;
 pub const SWAP_MAINNET_BITCOIN_FINALITY: u8 = 6;
 pub const SWAP_MAINNET_MONERO_FINALITY: u8 = 20;
@@ -112,6 +130,105 @@ impl Config {
         }
     }
 
+    /// Returns if the bitcoin sweeps of finished swaps are batched
+    pub fn is_batch_sweep_enable(&self) -> bool {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                batch_sweep: Some(BatchSweepConfig { enable, .. }),
+                ..
+            }) => *enable,
+            _ => false,
+        }
+    }
+
+    /// Returns the interval in seconds between two batch sweeps, if not set return the default
+    /// value
+    pub fn get_batch_sweep_interval(&self) -> u64 {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                batch_sweep:
+                    Some(BatchSweepConfig {
+                        interval: Some(interval),
+                        ..
+                    }),
+                ..
+            }) => *interval,
+            _ => BATCH_SWEEP_INTERVAL,
+        }
+    }
+
+    /// Returns the number of queued sweeps triggering a batch sweep, if not set return the
+    /// default value
+    pub fn get_batch_sweep_threshold(&self) -> usize {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                batch_sweep:
+                    Some(BatchSweepConfig {
+                        threshold: Some(threshold),
+                        ..
+                    }),
+                ..
+            }) => *threshold,
+            _ => BATCH_SWEEP_THRESHOLD,
+        }
+    }
+
+    /// Returns the market maker configuration if enable
+    pub fn get_market_maker_config(&self) -> Option<MarketMakerConfig> {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                market_maker: Some(market_maker),
+                ..
+            }) if market_maker.enable => Some(market_maker.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the limits on the running swaps, if not set return no limit
+    pub fn get_swap_limits(&self) -> SwapLimitsConfig {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                limits: Some(limits),
+                ..
+            }) => limits.clone(),
+            _ => SwapLimitsConfig::default(),
+        }
+    }
+
+    /// Returns the webhooks notified of the swap events, if not set return none
+    pub fn get_webhooks(&self) -> Vec<WebhookConfig> {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                webhooks: Some(webhooks),
+                ..
+            }) => webhooks.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns the reconnect policy for connecting peerd, if not set return the default values
+    pub fn get_reconnect_config(&self) -> ReconnectConfig {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                reconnect: Some(reconnect),
+                ..
+            }) => reconnect.clone(),
+            _ => ReconnectConfig::default(),
+        }
+    }
+
+    /// Returns the timeout in seconds of a single connection attempt to a maker's address, if not
+    /// set return the default value
+    pub fn get_connect_timeout(&self) -> u64 {
+        match &self.farcasterd {
+            Some(FarcasterdConfig {
+                connect_timeout: Some(timeout),
+                ..
+            }) => *timeout,
+            _ => PEERD_CONNECT_TIMEOUT,
+        }
+    }
+
     /// Returns the bind address to use to instanciate listening peerd, either loads the values
     /// from the config file or use the default values '0.0.0.0:7067'
     pub fn get_bind_addr(&self) -> Result<InetSocketAddr, Error> {
@@ -139,6 +256,15 @@ impl Config {
         }
     }
 
+    /// Returns the networks with syncer servers configured, i.e. the networks
+    /// the node is able to swap on
+    pub fn get_supported_networks(&self) -> Vec<Network> {
+        vec![Network::Mainnet, Network::Testnet, Network::Local]
+            .into_iter()
+            .filter(|network| self.get_syncer_servers(*network).is_some())
+            .collect()
+    }
+
     /// Returns the swap config for the specified network and arbitrating/accordant blockchains
     pub fn get_swap_config(
         &self,
@@ -169,6 +295,7 @@ impl Config {
                 Ok(ParsedSwapConfig {
                     arbitrating,
                     accordant,
+                    timeouts: swap.timeouts.clone().unwrap_or_default(),
                 })
             }
             None => {
@@ -181,6 +308,7 @@ impl Config {
                 Ok(ParsedSwapConfig {
                     arbitrating,
                     accordant,
+                    timeouts: SwapTimeouts::default(),
                 })
             }
         }
@@ -226,6 +354,148 @@ pub struct FarcasterdConfig {
     pub bind_ip: Option<String>,
     /// Whether checkpoints should be auto restored at start-up, or not
     pub auto_restore: Option<bool>,
+    /// Sets the reconnect policy of peerd connecting to a counterparty
+    pub reconnect: Option<ReconnectConfig>,
+    /// Sets the timeout in seconds of a connection attempt to each of the
+    /// maker's addresses
+    pub connect_timeout: Option<u64>,
+    /// Sets the batching of the bitcoin sweeps of finished swaps, default to
+    /// sweeping each swap individually
+    pub batch_sweep: Option<BatchSweepConfig>,
+    /// Sets the automated market maker, default to no market making
+    pub market_maker: Option<MarketMakerConfig>,
+    /// Sets the limits on the running swaps, default to no limit
+    pub limits: Option<SwapLimitsConfig>,
+    /// Sets the HTTP endpoints notified of the swap events, default to none
+    pub webhooks: Option<Vec<WebhookConfig>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+pub struct BatchSweepConfig {
+    /// Queue the bitcoin sweeps of finished swaps and sweep them together
+    pub enable: bool,
+    /// Interval in seconds between two batch sweeps
+    pub interval: Option<u64>,
+    /// Number of queued sweeps triggering a batch sweep before the interval
+    /// elapses
+    pub threshold: Option<usize>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+pub struct MarketMakerConfig {
+    /// Run the market maker
+    pub enable: bool,
+    /// Network the deals are made on
+    #[serde_as(as = "DisplayFromStr")]
+    pub network: Network,
+    /// Swap role of the maker in its deals, Bob sells bitcoin and Alice sells
+    /// monero
+    #[serde_as(as = "DisplayFromStr")]
+    pub maker_role: SwapRole,
+    /// Bitcoin address receiving the bitcoin of the swaps
+    #[serde_as(as = "DisplayFromStr")]
+    pub bitcoin_address: bitcoin::Address,
+    /// Monero address receiving the monero of the swaps
+    #[serde_as(as = "DisplayFromStr")]
+    pub monero_address: monero::Address,
+    /// Address advertised in the deals, reachable by the takers
+    #[serde_as(as = "DisplayFromStr")]
+    pub public_addr: InetSocketAddr,
+    /// Number of deals kept open
+    pub open_deals: usize,
+    /// Ladder of bitcoin amounts of the deals in BTC, the open deals cycle
+    /// through it
+    pub amounts: Vec<f64>,
+    /// Pricing strategy of the deals
+    pub pricing: PricingStrategy,
+    /// Rate in XMR per BTC of the fixed pricing strategy
+    pub rate: Option<f64>,
+    /// File containing the market price in XMR per BTC for the spread pricing
+    /// strategy, if not set the price is set with `swap-cli market-price`
+    pub price_file: Option<String>,
+    /// Spread in percent taken over the market price in favor of the maker by
+    /// the spread pricing strategy
+    pub spread: Option<f64>,
+    /// Interval in seconds between two updates of the open deals
+    pub interval: Option<u64>,
+    /// Change in percent of the price over which an open deal is revoked and
+    /// made again at the new price
+    pub reprice_threshold: Option<f64>,
+    /// Bitcoin available to the maker in BTC, deals are not made beyond it
+    /// when selling bitcoin, nor beyond the balance of the auto-funding
+    /// wallet if configured
+    pub bitcoin_inventory: Option<f64>,
+    /// Monero available to the maker in XMR, deals are not made beyond it when
+    /// selling monero, nor beyond the balance of the auto-funding wallet if
+    /// configured
+    pub monero_inventory: Option<f64>,
+    /// Cancel timelock of the deals in blocks
+    pub cancel_timelock: Option<u32>,
+    /// Punish timelock of the deals in blocks
+    pub punish_timelock: Option<u32>,
+    /// Fee strategy of the deals
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub fee_strategy: Option<FeeStrategy<SatPerVByte>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "serde_crate", rename_all = "lowercase")]
+pub enum PricingStrategy {
+    /// Deals are priced at a fixed rate
+    Fixed,
+    /// Deals are priced with a spread over the market price
+    Spread,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "serde_crate")]
+pub struct WebhookConfig {
+    /// URL the JSON notifications are POSTed to
+    pub url: String,
+    /// Secret the notifications are signed with, the hex encoded HMAC-SHA256
+    /// of the body is sent in the `X-Farcaster-Signature` header
+    pub secret: Option<String>,
+    /// Maximum number of delivery attempts of a notification, default to 10
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(crate = "serde_crate")]
+pub struct SwapLimitsConfig {
+    /// Maximum number of swaps running concurrently
+    pub max_swaps: Option<usize>,
+    /// Maximum number of swaps running concurrently with the same counterparty
+    pub max_swaps_per_counterparty: Option<usize>,
+    /// Maximum amount in BTC swapped across the running swaps
+    pub max_bitcoin_exposure: Option<f64>,
+    /// Maximum amount in XMR swapped across the running swaps
+    pub max_monero_exposure: Option<f64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(crate = "serde_crate")]
+pub struct ReconnectConfig {
+    /// Delay in seconds before the first reconnect attempt, doubled after each
+    /// failed attempt
+    pub initial_delay: u64,
+    /// Maximum delay in seconds between two reconnect attempts
+    pub max_delay: u64,
+    /// Number of attempts after which peerd gives up, retries forever if None
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_delay: PEERD_RECONNECT_INITIAL_DELAY,
+            max_delay: PEERD_RECONNECT_MAX_DELAY,
+            max_attempts: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -235,6 +505,30 @@ pub struct SwapConfig {
     pub bitcoin: Networked<Option<ArbConfig>>,
     /// Swap parameters for the Monero blockchain per network
     pub monero: Networked<Option<AccConfig>>,
+    /// Timeouts of the states awaiting the counterparty or the funding before
+    /// the arbitrating lock
+    pub timeouts: Option<SwapTimeouts>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(crate = "serde_crate")]
+pub struct SwapTimeouts {
+    /// Bob awaiting the counterparty to continue after the reveal
+    pub bob_reveal: Option<StateTimeout>,
+    /// Bob awaiting the funding of the swap
+    pub bob_fee_estimated: Option<StateTimeout>,
+    /// Alice awaiting the core arbitrating setup of the counterparty
+    pub alice_reveal: Option<StateTimeout>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "serde_crate")]
+pub struct StateTimeout {
+    /// Seconds after which the swap is aborted if still in the state
+    pub seconds: Option<u64>,
+    /// Number of Bitcoin blocks after which the swap is aborted if still in the
+    /// state
+    pub blocks: Option<u32>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -244,6 +538,8 @@ pub struct ParsedSwapConfig {
     pub arbitrating: ArbConfig,
     /// Swap parameters for an accordant blockchain
     pub accordant: AccConfig,
+    /// Timeouts of the swap states before the arbitrating lock
+    pub timeouts: SwapTimeouts,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -282,11 +578,18 @@ impl ArbConfig {
     }
 }
 
+#[serde_as]
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(crate = "serde_crate")]
 pub struct AccConfig {
     /// Number of confirmations required to consider a transaction final
     pub finality: u8,
+    /// Default destinations the swept funds are split between besides the
+    /// swap's target address, formatted as `<address>:<percent>%` or
+    /// `<address>:<amount>`
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sweep_split: Vec<SweepDestination>,
 }
 
 impl AccConfig {
@@ -303,12 +606,14 @@ impl AccConfig {
     fn xmr_mainnet_default() -> Self {
         AccConfig {
             finality: SWAP_MAINNET_MONERO_FINALITY,
+            sweep_split: vec![],
         }
     }
 
     fn xmr_testnet_default() -> Self {
         AccConfig {
             finality: SWAP_TESTNET_MONERO_FINALITY,
+            sweep_split: vec![],
         }
     }
 }
@@ -318,6 +623,7 @@ impl From<ArbConfig> for AccConfig {
     fn from(arb: ArbConfig) -> Self {
         Self {
             finality: arb.finality,
+            sweep_split: vec![],
         }
     }
 }
@@ -359,6 +665,14 @@ pub struct AutoFundingServers {
     pub bitcoin_rpc_pass: Option<String>,
     /// The monero wallet rpc url to auto-fund monero
     pub monero_rpc_wallet: String,
+    /// Maximum amount of bitcoin, in BTC, auto-funded for a single swap
+    pub bitcoin_max_per_swap: Option<f64>,
+    /// Maximum amount of bitcoin, in BTC, auto-funded over the last 24 hours
+    pub bitcoin_daily_limit: Option<f64>,
+    /// Maximum amount of monero, in XMR, auto-funded for a single swap
+    pub monero_max_per_swap: Option<f64>,
+    /// Maximum amount of monero, in XMR, auto-funded over the last 24 hours
+    pub monero_daily_limit: Option<f64>,
 }
 
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
@@ -414,6 +728,7 @@ impl Default for SwapConfig {
                 testnet: Some(AccConfig::xmr_testnet_default()),
                 local: None,
             },
+            timeouts: None,
         }
     }
 }
@@ -428,6 +743,12 @@ impl Default for FarcasterdConfig {
             // write the default port and ip in the generated config
             bind_port: Some(FARCASTER_BIND_PORT),
             bind_ip: Some(FARCASTER_BIND_IP.to_string()),
+            reconnect: None,
+            connect_timeout: None,
+            batch_sweep: None,
+            market_maker: None,
+            limits: None,
+            webhooks: None,
         }
     }
 }
@@ -4,10 +4,12 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-use farcaster_core::blockchain::Blockchain;
+use farcaster_core::blockchain::{Blockchain, Network};
 use farcaster_core::swap::btcxmr::Deal;
 use farcaster_core::swap::SwapId;
+use farcaster_core::Uuid;
 use lmdb::{Cursor, Transaction as LMDBTransaction};
+use std::convert::TryInto;
 use std::io::Cursor as IoCursor;
 use std::path::PathBuf;
 use strict_encoding::{StrictDecode, StrictEncode};
@@ -16,17 +18,27 @@ use crate::bus::{
     ctl::{Checkpoint, CtlMsg},
     info::{Address, InfoMsg},
     info::{BitcoinAddressSwapIdPair, DealStatusSelector, MoneroAddressSwapIdPair},
-    AddressSecretKey, BitcoinSecretKeyInfo, BusMsg, CheckpointEntry, DealStatus, DealStatusPair,
-    Failure, FailureCode, MoneroSecretKeyInfo, Outcome, ServiceBus,
+    p2p::MAX_CHAT_MESSAGES,
+    AddressSecretKey, AutoFundingRecord, BitcoinSecretKeyInfo, BusMsg, ChatRecord, CheckpointEntry,
+    DealStatus, DealStatusPair, EventFilter, Failure, FailureCode, MakerAddresses,
+    MoneroSecretKeyInfo, NodeEvent, OpenDeal, Outcome, OutcomeRecord, QueuedSweep, ServiceBus,
+    WebhookDelivery,
 };
+use crate::service::terminate_service;
+use crate::syncerd::{SweepBitcoinAddress, SweepBitcoinBatch};
+use crate::watchtowerd::WatchtowerPackage;
 use crate::{swapd::CheckpointSwapd, Endpoints};
 use crate::{CtlServer, Error, LogStyle, Service, ServiceConfig, ServiceId};
 use microservices::esb::{self, Handler};
 
 pub fn run(config: ServiceConfig, data_dir: PathBuf) -> Result<(), Error> {
+    let mut database = Database::new(data_dir).unwrap();
+    database.migrate_legacy_tables()?;
+    let next_event_id = database.last_event_id()?.map_or(0, |id| id + 1);
     let runtime = Runtime {
         identity: ServiceId::Database,
-        database: Database::new(data_dir).unwrap(),
+        database,
+        next_event_id,
     };
 
     Service::run(config, runtime, false)
@@ -35,6 +47,8 @@ pub fn run(config: ServiceConfig, data_dir: PathBuf) -> Result<(), Error> {
 pub struct Runtime {
     identity: ServiceId,
     database: Database,
+    /// Sequence number of the next event appended to the event log
+    next_event_id: u64,
 }
 
 impl Runtime {}
@@ -86,6 +100,11 @@ impl Runtime {
                 debug!("Received Hello from {}", source);
             }
 
+            CtlMsg::Terminate if source == ServiceId::Farcasterd => {
+                info!("Terminating {}", self.identity());
+                terminate_service();
+            }
+
             CtlMsg::Checkpoint(Checkpoint { swap_id, state }) => {
                 let info = CheckpointEntry {
                     swap_id,
@@ -103,15 +122,22 @@ impl Runtime {
                 };
                 let mut state_encoded = vec![];
                 state.strict_encode(&mut state_encoded)?;
-                self.database.set_checkpoint_state(&key, &state_encoded)?;
+                self.database.set_checkpoint_state(
+                    &key,
+                    &state_encoded,
+                    info.deal.parameters.network,
+                )?;
                 debug!("{} | checkpoint set", swap_id.swap_id());
             }
 
-            CtlMsg::RestoreCheckpoint(CheckpointEntry { swap_id, .. }) => {
-                match self.database.get_checkpoint_state(&CheckpointKey {
-                    swap_id,
-                    service_id: ServiceId::Swap(swap_id),
-                }) {
+            CtlMsg::RestoreCheckpoint(CheckpointEntry { swap_id, deal, .. }) => {
+                match self.database.get_checkpoint_state(
+                    &CheckpointKey {
+                        swap_id,
+                        service_id: ServiceId::Swap(swap_id),
+                    },
+                    Some(deal.parameters.network),
+                ) {
                     Ok(raw_state) => {
                         match CheckpointSwapd::strict_decode(IoCursor::new(raw_state)) {
                             Ok(state) => {
@@ -149,37 +175,82 @@ impl Runtime {
                 if let Err(err) = self.database.delete_checkpoint_info(swap_id) {
                     debug!("{} | Did not delete checkpoint info: {}", swap_id, err);
                 }
+                if let Err(err) = self.database.delete_maker_addresses(&swap_id) {
+                    debug!("{} | Did not delete maker addresses: {}", swap_id, err);
+                }
+                if let Err(err) = self.database.delete_chat_records(&swap_id) {
+                    debug!("{} | Did not delete chat records: {}", swap_id, err);
+                }
             }
 
-            CtlMsg::SetAddressSecretKey(AddressSecretKey::Bitcoin {
-                address,
-                secret_key_info,
-            }) => {
+            CtlMsg::SetAddressSecretKey(
+                AddressSecretKey::Bitcoin {
+                    address,
+                    secret_key_info,
+                },
+                network,
+            ) => {
                 self.database
-                    .set_bitcoin_address(&address, &secret_key_info)?;
+                    .set_bitcoin_address(&address, &secret_key_info, network)?;
             }
 
-            CtlMsg::SetAddressSecretKey(AddressSecretKey::Monero {
-                address,
-                secret_key_info,
-            }) => {
+            CtlMsg::SetAddressSecretKey(
+                AddressSecretKey::Monero {
+                    address,
+                    secret_key_info,
+                },
+                network,
+            ) => {
                 self.database
-                    .set_monero_address(&address, &secret_key_info)?;
+                    .set_monero_address(&address, &secret_key_info, network)?;
             }
 
             CtlMsg::SetDealStatus(DealStatusPair { deal, status }) => {
                 self.database.set_deal_status(&deal, &status)?;
             }
 
+            CtlMsg::SetChatRecord(record) => {
+                self.database.add_chat_record(&record)?;
+            }
+
+            CtlMsg::LogEvent(event) => {
+                let event = NodeEvent {
+                    id: self.next_event_id,
+                    ..event
+                };
+                self.database.add_event(&event)?;
+                self.next_event_id += 1;
+            }
+
+            CtlMsg::SetAutoFundingRecord(record) => {
+                self.database.add_auto_funding_record(&record)?;
+            }
+
+            CtlMsg::SetOutcomeRecord(record) => {
+                self.database.set_outcome_record(&record)?;
+            }
+
+            CtlMsg::QueueSweep(sweep) => {
+                self.database.set_queued_sweep(&sweep)?;
+            }
+
+            CtlMsg::RemoveQueuedSweeps(addresses) => {
+                for address in addresses.iter() {
+                    if let Err(err) = self.database.delete_queued_sweep(address) {
+                        debug!("Did not delete queued sweep of {}: {}", address, err);
+                    }
+                }
+            }
+
             CtlMsg::CleanDanglingDeals => {
                 let checkpointed_pub_deals: Vec<Deal> = self
                     .database
-                    .get_all_checkpoint_info()?
+                    .get_all_checkpoint_info(None)?
                     .drain(..)
                     .map(|info| info.deal)
                     .collect();
                 self.database
-                    .get_deals(DealStatusSelector::InProgress)?
+                    .get_deals(DealStatusSelector::InProgress, None)?
                     .drain(..)
                     .filter_map(|o| {
                         if !checkpointed_pub_deals.contains(&o.deal) {
@@ -193,6 +264,44 @@ impl Runtime {
                             .set_deal_status(&deal, &DealStatus::Ended(Outcome::FailureAbort))
                     })
                     .collect::<Result<_, _>>()?;
+                // open deals are kept if persisted, they are made again by
+                // farcasterd until they expire
+                let persisted_open_deals: Vec<Deal> = self
+                    .database
+                    .get_open_deals()?
+                    .drain(..)
+                    .map(|open_deal| open_deal.deal)
+                    .collect();
+                for o in self.database.get_deals(DealStatusSelector::Open, None)? {
+                    if !persisted_open_deals.contains(&o.deal) {
+                        self.database
+                            .set_deal_status(&o.deal, &DealStatus::Ended(Outcome::FailureAbort))?;
+                    }
+                }
+            }
+
+            CtlMsg::SetOpenDeal(open_deal) => {
+                self.database.set_open_deal(&open_deal)?;
+            }
+
+            CtlMsg::RemoveOpenDeal(deal) => {
+                if let Err(err) = self.database.delete_open_deal(&deal) {
+                    debug!("Did not delete open deal {}: {}", deal.id(), err);
+                }
+            }
+
+            CtlMsg::SetMakerAddresses(maker_addresses) => {
+                self.database.set_maker_addresses(&maker_addresses)?;
+            }
+
+            CtlMsg::QueueWebhookDelivery(delivery) => {
+                self.database.set_webhook_delivery(&delivery)?;
+            }
+
+            CtlMsg::RemoveWebhookDelivery(id) => {
+                if let Err(err) = self.database.delete_webhook_delivery(&id) {
+                    debug!("Did not delete webhook delivery {}: {}", id, err);
+                }
             }
 
             _ => {
@@ -210,8 +319,8 @@ impl Runtime {
         request: InfoMsg,
     ) -> Result<(), Error> {
         match request {
-            InfoMsg::ListDeals(selector) => {
-                let deal_status_pairs = self.database.get_deals(selector)?;
+            InfoMsg::ListDeals(selector, network) => {
+                let deal_status_pairs = self.database.get_deals(selector, network)?;
                 self.send_client_info(
                     endpoints,
                     source,
@@ -219,8 +328,8 @@ impl Runtime {
                 )?;
             }
 
-            InfoMsg::RetrieveAllCheckpointInfo => {
-                match self.database.get_all_checkpoint_info() {
+            InfoMsg::RetrieveAllCheckpointInfo(network) => {
+                match self.database.get_all_checkpoint_info(network) {
                     Ok(list) => {
                         self.send_client_info(
                             endpoints,
@@ -264,6 +373,135 @@ impl Runtime {
                 }
             }
 
+            InfoMsg::GetOutcomeRecord(swap_id) => {
+                match self.database.get_outcome_record(&swap_id) {
+                    Ok(record) => {
+                        self.send_client_info(endpoints, source, InfoMsg::OutcomeRecord(record))?;
+                    }
+                    Err(_) => {
+                        self.send_client_ctl(
+                            endpoints,
+                            source,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: format!("No outcome found for swap {}", swap_id),
+                            }),
+                        )?;
+                    }
+                }
+            }
+
+            InfoMsg::GetWatchtowerPackage(swap_id) => {
+                let package = self
+                    .database
+                    .get_checkpoint_state(
+                        &CheckpointKey {
+                            swap_id,
+                            service_id: ServiceId::Swap(swap_id),
+                        },
+                        None,
+                    )
+                    .map_err(|_| {
+                        Error::Farcaster(format!("No checkpoint found for swap {}", swap_id))
+                    })
+                    .and_then(|raw_state| {
+                        CheckpointSwapd::strict_decode(IoCursor::new(raw_state)).map_err(Into::into)
+                    })
+                    .and_then(|state| WatchtowerPackage::from_checkpoint(swap_id, &state))
+                    .and_then(|package| package.encode());
+                match package {
+                    Ok(package) => {
+                        self.send_client_info(
+                            endpoints,
+                            source,
+                            InfoMsg::WatchtowerPackage(package),
+                        )?;
+                    }
+                    Err(err) => {
+                        self.send_client_ctl(
+                            endpoints,
+                            source,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: err.to_string(),
+                            }),
+                        )?;
+                    }
+                }
+            }
+
+            InfoMsg::GetAutoFundingRecords => {
+                let records = self.database.get_auto_funding_records()?;
+                self.send_client_info(
+                    endpoints,
+                    source,
+                    InfoMsg::AutoFundingRecordList(records.into()),
+                )?;
+            }
+
+            InfoMsg::RetrieveOpenDeals => {
+                let open_deals = self.database.get_open_deals()?;
+                self.send_client_info(endpoints, source, InfoMsg::OpenDeals(open_deals))?;
+            }
+
+            InfoMsg::RetrieveMakerAddresses => {
+                let maker_addresses = self.database.get_all_maker_addresses()?;
+                self.send_client_info(
+                    endpoints,
+                    source,
+                    InfoMsg::MakerAddressesList(maker_addresses),
+                )?;
+            }
+
+            InfoMsg::GetWebhookDeliveries => {
+                let deliveries = self.database.get_webhook_deliveries()?;
+                self.send_client_info(endpoints, source, InfoMsg::WebhookDeliveries(deliveries))?;
+            }
+
+            InfoMsg::GetSweepBatch => {
+                let sweeps = self
+                    .database
+                    .get_queued_sweeps()?
+                    .drain(..)
+                    .filter_map(|sweep| {
+                        match self
+                            .database
+                            .get_bitcoin_address_secret_key(&sweep.source_address)
+                        {
+                            Ok(info) => Some(SweepBitcoinAddress {
+                                source_secret_key: info.secret_key,
+                                source_address: sweep.source_address,
+                                destination_address: sweep.destination_address,
+                            }),
+                            Err(err) => {
+                                warn!(
+                                    "{} | Not batching the sweep of {}, no secret key found: {}",
+                                    sweep.swap_id.swap_id(),
+                                    sweep.source_address,
+                                    err
+                                );
+                                None
+                            }
+                        }
+                    })
+                    .collect();
+                self.send_client_info(
+                    endpoints,
+                    source,
+                    InfoMsg::SweepBatch(SweepBitcoinBatch { sweeps }),
+                )?;
+            }
+
+            InfoMsg::GetChatMessages(swap_id) => {
+                let records = self.database.get_chat_records(&swap_id)?;
+                self.send_client_info(endpoints, source, InfoMsg::ChatMessageList(records.into()))?;
+            }
+
+            InfoMsg::GetEvents(filter) => {
+                let events = self.database.get_events(&filter)?;
+                self.send_client_info(endpoints, source, InfoMsg::EventList(events.into()))?;
+            }
+
             InfoMsg::GetAddressSecretKey(Address::Monero(address)) => {
                 match self.database.get_monero_address_secret_key(&address) {
                     Err(_) => {
@@ -315,8 +553,8 @@ impl Runtime {
                 }
             }
 
-            InfoMsg::GetAddresses(Blockchain::Bitcoin) => {
-                let mut addresses = self.database.get_all_bitcoin_addresses()?;
+            InfoMsg::GetAddresses(Blockchain::Bitcoin, network) => {
+                let mut addresses = self.database.get_all_bitcoin_addresses(network)?;
                 self.send_client_info(
                     endpoints,
                     source,
@@ -329,8 +567,8 @@ impl Runtime {
                 )?;
             }
 
-            InfoMsg::GetAddresses(Blockchain::Monero) => {
-                let mut addresses = self.database.get_all_monero_addresses()?;
+            InfoMsg::GetAddresses(Blockchain::Monero, network) => {
+                let mut addresses = self.database.get_all_monero_addresses(network)?;
                 self.send_client_info(
                     endpoints,
                     source,
@@ -376,31 +614,567 @@ struct CheckpointKey {
 
 struct Database(lmdb::Environment);
 
-const LMDB_CHECKPOINTS: &str = "checkpoints";
-const LMDB_CHECKPOINT_INFOS: &str = "checkpoint_infos";
-const LMDB_BITCOIN_ADDRESSES: &str = "bitcoin_addresses";
-const LMDB_MONERO_ADDRESSES: &str = "monero_addresses";
-const LMDB_DEAL_HISTORY: &str = "deal_history";
+/// Position the cursor on the first key greater or equal to the given key,
+/// returns false if there is none. Guards `iter_from`, which panics when
+/// seeking past the last key.
+fn seek(cursor: &lmdb::RoCursor, key: &[u8]) -> Result<bool, Error> {
+    match cursor.get(Some(key), None, lmdb_sys::MDB_SET_RANGE) {
+        Ok(_) => Ok(true),
+        Err(lmdb::Error::NotFound) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Last key made of the prefix followed by eight bytes, if any
+fn last_key_with_prefix(cursor: &lmdb::RoCursor, prefix: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let mut upper = prefix.to_vec();
+    upper.extend([0xff; 8]);
+    let found = match cursor.get(Some(&upper), None, lmdb_sys::MDB_SET_RANGE) {
+        Ok(_) => cursor.get(None, None, lmdb_sys::MDB_PREV),
+        Err(lmdb::Error::NotFound) => cursor.get(None, None, lmdb_sys::MDB_LAST),
+        Err(err) => return Err(err.into()),
+    };
+    match found {
+        Ok((Some(key), _)) if key.starts_with(prefix) => Ok(Some(key.to_vec())),
+        Ok(_) | Err(lmdb::Error::NotFound) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The network of the swap prefixes the keys of these tables, each network
+/// reads and lists its own entries only
+const LMDB_CHECKPOINTS: &str = "network_checkpoints";
+const LMDB_CHECKPOINT_INFOS: &str = "network_checkpoint_infos";
+const LMDB_BITCOIN_ADDRESSES: &str = "network_bitcoin_addresses";
+const LMDB_MONERO_ADDRESSES: &str = "network_monero_addresses";
+const LMDB_DEAL_HISTORY: &str = "network_deal_history";
+/// The same tables before they were partitioned by network, their entries are
+/// moved on startup
+const LMDB_LEGACY_CHECKPOINTS: &str = "checkpoints";
+const LMDB_LEGACY_CHECKPOINT_INFOS: &str = "checkpoint_infos";
+const LMDB_LEGACY_BITCOIN_ADDRESSES: &str = "bitcoin_addresses";
+const LMDB_LEGACY_MONERO_ADDRESSES: &str = "monero_addresses";
+const LMDB_LEGACY_DEAL_HISTORY: &str = "deal_history";
+const LMDB_CHAT_MESSAGES: &str = "chat_messages";
+const LMDB_AUTO_FUNDING: &str = "auto_funding";
+const LMDB_OUTCOMES: &str = "outcomes";
+const LMDB_QUEUED_SWEEPS: &str = "queued_sweeps";
+const LMDB_OPEN_DEALS: &str = "open_deals";
+const LMDB_WEBHOOK_DELIVERIES: &str = "webhook_deliveries";
+const LMDB_EVENTS: &str = "events";
+const LMDB_MAKER_ADDRESSES: &str = "maker_addresses";
+
+/// A raw key and value pair
+type Entry = (Vec<u8>, Vec<u8>);
+
+const NETWORKS: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Local];
+
+fn network_key(network: Network, key: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut network_key = vec![];
+    network.strict_encode(&mut network_key)?;
+    network_key.extend_from_slice(key);
+    Ok(network_key)
+}
 
 impl Database {
     fn new(path: PathBuf) -> Result<Database, lmdb::Error> {
         let env = lmdb::Environment::new()
             .set_map_size(10485760 * 1024 * 64)
-            .set_max_dbs(10)
+            .set_max_dbs(32)
             .open(&path)?;
         env.create_db(Some(LMDB_CHECKPOINTS), lmdb::DatabaseFlags::empty())?;
         env.create_db(Some(LMDB_CHECKPOINT_INFOS), lmdb::DatabaseFlags::empty())?;
         env.create_db(Some(LMDB_BITCOIN_ADDRESSES), lmdb::DatabaseFlags::empty())?;
         env.create_db(Some(LMDB_DEAL_HISTORY), lmdb::DatabaseFlags::empty())?;
         env.create_db(Some(LMDB_MONERO_ADDRESSES), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_CHAT_MESSAGES), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_AUTO_FUNDING), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_OUTCOMES), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_QUEUED_SWEEPS), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_OPEN_DEALS), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_WEBHOOK_DELIVERIES), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_EVENTS), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_MAKER_ADDRESSES), lmdb::DatabaseFlags::empty())?;
+        env.create_db(Some(LMDB_LEGACY_CHECKPOINTS), lmdb::DatabaseFlags::empty())?;
+        env.create_db(
+            Some(LMDB_LEGACY_CHECKPOINT_INFOS),
+            lmdb::DatabaseFlags::empty(),
+        )?;
+        env.create_db(
+            Some(LMDB_LEGACY_BITCOIN_ADDRESSES),
+            lmdb::DatabaseFlags::empty(),
+        )?;
+        env.create_db(Some(LMDB_LEGACY_DEAL_HISTORY), lmdb::DatabaseFlags::empty())?;
+        env.create_db(
+            Some(LMDB_LEGACY_MONERO_ADDRESSES),
+            lmdb::DatabaseFlags::empty(),
+        )?;
         Ok(Database(env))
     }
 
+    /// Move the entries of the tables shared by all networks to their network
+    /// partitioned tables. The network of a swap is the one of its deal, a
+    /// legacy address without known swap falls back to its encoded network.
+    fn migrate_legacy_tables(&mut self) -> Result<(), Error> {
+        let mut swap_networks = std::collections::HashMap::new();
+        for (_, val) in self.table_entries(LMDB_OUTCOMES)? {
+            let record = OutcomeRecord::strict_decode(IoCursor::new(val))?;
+            swap_networks.insert(record.swap_id, record.deal.parameters.network);
+        }
+        for (_, val) in self.table_entries(LMDB_LEGACY_CHECKPOINT_INFOS)? {
+            let entry = CheckpointEntry::strict_decode(IoCursor::new(val))?;
+            swap_networks.insert(entry.swap_id, entry.deal.parameters.network);
+        }
+        let swap_network = |swap_id: &Option<SwapId>| {
+            swap_id.and_then(|swap_id| swap_networks.get(&swap_id).copied())
+        };
+
+        self.migrate_legacy_table(LMDB_LEGACY_DEAL_HISTORY, LMDB_DEAL_HISTORY, |key, _| {
+            Ok(Some(
+                Deal::strict_decode(IoCursor::new(key))?.parameters.network,
+            ))
+        })?;
+        self.migrate_legacy_table(
+            LMDB_LEGACY_CHECKPOINT_INFOS,
+            LMDB_CHECKPOINT_INFOS,
+            |_, val| {
+                Ok(Some(
+                    CheckpointEntry::strict_decode(IoCursor::new(val))?
+                        .deal
+                        .parameters
+                        .network,
+                ))
+            },
+        )?;
+        self.migrate_legacy_table(LMDB_LEGACY_CHECKPOINTS, LMDB_CHECKPOINTS, |key, val| {
+            let checkpoint_key = CheckpointKey::strict_decode(IoCursor::new(key))?;
+            Ok(swap_network(&Some(checkpoint_key.swap_id)).or_else(|| {
+                CheckpointSwapd::strict_decode(IoCursor::new(val))
+                    .map(|state| state.deal.parameters.network)
+                    .ok()
+            }))
+        })?;
+        self.migrate_legacy_table(
+            LMDB_LEGACY_BITCOIN_ADDRESSES,
+            LMDB_BITCOIN_ADDRESSES,
+            |key, val| {
+                let address = bitcoin::Address::strict_decode(IoCursor::new(key))?;
+                let info = BitcoinSecretKeyInfo::strict_decode(IoCursor::new(val))?;
+                Ok(Some(
+                    swap_network(&info.swap_id).unwrap_or_else(|| address.network.into()),
+                ))
+            },
+        )?;
+        self.migrate_legacy_table(
+            LMDB_LEGACY_MONERO_ADDRESSES,
+            LMDB_MONERO_ADDRESSES,
+            |key, val| {
+                let address = monero::Address::from_bytes(key)?;
+                let info = MoneroSecretKeyInfo::strict_decode(IoCursor::new(val))?;
+                Ok(Some(
+                    swap_network(&info.swap_id).unwrap_or_else(|| address.network.into()),
+                ))
+            },
+        )
+    }
+
+    /// Move the entries of a legacy table whose network is known under their
+    /// network prefix, the others are left in place
+    fn migrate_legacy_table<F>(
+        &mut self,
+        legacy: &str,
+        table: &str,
+        network: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&[u8], &[u8]) -> Result<Option<Network>, Error>,
+    {
+        let entries = self.table_entries(legacy)?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let legacy_db = self.0.open_db(Some(legacy))?;
+        let db = self.0.open_db(Some(table))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut migrated = 0;
+        for (key, val) in entries.iter() {
+            match network(key, val) {
+                Ok(Some(network)) => {
+                    tx.put(
+                        db,
+                        &network_key(network, key)?,
+                        val,
+                        lmdb::WriteFlags::empty(),
+                    )?;
+                    tx.del(legacy_db, key, None)?;
+                    migrated += 1;
+                }
+                Ok(None) => warn!("Could not find the network of a {} entry", legacy),
+                Err(err) => warn!("Could not migrate a {} entry: {}", legacy, err),
+            }
+        }
+        tx.commit()?;
+        info!(
+            "Migrated {} of {} {} entries to their network",
+            migrated,
+            entries.len(),
+            legacy
+        );
+        Ok(())
+    }
+
+    fn table_entries(&mut self, table: &str) -> Result<Vec<Entry>, Error> {
+        let db = self.0.open_db(Some(table))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let entries = cursor
+            .iter()
+            .map(|(key, val)| (key.to_vec(), val.to_vec()))
+            .collect();
+        drop(cursor);
+        tx.abort();
+        Ok(entries)
+    }
+
+    fn add_auto_funding_record(&mut self, record: &AutoFundingRecord) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_AUTO_FUNDING))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        // big endian timestamp first so that records are iterated in order
+        let mut key = record.timestamp.to_be_bytes().to_vec();
+        record.swap_id.strict_encode(&mut key)?;
+        record.blockchain.strict_encode(&mut key)?;
+        let mut val = vec![];
+        record.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_auto_funding_records(&mut self) -> Result<Vec<AutoFundingRecord>, Error> {
+        let db = self.0.open_db(Some(LMDB_AUTO_FUNDING))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = cursor
+            .iter()
+            .map(|(_, val)| {
+                AutoFundingRecord::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+            })
+            .collect();
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn set_outcome_record(&mut self, record: &OutcomeRecord) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_OUTCOMES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        record.swap_id.strict_encode(&mut key)?;
+        let mut val = vec![];
+        record.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_outcome_record(&mut self, swap_id: &SwapId) -> Result<OutcomeRecord, Error> {
+        let db = self.0.open_db(Some(LMDB_OUTCOMES))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut key = vec![];
+        swap_id.strict_encode(&mut key)?;
+        let val = tx.get(db, &key)?.to_vec();
+        tx.abort();
+        Ok(OutcomeRecord::strict_decode(IoCursor::new(val))?)
+    }
+
+    fn set_queued_sweep(&mut self, sweep: &QueuedSweep) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_QUEUED_SWEEPS))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        sweep.source_address.strict_encode(&mut key)?;
+        let mut val = vec![];
+        sweep.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_queued_sweeps(&mut self) -> Result<Vec<QueuedSweep>, Error> {
+        let db = self.0.open_db(Some(LMDB_QUEUED_SWEEPS))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = cursor
+            .iter()
+            .map(|(_, val)| {
+                QueuedSweep::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+            })
+            .collect();
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn delete_queued_sweep(&mut self, source_address: &bitcoin::Address) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_QUEUED_SWEEPS))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        source_address.strict_encode(&mut key)?;
+        tx.del(db, &key, None)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn set_open_deal(&mut self, open_deal: &OpenDeal) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_OPEN_DEALS))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        open_deal.deal.strict_encode(&mut key)?;
+        let mut val = vec![];
+        open_deal.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_open_deals(&mut self) -> Result<Vec<OpenDeal>, Error> {
+        let db = self.0.open_db(Some(LMDB_OPEN_DEALS))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = cursor
+            .iter()
+            .map(|(_, val)| {
+                OpenDeal::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+            })
+            .collect();
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn delete_open_deal(&mut self, deal: &Deal) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_OPEN_DEALS))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        deal.strict_encode(&mut key)?;
+        tx.del(db, &key, None)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn set_maker_addresses(&mut self, maker_addresses: &MakerAddresses) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_MAKER_ADDRESSES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        maker_addresses.swap_id.strict_encode(&mut key)?;
+        let mut val = vec![];
+        maker_addresses.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_all_maker_addresses(&mut self) -> Result<Vec<MakerAddresses>, Error> {
+        let db = self.0.open_db(Some(LMDB_MAKER_ADDRESSES))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = cursor
+            .iter()
+            .map(|(_, val)| {
+                MakerAddresses::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+            })
+            .collect();
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn delete_maker_addresses(&mut self, swap_id: &SwapId) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_MAKER_ADDRESSES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        swap_id.strict_encode(&mut key)?;
+        tx.del(db, &key, None)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn set_webhook_delivery(&mut self, delivery: &WebhookDelivery) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_WEBHOOK_DELIVERIES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        delivery.id.strict_encode(&mut key)?;
+        let mut val = vec![];
+        delivery.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_webhook_deliveries(&mut self) -> Result<Vec<WebhookDelivery>, Error> {
+        let db = self.0.open_db(Some(LMDB_WEBHOOK_DELIVERIES))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = cursor
+            .iter()
+            .map(|(_, val)| {
+                WebhookDelivery::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+            })
+            .collect();
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn delete_webhook_delivery(&mut self, id: &Uuid) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_WEBHOOK_DELIVERIES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut key = vec![];
+        id.strict_encode(&mut key)?;
+        tx.del(db, &key, None)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn add_event(&mut self, event: &NodeEvent) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_EVENTS))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        // big endian sequence number so that events are iterated in order
+        let key = event.id.to_be_bytes();
+        let mut val = vec![];
+        event.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The events logged after `filter.after` are read from its successor key
+    /// on, a polling client only reads the events logged since its last poll
+    fn get_events(&mut self, filter: &EventFilter) -> Result<Vec<NodeEvent>, Error> {
+        let first_id = match filter.after {
+            Some(after) => match after.checked_add(1) {
+                Some(first_id) => first_id,
+                None => return Ok(vec![]),
+            },
+            None => 0,
+        };
+        let start = first_id.to_be_bytes();
+        let db = self.0.open_db(Some(LMDB_EVENTS))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = match seek(&cursor, &start)? {
+            true => cursor
+                .iter_from(start)
+                .map(|(_, val)| NodeEvent::strict_decode(IoCursor::new(val.to_vec())))
+                .filter(|event| event.as_ref().map_or(true, |event| filter.matches(event)))
+                .map(|event| event.map_err(Into::into))
+                .collect(),
+            false => Ok(vec![]),
+        };
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn last_event_id(&mut self) -> Result<Option<u64>, Error> {
+        let db = self.0.open_db(Some(LMDB_EVENTS))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = match cursor.iter().last() {
+            Some((key, _)) => Some(u64::from_be_bytes(key.try_into().map_err(|_| {
+                Error::Farcaster("Invalid event log sequence number".to_string())
+            })?)),
+            None => None,
+        };
+        drop(cursor);
+        tx.abort();
+        Ok(res)
+    }
+
+    /// Chat records are keyed by swap id followed by their big endian
+    /// sequence number in the swap, keeping them in order. The records over
+    /// the cap of the swap are dropped.
+    fn add_chat_record(&mut self, record: &ChatRecord) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_CHAT_MESSAGES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut prefix = vec![];
+        record.swap_id.strict_encode(&mut prefix)?;
+        let cursor = tx.open_ro_cursor(db)?;
+        let seq = match last_key_with_prefix(&cursor, &prefix)? {
+            Some(last_key) => {
+                let seq_bytes: [u8; 8] = last_key[prefix.len()..]
+                    .try_into()
+                    .map_err(|_| Error::Farcaster("Invalid chat record key".to_string()))?;
+                u64::from_be_bytes(seq_bytes) + 1
+            }
+            None => 0,
+        };
+        drop(cursor);
+        if seq >= MAX_CHAT_MESSAGES {
+            warn!(
+                "{} | Dropping chat record, the swap has {} already",
+                record.swap_id.swap_id(),
+                MAX_CHAT_MESSAGES
+            );
+            return Ok(());
+        }
+        let mut key = prefix;
+        key.extend(seq.to_be_bytes());
+        let mut val = vec![];
+        record.strict_encode(&mut val)?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_chat_records(&mut self, swap_id: &SwapId) -> Result<Vec<ChatRecord>, Error> {
+        let db = self.0.open_db(Some(LMDB_CHAT_MESSAGES))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut prefix = vec![];
+        swap_id.strict_encode(&mut prefix)?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let res = match seek(&cursor, &prefix)? {
+            true => cursor
+                .iter_from(&prefix)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .map(|(_, val)| {
+                    ChatRecord::strict_decode(IoCursor::new(val.to_vec())).map_err(Into::into)
+                })
+                .collect(),
+            false => Ok(vec![]),
+        };
+        drop(cursor);
+        tx.abort();
+        res
+    }
+
+    fn delete_chat_records(&mut self, swap_id: &SwapId) -> Result<(), Error> {
+        let db = self.0.open_db(Some(LMDB_CHAT_MESSAGES))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut prefix = vec![];
+        swap_id.strict_encode(&mut prefix)?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let keys: Vec<Vec<u8>> = match seek(&cursor, &prefix)? {
+            true => cursor
+                .iter_from(&prefix)
+                .take_while(|(key, _)| key.starts_with(&prefix))
+                .map(|(key, _)| key.to_vec())
+                .collect(),
+            false => vec![],
+        };
+        drop(cursor);
+        for key in keys {
+            tx.del(db, &key, None)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     fn set_deal_status(&mut self, deal: &Deal, status: &DealStatus) -> Result<(), Error> {
         let db = self.0.open_db(Some(LMDB_DEAL_HISTORY))?;
         let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         deal.strict_encode(&mut key)?;
+        let key = network_key(deal.parameters.network, &key)?;
         if tx.get(db, &key).is_ok() {
             tx.del(db, &key, None)?;
         }
@@ -411,14 +1185,15 @@ impl Database {
         Ok(())
     }
 
-    fn get_deals(&mut self, selector: DealStatusSelector) -> Result<Vec<DealStatusPair>, Error> {
-        let db = self.0.open_db(Some(LMDB_DEAL_HISTORY))?;
-        let tx = self.0.begin_ro_txn()?;
-        let mut cursor = tx.open_ro_cursor(db)?;
-        cursor
-            .iter()
+    fn get_deals(
+        &mut self,
+        selector: DealStatusSelector,
+        network: Option<Network>,
+    ) -> Result<Vec<DealStatusPair>, Error> {
+        self.network_entries(LMDB_DEAL_HISTORY, network)?
+            .into_iter()
             .filter_map(|(key, val)| {
-                let status = DealStatus::strict_decode(IoCursor::new(val.to_vec()));
+                let status = DealStatus::strict_decode(IoCursor::new(val));
                 let filtered_status = match status {
                     Err(err) => {
                         return Some(Err(Error::from(err)));
@@ -429,20 +1204,21 @@ impl Database {
                     Ok(DealStatus::InProgress) if selector == DealStatusSelector::InProgress => {
                         Some(status.unwrap())
                     }
-                    Ok(DealStatus::Ended(_)) if selector == DealStatusSelector::Ended => {
+                    Ok(DealStatus::Ended(_)) | Ok(DealStatus::Exhausted)
+                        if selector == DealStatusSelector::Ended =>
+                    {
                         Some(status.unwrap())
                     }
                     _ if selector == DealStatusSelector::All => Some(status.unwrap()),
                     _ => None,
                 }?;
-                Some(
-                    Deal::strict_decode(IoCursor::new(key.to_vec()))
-                        .map(|deal| DealStatusPair {
-                            deal,
-                            status: filtered_status,
-                        })
-                        .map_err(|err| Error::from(err)),
-                )
+                match Deal::strict_decode(IoCursor::new(key)) {
+                    Ok(deal) => Some(Ok(DealStatusPair {
+                        deal,
+                        status: filtered_status,
+                    })),
+                    Err(err) => Some(Err(Error::from(err))),
+                }
             })
             .collect()
     }
@@ -451,11 +1227,13 @@ impl Database {
         &mut self,
         address: &bitcoin::Address,
         secret_key_info: &BitcoinSecretKeyInfo,
+        network: Network,
     ) -> Result<(), Error> {
         let db = self.0.open_db(Some(LMDB_BITCOIN_ADDRESSES))?;
         let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         address.strict_encode(&mut key)?;
+        let key = network_key(network, &key)?;
         let mut val = vec![];
         secret_key_info.strict_encode(&mut val)?;
         if tx.get(db, &key).is_err() {
@@ -474,43 +1252,36 @@ impl Database {
         &mut self,
         address: &bitcoin::Address,
     ) -> Result<BitcoinSecretKeyInfo, Error> {
-        let db = self.0.open_db(Some(LMDB_BITCOIN_ADDRESSES))?;
-        let tx = self.0.begin_ro_txn()?;
         let mut key = vec![];
         address.strict_encode(&mut key)?;
-        let val = BitcoinSecretKeyInfo::strict_decode(tx.get(db, &key)?)?;
-        tx.abort();
-        Ok(val)
+        let val = self.find_network_entry(LMDB_BITCOIN_ADDRESSES, &key, None)?;
+        Ok(BitcoinSecretKeyInfo::strict_decode(IoCursor::new(val))?)
     }
 
     fn get_all_bitcoin_addresses(
         &mut self,
+        network: Option<Network>,
     ) -> Result<Vec<(bitcoin::Address, Option<SwapId>)>, Error> {
-        let db = self.0.open_db(Some(LMDB_BITCOIN_ADDRESSES))?;
-        let tx = self.0.begin_ro_txn()?;
-        let mut cursor = tx.open_ro_cursor(db)?;
-        let res = cursor
-            .iter()
+        self.network_entries(LMDB_BITCOIN_ADDRESSES, network)?
+            .into_iter()
             .map(|(key, val)| {
                 Ok((
-                    bitcoin::Address::strict_decode(IoCursor::new(key.to_vec()))?,
-                    BitcoinSecretKeyInfo::strict_decode(IoCursor::new(val.to_vec()))?.swap_id,
+                    bitcoin::Address::strict_decode(IoCursor::new(key))?,
+                    BitcoinSecretKeyInfo::strict_decode(IoCursor::new(val))?.swap_id,
                 ))
             })
-            .collect();
-        drop(cursor);
-        tx.abort();
-        res
+            .collect()
     }
 
     fn set_monero_address(
         &mut self,
         address: &monero::Address,
         secret_key_info: &MoneroSecretKeyInfo,
+        network: Network,
     ) -> Result<(), Error> {
         let db = self.0.open_db(Some(LMDB_MONERO_ADDRESSES))?;
         let mut tx = self.0.begin_rw_txn()?;
-        let key = address.as_bytes();
+        let key = network_key(network, &address.as_bytes())?;
         let mut val = vec![];
         secret_key_info.strict_encode(&mut val)?;
         if tx.get(db, &key).is_err() {
@@ -529,47 +1300,40 @@ impl Database {
         &mut self,
         address: &monero::Address,
     ) -> Result<MoneroSecretKeyInfo, Error> {
-        let db = self.0.open_db(Some(LMDB_MONERO_ADDRESSES))?;
-        let tx = self.0.begin_ro_txn()?;
-        let key = address.as_bytes();
-        let val = MoneroSecretKeyInfo::strict_decode(tx.get(db, &key)?)?;
-        tx.abort();
-        Ok(val)
+        let val = self.find_network_entry(LMDB_MONERO_ADDRESSES, &address.as_bytes(), None)?;
+        Ok(MoneroSecretKeyInfo::strict_decode(IoCursor::new(val))?)
     }
 
     fn get_all_monero_addresses(
         &mut self,
+        network: Option<Network>,
     ) -> Result<Vec<(monero::Address, Option<SwapId>)>, Error> {
-        let db = self.0.open_db(Some(LMDB_MONERO_ADDRESSES))?;
-        let tx = self.0.begin_ro_txn()?;
-        let mut cursor = tx.open_ro_cursor(db)?;
-        let res = cursor
-            .iter()
+        self.network_entries(LMDB_MONERO_ADDRESSES, network)?
+            .into_iter()
             .map(|(key, val)| {
                 Ok((
-                    monero::Address::from_bytes(key)?,
-                    MoneroSecretKeyInfo::strict_decode(IoCursor::new(val.to_vec()))?.swap_id,
+                    monero::Address::from_bytes(&key)?,
+                    MoneroSecretKeyInfo::strict_decode(IoCursor::new(val))?.swap_id,
                 ))
             })
-            .collect();
-        drop(cursor);
-        tx.abort();
-        res
+            .collect()
     }
 
     fn set_checkpoint_state(
         &mut self,
         checkpoint_key: &CheckpointKey,
         val: &[u8],
+        network: Network,
     ) -> Result<(), Error> {
         let db = self.0.open_db(Some(LMDB_CHECKPOINTS))?;
         let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         checkpoint_key.strict_encode(&mut key)?;
+        let key = network_key(network, &key)?;
         if tx.get(db, &key).is_ok() {
             tx.del(db, &key, None)?;
         }
-        tx.put(db, &Vec::from(key.clone()), &val, lmdb::WriteFlags::empty())?;
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty())?;
         tx.commit()?;
         Ok(())
     }
@@ -583,6 +1347,7 @@ impl Database {
         let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         swap_key.strict_encode(&mut key)?;
+        let key = network_key(checkpoint_entry.deal.parameters.network, &key)?;
         if tx.get(db, &key).is_ok() {
             tx.del(db, &key, None)?;
         }
@@ -594,58 +1359,113 @@ impl Database {
     }
 
     fn get_checkpoint_info(&mut self, swap_key: &SwapId) -> Result<CheckpointEntry, Error> {
-        let db = self.0.open_db(Some(LMDB_CHECKPOINT_INFOS))?;
-        let tx = self.0.begin_ro_txn()?;
         let mut key = vec![];
         swap_key.strict_encode(&mut key)?;
-        let val = tx.get(db, &key)?.to_vec();
-        tx.abort();
+        let val = self.find_network_entry(LMDB_CHECKPOINT_INFOS, &key, None)?;
         Ok(CheckpointEntry::strict_decode(IoCursor::new(val))?)
     }
 
-    fn get_all_checkpoint_info(&mut self) -> Result<Vec<CheckpointEntry>, Error> {
-        let db = self.0.open_db(Some(LMDB_CHECKPOINT_INFOS))?;
-        let tx = self.0.begin_ro_txn()?;
-        let mut cursor = tx.open_ro_cursor(db)?;
-        let res = cursor
-            .iter()
-            .map(|(_, value)| {
-                Ok(CheckpointEntry::strict_decode(IoCursor::new(
-                    value.to_vec(),
-                ))?)
-            })
-            .collect();
-        drop(cursor);
-        tx.abort();
-        res
+    fn get_all_checkpoint_info(
+        &mut self,
+        network: Option<Network>,
+    ) -> Result<Vec<CheckpointEntry>, Error> {
+        self.network_entries(LMDB_CHECKPOINT_INFOS, network)?
+            .into_iter()
+            .map(|(_, val)| Ok(CheckpointEntry::strict_decode(IoCursor::new(val))?))
+            .collect()
     }
 
-    fn get_checkpoint_state(&mut self, checkpoint_key: &CheckpointKey) -> Result<Vec<u8>, Error> {
-        let db = self.0.open_db(Some(LMDB_CHECKPOINTS))?;
-        let tx = self.0.begin_ro_txn()?;
+    fn get_checkpoint_state(
+        &mut self,
+        checkpoint_key: &CheckpointKey,
+        network: Option<Network>,
+    ) -> Result<Vec<u8>, Error> {
         let mut key = vec![];
         checkpoint_key.strict_encode(&mut key)?;
-        let val: Vec<u8> = tx.get(db, &key)?.into();
-        tx.abort();
-        Ok(val)
+        self.find_network_entry(LMDB_CHECKPOINTS, &key, network)
     }
 
     fn delete_checkpoint_state(&mut self, checkpoint_key: CheckpointKey) -> Result<(), Error> {
-        let db = self.0.open_db(Some(LMDB_CHECKPOINTS))?;
-        let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         checkpoint_key.strict_encode(&mut key)?;
-        tx.del(db, &key, None)?;
-        tx.commit()?;
-        Ok(())
+        self.delete_network_entry(LMDB_CHECKPOINTS, &key)
     }
 
     fn delete_checkpoint_info(&mut self, swap_key: SwapId) -> Result<(), Error> {
-        let db = self.0.open_db(Some(LMDB_CHECKPOINT_INFOS))?;
-        let mut tx = self.0.begin_rw_txn()?;
         let mut key = vec![];
         swap_key.strict_encode(&mut key)?;
-        tx.del(db, &key, None)?;
+        self.delete_network_entry(LMDB_CHECKPOINT_INFOS, &key)
+    }
+
+    /// Entries of a network partitioned table with their network prefix
+    /// stripped, in all networks when none is given
+    fn network_entries(
+        &mut self,
+        table: &str,
+        network: Option<Network>,
+    ) -> Result<Vec<Entry>, Error> {
+        let db = self.0.open_db(Some(table))?;
+        let tx = self.0.begin_ro_txn()?;
+        let mut cursor = tx.open_ro_cursor(db)?;
+        let mut entries = vec![];
+        for network in NETWORKS
+            .iter()
+            .filter(|n| network.map_or(true, |net| **n == net))
+        {
+            let prefix = network_key(*network, &[])?;
+            if seek(&cursor, &prefix)? {
+                entries.extend(
+                    cursor
+                        .iter_from(&prefix)
+                        .take_while(|(key, _)| key.starts_with(&prefix))
+                        .map(|(key, val)| (key[prefix.len()..].to_vec(), val.to_vec())),
+                );
+            }
+        }
+        drop(cursor);
+        tx.abort();
+        Ok(entries)
+    }
+
+    /// Value of the key in the given network, or in the first network holding
+    /// it when none is given
+    fn find_network_entry(
+        &mut self,
+        table: &str,
+        key: &[u8],
+        network: Option<Network>,
+    ) -> Result<Vec<u8>, Error> {
+        let db = self.0.open_db(Some(table))?;
+        let tx = self.0.begin_ro_txn()?;
+        for network in NETWORKS
+            .iter()
+            .filter(|n| network.map_or(true, |net| **n == net))
+        {
+            match tx.get(db, &network_key(*network, key)?) {
+                Ok(val) => return Ok(val.to_vec()),
+                Err(lmdb::Error::NotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        tx.abort();
+        Err(lmdb::Error::NotFound.into())
+    }
+
+    /// Delete the key in every network, fails if no network holds it
+    fn delete_network_entry(&mut self, table: &str, key: &[u8]) -> Result<(), Error> {
+        let db = self.0.open_db(Some(table))?;
+        let mut tx = self.0.begin_rw_txn()?;
+        let mut found = false;
+        for network in NETWORKS.iter() {
+            match tx.del(db, &network_key(*network, key)?, None) {
+                Ok(()) => found = true,
+                Err(lmdb::Error::NotFound) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if !found {
+            return Err(lmdb::Error::NotFound.into());
+        }
         tx.commit()?;
         Ok(())
     }
@@ -653,7 +1473,7 @@ impl Database {
 
 #[test]
 fn test_lmdb_state() {
-    use crate::bus::Outcome;
+    use crate::bus::{ChatDirection, EventKind, Outcome};
     use bitcoin::secp256k1::SecretKey;
     use farcaster_core::role::TradeRole;
     use farcaster_core::Uuid;
@@ -674,17 +1494,28 @@ fn test_lmdb_state() {
     };
     let path = std::env::current_dir().unwrap();
     let mut database = Database::new(path.to_path_buf()).unwrap();
-    database.set_checkpoint_state(&key1, &val1).unwrap();
-    let res = database.get_checkpoint_state(&key1).unwrap();
+    database
+        .set_checkpoint_state(&key1, &val1, Network::Testnet)
+        .unwrap();
+    let res = database.get_checkpoint_state(&key1, None).unwrap();
     assert_eq!(val1, res);
-    database.set_checkpoint_state(&key1, &val2).unwrap();
-    let res = database.get_checkpoint_state(&key1).unwrap();
+    database
+        .set_checkpoint_state(&key1, &val2, Network::Testnet)
+        .unwrap();
+    let res = database
+        .get_checkpoint_state(&key1, Some(Network::Testnet))
+        .unwrap();
     assert_eq!(val2, res);
-    database.set_checkpoint_state(&key2, &val2).unwrap();
-    let res = database.get_checkpoint_state(&key2).unwrap();
+    assert!(database
+        .get_checkpoint_state(&key1, Some(Network::Mainnet))
+        .is_err());
+    database
+        .set_checkpoint_state(&key2, &val2, Network::Local)
+        .unwrap();
+    let res = database.get_checkpoint_state(&key2, None).unwrap();
     assert_eq!(val2, res);
     database.delete_checkpoint_state(key2.clone()).unwrap();
-    let res = database.get_checkpoint_state(&key2);
+    let res = database.get_checkpoint_state(&key2, None);
     assert!(res.is_err());
 
     let key_info = SwapId(Uuid::new());
@@ -708,11 +1539,34 @@ fn test_lmdb_state() {
         swap_id: None,
         secret_key: sk,
     };
-    database.set_bitcoin_address(&addr, &addr_info).unwrap();
+    database
+        .set_bitcoin_address(&addr, &addr_info, Network::Testnet)
+        .unwrap();
     let val_retrieved = database.get_bitcoin_address_secret_key(&addr).unwrap();
     assert_eq!(addr_info, val_retrieved);
-    let addrs = database.get_all_bitcoin_addresses().unwrap();
+    let addrs = database.get_all_bitcoin_addresses(None).unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == addr).is_some());
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Testnet))
+        .unwrap();
     assert!(addrs.iter().find(|(a, _)| *a == addr).is_some());
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Mainnet))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == addr).is_none());
+    // a base58 regtest address reads as testnet but is listed on its network
+    let local_addr = bitcoin::Address::p2pkh(&pk, bitcoin::Network::Regtest);
+    database
+        .set_bitcoin_address(&local_addr, &addr_info, Network::Local)
+        .unwrap();
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Local))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == local_addr).is_some());
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Testnet))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == local_addr).is_none());
     let key_pair = monero::KeyPair {
         spend: monero::PrivateKey::from_str(
             "77916d0cd56ed1920aef6ca56d8a41bac915b68e4c46a589e0956e27a7b77404",
@@ -731,11 +1585,34 @@ fn test_lmdb_state() {
     };
 
     let addr = monero::Address::from_keypair(monero::Network::Stagenet, &key_pair);
-    database.set_monero_address(&addr, &addr_info).unwrap();
+    database
+        .set_monero_address(&addr, &addr_info, Network::Testnet)
+        .unwrap();
     let val_retrieved = database.get_monero_address_secret_key(&addr).unwrap();
     assert_eq!(addr_info, val_retrieved);
-    let addrs = database.get_all_monero_addresses().unwrap();
+    let addrs = database.get_all_monero_addresses(None).unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == addr).is_some());
+    let addrs = database
+        .get_all_monero_addresses(Some(Network::Testnet))
+        .unwrap();
     assert!(addrs.iter().find(|(a, _)| *a == addr).is_some());
+    let addrs = database
+        .get_all_monero_addresses(Some(Network::Mainnet))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == addr).is_none());
+    // a local swap address is encoded for mainnet but listed on the deal network
+    let local_addr = monero::Address::from_keypair(monero::Network::Mainnet, &key_pair);
+    database
+        .set_monero_address(&local_addr, &addr_info, Network::Local)
+        .unwrap();
+    let addrs = database
+        .get_all_monero_addresses(Some(Network::Local))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == local_addr).is_some());
+    let addrs = database
+        .get_all_monero_addresses(Some(Network::Mainnet))
+        .unwrap();
+    assert!(addrs.iter().find(|(a, _)| *a == local_addr).is_none());
 
     let deal_1 = Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap();
     let deal_2 = Deal::from_str("Deal:Cke4ftrP5A7Km9Kmc2UDBePio1p7wM56P1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBuLWyw3M").unwrap();
@@ -743,28 +1620,30 @@ fn test_lmdb_state() {
     database
         .set_deal_status(&deal_1, &DealStatus::Open)
         .unwrap();
-    let deals_retrieved = database.get_deals(DealStatusSelector::All).unwrap();
+    let deals_retrieved = database.get_deals(DealStatusSelector::All, None).unwrap();
     assert_eq!(deal_1, deals_retrieved[0].deal);
 
-    let deals_retrieved = database.get_deals(DealStatusSelector::Open).unwrap();
+    let deals_retrieved = database.get_deals(DealStatusSelector::Open, None).unwrap();
     assert_eq!(deal_1, deals_retrieved[0].deal);
 
     database
         .set_deal_status(&deal_1, &DealStatus::InProgress)
         .unwrap();
-    let deals_retrieved = database.get_deals(DealStatusSelector::InProgress).unwrap();
+    let deals_retrieved = database
+        .get_deals(DealStatusSelector::InProgress, None)
+        .unwrap();
     assert_eq!(deal_1, deals_retrieved[0].deal);
 
     database
         .set_deal_status(&deal_1, &DealStatus::Ended(Outcome::SuccessSwap))
         .unwrap();
-    let deals_retrieved = database.get_deals(DealStatusSelector::Ended).unwrap();
+    let deals_retrieved = database.get_deals(DealStatusSelector::Ended, None).unwrap();
     assert_eq!(deal_1, deals_retrieved[0].deal);
 
     database
         .set_deal_status(&deal_2, &DealStatus::Open)
         .unwrap();
-    let deals_retrieved = database.get_deals(DealStatusSelector::All).unwrap();
+    let deals_retrieved = database.get_deals(DealStatusSelector::All, None).unwrap();
     let status_1 = DealStatusPair {
         deal: deal_1,
         status: DealStatus::Ended(Outcome::SuccessSwap),
@@ -776,4 +1655,135 @@ fn test_lmdb_state() {
     assert!(deals_retrieved.len() == 2);
     assert!(deals_retrieved.contains(&status_1));
     assert!(deals_retrieved.contains(&status_2));
+
+    let swap_id = SwapId(Uuid::new());
+    let next_id = database.last_event_id().unwrap().map_or(0, |id| id + 1);
+    let events: Vec<NodeEvent> = [EventKind::SwapLaunched, EventKind::StateTransition]
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| NodeEvent {
+            id: next_id + i as u64,
+            ..NodeEvent::new(*kind, Some(swap_id), "details")
+        })
+        .collect();
+    for event in events.iter() {
+        database.add_event(event).unwrap();
+    }
+    assert_eq!(database.last_event_id().unwrap(), Some(next_id + 1));
+    let filter = EventFilter {
+        swap_id: Some(swap_id),
+        ..Default::default()
+    };
+    assert_eq!(database.get_events(&filter).unwrap(), events);
+    let filter = EventFilter {
+        after: Some(next_id),
+        ..filter
+    };
+    assert_eq!(database.get_events(&filter).unwrap(), events[1..]);
+    // polling past the last event reads nothing
+    let filter = EventFilter {
+        after: Some(next_id + 1),
+        ..filter
+    };
+    assert!(database.get_events(&filter).unwrap().is_empty());
+    let filter = EventFilter {
+        after: Some(u64::MAX),
+        ..filter
+    };
+    assert!(database.get_events(&filter).unwrap().is_empty());
+
+    // chat records of a swap are kept in order, apart from the other swaps
+    let other_swap_id = SwapId(Uuid::new());
+    let records: Vec<ChatRecord> = (0..3)
+        .map(|i| ChatRecord {
+            swap_id,
+            timestamp: i,
+            direction: ChatDirection::Sent,
+            text: format!("message {}", i),
+        })
+        .collect();
+    for record in records.iter() {
+        database.add_chat_record(record).unwrap();
+        database
+            .add_chat_record(&ChatRecord {
+                swap_id: other_swap_id,
+                ..record.clone()
+            })
+            .unwrap();
+    }
+    assert_eq!(database.get_chat_records(&swap_id).unwrap(), records);
+    assert_eq!(
+        database
+            .get_chat_records(&SwapId(Uuid::new()))
+            .unwrap()
+            .len(),
+        0
+    );
+
+    // and deleted with their swap only
+    database.delete_chat_records(&swap_id).unwrap();
+    assert!(database.get_chat_records(&swap_id).unwrap().is_empty());
+    assert_eq!(database.get_chat_records(&other_swap_id).unwrap().len(), 3);
+    database.delete_chat_records(&swap_id).unwrap();
+
+    // the records over the cap of the swap are dropped
+    for _ in 0..MAX_CHAT_MESSAGES {
+        database.add_chat_record(&records[0]).unwrap();
+    }
+    database.add_chat_record(&records[1]).unwrap();
+    let capped = database.get_chat_records(&swap_id).unwrap();
+    assert_eq!(capped.len() as u64, MAX_CHAT_MESSAGES);
+    assert!(!capped.contains(&records[1]));
+    database.delete_chat_records(&swap_id).unwrap();
+
+    // legacy entries move to the network of their swap
+    let mut local_deal = val_info.deal.clone();
+    local_deal.parameters.network = Network::Local;
+    let legacy_info = CheckpointEntry {
+        swap_id,
+        deal: local_deal,
+        ..val_info.clone()
+    };
+    let legacy_addr = bitcoin::Address::p2pkh(&pk, bitcoin::Network::Testnet);
+    let legacy_addr_info = BitcoinSecretKeyInfo {
+        swap_id: Some(swap_id),
+        secret_key: sk,
+    };
+    let mut tx = database.0.begin_rw_txn().unwrap();
+    for (table, key, val) in [
+        (
+            LMDB_LEGACY_CHECKPOINT_INFOS,
+            strict_encoding::strict_serialize(&swap_id).unwrap(),
+            strict_encoding::strict_serialize(&legacy_info).unwrap(),
+        ),
+        (
+            LMDB_LEGACY_BITCOIN_ADDRESSES,
+            strict_encoding::strict_serialize(&legacy_addr).unwrap(),
+            strict_encoding::strict_serialize(&legacy_addr_info).unwrap(),
+        ),
+    ] {
+        let db = database.0.open_db(Some(table)).unwrap();
+        tx.put(db, &key, &val, lmdb::WriteFlags::empty()).unwrap();
+    }
+    tx.commit().unwrap();
+    database.migrate_legacy_tables().unwrap();
+    assert!(database
+        .table_entries(LMDB_LEGACY_BITCOIN_ADDRESSES)
+        .unwrap()
+        .is_empty());
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Local))
+        .unwrap();
+    assert!(addrs.contains(&(legacy_addr.clone(), Some(swap_id))));
+    let addrs = database
+        .get_all_bitcoin_addresses(Some(Network::Testnet))
+        .unwrap();
+    assert!(!addrs.contains(&(legacy_addr, Some(swap_id))));
+    assert_eq!(
+        database
+            .get_all_checkpoint_info(Some(Network::Local))
+            .unwrap(),
+        vec![legacy_info]
+    );
+    database.delete_checkpoint_info(swap_id).unwrap();
 }
@@ -0,0 +1,289 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Embedded node: farcasterd and the microservices it launches run as threads
+//! of the embedding application instead of separate processes, connected over
+//! ZMQ inproc endpoints.
+//!
+//! ```ignore
+//! let node = EmbeddedNode::start("/path/to/data_dir", config)?;
+//! let mut client = node.client()?;
+//! client.request_info(ServiceId::Farcasterd, InfoMsg::GetInfo)?;
+//! let info = client.response()?;
+//! ```
+
+use std::any::Any;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
+use std::{fs, io, thread};
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::secp256k1::rand::{thread_rng, RngCore};
+use clap::Parser;
+use internet2::addr::InetSocketAddr;
+use internet2::session::BrontozaurSession;
+use lazy_static::lazy_static;
+use microservices::peer::PeerConnection;
+
+use crate::bus::ctl::Token;
+use crate::client::Client;
+use crate::{databased, farcasterd, grpcd, peerd, swapd, syncerd, walletd};
+use crate::{Config, Error, ServiceConfig};
+
+lazy_static! {
+    /// Shared arguments of the microservices, set once the embedded node is
+    /// started
+    static ref SHARED_ARGS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+}
+
+/// Unwinding payload ending the thread of a terminated microservice
+pub struct Terminated;
+
+/// Whether the node runs embedded in this process
+pub fn is_embedded() -> bool {
+    shared_args().is_some()
+}
+
+/// Shared arguments given to the launched microservices, None if the node is
+/// not embedded
+pub fn shared_args() -> Option<Vec<String>> {
+    SHARED_ARGS.lock().ok().and_then(|args| args.clone())
+}
+
+/// A farcaster node running in the threads of the embedding application
+pub struct EmbeddedNode {
+    service_config: ServiceConfig,
+}
+
+impl EmbeddedNode {
+    /// Start farcasterd with the data directory and the node configuration,
+    /// farcasterd then launches the other microservices as threads. Only one
+    /// node can be embedded in a process.
+    pub fn start(data_dir: impl Into<PathBuf>, config: Config) -> Result<Self, Error> {
+        let data_dir = data_dir.into();
+        fs::create_dir_all(&data_dir)?;
+        let shared_args = vec![
+            "--data-dir".to_string(),
+            data_dir.to_string_lossy().to_string(),
+            "--msg-socket".to_string(),
+            "inproc://farcaster-msg".to_string(),
+            "--ctl-socket".to_string(),
+            "inproc://farcaster-ctl".to_string(),
+            "--info-socket".to_string(),
+            "inproc://farcaster-info".to_string(),
+            "--sync-socket".to_string(),
+            "inproc://farcaster-sync".to_string(),
+        ];
+        {
+            let mut args = SHARED_ARGS
+                .lock()
+                .map_err(|_| Error::Farcaster("Embedded node state poisoned".to_string()))?;
+            if args.is_some() {
+                return Err(Error::Farcaster(
+                    "A node is already embedded in this process".to_string(),
+                ));
+            }
+            *args = Some(shared_args.clone());
+        }
+
+        let opts = farcasterd::Opts::try_parse_from(
+            iter::once("farcasterd".to_string()).chain(shared_args),
+        )
+        .map_err(|err| Error::Farcaster(err.to_string()))?;
+        let service_config: ServiceConfig = opts.shared.clone().into();
+
+        // Generate runtime token
+        let mut dest = [0u8; 16];
+        thread_rng().fill_bytes(&mut dest);
+        let token = Token(dest.to_hex());
+
+        let farcasterd_config = service_config.clone();
+        thread::Builder::new()
+            .name("farcasterd".to_string())
+            .spawn(move || {
+                if let Err(err) = farcasterd::run(farcasterd_config, config, opts, token) {
+                    error!("Error running farcasterd runtime: {}", err);
+                }
+            })?;
+
+        Ok(EmbeddedNode { service_config })
+    }
+
+    /// A client sending requests to farcasterd, as swap-cli does
+    pub fn client(&self) -> Result<Client, Error> {
+        Client::with(self.service_config.clone())
+    }
+
+    /// Bus endpoints of the embedded node
+    pub fn service_config(&self) -> &ServiceConfig {
+        &self.service_config
+    }
+}
+
+/// A microservice running in a thread of the embedded node
+pub struct ServiceThread {
+    name: String,
+    exit: Receiver<Result<(), String>>,
+}
+
+impl ServiceThread {
+    /// The exit of the service if its thread ended: Ok if it terminated, the
+    /// failure if it crashed
+    pub fn try_exit(&mut self) -> Option<Result<(), String>> {
+        match self.exit.try_recv() {
+            Ok(exit) => Some(exit),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("thread ended".to_string())),
+        }
+    }
+}
+
+impl Display for ServiceThread {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "thread {}", self.name)
+    }
+}
+
+/// Run the microservice with its command-line arguments in a new thread
+pub fn spawn_service(name: &str, args: Vec<String>) -> io::Result<ServiceThread> {
+    let (tx, rx) = mpsc::channel();
+    let service = name.to_string();
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let exit = match panic::catch_unwind(AssertUnwindSafe(|| run_service(&service, args))) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(err)) => Err(err.to_string()),
+                Err(payload) if payload.is::<Terminated>() => Ok(()),
+                Err(payload) => Err(panic_message(payload)),
+            };
+            if let Err(failure) = &exit {
+                error!("{} stopped: {}", service, failure);
+            }
+            let _ = tx.send(exit);
+        })?;
+    Ok(ServiceThread {
+        name: name.to_string(),
+        exit: rx,
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => format!("panic: {}", msg),
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(msg) => format!("panic: {}", msg),
+            Err(_) => "panic".to_string(),
+        },
+    }
+}
+
+fn parse<T: Parser>(name: &str, args: Vec<String>) -> Result<T, Error> {
+    T::try_parse_from(iter::once(name.to_string()).chain(args))
+        .map_err(|err| Error::Farcaster(err.to_string()))
+}
+
+/// Run the microservice as its binary does, the arguments are already
+/// processed by the embedded node
+fn run_service(name: &str, args: Vec<String>) -> Result<(), Error> {
+    match name {
+        "walletd" => {
+            let mut opts: walletd::Opts = parse(name, args)?;
+            opts.key_opts.process(&opts.shared);
+            let node_secrets = walletd::NodeSecrets::new(opts.key_opts.key_file.clone());
+            walletd::run(
+                opts.shared.clone().into(),
+                Token(opts.wallet_token.token),
+                node_secrets,
+            )
+        }
+        "databased" => {
+            let opts: databased::Opts = parse(name, args)?;
+            databased::run(opts.shared.clone().into(), opts.absolute_data_dir_path())
+        }
+        "syncerd" => {
+            let opts: syncerd::Opts = parse(name, args)?;
+            syncerd::run(opts.shared.clone().into(), opts)
+        }
+        "swapd" => {
+            let opts: swapd::Opts = parse(name, args)?;
+            swapd::run(opts.shared.clone().into(), opts)
+        }
+        "grpcd" => {
+            let opts: grpcd::Opts = parse(name, args)?;
+            grpcd::run(opts.shared.clone().into(), opts.grpc_port, opts.grpc_ip)
+        }
+        "peerd" => run_peerd(parse(name, args)?),
+        _ => Err(Error::Farcaster(format!("Unknown microservice {}", name))),
+    }
+}
+
+/// Run a connecting peerd, or a listening one running a new peerd thread for
+/// each incoming connection instead of forking
+fn run_peerd(opts: peerd::Opts) -> Result<(), Error> {
+    let service_config: ServiceConfig = opts.shared.clone().into();
+    let local_node = opts.peer_key_opts.local_node();
+    if let Some(remote_node) = opts.connect {
+        return peerd::run_from_connect(
+            service_config,
+            remote_node,
+            None,
+            local_node,
+            opts.local_init(),
+            opts.connect_timeout(),
+            opts.reconnect_backoff(),
+        );
+    }
+    let bind_addr = InetSocketAddr::socket(
+        opts.listen
+            .flatten()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        opts.port,
+    );
+    let listener = TcpListener::bind(
+        SocketAddr::try_from(bind_addr)
+            .map_err(|_| Error::Farcaster("Tor is not yet supported".to_string()))?,
+    )?;
+    loop {
+        let (stream, remote_socket_addr) = listener.accept()?;
+        debug!("New connection from {}", remote_socket_addr);
+        let service_config = service_config.clone();
+        let local_init = opts.local_init();
+        thread::Builder::new()
+            .name("peerd".to_string())
+            .spawn(move || {
+                let run = || -> Result<(), Error> {
+                    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+                    let session =
+                        BrontozaurSession::with(stream, local_node.private_key(), bind_addr)?;
+                    peerd::run_from_listener(
+                        service_config,
+                        PeerConnection::with(session),
+                        None,
+                        Some(bind_addr),
+                        local_node,
+                        local_init,
+                    )
+                };
+                match panic::catch_unwind(AssertUnwindSafe(run)) {
+                    Ok(Err(err)) => error!("peerd of {} stopped: {}", remote_socket_addr, err),
+                    Err(payload) if !payload.is::<Terminated>() => error!(
+                        "peerd of {} stopped: {}",
+                        remote_socket_addr,
+                        panic_message(payload)
+                    ),
+                    _ => {}
+                }
+            })?;
+    }
+}
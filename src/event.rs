@@ -102,8 +102,21 @@ impl<'esb> Event<'esb> {
         }
     }
 
+    /// Whether the event is a deal made or revoked by the service itself, as
+    /// done by the market maker, such events are not replied to
+    pub fn is_internal(&self) -> bool {
+        self.source == self.service
+            && matches!(
+                self.request,
+                BusMsg::Ctl(ctl::CtlMsg::MakeDeal(_) | ctl::CtlMsg::RevokeDeal(_))
+            )
+    }
+
     /// Finalizes event processing by sending reply request via CTL message bus
     pub fn complete_ctl(self, request: ctl::CtlMsg) -> Result<(), esb::Error<ServiceId>> {
+        if self.is_internal() {
+            return Ok(());
+        }
         self.endpoints.send_to(
             ServiceBus::Ctl,
             self.service,
@@ -115,6 +128,9 @@ impl<'esb> Event<'esb> {
     /// Finalizes event processing by sending reply request via CTL message bus to a client
     pub fn complete_client_ctl(self, request: ctl::CtlMsg) -> Result<(), esb::Error<ServiceId>> {
         let bus = ServiceBus::Ctl;
+        if self.is_internal() {
+            return Ok(());
+        }
         if let ServiceId::GrpcdClient(_) = self.source {
             self.endpoints
                 .send_to(bus, self.source, ServiceId::Grpcd, BusMsg::Ctl(request))?;
@@ -127,6 +143,9 @@ impl<'esb> Event<'esb> {
 
     /// Finalizes event processing by sending reply request via RPC message bus
     pub fn complete_info(self, request: info::InfoMsg) -> Result<(), esb::Error<ServiceId>> {
+        if self.is_internal() {
+            return Ok(());
+        }
         self.endpoints.send_to(
             ServiceBus::Info,
             self.service,
@@ -138,6 +157,9 @@ impl<'esb> Event<'esb> {
     /// Finalizes event processing by sending reply request via RPC message bus to a client
     pub fn complete_client_info(self, request: info::InfoMsg) -> Result<(), esb::Error<ServiceId>> {
         let bus = ServiceBus::Info;
+        if self.is_internal() {
+            return Ok(());
+        }
         if let ServiceId::GrpcdClient(_) = self.source {
             self.endpoints
                 .send_to(bus, self.source, ServiceId::Grpcd, BusMsg::Info(request))?;
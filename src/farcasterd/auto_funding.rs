@@ -0,0 +1,508 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use farcaster_core::blockchain::{Blockchain, Network};
+use farcaster_core::swap::btcxmr::Deal;
+use farcaster_core::swap::SwapId;
+
+use crate::bus::ctl::{BitcoinFundingInfo, CtlMsg, FundingInfo, MoneroFundingInfo};
+use crate::bus::{AutoFundingOutcome, AutoFundingRecord};
+use crate::config::{AutoFundingServers, Config};
+use crate::event::Event;
+use crate::farcasterd::runtime::Runtime;
+use crate::swapd::{StateReport, TemporalSafety, SWEEP_MONERO_THRESHOLD};
+use crate::{Error, LogStyle, ServiceId};
+
+/// Seconds in the sliding window of the daily limits
+const DAY: u64 = 24 * 60 * 60;
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Automatic funding attempts made by farcasterd, loaded from databased at
+/// start-up and used to enforce the daily limits.
+#[derive(Debug, Default)]
+pub struct AutoFundingLedger {
+    records: Vec<AutoFundingRecord>,
+}
+
+impl AutoFundingLedger {
+    pub fn load(&mut self, records: Vec<AutoFundingRecord>) {
+        // records made before the ledger was loaded are kept
+        let mut records = records;
+        records.retain(|r| !self.records.contains(r));
+        self.records.extend(records);
+    }
+
+    pub fn insert(&mut self, record: AutoFundingRecord) {
+        self.records.push(record);
+    }
+
+    /// Amount paid on the blockchain and network over the last 24 hours
+    pub fn spent_last_day(&self, blockchain: Blockchain, network: Network, now: u64) -> u64 {
+        self.records
+            .iter()
+            .filter(|r| {
+                r.blockchain == blockchain
+                    && r.network == network
+                    && r.timestamp + DAY > now
+                    && matches!(r.outcome, AutoFundingOutcome::Paid(_))
+            })
+            .map(|r| r.amount)
+            .sum()
+    }
+
+    /// Whether the swap has been funded automatically on the blockchain
+    pub fn paid(&self, swap_id: SwapId, blockchain: Blockchain) -> bool {
+        self.records.iter().any(|r| {
+            r.swap_id == swap_id
+                && r.blockchain == blockchain
+                && matches!(r.outcome, AutoFundingOutcome::Paid(_))
+        })
+    }
+}
+
+fn to_base_units(blockchain: Blockchain, amount: f64) -> Result<u64, String> {
+    match blockchain {
+        Blockchain::Bitcoin => bitcoin::Amount::from_btc(amount)
+            .map(|a| a.as_sat())
+            .map_err(|e| e.to_string()),
+        Blockchain::Monero => monero::Amount::from_xmr(amount)
+            .map(|a| a.as_pico())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+fn display_amount(blockchain: Blockchain, amount: u64) -> String {
+    match blockchain {
+        Blockchain::Bitcoin => bitcoin::Amount::from_sat(amount).to_string(),
+        Blockchain::Monero => monero::Amount::from_pico(amount).to_string(),
+    }
+}
+
+/// Check the payment against the per-swap cap and the daily limit configured
+/// for the network, returns the reason for refusing the payment.
+pub fn check_limits(
+    servers: &AutoFundingServers,
+    ledger: &AutoFundingLedger,
+    blockchain: Blockchain,
+    network: Network,
+    amount: u64,
+    now: u64,
+) -> Result<(), String> {
+    let (max_per_swap, daily_limit) = match blockchain {
+        Blockchain::Bitcoin => (servers.bitcoin_max_per_swap, servers.bitcoin_daily_limit),
+        Blockchain::Monero => (servers.monero_max_per_swap, servers.monero_daily_limit),
+    };
+    if let Some(max) = max_per_swap {
+        let max = to_base_units(blockchain, max)?;
+        if amount > max {
+            return Err(format!(
+                "{} exceeds the maximum of {} per swap",
+                display_amount(blockchain, amount),
+                display_amount(blockchain, max)
+            ));
+        }
+    }
+    if let Some(limit) = daily_limit {
+        let limit = to_base_units(blockchain, limit)?;
+        let spent = ledger.spent_last_day(blockchain, network, now);
+        if spent + amount > limit {
+            return Err(format!(
+                "{} would exceed the daily limit of {}, {} already spent in the last 24 hours",
+                display_amount(blockchain, amount),
+                display_amount(blockchain, limit),
+                display_amount(blockchain, spent)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the swap is still within its safe funding window. Bitcoin is
+/// funded before the lock, no timelock is running yet. Monero is funded once
+/// the Bitcoin lock is final and must stop before the cancel timelock can be
+/// raced, as swapd does before aborting the funding. Returns false if the swap
+/// did not report the confirmations of its Bitcoin lock yet.
+pub fn check_temporal_safety(
+    config: &Config,
+    deal: &Deal,
+    blockchain: Blockchain,
+    state: Option<&StateReport>,
+) -> Result<bool, String> {
+    if blockchain == Blockchain::Bitcoin {
+        return Ok(true);
+    }
+    let params = &deal.parameters;
+    let swap_config = config
+        .get_swap_config(
+            params
+                .arbitrating_blockchain
+                .try_into()
+                .map_err(|e: farcaster_core::consensus::Error| e.to_string())?,
+            params
+                .accordant_blockchain
+                .try_into()
+                .map_err(|e: farcaster_core::consensus::Error| e.to_string())?,
+            params.network,
+        )
+        .map_err(|e| e.to_string())?;
+    let temporal_safety = TemporalSafety {
+        cancel_timelock: params.cancel_timelock.as_u32(),
+        punish_timelock: params.punish_timelock.as_u32(),
+        btc_finality_thr: swap_config.arbitrating.finality.into(),
+        race_thr: swap_config.arbitrating.safety.into(),
+        xmr_finality_thr: swap_config.accordant.finality.into(),
+        sweep_monero_thr: SWEEP_MONERO_THRESHOLD,
+    };
+    match state.and_then(|state| state.arb_lock_confirmations) {
+        Some(confs) if temporal_safety.stop_funding_before_cancel(confs) => Err(format!(
+            "Bitcoin lock has {} confirmations, too close to the cancel timelock",
+            confs
+        )),
+        Some(_) => Ok(true),
+        None => Ok(false),
+    }
+}
+
+fn bitcoin_rpc(servers: &AutoFundingServers) -> Result<bitcoincore_rpc::Client, String> {
+    use bitcoincore_rpc::{Auth, Client};
+
+    let auth = match (
+        &servers.bitcoin_cookie_path,
+        &servers.bitcoin_rpc_user,
+        &servers.bitcoin_rpc_pass,
+    ) {
+        (Some(cookie), _, _) => {
+            let path = PathBuf::from_str(&shellexpand::tilde(cookie)).map_err(|e| e.to_string())?;
+            debug!("bitcoin-rpc connecting with cookie auth");
+            Auth::CookieFile(path)
+        }
+        (None, Some(rpc_user), Some(rpc_pass)) => {
+            debug!("bitcoin-rpc connecting with userpass auth");
+            Auth::UserPass(rpc_user.clone(), rpc_pass.clone())
+        }
+        _ => {
+            return Err("Couldn't instantiate Bitcoin RPC - provide either `bitcoin_cookie_path` or `bitcoin_rpc_user` AND `bitcoin_rpc_pass` configuration parameters".to_string());
+        }
+    };
+    Client::new(&servers.bitcoin_rpc, auth).map_err(|e| e.to_string())
+}
+
+fn monero_runtime() -> Result<tokio::runtime::Runtime, String> {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Send the Bitcoin funding from the configured bitcoin-core wallet, returns
+/// the funding txid.
+pub fn send_bitcoin(
+    servers: &AutoFundingServers,
+    address: &bitcoin::Address,
+    amount: bitcoin::Amount,
+) -> Result<bitcoin::Txid, String> {
+    use bitcoincore_rpc::RpcApi;
+
+    bitcoin_rpc(servers)?
+        .send_to_address(address, amount, None, None, None, None, None, None)
+        .map_err(|e| e.to_string())
+}
+
+/// Spendable balance of the configured wallet of the blockchain, in satoshi
+/// for bitcoin-core and piconero for monero-wallet-rpc.
+pub fn wallet_balance(servers: &AutoFundingServers, blockchain: Blockchain) -> Result<u64, String> {
+    match blockchain {
+        Blockchain::Bitcoin => {
+            use bitcoincore_rpc::RpcApi;
+
+            bitcoin_rpc(servers)?
+                .get_balance(None, None)
+                .map(|balance| balance.as_sat())
+                .map_err(|e| e.to_string())
+        }
+        Blockchain::Monero => monero_runtime()?.block_on(async {
+            monero_rpc::RpcClientBuilder::new()
+                .build(servers.monero_rpc_wallet.clone())
+                .map_err(|e| e.to_string())?
+                .wallet()
+                .get_balance(0, None)
+                .await
+                .map(|balance| balance.unlocked_balance.as_pico())
+                .map_err(|e| e.to_string())
+        }),
+    }
+}
+
+/// Send the Monero funding from the configured monero-wallet-rpc, returns the
+/// funding tx hash.
+pub fn send_monero(
+    servers: &AutoFundingServers,
+    address: monero::Address,
+    amount: monero::Amount,
+) -> Result<String, String> {
+    monero_runtime()?.block_on(async {
+        let wallet = monero_rpc::RpcClientBuilder::new()
+            .build(servers.monero_rpc_wallet.clone())
+            .map_err(|e| e.to_string())?
+            .wallet();
+        let options = monero_rpc::TransferOptions::default();
+
+        let mut retries = 10;
+        loop {
+            match wallet
+                .transfer(
+                    [(address, amount)].iter().cloned().collect(),
+                    monero_rpc::TransferPriority::Default,
+                    options.clone(),
+                )
+                .await
+            {
+                Ok(tx) => return Ok(tx.tx_hash.to_string()),
+                Err(err) => {
+                    retries -= 1;
+                    if (err.to_string().contains("not enough") && err.to_string().contains("money"))
+                        || retries == 0
+                    {
+                        return Err(err.to_string());
+                    }
+                    warn!(
+                        "Auto-funding Monero transaction failed with {}, retrying, {} retries left",
+                        err, retries
+                    );
+                }
+            }
+        }
+    })
+}
+
+/// Attempt to fund the swap automatically if auto-funding is configured for the
+/// network. The payment is checked against the spending limits and the safe
+/// funding window first, every attempt is recorded in the ledger and sent to
+/// databased. If the swap state is unknown, the attempt is deferred until
+/// swapd reports it. Returns whether the swap has been funded.
+pub fn auto_fund(
+    runtime: &mut Runtime,
+    event: &mut Event,
+    deal: &Deal,
+    info: &FundingInfo,
+) -> Result<bool, Error> {
+    let (swap_id, blockchain, network, address, amount) = match info {
+        FundingInfo::Bitcoin(BitcoinFundingInfo {
+            swap_id,
+            address,
+            amount,
+        }) => (
+            *swap_id,
+            Blockchain::Bitcoin,
+            address.network.into(),
+            address.to_string(),
+            amount.as_sat(),
+        ),
+        FundingInfo::Monero(MoneroFundingInfo {
+            swap_id,
+            address,
+            amount,
+        }) => (
+            *swap_id,
+            Blockchain::Monero,
+            address.network.into(),
+            address.to_string(),
+            amount.as_pico(),
+        ),
+    };
+    let servers = match runtime.config.get_auto_funding_config(network) {
+        Some(servers) => servers,
+        None => return Ok(false),
+    };
+    info!(
+        "{} | Attempting to auto-fund {}",
+        swap_id.swap_id(),
+        blockchain.label()
+    );
+    debug!("{} | Auto funding config: {:#?}", swap_id, servers);
+
+    let timestamp = now();
+    let outcome = match check_limits(
+        &servers,
+        &runtime.auto_funding_ledger,
+        blockchain,
+        network,
+        amount,
+        timestamp,
+    )
+    .and_then(|_| {
+        check_temporal_safety(
+            &runtime.config,
+            deal,
+            blockchain,
+            runtime.latest_state_report(swap_id),
+        )
+    }) {
+        Err(reason) => AutoFundingOutcome::Refused(reason),
+        Ok(false) => {
+            info!(
+                "{} | Deferring auto-funding {} until the swap reports its state",
+                swap_id.swap_id(),
+                blockchain.label()
+            );
+            runtime.auto_funding_deferred.insert(swap_id);
+            // otherwise the next state update of the swap retries
+            if runtime.latest_state_report(swap_id).is_none() {
+                event.send_ctl_service(ServiceId::Swap(swap_id), CtlMsg::ReportState)?;
+            }
+            return Ok(false);
+        }
+        Ok(true) => {
+            let res = match info {
+                FundingInfo::Bitcoin(BitcoinFundingInfo {
+                    address, amount, ..
+                }) => send_bitcoin(&servers, address, *amount).map(|txid| txid.to_string()),
+                FundingInfo::Monero(MoneroFundingInfo {
+                    address, amount, ..
+                }) => send_monero(&servers, *address, *amount),
+            };
+            match res {
+                Ok(txid) => AutoFundingOutcome::Paid(txid),
+                Err(err) => AutoFundingOutcome::Failed(err),
+            }
+        }
+    };
+    let paid = matches!(outcome, AutoFundingOutcome::Paid(_));
+    let record = AutoFundingRecord {
+        swap_id,
+        timestamp,
+        blockchain,
+        network,
+        address,
+        amount,
+        outcome,
+    };
+    log_outcome(&record);
+    runtime.auto_funding_ledger.insert(record.clone());
+    event.send_ctl_service(ServiceId::Database, CtlMsg::SetAutoFundingRecord(record))?;
+    Ok(paid)
+}
+
+/// Log the outcome of an automatic funding attempt
+pub fn log_outcome(record: &AutoFundingRecord) {
+    let amount = display_amount(record.blockchain, record.amount);
+    match &record.outcome {
+        AutoFundingOutcome::Paid(txid) => info!(
+            "{} | Auto-funded {} {} with txid: {}",
+            record.swap_id.swap_id(),
+            amount,
+            record.blockchain.label(),
+            txid.tx_hash()
+        ),
+        AutoFundingOutcome::Refused(reason) => warn!(
+            "{} | Auto-funding {} {} refused: {}. Use `swap-cli needs-funding {}` to retrieve address and amount",
+            record.swap_id.swap_id(),
+            amount,
+            record.blockchain.label(),
+            reason,
+            record.blockchain
+        ),
+        AutoFundingOutcome::Failed(reason) => error!(
+            "{} | Auto-funding {} {} failed: {}. Use `swap-cli needs-funding {}` to retrieve address and amount",
+            record.swap_id.swap_id(),
+            amount,
+            record.blockchain.label(),
+            reason,
+            record.blockchain
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use farcaster_core::swap::SwapId;
+    use farcaster_core::Uuid;
+
+    fn servers() -> AutoFundingServers {
+        AutoFundingServers {
+            bitcoin_rpc: "http://localhost:18443".to_string(),
+            bitcoin_cookie_path: None,
+            bitcoin_rpc_user: None,
+            bitcoin_rpc_pass: None,
+            monero_rpc_wallet: "http://localhost:18083".to_string(),
+            bitcoin_max_per_swap: Some(0.1),
+            bitcoin_daily_limit: Some(0.25),
+            monero_max_per_swap: None,
+            monero_daily_limit: None,
+        }
+    }
+
+    fn paid(amount: u64, timestamp: u64) -> AutoFundingRecord {
+        AutoFundingRecord {
+            swap_id: SwapId(Uuid::new()),
+            timestamp,
+            blockchain: Blockchain::Bitcoin,
+            network: Network::Testnet,
+            address: String::new(),
+            amount,
+            outcome: AutoFundingOutcome::Paid(String::new()),
+        }
+    }
+
+    #[test]
+    fn auto_funding_limits() {
+        let servers = servers();
+        let mut ledger = AutoFundingLedger::default();
+        let now = 10 * DAY;
+        let check = |ledger: &AutoFundingLedger, amount| {
+            check_limits(
+                &servers,
+                ledger,
+                Blockchain::Bitcoin,
+                Network::Testnet,
+                amount,
+                now,
+            )
+        };
+        assert!(check(&ledger, 10_000_000).is_ok());
+        assert!(check(&ledger, 10_000_001).is_err());
+        ledger.insert(paid(10_000_000, now - 10));
+        ledger.insert(paid(10_000_000, now - 20));
+        assert!(check(&ledger, 5_000_000).is_ok());
+        assert!(check(&ledger, 5_000_001).is_err());
+        // payments older than a day do not count
+        let mut ledger = AutoFundingLedger::default();
+        ledger.insert(paid(20_000_000, now - DAY));
+        assert!(check(&ledger, 10_000_000).is_ok());
+        // monero is not limited
+        assert!(check_limits(
+            &servers,
+            &ledger,
+            Blockchain::Monero,
+            Network::Testnet,
+            u64::MAX / 2,
+            now
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unknown_swap_state_defers_monero_funding() {
+        let deal = Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap();
+        let config = Config::default();
+        let check = |blockchain, state| check_temporal_safety(&config, &deal, blockchain, state);
+        assert_eq!(check(Blockchain::Bitcoin, None), Ok(true));
+        assert_eq!(check(Blockchain::Monero, None), Ok(false));
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use farcaster_core::swap::btcxmr::{Deal, DealParameters};
+use internet2::addr::NodeId;
+
+use crate::config::SwapLimitsConfig;
+use crate::Error;
+
+/// Check that running a swap of the deal with the counterparty, if known, next
+/// to the running swaps and their counterparties stays within the limits
+pub fn check_swap_limits(
+    limits: &SwapLimitsConfig,
+    running: &[(Deal, Option<NodeId>)],
+    deal_parameters: &DealParameters,
+    counterparty: Option<NodeId>,
+) -> Result<(), Error> {
+    if let Some(max_swaps) = limits.max_swaps {
+        if running.len() >= max_swaps {
+            return Err(Error::Farcaster(format!(
+                "Limit of {} concurrent swaps reached",
+                max_swaps
+            )));
+        }
+    }
+    if let (Some(max_swaps), Some(counterparty)) = (limits.max_swaps_per_counterparty, counterparty)
+    {
+        let swaps = running
+            .iter()
+            .filter(|(_, node_id)| *node_id == Some(counterparty))
+            .count();
+        if swaps >= max_swaps {
+            return Err(Error::Farcaster(format!(
+                "Limit of {} concurrent swaps with counterparty {} reached",
+                max_swaps, counterparty
+            )));
+        }
+    }
+    if let Some(max_exposure) = limits.max_bitcoin_exposure {
+        let exposure = running
+            .iter()
+            .map(|(deal, _)| deal.parameters.arbitrating_amount)
+            .fold(deal_parameters.arbitrating_amount, |sum, amount| {
+                sum + amount
+            });
+        if exposure.as_btc() > max_exposure {
+            return Err(Error::Farcaster(format!(
+                "Bitcoin exposure of the running swaps would reach {}, above the limit of {} BTC",
+                exposure, max_exposure
+            )));
+        }
+    }
+    if let Some(max_exposure) = limits.max_monero_exposure {
+        let exposure = running
+            .iter()
+            .map(|(deal, _)| deal.parameters.accordant_amount)
+            .fold(deal_parameters.accordant_amount, |sum, amount| sum + amount);
+        if exposure.as_xmr() > max_exposure {
+            return Err(Error::Farcaster(format!(
+                "Monero exposure of the running swaps would reach {}, above the limit of {} XMR",
+                exposure, max_exposure
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{PublicKey, SecretKey, SECP256K1};
+    use std::str::FromStr;
+
+    fn deal() -> Deal {
+        Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap()
+    }
+
+    fn node_id(byte: u8) -> NodeId {
+        NodeId::from(PublicKey::from_secret_key(
+            SECP256K1,
+            &SecretKey::from_slice(&[byte; 32]).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn swap_limits() {
+        let deal = deal();
+        let running = vec![(deal.clone(), Some(node_id(1)))];
+        let check = |limits: SwapLimitsConfig, counterparty: Option<NodeId>| {
+            check_swap_limits(&limits, &running, &deal.parameters, counterparty).is_ok()
+        };
+        assert!(check(SwapLimitsConfig::default(), Some(node_id(1))));
+
+        let max_swaps = |max_swaps| SwapLimitsConfig {
+            max_swaps: Some(max_swaps),
+            ..Default::default()
+        };
+        assert!(!check(max_swaps(1), None));
+        assert!(check(max_swaps(2), None));
+
+        let per_counterparty = SwapLimitsConfig {
+            max_swaps_per_counterparty: Some(1),
+            ..Default::default()
+        };
+        assert!(!check(per_counterparty.clone(), Some(node_id(1))));
+        assert!(check(per_counterparty.clone(), Some(node_id(2))));
+        // the counterparty of a deal not taken yet is unknown
+        assert!(check(per_counterparty, None));
+
+        // the exposure includes the deal checked
+        let bitcoin_exposure = (deal.parameters.arbitrating_amount * 2).as_btc();
+        let max_bitcoin_exposure = |max_exposure| SwapLimitsConfig {
+            max_bitcoin_exposure: Some(max_exposure),
+            ..Default::default()
+        };
+        assert!(check(max_bitcoin_exposure(bitcoin_exposure), None));
+        assert!(!check(max_bitcoin_exposure(bitcoin_exposure * 0.99), None));
+
+        let monero_exposure = (deal.parameters.accordant_amount * 2).as_xmr();
+        let max_monero_exposure = |max_exposure| SwapLimitsConfig {
+            max_monero_exposure: Some(max_exposure),
+            ..Default::default()
+        };
+        assert!(check(max_monero_exposure(monero_exposure), None));
+        assert!(!check(max_monero_exposure(monero_exposure * 0.99), None));
+    }
+}
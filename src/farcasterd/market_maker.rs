@@ -0,0 +1,222 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::collections::HashSet;
+
+use farcaster_core::bitcoin::{fee::SatPerVByte, timelock::CSVTimelock};
+use farcaster_core::blockchain::{Blockchain, FeeStrategy};
+use farcaster_core::role::SwapRole;
+use farcaster_core::swap::btcxmr::DealParameters;
+use farcaster_core::trade::DealId;
+use farcaster_core::Uuid;
+
+use crate::config::{
+    MarketMakerConfig, PricingStrategy, MARKET_MAKER_CANCEL_TIMELOCK, MARKET_MAKER_PUNISH_TIMELOCK,
+};
+use crate::Error;
+
+/// Deals made by the automated market maker and the latest market price set by
+/// a client. The deals themselves are tracked by the trade state machines.
+#[derive(Debug, Default)]
+pub struct MarketMaker {
+    /// Deals made by the market maker, open or consumed by a running swap
+    pub deals: HashSet<DealId>,
+    /// Market price in XMR per BTC set with `swap-cli market-price`
+    pub price: Option<f64>,
+    /// Set once the persisted open deals are restored, the market maker waits
+    /// for its restored deals before making new ones
+    pub ready: bool,
+}
+
+/// Market price in XMR per BTC the deals are priced from
+pub fn market_price(config: &MarketMakerConfig, price: Option<f64>) -> Result<f64, Error> {
+    let price = match config.pricing {
+        PricingStrategy::Fixed => config.rate.ok_or_else(|| {
+            Error::Farcaster("The fixed pricing strategy requires a rate".to_string())
+        })?,
+        PricingStrategy::Spread => match &config.price_file {
+            Some(path) => read_price_file(path)?,
+            None => price.ok_or_else(|| {
+                Error::Farcaster(
+                    "No market price known yet, set it with swap-cli market-price".to_string(),
+                )
+            })?,
+        },
+    };
+    if !price.is_finite() || price <= 0.0 {
+        return Err(Error::Farcaster(format!("Invalid market price {}", price)));
+    }
+    Ok(price)
+}
+
+fn read_price_file(path: &str) -> Result<f64, Error> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|err| Error::Farcaster(format!("Cannot read price file {}: {}", path, err)))?;
+    content
+        .trim()
+        .parse()
+        .map_err(|err| Error::Farcaster(format!("Invalid price in file {}: {}", path, err)))
+}
+
+/// Rate in XMR per BTC of the deals, the spread is taken in favor of the maker:
+/// more monero per bitcoin when selling bitcoin, less when selling monero
+pub fn target_rate(config: &MarketMakerConfig, price: f64) -> f64 {
+    match config.pricing {
+        PricingStrategy::Fixed => price,
+        PricingStrategy::Spread => {
+            let spread = config.spread.unwrap_or(0.0) / 100.0;
+            match config.maker_role {
+                SwapRole::Bob => price * (1.0 + spread),
+                SwapRole::Alice => price * (1.0 - spread),
+            }
+        }
+    }
+}
+
+/// Rate in XMR per BTC of a deal
+pub fn deal_rate(deal_parameters: &DealParameters) -> f64 {
+    deal_parameters.accordant_amount.as_xmr() / deal_parameters.arbitrating_amount.as_btc()
+}
+
+/// Whether the rate of a deal moved away from the target rate by more than the
+/// threshold in percent
+pub fn is_stale(deal_parameters: &DealParameters, target_rate: f64, threshold: f64) -> bool {
+    ((deal_rate(deal_parameters) - target_rate) / target_rate).abs() * 100.0 > threshold
+}
+
+/// Bitcoin amounts of the deals to make for `open_deals` deals to be open,
+/// cycling through the ladder of amounts
+pub fn missing_amounts(
+    ladder: &[bitcoin::Amount],
+    open_deals: usize,
+    open: &[bitcoin::Amount],
+) -> Vec<bitcoin::Amount> {
+    let mut open = open.to_vec();
+    let mut missing = vec![];
+    for amount in ladder.iter().cycle().take(open_deals) {
+        match open.iter().position(|open_amount| open_amount == amount) {
+            Some(pos) => {
+                open.remove(pos);
+            }
+            None => missing.push(*amount),
+        }
+    }
+    missing
+}
+
+/// Amount of the asset sold by the maker in a deal, in satoshi when selling
+/// bitcoin and piconero when selling monero
+pub fn commitment(deal_parameters: &DealParameters) -> u64 {
+    match deal_parameters.maker_role {
+        SwapRole::Bob => deal_parameters.arbitrating_amount.as_sat(),
+        SwapRole::Alice => deal_parameters.accordant_amount.as_pico(),
+    }
+}
+
+/// Inventory of the asset sold by the maker, in the same unit as
+/// [`commitment`], or None if unlimited
+pub fn inventory(config: &MarketMakerConfig) -> Option<u64> {
+    match config.maker_role {
+        SwapRole::Bob => config
+            .bitcoin_inventory
+            .and_then(|btc| bitcoin::Amount::from_btc(btc).ok())
+            .map(|amount| amount.as_sat()),
+        SwapRole::Alice => config
+            .monero_inventory
+            .and_then(|xmr| monero::Amount::from_xmr(xmr).ok())
+            .map(|amount| amount.as_pico()),
+    }
+}
+
+/// Blockchain of the asset sold by the maker, funded from its wallet
+pub fn sold_blockchain(config: &MarketMakerConfig) -> Blockchain {
+    match config.maker_role {
+        SwapRole::Bob => Blockchain::Bitcoin,
+        SwapRole::Alice => Blockchain::Monero,
+    }
+}
+
+/// Amount of the asset sold left for new deals, or None if unlimited: the
+/// inventory less the commitments of the deals, and the balance of the
+/// funding wallet less the commitments not funded from it yet
+pub fn available(
+    inventory: Option<u64>,
+    committed: u64,
+    balance: Option<u64>,
+    unfunded: u64,
+) -> Option<u64> {
+    let inventory = inventory.map(|inventory| inventory.saturating_sub(committed));
+    let balance = balance.map(|balance| balance.saturating_sub(unfunded));
+    match (inventory, balance) {
+        (Some(inventory), Some(balance)) => Some(inventory.min(balance)),
+        (inventory, balance) => inventory.or(balance),
+    }
+}
+
+/// Parameters of a new deal of the market maker
+pub fn deal_parameters(
+    config: &MarketMakerConfig,
+    arbitrating_amount: bitcoin::Amount,
+    rate: f64,
+) -> Result<DealParameters, Error> {
+    let accordant_amount = monero::Amount::from_xmr(arbitrating_amount.as_btc() * rate)
+        .map_err(|err| Error::Farcaster(err.to_string()))?;
+    Ok(DealParameters {
+        uuid: Uuid::new().into(),
+        network: config.network,
+        arbitrating_blockchain: Blockchain::Bitcoin,
+        accordant_blockchain: Blockchain::Monero,
+        arbitrating_amount,
+        accordant_amount,
+        cancel_timelock: CSVTimelock::new(
+            config
+                .cancel_timelock
+                .unwrap_or(MARKET_MAKER_CANCEL_TIMELOCK),
+        ),
+        punish_timelock: CSVTimelock::new(
+            config
+                .punish_timelock
+                .unwrap_or(MARKET_MAKER_PUNISH_TIMELOCK),
+        ),
+        fee_strategy: config
+            .fee_strategy
+            .unwrap_or_else(|| FeeStrategy::Fixed(SatPerVByte::from_sat(1))),
+        maker_role: config.maker_role,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn market_maker_ladder() {
+        let btc = |amount: f64| bitcoin::Amount::from_btc(amount).unwrap();
+        let ladder = [btc(0.01), btc(0.05), btc(0.1)];
+        assert_eq!(
+            missing_amounts(&ladder, 4, &[]),
+            vec![btc(0.01), btc(0.05), btc(0.1), btc(0.01)]
+        );
+        assert_eq!(
+            missing_amounts(&ladder, 4, &[btc(0.01), btc(0.1)]),
+            vec![btc(0.05), btc(0.01)]
+        );
+        // deals off the ladder are not counted
+        assert_eq!(missing_amounts(&ladder, 1, &[btc(0.2)]), vec![btc(0.01)]);
+        assert!(missing_amounts(&[], 3, &[]).is_empty());
+    }
+
+    #[test]
+    fn market_maker_available_funds() {
+        assert_eq!(available(None, 10, None, 10), None);
+        assert_eq!(available(Some(100), 30, None, 0), Some(70));
+        // funded swaps are already out of the wallet balance
+        assert_eq!(available(None, 30, Some(50), 20), Some(30));
+        assert_eq!(available(Some(100), 30, Some(50), 20), Some(30));
+        assert_eq!(available(Some(100), 90, Some(50), 20), Some(10));
+        assert_eq!(available(Some(100), 30, Some(10), 20), Some(0));
+    }
+}
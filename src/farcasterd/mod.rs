@@ -4,12 +4,19 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+mod auto_funding;
+mod limits;
+mod market_maker;
 #[cfg(feature = "shell")]
 mod opts;
 mod runtime;
+mod shutdown;
+mod simulation;
 pub mod stats;
+mod supervisor;
 mod syncer_state_machine;
 mod trade_state_machine;
+mod webhooks;
 
 #[cfg(feature = "shell")]
 pub use opts::Opts;
@@ -6,24 +6,40 @@
 
 use crate::bus::ctl::{CtlMsg, FundingInfo, GetKeys, SwapKeys};
 use crate::bus::info::FundingInfos;
-use crate::bus::p2p::{PeerMsg, TakerCommit};
+use crate::bus::p2p::{DealRejected, PeerMsg, TakerCommit};
 use crate::bus::sync::SyncMsg;
 use crate::bus::{BusMsg, List, ServiceBus};
+#[cfg(feature = "embedded")]
+use crate::embedded::{self, ServiceThread};
 use crate::event::StateMachineExecutor;
+use crate::farcasterd::auto_funding::{self, AutoFundingLedger};
+use crate::farcasterd::limits;
+use crate::farcasterd::market_maker::{self, MarketMaker};
+use crate::farcasterd::shutdown::{Shutdown, SwapDeadline, IMMINENT_DEADLINE_BLOCKS};
 use crate::farcasterd::stats::Stats;
+use crate::farcasterd::supervisor::Supervisor;
 use crate::farcasterd::syncer_state_machine::{SyncerStateMachine, SyncerStateMachineExecutor};
 use crate::farcasterd::trade_state_machine::{TradeStateMachine, TradeStateMachineExecutor};
+use crate::farcasterd::webhooks::{self, WebhookEvent, Webhooks};
 use crate::farcasterd::Opts;
-use crate::syncerd::{AddressBalance, TaskAborted};
-use crate::syncerd::{Event as SyncerEvent, HealthResult, SweepSuccess, TaskId};
+use crate::swapd::StateReport;
+use crate::syncerd::{
+    AddressBalance, SweepAddressAddendum, SweepBitcoinAddress, SweepBitcoinBatch, TaskAborted,
+};
+use crate::syncerd::{
+    Event as SyncerEvent, FeeEstimation, HealthResult, HeightChanged, SweepSuccess, TaskId,
+};
 use crate::{
-    bus::ctl::{Keys, ProgressStack, Token},
+    bus::ctl::{Keys, ProgressStack, ProtoDeal, Token},
     bus::info::{DealInfo, DealStatusSelector, InfoMsg, NodeInfo, ProgressEvent, SwapProgress},
-    bus::{Failure, FailureCode, Progress},
+    bus::{
+        DealExpiry, DealRange, DealStatus, DealStatusPair, EventKind, Failure, FailureCode,
+        MakerAddresses, NodeEvent, OpenDeal, Outcome, Progress,
+    },
     clap::Parser,
-    config::ParsedSwapConfig,
+    config::{ParsedSwapConfig, MARKET_MAKER_INTERVAL, MARKET_MAKER_REPRICE_THRESHOLD},
     error::SyncerError,
-    service::Endpoints,
+    service::{terminate_service, Endpoints},
 };
 use crate::{Config, CtlServer, Error, LogStyle, Service, ServiceConfig, ServiceId};
 
@@ -40,12 +56,16 @@ use clap::IntoApp;
 use farcaster_core::{
     blockchain::{Blockchain, Network},
     role::TradeRole,
-    swap::btcxmr::Deal,
+    swap::btcxmr::{Deal, DealParameters},
     swap::SwapId,
 };
 use internet2::addr::NodeId;
+use internet2::session::LocalSession;
+use internet2::SendRecvMessage;
 use internet2::{addr::InetSocketAddr, addr::NodeAddr};
+use internet2::{zeromq::ZmqSocketType, TypedEnum};
 use microservices::esb::{self, Handler};
+use microservices::ZMQ_CONTEXT;
 
 pub fn run(
     service_config: ServiceConfig,
@@ -53,20 +73,25 @@ pub fn run(
     _opts: Opts,
     wallet_token: Token,
 ) -> Result<(), Error> {
-    let _walletd = launch("walletd", &["--token", &wallet_token.to_string()])?;
+    let mut supervisor = Supervisor::default();
+    supervisor.launch(
+        ServiceId::Wallet,
+        "walletd",
+        vec!["--token".to_string(), wallet_token.to_string()],
+    )?;
     if config.is_grpc_enable() {
-        let _grpcd = launch(
+        supervisor.launch(
+            ServiceId::Grpcd,
             "grpcd",
-            &[
-                "--grpc-port",
-                &config.grpc.clone().unwrap().bind_port.to_string(),
-                "--grpc-ip",
-                &config.grpc_bind_ip(),
+            vec![
+                "--grpc-port".to_string(),
+                config.grpc.clone().unwrap().bind_port.to_string(),
+                "--grpc-ip".to_string(),
+                config.grpc_bind_ip(),
             ],
         )?;
     }
-    let empty: Vec<String> = vec![];
-    let _databased = launch("databased", empty)?;
+    supervisor.launch(ServiceId::Database, "databased", vec![])?;
 
     if config.is_auto_funding_enable() {
         info!(
@@ -76,6 +101,36 @@ pub fn run(
         );
     }
 
+    let batch_sweep_interval = if config.is_batch_sweep_enable() {
+        info!(
+            "{} will {} of finished swaps",
+            "farcasterd".label(),
+            "batch bitcoin sweeps".label()
+        );
+        Some(Duration::from_secs(config.get_batch_sweep_interval()))
+    } else {
+        None
+    };
+
+    let market_maker_interval = config.get_market_maker_config().map(|market_maker| {
+        info!(
+            "{} will run as an {}",
+            "farcasterd".label(),
+            "automated market maker".label()
+        );
+        Duration::from_secs(market_maker.interval.unwrap_or(MARKET_MAKER_INTERVAL))
+    });
+
+    let webhook_configs = config.get_webhooks();
+    let (webhooks, webhook_rx) = Webhooks::new(webhook_configs.clone());
+    if !webhook_configs.is_empty() {
+        info!(
+            "{} will notify {} webhooks of the swap events",
+            "farcasterd".label(),
+            webhook_configs.len()
+        );
+    }
+
     let runtime = Runtime {
         identity: ServiceId::Farcasterd,
         node_secret_key: None,
@@ -86,6 +141,7 @@ pub fn run(
         spawning_services: none!(),
         registered_services: none!(),
         deals: none!(),
+        exhausted_ranges: none!(),
         wallet_token,
         progress: none!(),
         progress_subscriptions: none!(),
@@ -94,10 +150,75 @@ pub fn run(
         syncer_task_counter: 0,
         trade_state_machines: vec![],
         syncer_state_machines: none!(),
+        auto_funding_ledger: none!(),
+        auto_funding_deferred: none!(),
+        queued_sweeps: 0,
+        market_maker: none!(),
+        block_heights: none!(),
+        open_deals_restored: false,
+        maker_addresses: none!(),
+        maker_connect_failures: none!(),
+        supervisor,
+        listeners: vec![],
+        shutdown: None,
+        webhooks,
     };
 
-    let broker = true;
-    Service::run(service_config, runtime, broker)
+    let mut service = Service::broker(service_config, runtime)?;
+    let rx = ZMQ_CONTEXT.socket(zmq::PULL)?;
+    rx.bind("inproc://farcasterdbridge")?;
+    run_timer(
+        Duration::from_secs(DEAL_EXPIRY_CHECK_INTERVAL),
+        CtlMsg::ExpireDeals,
+    )?;
+    run_timer(
+        Duration::from_secs(SUPERVISION_INTERVAL),
+        CtlMsg::SuperviseServices,
+    )?;
+    if let Some(interval) = batch_sweep_interval {
+        run_timer(interval, CtlMsg::BatchSweep)?;
+    }
+    if let Some(interval) = market_maker_interval {
+        run_timer(interval, CtlMsg::MarketMakerTick)?;
+    }
+    if !webhook_configs.is_empty() {
+        webhooks::run_delivery(webhook_configs, webhook_rx)?;
+    }
+    service.add_bridge_service_bus(rx)?;
+    service.run_loop()?;
+    unreachable!()
+}
+
+/// Interval in seconds between two checks of the made deals expiry
+const DEAL_EXPIRY_CHECK_INTERVAL: u64 = 30;
+
+/// Interval in seconds between two checks of the launched services
+const SUPERVISION_INTERVAL: u64 = 2;
+
+/// Periodically send the request to farcasterd over the bridge, used to
+/// trigger the deal expiries, the service supervision, the batch sweeps and
+/// the market maker
+fn run_timer(interval: Duration, request: CtlMsg) -> Result<(), Error> {
+    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
+    tx.connect("inproc://farcasterdbridge")?;
+    std::thread::spawn(move || {
+        let mut session = LocalSession::with_zmq_socket(ZmqSocketType::Push, tx);
+        let farcasterd_address: Vec<u8> = ServiceId::Farcasterd.into();
+        let request = BusMsg::Ctl(request);
+        loop {
+            std::thread::sleep(interval);
+            trace!("sending request over farcasterd bridge: {}", request);
+            if let Err(err) = session.send_routed_message(
+                &farcasterd_address,
+                &farcasterd_address,
+                &farcasterd_address,
+                &request.serialize(),
+            ) {
+                error!("Failed to send {} over the bridge: {}", request, err);
+            }
+        }
+    });
+    Ok(())
 }
 
 pub struct Runtime {
@@ -111,6 +232,7 @@ pub struct Runtime {
     pub spawning_services: HashSet<ServiceId>, // Services that have been launched, but have not replied with Hello yet
     pub registered_services: HashSet<ServiceId>, // Services that have announced themselves with Hello
     pub deals: HashSet<Deal>, // The set of all known deals. Includes open, consumed and ended deals includes open, consumed and ended deals
+    pub exhausted_ranges: HashSet<Deal>, // Range deals whose liquidity is exhausted, their late takers are told so
     progress: HashMap<ServiceId, VecDeque<ProgressStack>>, // A mapping from Swap ServiceId to its sent and received progress messages (Progress, Success, Failure)
     progress_subscriptions: HashMap<ServiceId, HashSet<ServiceId>>, // A mapping from a Client ServiceId to its subsribed swap progresses
     pub stats: Stats,             // Some stats about deals and swaps
@@ -118,6 +240,18 @@ pub struct Runtime {
     pub syncer_task_counter: u32, // A strictly incrementing counter of issued syncer tasks
     pub trade_state_machines: Vec<TradeStateMachine>, // New trade state machines are inserted on creation and destroyed upon state machine end transitions
     syncer_state_machines: HashMap<TaskId, SyncerStateMachine>, // New syncer state machines are inserted by their syncer task id when sending a syncer request and destroyed upon matching syncer request receival
+    pub auto_funding_ledger: AutoFundingLedger, // Automatic funding attempts, loaded from databased once connected
+    pub auto_funding_deferred: HashSet<SwapId>, // Swaps whose automatic funding waits for their state report
+    queued_sweeps: usize, // Number of bitcoin sweeps queued since the last batch sweep
+    market_maker: MarketMaker, // Deals made by the automated market maker and the market price
+    pub block_heights: HashMap<Network, u64>, // Last known bitcoin block heights, used to expire the deals
+    open_deals_restored: bool, // Set once the persisted open deals are requested from databased
+    pub maker_addresses: HashMap<SwapId, Vec<InetSocketAddr>>, // Addresses of the makers of the taken deals, loaded from databased once connected
+    maker_connect_failures: HashMap<SwapId, usize>, // Maker addresses that failed in a row, the connection gives up once all failed
+    pub supervisor: Supervisor, // Launched services, restarted or restored when they crash
+    listeners: Vec<Launched>, // Listening peerds, not connected to the bus until they accept a connection
+    shutdown: Option<Shutdown>, // Set by a shutdown request, the node exits once its services are terminated
+    webhooks: Webhooks,         // HTTP endpoints notified of the swap events
 }
 
 impl CtlServer for Runtime {}
@@ -146,6 +280,29 @@ impl esb::Handler<ServiceBus> for Runtime {
             (ServiceBus::Info, BusMsg::Info(req)) => self.handle_info(endpoints, source, req),
             // Syncer event bus for blockchain tasks and events, only accept Sync message
             (ServiceBus::Sync, BusMsg::Sync(req)) => self.handle_sync(endpoints, source, req),
+            // Internal bridge triggering the scheduled deal expiries, service
+            // supervision, batch sweeps and market maker runs
+            (ServiceBus::Bridge, BusMsg::Ctl(CtlMsg::ExpireDeals)) => self.expire_deals(endpoints),
+            (ServiceBus::Bridge, BusMsg::Ctl(CtlMsg::SuperviseServices)) => {
+                self.supervise_services(endpoints)
+            }
+            (ServiceBus::Bridge, BusMsg::Ctl(CtlMsg::BatchSweep)) => {
+                self.request_batch_sweep(endpoints)
+            }
+            (ServiceBus::Bridge, BusMsg::Ctl(CtlMsg::MarketMakerTick)) => {
+                self.run_market_maker(endpoints)
+            }
+            // Webhook notifications delivered or given up by the delivery
+            // thread, no longer persisted
+            (ServiceBus::Bridge, BusMsg::Ctl(req @ CtlMsg::RemoveWebhookDelivery(_))) => {
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Database,
+                    BusMsg::Ctl(req),
+                )?;
+                Ok(())
+            }
             // All other pairs are not supported
             (_, request) => Err(Error::NotSupported(bus, request.to_string())),
         }
@@ -198,6 +355,21 @@ impl esb::Handler<ServiceBus> for Runtime {
 }
 
 impl Runtime {
+    /// Returns the latest state report received from the swap, if any
+    pub fn latest_state_report(&self, swap_id: SwapId) -> Option<&StateReport> {
+        self.progress
+            .get(&ServiceId::Swap(swap_id))?
+            .iter()
+            .rev()
+            .find_map(|p| match p {
+                ProgressStack::Progress(Progress::StateUpdate(report)) => Some(report),
+                ProgressStack::Progress(Progress::StateTransition(transition)) => {
+                    Some(&transition.new_state)
+                }
+                _ => None,
+            })
+    }
+
     fn handle_msg(
         &mut self,
         endpoints: &mut Endpoints,
@@ -208,7 +380,13 @@ impl Runtime {
             "{} received {} from peer - processing with trade state machine",
             self.identity, request
         );
-        self.process_request_with_state_machines(BusMsg::P2p(request), source, endpoints)
+        let taker_commit = matches!(request, PeerMsg::TakerCommit(..));
+        self.process_request_with_state_machines(BusMsg::P2p(request), source, endpoints)?;
+        // re-create the market maker deal that was just taken
+        if taker_commit {
+            self.run_market_maker(endpoints)?;
+        }
+        Ok(())
     }
 
     fn handle_ctl(
@@ -235,13 +413,45 @@ impl Runtime {
                     }
                     ServiceId::Database => {
                         self.registered_services.insert(source.clone());
-                        endpoints.send_to(
-                            ServiceBus::Ctl,
-                            self.identity(),
-                            ServiceId::Database,
-                            BusMsg::Ctl(CtlMsg::CleanDanglingDeals),
-                        )?;
+                        // the deals of the running swaps are not dangling if
+                        // databased restarted after a crash
+                        if !self.supervisor.is_restarted(&source) {
+                            endpoints.send_to(
+                                ServiceBus::Ctl,
+                                self.identity(),
+                                ServiceId::Database,
+                                BusMsg::Ctl(CtlMsg::CleanDanglingDeals),
+                            )?;
+                            // requested before the checkpoints, restored swaps
+                            // reconnect to the address their deal was taken
+                            // through
+                            endpoints.send_to(
+                                ServiceBus::Info,
+                                self.identity(),
+                                ServiceId::Database,
+                                BusMsg::Info(InfoMsg::RetrieveMakerAddresses),
+                            )?;
+                            // notifications persisted before a restart are
+                            // delivered again
+                            if self.webhooks.is_enabled() {
+                                endpoints.send_to(
+                                    ServiceBus::Info,
+                                    self.identity(),
+                                    ServiceId::Database,
+                                    BusMsg::Info(InfoMsg::GetWebhookDeliveries),
+                                )?;
+                            }
+                        }
+                        if self.config.is_auto_funding_enable() {
+                            endpoints.send_to(
+                                ServiceBus::Info,
+                                self.identity(),
+                                ServiceId::Database,
+                                BusMsg::Info(InfoMsg::GetAutoFundingRecords),
+                            )?;
+                        }
                         self.handle_auto_restore(endpoints)?;
+                        self.handle_open_deals_restore(endpoints)?;
                     }
                     ServiceId::Wallet => {
                         self.registered_services.insert(source.clone());
@@ -260,9 +470,17 @@ impl Runtime {
                         let awaiting_swaps: Vec<_> = self
                             .trade_state_machines
                             .iter()
-                            .filter(|tsm| tsm.awaiting_connect_from() == Some(*addr))
+                            .filter(|tsm| {
+                                tsm.awaiting_connect_from(&self.maker_addresses) == Some(*addr)
+                            })
                             .map(|tsm| tsm.swap_id().map_or("…".to_string(), |s| s.to_string()))
                             .collect();
+                        self.log_event(
+                            endpoints,
+                            EventKind::PeerConnected,
+                            None,
+                            format!("Peer {} connected", addr),
+                        )?;
                         if !awaiting_swaps.is_empty() {
                             debug!("Received hello from awaited peerd connection {}, will continue processing once swaps {:?} are connected.", source, awaiting_swaps);
                         } else {
@@ -332,9 +550,17 @@ impl Runtime {
                 self.node_secret_key = Some(sk);
                 self.node_public_key = Some(pk);
                 self.handle_auto_restore(endpoints)?;
+                self.handle_open_deals_restore(endpoints)?;
+                self.run_market_maker(endpoints)?;
             }
 
             CtlMsg::PeerdTerminated if matches!(source, ServiceId::Peer(..)) => {
+                self.log_event(
+                    endpoints,
+                    EventKind::PeerDisconnected,
+                    None,
+                    format!("Peer {} terminated", source),
+                )?;
                 self.handle_failed_connection(endpoints, source.clone())?;
 
                 // log a message if a swap running over this connection
@@ -344,8 +570,38 @@ impl Runtime {
                 }
             }
 
+            // A connecting peerd gave up reconnecting, notify the swapds using
+            // it and clean up the connection
+            CtlMsg::PeerdUnreachable(unreachable) if matches!(source, ServiceId::Peer(..)) => {
+                warn!(
+                    "Peerd {} gave up on the connection: {}",
+                    source.bright_blue_italic(),
+                    unreachable.reason
+                );
+                for swap_id in self
+                    .trade_state_machines
+                    .iter()
+                    .filter_map(|tsm| tsm.get_swap_id_with_matching_connection(&source))
+                {
+                    endpoints.send_to(
+                        ServiceBus::Ctl,
+                        self.identity(),
+                        ServiceId::Swap(swap_id),
+                        BusMsg::Ctl(CtlMsg::PeerdUnreachable(unreachable.clone())),
+                    )?;
+                }
+                self.handle_failed_connection(endpoints, source.clone())?;
+            }
+
             // Notify all swapds in case of disconnect
             req @ (CtlMsg::Disconnected | CtlMsg::Reconnected) => {
+                if let ServiceId::Peer(_, addr) = &source {
+                    let (kind, details) = match req {
+                        CtlMsg::Disconnected => (EventKind::PeerDisconnected, "disconnected"),
+                        _ => (EventKind::PeerConnected, "reconnected"),
+                    };
+                    self.log_event(endpoints, kind, None, format!("Peer {} {}", addr, details))?;
+                }
                 for swap_id in self
                     .trade_state_machines
                     .iter()
@@ -362,6 +618,15 @@ impl Runtime {
 
             // Add progress in queues and forward to subscribed clients
             event @ (CtlMsg::Progress(..) | CtlMsg::Success(..) | CtlMsg::Failure(..)) => {
+                match (&source, &event) {
+                    (ServiceId::Swap(swap_id), CtlMsg::Progress(progress)) => {
+                        self.notify_progress(endpoints, *swap_id, progress)?;
+                    }
+                    (ServiceId::Swap(swap_id), CtlMsg::Failure(failure)) => {
+                        self.log_event(endpoints, EventKind::Error, Some(*swap_id), &failure.info)?;
+                    }
+                    _ => {}
+                }
                 if !self.progress.contains_key(&source) {
                     self.progress.insert(source.clone(), none!());
                 };
@@ -385,7 +650,70 @@ impl Runtime {
                 };
                 queue.push_back(prog.0);
                 // forward the request to each subscribed clients
+                let retry_auto_funding = match (&source, &prog.1) {
+                    (ServiceId::Swap(swap_id), InfoMsg::Progress(progress))
+                        if self.auto_funding_deferred.contains(swap_id) =>
+                    {
+                        Some(progress.clone())
+                    }
+                    _ => None,
+                };
                 self.notify_subscribed_clients(endpoints, &source, prog.1);
+                // the swap state is now known to the deferred auto-funding
+                if let Some(progress) = retry_auto_funding {
+                    self.process_request_with_state_machines(
+                        BusMsg::Ctl(CtlMsg::Progress(progress)),
+                        source,
+                        endpoints,
+                    )?;
+                }
+            }
+
+            CtlMsg::SetMarketPrice(price) => {
+                info!("Market price set to {} for 1 BTC", price);
+                self.market_maker.price = Some(price.as_xmr());
+                self.run_market_maker(endpoints)?;
+                endpoints.send_to(
+                    ServiceBus::Info,
+                    self.identity(),
+                    source,
+                    BusMsg::Info(InfoMsg::String(format!(
+                        "Market price set to {} for 1 BTC",
+                        price
+                    ))),
+                )?;
+            }
+
+            CtlMsg::Shutdown(force) => {
+                self.shutdown(endpoints, source, force)?;
+            }
+
+            CtlMsg::SwapCheckpointed => {
+                if let (ServiceId::Swap(swap_id), Some(shutdown)) = (&source, &mut self.shutdown) {
+                    info!("{} | Checkpointed for shutdown", swap_id.swap_id());
+                    shutdown.checkpointing.remove(swap_id);
+                    if shutdown.checkpointing.is_empty() && shutdown.terminating.is_none() {
+                        self.terminate_services(endpoints)?;
+                    }
+                }
+            }
+
+            CtlMsg::QueueSweep(sweep) if matches!(source, ServiceId::Swap(..)) => {
+                info!(
+                    "{} | Queued the sweep of {} for the next batch",
+                    sweep.swap_id.swap_id(),
+                    sweep.source_address.addr()
+                );
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Database,
+                    BusMsg::Ctl(CtlMsg::QueueSweep(sweep)),
+                )?;
+                self.queued_sweeps += 1;
+                if self.queued_sweeps >= self.config.get_batch_sweep_threshold() {
+                    self.request_batch_sweep(endpoints)?;
+                }
             }
 
             req => {
@@ -431,6 +759,7 @@ impl Runtime {
                             .filter_map(|tsm| tsm.open_deal())
                             .collect(),
                         stats: self.stats.clone(),
+                        crashes: self.supervisor.crashes(),
                     }),
                 )?;
             }
@@ -443,26 +772,33 @@ impl Runtime {
                 )?;
             }
 
-            InfoMsg::ListSwaps => {
+            InfoMsg::ListSwaps(network) => {
                 self.send_client_info(
                     endpoints,
                     source,
                     InfoMsg::SwapList(
                         self.trade_state_machines
                             .iter()
+                            .filter(|tsm| {
+                                network.map_or(true, |network| tsm.network() == Some(network))
+                            })
                             .filter_map(|tsm| tsm.swap_id())
                             .collect(),
                     ),
                 )?;
             }
 
-            InfoMsg::ListDeals(ref deal_status_selector) => {
+            InfoMsg::ListDeals(ref deal_status_selector, network) => {
+                let on_network = |deal: &Deal| {
+                    network.map_or(true, |network| deal.parameters.network == network)
+                };
                 match deal_status_selector {
                     DealStatusSelector::Open => {
                         let open_deals = self
                             .trade_state_machines
                             .iter()
                             .filter_map(|tsm| tsm.open_deal())
+                            .filter(on_network)
                             .map(|deal| DealInfo {
                                 deal: deal.to_string(),
                                 details: deal.clone(),
@@ -474,7 +810,7 @@ impl Runtime {
                         let pub_deals = self
                             .deals
                             .iter()
-                            .filter(|k| self.consumed_deals_contains(k))
+                            .filter(|k| self.consumed_deals_contains(k) && on_network(k))
                             .map(|deal| DealInfo {
                                 deal: deal.to_string(),
                                 details: deal.clone(),
@@ -555,6 +891,35 @@ impl Runtime {
             //
             // If the request commes from a client, return the diff between the list and running
             // swaps, otherwise handle a restore command for each checkpoint.
+            InfoMsg::AutoFundingRecordList(list) if source == ServiceId::Database => {
+                self.auto_funding_ledger.load(list.into());
+            }
+
+            InfoMsg::SweepBatch(batch) if source == ServiceId::Database => {
+                self.start_batch_sweeps(endpoints, batch)?;
+            }
+
+            InfoMsg::OpenDeals(open_deals) if source == ServiceId::Database => {
+                self.restore_open_deals(endpoints, open_deals)?;
+            }
+
+            InfoMsg::MakerAddressesList(list) if source == ServiceId::Database => {
+                for MakerAddresses { swap_id, addrs } in list {
+                    self.maker_addresses.entry(swap_id).or_insert(addrs);
+                }
+            }
+
+            InfoMsg::WebhookDeliveries(deliveries) if source == ServiceId::Database => {
+                for delivery in deliveries {
+                    self.webhooks.deliver(delivery);
+                }
+            }
+
+            // The checkpoint of a swap whose swapd crashed
+            InfoMsg::CheckpointEntry(entry) if source == ServiceId::Database => {
+                self.handle_ctl(endpoints, source, CtlMsg::RestoreCheckpoint(entry))?;
+            }
+
             InfoMsg::CheckpointList(mut list) => {
                 if matches!(source, ServiceId::Client(_) | ServiceId::GrpcdClient(_)) {
                     self.send_client_info(
@@ -704,13 +1069,275 @@ impl Runtime {
                 ServiceBus::Info,
                 self.identity(),
                 ServiceId::Database,
-                BusMsg::Info(InfoMsg::RetrieveAllCheckpointInfo),
+                BusMsg::Info(InfoMsg::RetrieveAllCheckpointInfo(None)),
             )?;
             self.auto_restored = true;
         }
         Ok(())
     }
 
+    fn handle_open_deals_restore(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        if self.services_ready().is_ok()
+            && self.peer_keys_ready().is_ok()
+            && !self.open_deals_restored
+        {
+            endpoints.send_to(
+                ServiceBus::Info,
+                self.identity(),
+                ServiceId::Database,
+                BusMsg::Info(InfoMsg::RetrieveOpenDeals),
+            )?;
+            self.open_deals_restored = true;
+        }
+        Ok(())
+    }
+
+    /// Make again the deals persisted as open before a restart, the expired
+    /// ones are ended instead
+    fn restore_open_deals(
+        &mut self,
+        endpoints: &mut Endpoints,
+        open_deals: Vec<OpenDeal>,
+    ) -> Result<(), Error> {
+        for open_deal in open_deals {
+            let OpenDeal {
+                deal,
+                arbitrating_addr,
+                accordant_addr,
+                accordant_split,
+                alternate_addrs,
+                expiry,
+                range,
+                market_maker,
+            } = open_deal;
+            if self.trade_state_machines.iter().any(|tsm| {
+                tsm.open_deal().as_ref() == Some(&deal)
+                    || tsm.consumed_deal().as_ref() == Some(&deal)
+            }) {
+                continue;
+            }
+            if expiry.map_or(false, |expiry| {
+                self.is_expired(&expiry, deal.parameters.network)
+            }) {
+                info!("Deal {} expired while offline", deal.id().label());
+                self.log_event(
+                    endpoints,
+                    EventKind::DealRevoked,
+                    None,
+                    format!("Deal {} expired while offline", deal.id()),
+                )?;
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Database,
+                    BusMsg::Ctl(CtlMsg::RemoveOpenDeal(deal.clone())),
+                )?;
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Database,
+                    BusMsg::Ctl(CtlMsg::SetDealStatus(DealStatusPair {
+                        deal,
+                        status: DealStatus::Ended(Outcome::FailureAbort),
+                    })),
+                )?;
+                continue;
+            }
+            info!("Restoring open deal {}", deal.id().label());
+            if market_maker {
+                self.market_maker.deals.insert(deal.id());
+            }
+            self.process_request_with_state_machines(
+                BusMsg::Ctl(CtlMsg::MakeDeal(ProtoDeal {
+                    deal_parameters: deal.parameters,
+                    public_addr: deal.peer_address,
+                    alternate_addrs,
+                    arbitrating_addr,
+                    accordant_addr,
+                    accordant_split,
+                    expiry,
+                    range,
+                })),
+                self.identity(),
+                endpoints,
+            )?;
+        }
+        // the restored deals of the market maker are repriced and completed
+        self.market_maker.ready = true;
+        self.run_market_maker(endpoints)
+    }
+
+    /// Whether the deal is made by the market maker
+    pub fn is_market_maker_deal(&self, deal: &Deal) -> bool {
+        self.market_maker.deals.contains(&deal.id())
+    }
+
+    /// Whether the deal expiry passed, block heights are compared to the last
+    /// known height of the deal's network
+    pub fn is_expired(&self, expiry: &DealExpiry, network: Network) -> bool {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        expiry.is_passed(now, self.block_heights.get(&network).copied())
+    }
+
+    /// Handle the launched services that exited: crashed syncers, walletd,
+    /// databased and grpcd are restarted by the supervisor, swaps of a crashed
+    /// swapd or syncer are restored from their latest checkpoint
+    fn supervise_services(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        // while shutting down the exited services are neither restarted nor
+        // restored
+        if let Some(shutdown) = &self.shutdown {
+            if shutdown.checkpoint_timed_out() {
+                warn!(
+                    "{} swaps did not confirm their checkpoint, shutting down anyway",
+                    shutdown.checkpointing.len()
+                );
+                self.terminate_services(endpoints)?;
+            } else if shutdown.terminating.is_some() {
+                self.supervisor.reap();
+                let running = self.supervisor.running();
+                if running.is_empty() {
+                    info!("All services terminated, {}", "shutting down".label());
+                    terminate_service();
+                } else if shutdown.termination_timed_out() {
+                    warn!(
+                        "{} did not terminate, shutting down anyway",
+                        running
+                            .iter()
+                            .map(|service| service.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    terminate_service();
+                }
+            }
+            return Ok(());
+        }
+        for exit in self.supervisor.reap() {
+            let service = exit.service;
+            self.registered_services.remove(&service);
+            match service {
+                ServiceId::Swap(swap_id) => {
+                    // the pending restore is consumed even if swapd crashed
+                    let restore = self.supervisor.take_restore(&swap_id);
+                    if exit.crashed || restore {
+                        self.restore_swap(endpoints, swap_id)?;
+                    }
+                }
+                ServiceId::Syncer(_, network) if exit.crashed => {
+                    // the syncer is spawning until it is restarted, the swaps
+                    // lost their syncer tasks and are restored once their
+                    // swapd terminated
+                    self.spawning_services.insert(service.clone());
+                    let swap_ids: Vec<SwapId> = self
+                        .trade_state_machines
+                        .iter()
+                        .filter(|tsm| {
+                            tsm.consumed_deal()
+                                .map_or(false, |deal| deal.parameters.network == network)
+                        })
+                        .filter_map(|tsm| tsm.swap_id())
+                        .collect();
+                    for swap_id in swap_ids {
+                        info!(
+                            "{} | Terminating swapd to restore the swap once {} is restarted",
+                            swap_id.swap_id(),
+                            service
+                        );
+                        self.supervisor.restore_on_exit(swap_id);
+                        if let Err(err) = endpoints.send_to(
+                            ServiceBus::Ctl,
+                            self.identity(),
+                            ServiceId::Swap(swap_id),
+                            BusMsg::Ctl(CtlMsg::Terminate),
+                        ) {
+                            warn!("Failed to terminate swapd of swap {}: {}", swap_id, err);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.supervisor.restart_due();
+        Ok(())
+    }
+
+    /// Drop the trade state machine of the swap and request its checkpoint
+    /// from databased, the swap is restored once the checkpoint is received
+    fn restore_swap(&mut self, endpoints: &mut Endpoints, swap_id: SwapId) -> Result<(), Error> {
+        if let Some(pos) = self
+            .trade_state_machines
+            .iter()
+            .position(|tsm| tsm.swap_id() == Some(swap_id))
+        {
+            self.trade_state_machines.remove(pos);
+        }
+        info!(
+            "{} | Restoring the swap from its latest checkpoint",
+            swap_id.swap_id()
+        );
+        endpoints.send_to(
+            ServiceBus::Info,
+            self.identity(),
+            ServiceId::Database,
+            BusMsg::Info(InfoMsg::GetCheckpointEntry(swap_id)),
+        )?;
+        Ok(())
+    }
+
+    /// Revoke the expired open deals and retrieve the block heights of the
+    /// networks with deals expiring at a block height
+    fn expire_deals(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        self.revoke_expired_deals(endpoints)?;
+        let networks: HashSet<Network> = self
+            .trade_state_machines
+            .iter()
+            .filter_map(|tsm| match (tsm.open_deal(), tsm.open_deal_expiry()) {
+                (Some(deal), Some(DealExpiry::BlockHeight(_))) => Some(deal.parameters.network),
+                _ => None,
+            })
+            .collect();
+        for network in networks {
+            let syncer = ServiceId::Syncer(Blockchain::Bitcoin, network);
+            if self
+                .syncer_state_machines
+                .values()
+                .any(|ssm| ssm.is_block_height() && ssm.syncer() == Some(syncer.clone()))
+            {
+                continue;
+            }
+            self.process_request_with_state_machines(
+                BusMsg::Ctl(CtlMsg::GetBlockHeight(network)),
+                self.identity(),
+                endpoints,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn revoke_expired_deals(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        let expired: Vec<Deal> = self
+            .trade_state_machines
+            .iter()
+            .filter_map(|tsm| match (tsm.open_deal(), tsm.open_deal_expiry()) {
+                (Some(deal), Some(expiry)) if self.is_expired(&expiry, deal.parameters.network) => {
+                    Some(deal)
+                }
+                _ => None,
+            })
+            .collect();
+        for deal in expired {
+            info!("Deal {} expired, revoking it", deal.id().label());
+            self.process_request_with_state_machines(
+                BusMsg::Ctl(CtlMsg::RevokeDeal(deal)),
+                self.identity(),
+                endpoints,
+            )?;
+        }
+        Ok(())
+    }
+
     pub fn services_ready(&self) -> Result<(), Error> {
         if !self.registered_services.contains(&ServiceId::Wallet) {
             Err(Error::Farcaster(
@@ -792,6 +1419,9 @@ impl Runtime {
             ServiceId::Database,
             BusMsg::Ctl(CtlMsg::RemoveCheckpoint(*swap_id)),
         )?;
+        self.maker_addresses.remove(swap_id);
+        self.maker_connect_failures.remove(swap_id);
+        self.auto_funding_deferred.remove(swap_id);
 
         self.registered_services = self
             .registered_services
@@ -869,6 +1499,493 @@ impl Runtime {
             .any(|tsm_deal| tsm_deal.id() == deal.id())
     }
 
+    /// Check that running a swap of the deal with the counterparty, if known,
+    /// stays within the configured limits on the running swaps
+    pub fn check_swap_limits(
+        &self,
+        deal_parameters: &DealParameters,
+        counterparty: Option<NodeId>,
+    ) -> Result<(), Error> {
+        let limits = self.config.get_swap_limits();
+        let running: Vec<(Deal, Option<NodeId>)> = self
+            .trade_state_machines
+            .iter()
+            .filter_map(|tsm| {
+                tsm.consumed_deal()
+                    .map(|deal| (deal, self.swap_counterparty(tsm)))
+            })
+            .collect();
+        limits::check_swap_limits(&limits, &running, deal_parameters, counterparty)
+    }
+
+    /// The counterparty of a swap, the connected peer or the maker of a taken
+    /// deal if not connected
+    fn swap_counterparty(&self, tsm: &TradeStateMachine) -> Option<NodeId> {
+        tsm.get_connection()
+            .and_then(|peerd| peerd.node_addr())
+            .map(|node_addr| node_addr.id)
+            .or_else(|| {
+                tsm.consumed_deal()
+                    .filter(|deal| Some(deal.node_id) != self.node_public_key)
+                    .map(|deal| NodeId::from(deal.node_id))
+            })
+    }
+
+    /// Ask databased for the queued bitcoin sweeps, unless a batch sweep is
+    /// already in progress
+    fn request_batch_sweep(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        if self.batch_sweep_in_progress() {
+            debug!("A batch sweep is already in progress, not starting another one");
+            return Ok(());
+        }
+        self.queued_sweeps = 0;
+        endpoints.send_to(
+            ServiceBus::Info,
+            self.identity(),
+            ServiceId::Database,
+            BusMsg::Info(InfoMsg::GetSweepBatch),
+        )?;
+        Ok(())
+    }
+
+    /// Sweep the queued bitcoin sweeps with one transaction per network
+    fn start_batch_sweeps(
+        &mut self,
+        endpoints: &mut Endpoints,
+        batch: SweepBitcoinBatch,
+    ) -> Result<(), Error> {
+        if self.batch_sweep_in_progress() {
+            return Ok(());
+        }
+        let mut batches: HashMap<bitcoin::Network, Vec<SweepBitcoinAddress>> = none!();
+        for sweep in batch.sweeps {
+            batches
+                .entry(sweep.source_address.network)
+                .or_default()
+                .push(sweep);
+        }
+        for (network, sweeps) in batches {
+            info!(
+                "Sweeping {} queued addresses on {} in a batch",
+                sweeps.len().bright_blue_bold(),
+                network
+            );
+            self.process_request_with_state_machines(
+                BusMsg::Ctl(CtlMsg::SweepAddress(SweepAddressAddendum::BitcoinBatch(
+                    SweepBitcoinBatch { sweeps },
+                ))),
+                self.identity(),
+                endpoints,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn batch_sweep_in_progress(&self) -> bool {
+        self.syncer_state_machines
+            .values()
+            .any(|ssm| ssm.is_batch_sweep())
+    }
+
+    /// Keep the configured number of market maker deals open: revoke the
+    /// deals whose rate is stale and make the deals missing from the ladder,
+    /// within the inventory of the asset sold and the balance of the
+    /// auto-funding wallet
+    fn run_market_maker(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        let config = match self.config.get_market_maker_config() {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        if self.peer_keys_ready().is_err() {
+            debug!("Market maker waiting for the peerd keys");
+            return Ok(());
+        }
+        if !self.market_maker.ready {
+            debug!("Market maker waiting for the open deals to be restored");
+            return Ok(());
+        }
+        let rate = match market_maker::market_price(&config, self.market_maker.price) {
+            Ok(price) => market_maker::target_rate(&config, price),
+            Err(err) => {
+                warn!("Market maker cannot price its deals: {}", err);
+                return Ok(());
+            }
+        };
+
+        // forget the deals that are taken and ended or revoked
+        let (open_deals, consumed_deals): (Vec<Deal>, Vec<Deal>) = (
+            self.trade_state_machines
+                .iter()
+                .filter_map(|tsm| tsm.open_deal())
+                .filter(|deal| self.market_maker.deals.contains(&deal.id()))
+                .collect(),
+            self.trade_state_machines
+                .iter()
+                .filter_map(|tsm| tsm.consumed_deal())
+                .filter(|deal| self.market_maker.deals.contains(&deal.id()))
+                .collect(),
+        );
+        self.market_maker.deals = open_deals
+            .iter()
+            .chain(consumed_deals.iter())
+            .map(|deal| deal.id())
+            .collect();
+
+        let threshold = config
+            .reprice_threshold
+            .unwrap_or(MARKET_MAKER_REPRICE_THRESHOLD);
+        let mut open_amounts = vec![];
+        for deal in open_deals {
+            if market_maker::is_stale(&deal.parameters, rate, threshold) {
+                info!(
+                    "Market maker revoking deal {} with stale rate {:.4}",
+                    deal.id().label(),
+                    market_maker::deal_rate(&deal.parameters)
+                );
+                self.market_maker.deals.remove(&deal.id());
+                self.process_request_with_state_machines(
+                    BusMsg::Ctl(CtlMsg::RevokeDeal(deal)),
+                    self.identity(),
+                    endpoints,
+                )?;
+            } else {
+                open_amounts.push(deal.parameters.arbitrating_amount);
+            }
+        }
+
+        let ladder: Vec<bitcoin::Amount> = config
+            .amounts
+            .iter()
+            .filter_map(|amount| bitcoin::Amount::from_btc(*amount).ok())
+            .collect();
+        let missing_amounts =
+            market_maker::missing_amounts(&ladder, config.open_deals, &open_amounts);
+        if missing_amounts.is_empty() {
+            return Ok(());
+        }
+        let market_maker_deals: Vec<(Deal, Option<SwapId>)> = self
+            .trade_state_machines
+            .iter()
+            .filter_map(|tsm| {
+                tsm.open_deal()
+                    .or_else(|| tsm.consumed_deal())
+                    .map(|deal| (deal, tsm.swap_id()))
+            })
+            .filter(|(deal, _)| self.market_maker.deals.contains(&deal.id()))
+            .collect();
+        let committed: u64 = market_maker_deals
+            .iter()
+            .map(|(deal, _)| market_maker::commitment(&deal.parameters))
+            .sum();
+        // the deals are funded from the auto-funding wallet when configured,
+        // the funds of the running swaps it did not pay yet are still in it
+        let sold_blockchain = market_maker::sold_blockchain(&config);
+        let (balance, unfunded) = match self.config.get_auto_funding_config(config.network) {
+            Some(servers) => match auto_funding::wallet_balance(&servers, sold_blockchain) {
+                Ok(balance) => {
+                    let unfunded = market_maker_deals
+                        .iter()
+                        .filter(|(_, swap_id)| {
+                            swap_id.map_or(true, |swap_id| {
+                                !self.auto_funding_ledger.paid(swap_id, sold_blockchain)
+                            })
+                        })
+                        .map(|(deal, _)| market_maker::commitment(&deal.parameters))
+                        .sum();
+                    (Some(balance), unfunded)
+                }
+                Err(err) => {
+                    warn!(
+                        "Market maker cannot query the {} wallet balance, not making deals: {}",
+                        sold_blockchain, err
+                    );
+                    return Ok(());
+                }
+            },
+            None => (None, 0),
+        };
+        let mut available = market_maker::available(
+            market_maker::inventory(&config),
+            committed,
+            balance,
+            unfunded,
+        );
+        for amount in missing_amounts {
+            let deal_parameters = market_maker::deal_parameters(&config, amount, rate)?;
+            let commitment = market_maker::commitment(&deal_parameters);
+            if let Some(available) = available.as_mut() {
+                if commitment > *available {
+                    debug!(
+                        "Market maker inventory exhausted, not making a deal of {}",
+                        amount
+                    );
+                    continue;
+                }
+                *available -= commitment;
+            }
+            self.market_maker.deals.insert(deal_parameters.id());
+            self.process_request_with_state_machines(
+                BusMsg::Ctl(CtlMsg::MakeDeal(ProtoDeal {
+                    deal_parameters,
+                    public_addr: config.public_addr,
+                    alternate_addrs: vec![],
+                    arbitrating_addr: config.bitcoin_address.clone(),
+                    accordant_addr: config.monero_address,
+                    accordant_split: vec![],
+                    expiry: None,
+                    range: None,
+                })),
+                self.identity(),
+                endpoints,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checkpoint the running swaps and terminate the services, unless a
+    /// critical action of a swap is imminent and the shutdown is not forced
+    fn shutdown(
+        &mut self,
+        endpoints: &mut Endpoints,
+        source: ServiceId,
+        force: bool,
+    ) -> Result<(), Error> {
+        if self.shutdown.is_some() {
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                source,
+                BusMsg::Ctl(CtlMsg::Failure(Failure {
+                    code: FailureCode::Unknown,
+                    info: "The node is already shutting down.".to_string(),
+                })),
+            )?;
+            return Ok(());
+        }
+        let deadlines: Vec<SwapDeadline> = self
+            .trade_state_machines
+            .iter()
+            .filter_map(|tsm| tsm.swap_id())
+            .map(|swap_id| SwapDeadline {
+                swap_id,
+                report: self.latest_state_report(swap_id).cloned(),
+            })
+            .collect();
+        let imminent: Vec<String> = deadlines
+            .iter()
+            .filter(|deadline| deadline.is_imminent())
+            .map(|deadline| deadline.to_string())
+            .collect();
+        if !imminent.is_empty() && !force {
+            warn!("Refusing to shut down, swap deadlines are imminent");
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                source,
+                BusMsg::Ctl(CtlMsg::Failure(Failure {
+                    code: FailureCode::Unknown,
+                    info: format!(
+                        "Refusing to shut down, swaps may have to broadcast their cancel or punish transaction within {} blocks:\n{}\nUse --force to shut down anyway.",
+                        IMMINENT_DEADLINE_BLOCKS,
+                        imminent.join("\n")
+                    ),
+                })),
+            )?;
+            return Ok(());
+        }
+
+        info!(
+            "{}, checkpointing {} running swaps",
+            "Shutting down".label(),
+            deadlines.len()
+        );
+        let mut checkpointing = HashSet::new();
+        for deadline in deadlines.iter() {
+            if deadline.is_imminent() {
+                warn!("{} | Shutting down with an imminent deadline", deadline);
+            }
+            match endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                ServiceId::Swap(deadline.swap_id),
+                BusMsg::Ctl(CtlMsg::CheckpointSwap),
+            ) {
+                Ok(()) => {
+                    checkpointing.insert(deadline.swap_id);
+                }
+                Err(err) => warn!(
+                    "{} | Failed to checkpoint the swap: {}",
+                    deadline.swap_id.swap_id(),
+                    err
+                ),
+            }
+        }
+        let checkpointed = checkpointing.is_empty();
+        self.shutdown = Some(Shutdown {
+            client: source,
+            deadlines,
+            checkpointing,
+            started: SystemTime::now(),
+            terminating: None,
+        });
+        if checkpointed {
+            self.terminate_services(endpoints)?;
+        }
+        Ok(())
+    }
+
+    /// Terminate the launched services and the connected peerds, databased
+    /// is terminated last to persist the checkpoints it received first.
+    /// farcasterd exits once they all stopped.
+    fn terminate_services(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        let mut services: Vec<ServiceId> = self
+            .supervisor
+            .running()
+            .into_iter()
+            .filter(|service| *service != ServiceId::Database)
+            .collect();
+        services.extend(
+            self.registered_services
+                .iter()
+                .filter(|service| matches!(service, ServiceId::Peer(..)))
+                .cloned(),
+        );
+        services.push(ServiceId::Database);
+        for service in services {
+            info!("Terminating {}", service.label());
+            if let Err(err) = endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                service.clone(),
+                BusMsg::Ctl(CtlMsg::Terminate),
+            ) {
+                warn!("Failed to terminate {}: {}", service, err);
+            }
+        }
+        for mut listener in self.listeners.drain(..) {
+            if let Err(err) = listener.kill() {
+                warn!("Failed to stop the listening peerd {}: {}", listener, err);
+            }
+        }
+
+        let shutdown = match self.shutdown.as_mut() {
+            Some(shutdown) => shutdown,
+            None => return Ok(()),
+        };
+        shutdown.terminating = Some(SystemTime::now());
+        let mut report = vec![format!(
+            "Shutting down, {} swaps checkpointed",
+            shutdown.deadlines.len() - shutdown.checkpointing.len()
+        )];
+        report.extend(
+            shutdown
+                .deadlines
+                .iter()
+                .map(|deadline| deadline.to_string()),
+        );
+        let client = shutdown.client.clone();
+        endpoints.send_to(
+            ServiceBus::Info,
+            self.identity(),
+            client,
+            BusMsg::Info(InfoMsg::String(report.join("\n"))),
+        )?;
+        Ok(())
+    }
+
+    /// Persist and deliver the notifications of the swap event to the webhooks
+    pub fn notify_webhooks(
+        &mut self,
+        endpoints: &mut Endpoints,
+        swap_id: SwapId,
+        event: WebhookEvent,
+    ) -> Result<(), Error> {
+        if !self.webhooks.is_enabled() {
+            return Ok(());
+        }
+        for delivery in self.webhooks.notifications(swap_id, &event)? {
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                ServiceId::Database,
+                BusMsg::Ctl(CtlMsg::QueueWebhookDelivery(delivery.clone())),
+            )?;
+            self.webhooks.deliver(delivery);
+        }
+        Ok(())
+    }
+
+    /// Append the event to the event log persisted by databased
+    pub fn log_event(
+        &mut self,
+        endpoints: &mut Endpoints,
+        kind: EventKind,
+        swap_id: Option<SwapId>,
+        details: impl ToString,
+    ) -> Result<(), Error> {
+        endpoints.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            ServiceId::Database,
+            BusMsg::Ctl(CtlMsg::LogEvent(NodeEvent::new(kind, swap_id, details))),
+        )?;
+        Ok(())
+    }
+
+    /// Log the state transitions and notify the webhooks of them and of the
+    /// locks seen in the progress of the swap, compared to its previous state
+    /// report
+    fn notify_progress(
+        &mut self,
+        endpoints: &mut Endpoints,
+        swap_id: SwapId,
+        progress: &Progress,
+    ) -> Result<(), Error> {
+        if let Progress::StateTransition(transition) = progress {
+            self.log_event(
+                endpoints,
+                EventKind::StateTransition,
+                Some(swap_id),
+                format!(
+                    "{} -> {}",
+                    transition.old_state.state, transition.new_state.state
+                ),
+            )?;
+        }
+        if !self.webhooks.is_enabled() {
+            return Ok(());
+        }
+        let report = match progress {
+            Progress::StateUpdate(report) => report,
+            Progress::StateTransition(transition) => &transition.new_state,
+            Progress::Message(_) => return Ok(()),
+        };
+        if let Some(previous) = self.latest_state_report(swap_id).cloned() {
+            for (blockchain, was_locked, locked) in [
+                (Blockchain::Bitcoin, previous.arb_locked, report.arb_locked),
+                (Blockchain::Monero, previous.acc_locked, report.acc_locked),
+            ] {
+                if locked && !was_locked {
+                    self.notify_webhooks(
+                        endpoints,
+                        swap_id,
+                        WebhookEvent::Locked {
+                            blockchain: blockchain.to_string(),
+                        },
+                    )?;
+                }
+            }
+        }
+        if let Progress::StateTransition(transition) = progress {
+            self.notify_webhooks(
+                endpoints,
+                swap_id,
+                WebhookEvent::StateTransition(transition.clone()),
+            )?;
+        }
+        Ok(())
+    }
+
     fn running_swaps_contain(&self, swap_id: &SwapId) -> bool {
         self.trade_state_machines
             .iter()
@@ -924,7 +2041,9 @@ impl Runtime {
         match (req, source) {
             (BusMsg::Ctl(CtlMsg::SweepAddress(..)), _)
             | (BusMsg::Ctl(CtlMsg::HealthCheck(..)), _)
-            | (BusMsg::Ctl(CtlMsg::GetBalance(..)), _) => Ok(Some(SyncerStateMachine::Start)),
+            | (BusMsg::Ctl(CtlMsg::GetBalance(..)), _)
+            | (BusMsg::Ctl(CtlMsg::Simulate(..)), _)
+            | (BusMsg::Ctl(CtlMsg::GetBlockHeight(..)), _) => Ok(Some(SyncerStateMachine::Start)),
             (
                 BusMsg::Sync(SyncMsg::Event(SyncerEvent::SweepSuccess(SweepSuccess {
                     id, ..
@@ -943,6 +2062,18 @@ impl Runtime {
                     id, ..
                 }))),
                 _,
+            )
+            | (
+                BusMsg::Sync(SyncMsg::Event(SyncerEvent::FeeEstimation(FeeEstimation {
+                    id, ..
+                }))),
+                _,
+            ) => Ok(self.syncer_state_machines.remove(id)),
+            (
+                BusMsg::Sync(SyncMsg::Event(SyncerEvent::HeightChanged(HeightChanged {
+                    id, ..
+                }))),
+                _,
             ) => Ok(self.syncer_state_machines.remove(id)),
             (BusMsg::Sync(SyncMsg::Event(SyncerEvent::TaskAborted(TaskAborted { id, .. }))), _) => {
                 // can only match to a syncer state machine if `id` vec is singleton, i.e. a single ssm.
@@ -972,8 +2103,12 @@ impl Runtime {
             }
             (BusMsg::Ctl(CtlMsg::MakeDeal(..)), _) => Ok(Some(TradeStateMachine::StartMaker)),
             (BusMsg::Ctl(CtlMsg::TakeDeal(..)), _) => Ok(Some(TradeStateMachine::StartTaker)),
-            (BusMsg::P2p(PeerMsg::TakerCommit(TakerCommit { deal, .. })), _)
-            | (BusMsg::Ctl(CtlMsg::RevokeDeal(deal)), _) => Ok(self
+            (BusMsg::P2p(PeerMsg::TakerCommit(TakerCommit { deal, .. })), _) => Ok(self
+                .trade_state_machines
+                .iter()
+                .position(|tsm| tsm.open_deal_taken_by(deal))
+                .map(|pos| self.trade_state_machines.remove(pos))),
+            (BusMsg::Ctl(CtlMsg::RevokeDeal(deal)), _) => Ok(self
                 .trade_state_machines
                 .iter()
                 .position(|tsm| {
@@ -996,11 +2131,11 @@ impl Runtime {
                 })
                 .map(|pos| self.trade_state_machines.remove(pos))),
             (BusMsg::Ctl(CtlMsg::ConnectSuccess), ServiceId::Peer(_, addr))
-            | (BusMsg::Ctl(CtlMsg::ConnectFailed), ServiceId::Peer(_, addr)) => Ok(self
+            | (BusMsg::Ctl(CtlMsg::ConnectFailed(_)), ServiceId::Peer(_, addr)) => Ok(self
                 .trade_state_machines
                 .iter()
                 .position(|tsm| {
-                    if let Some(tsm_addr) = tsm.awaiting_connect_from() {
+                    if let Some(tsm_addr) = tsm.awaiting_connect_from(&self.maker_addresses) {
                         tsm_addr == *addr
                     } else {
                         false
@@ -1011,6 +2146,7 @@ impl Runtime {
             | (BusMsg::Ctl(CtlMsg::FundingInfo(..)), ServiceId::Swap(swap_id))
             | (BusMsg::Ctl(CtlMsg::FundingCanceled(..)), ServiceId::Swap(swap_id))
             | (BusMsg::Ctl(CtlMsg::FundingCompleted(..)), ServiceId::Swap(swap_id))
+            | (BusMsg::Ctl(CtlMsg::Progress(..)), ServiceId::Swap(swap_id))
             | (BusMsg::Ctl(CtlMsg::Connect(swap_id)), _)
             | (BusMsg::Ctl(CtlMsg::SwapOutcome(..)), ServiceId::Swap(swap_id)) => Ok(self
                 .trade_state_machines
@@ -1053,6 +2189,13 @@ impl Runtime {
             Ok(())
         } else {
             match request {
+                BusMsg::Ctl(CtlMsg::RevokeDeal(deal)) if source == self.identity() => {
+                    debug!(
+                        "Deal {} to revoke already taken or revoked",
+                        deal.id().label()
+                    );
+                    Ok(())
+                }
                 BusMsg::Ctl(CtlMsg::RevokeDeal(..)) => {
                     endpoints.send_to(
                         ServiceBus::Ctl,
@@ -1077,6 +2220,27 @@ impl Runtime {
                     )?;
                     Ok(())
                 }
+                BusMsg::P2p(PeerMsg::TakerCommit(TakerCommit { commit, deal }))
+                    if self.exhausted_ranges.iter().any(|range_deal| {
+                        *range_deal == deal || DealRange::is_child(range_deal, &deal)
+                    }) =>
+                {
+                    debug!(
+                        "{} | Deal {} taken from an exhausted range deal, rejecting it",
+                        commit.swap_id(),
+                        deal.id(),
+                    );
+                    endpoints.send_to(
+                        ServiceBus::Msg,
+                        self.identity(),
+                        source,
+                        BusMsg::P2p(PeerMsg::DealRejected(DealRejected {
+                            swap_id: commit.swap_id(),
+                            reason: "The range deal is exhausted".to_string(),
+                        })),
+                    )?;
+                    Ok(())
+                }
                 BusMsg::P2p(PeerMsg::TakerCommit(TakerCommit { commit, deal })) => {
                     debug!(
                         "{} | Deal {} already taken or aborted, replying with deal not found to the counterparty",
@@ -1120,31 +2284,30 @@ impl Runtime {
         ))?;
 
         debug!("Instantiating peerd...");
-        let child = launch(
-            "peerd",
-            &[
-                "--listen",
-                &format!("{}", address),
-                "--port",
-                &port.to_string(),
-                "--peer-secret-key",
-                &format!("{}", peer_secret_key.display_secret()),
-                "--token",
-                &self.wallet_token.clone().to_string(),
-            ],
-        );
+        let mut args = vec![
+            "--listen".to_string(),
+            format!("{}", address),
+            "--port".to_string(),
+            port.to_string(),
+            "--peer-secret-key".to_string(),
+            format!("{}", peer_secret_key.display_secret()),
+            "--token".to_string(),
+            self.wallet_token.clone().to_string(),
+        ];
+        args.extend(self.network_args());
+        let mut child = launch("peerd", args)?;
 
         // in case it can't connect wait for it to crash
         std::thread::sleep(Duration::from_secs_f32(0.1));
 
-        // status is Some if peerd returns because it crashed
-        let (child, status) = child.and_then(|mut c| c.try_wait().map(|s| (c, s)))?;
-        if status.is_some() {
+        // exit is Some if peerd returns because it crashed
+        if child.try_exit()?.is_some() {
             return Err(Error::Peer(internet2::presentation::Error::InvalidEndpoint));
         }
 
         self.listens.insert(bind_addr);
-        debug!("New instance of peerd launched with PID {}", child.id());
+        debug!("New instance of peerd launched with {}", child);
+        self.listeners.push(child);
         info!(
             "Connection daemon {} for incoming peer connections on {}",
             "listens".bright_green_bold(),
@@ -1153,6 +2316,15 @@ impl Runtime {
         Ok(node_id)
     }
 
+    /// Arguments announcing the networks the node swaps on to a peerd
+    fn network_args(&self) -> Vec<String> {
+        self.config
+            .get_supported_networks()
+            .into_iter()
+            .flat_map(|network| ["--network".to_string(), network.to_string().to_lowercase()])
+            .collect()
+    }
+
     pub fn connect_peer(&mut self, node_addr: &NodeAddr) -> Result<(bool, ServiceId), Error> {
         self.services_ready()?;
         let (peer_secret_key, _) = self.peer_keys_ready()?;
@@ -1186,26 +2358,34 @@ impl Runtime {
         debug!("{} to remote peer {}", "Connecting", node_addr);
 
         // Start peerd
-        let child = launch(
-            "peerd",
-            &[
-                "--connect",
-                &node_addr.to_string(),
-                "--peer-secret-key",
-                &format!("{}", peer_secret_key.display_secret()),
-                "--token",
-                &self.wallet_token.clone().to_string(),
-            ],
-        );
-
-        // status is Some if peerd returns because it crashed
-        let (child, status) = child.and_then(|mut c| c.try_wait().map(|s| (c, s)))?;
+        let reconnect = self.config.get_reconnect_config();
+        let mut args = vec![
+            "--connect".to_string(),
+            node_addr.to_string(),
+            "--peer-secret-key".to_string(),
+            format!("{}", peer_secret_key.display_secret()),
+            "--token".to_string(),
+            self.wallet_token.clone().to_string(),
+            "--connect-timeout".to_string(),
+            self.config.get_connect_timeout().to_string(),
+            "--reconnect-initial-delay".to_string(),
+            reconnect.initial_delay.to_string(),
+            "--reconnect-max-delay".to_string(),
+            reconnect.max_delay.to_string(),
+        ];
+        if let Some(max_attempts) = reconnect.max_attempts {
+            args.push("--reconnect-max-attempts".to_string());
+            args.push(max_attempts.to_string());
+        }
+        args.extend(self.network_args());
+        let mut child = launch("peerd", args)?;
 
-        if status.is_some() {
+        // exit is Some if peerd returns because it crashed
+        if child.try_exit()?.is_some() {
             return Err(Error::Peer(internet2::presentation::Error::InvalidEndpoint));
         }
 
-        debug!("New instance of peerd launched with PID {}", child.id());
+        debug!("New instance of peerd launched with {}", child);
 
         self.spawning_services
             .insert(ServiceId::dummy_peer_service_id(*node_addr));
@@ -1214,6 +2394,77 @@ impl Runtime {
         Ok((false, ServiceId::dummy_peer_service_id(*node_addr)))
     }
 
+    /// Connect to the maker of a swap taken by the node, through the address
+    /// the deal was taken through first, then the maker's other addresses.
+    /// The address connected through is kept first so the connection is
+    /// matched to the swap.
+    pub fn connect_maker(&mut self, deal: &Deal) -> Result<(bool, ServiceId), Error> {
+        self.maker_connect_failures.remove(&deal.id().into());
+        self.connect_maker_address(deal)
+    }
+
+    /// Retry the connection to the maker through its next address once the
+    /// connection through the current one failed, `None` once all the
+    /// maker's addresses failed.
+    pub fn reconnect_maker(&mut self, deal: &Deal) -> Option<(bool, ServiceId)> {
+        let swap_id: SwapId = deal.id().into();
+        self.maker_address_failed(&swap_id);
+        match self.connect_maker_address(deal) {
+            Ok(res) => Some(res),
+            Err(err) => {
+                warn!("{} | Could not reconnect to the maker: {}", swap_id, err);
+                None
+            }
+        }
+    }
+
+    /// Reset the failed addresses once connected to the maker
+    pub fn maker_connected(&mut self, swap_id: &SwapId) {
+        self.maker_connect_failures.remove(swap_id);
+    }
+
+    fn connect_maker_address(&mut self, deal: &Deal) -> Result<(bool, ServiceId), Error> {
+        let swap_id: SwapId = deal.id().into();
+        let mut last_error = None;
+        loop {
+            let addrs = self
+                .maker_addresses
+                .get(&swap_id)
+                .cloned()
+                .unwrap_or_else(|| vec![deal.peer_address]);
+            let failures = self
+                .maker_connect_failures
+                .get(&swap_id)
+                .copied()
+                .unwrap_or(0);
+            if failures >= addrs.len() {
+                self.maker_connect_failures.remove(&swap_id);
+                return Err(last_error
+                    .unwrap_or_else(|| Error::Farcaster(s!("all the maker addresses failed"))));
+            }
+            let node_addr = NodeAddr {
+                id: NodeId::from(deal.node_id),
+                addr: addrs[0],
+            };
+            match self.connect_peer(&node_addr) {
+                Ok(res) => return Ok(res),
+                Err(err) => {
+                    warn!("{} | Failed to connect to {}: {}", swap_id, node_addr, err);
+                    last_error = Some(err);
+                    self.maker_address_failed(&swap_id);
+                }
+            }
+        }
+    }
+
+    /// Move the maker's address that failed last, the next one is tried first
+    fn maker_address_failed(&mut self, swap_id: &SwapId) {
+        if let Some(addrs) = self.maker_addresses.get_mut(swap_id) {
+            addrs.rotate_left(1);
+        }
+        *self.maker_connect_failures.entry(*swap_id).or_insert(0) += 1;
+    }
+
     /// Notify(forward to) the subscribed clients still online with the given request
     fn notify_subscribed_clients(
         &mut self,
@@ -1242,6 +2493,7 @@ impl Runtime {
 pub fn syncer_up(
     spawning_services: &mut HashSet<ServiceId>,
     registered_services: &mut HashSet<ServiceId>,
+    supervisor: &mut Supervisor,
     blockchain: Blockchain,
     network: Network,
     config: &Config,
@@ -1258,7 +2510,7 @@ pub fn syncer_up(
         ];
         args.append(&mut syncer_servers_args(config, blockchain, network)?);
         debug!("launching syncer with: {:?}", args);
-        launch("syncerd", args)?;
+        supervisor.launch(syncer_service.clone(), "syncerd", args)?;
         spawning_services.insert(syncer_service.clone());
     }
     if registered_services.contains(&syncer_service) {
@@ -1269,17 +2521,39 @@ pub fn syncer_up(
 }
 
 /// Launch a swapd instance with all the necessary paramters for: swap id, deal to use, trade role
-/// to execute, temporal safety arguments.
+/// to execute, temporal safety arguments, and whether its bitcoin sweeps are batched.
 pub fn launch_swapd(
+    supervisor: &mut Supervisor,
     local_trade_role: TradeRole,
     deal: Deal,
     swap_id: SwapId,
     swap_config: ParsedSwapConfig,
+    batch_sweep: bool,
 ) -> Result<(), Error> {
     debug!("Instantiating swapd...");
-    let child = launch(
+    let mut optional_args = vec![];
+    let timeouts = &swap_config.timeouts;
+    for (state, timeout) in [
+        ("bob-reveal", &timeouts.bob_reveal),
+        ("bob-fee-estimated", &timeouts.bob_fee_estimated),
+        ("alice-reveal", &timeouts.alice_reveal),
+    ] {
+        if let Some(seconds) = timeout.and_then(|t| t.seconds) {
+            optional_args.push(format!("--{}-timeout-secs", state));
+            optional_args.push(seconds.to_string());
+        }
+        if let Some(blocks) = timeout.and_then(|t| t.blocks) {
+            optional_args.push(format!("--{}-timeout-blocks", state));
+            optional_args.push(blocks.to_string());
+        }
+    }
+    if batch_sweep {
+        optional_args.push("--batch-sweep".to_string());
+    }
+    supervisor.launch(
+        ServiceId::Swap(swap_id),
         "swapd",
-        &[
+        vec![
             "--arb-finality".to_string(),
             swap_config.arbitrating.finality.to_string(),
             "--arb-safety".to_string(),
@@ -1292,9 +2566,11 @@ pub fn launch_swapd(
             deal.to_string(),
             "--trade-role".to_string(),
             local_trade_role.to_string(),
-        ],
+        ]
+        .into_iter()
+        .chain(optional_args)
+        .collect(),
     )?;
-    debug!("New instance of swapd launched with PID {}", child.id());
     debug!("Awaiting for swapd to connect...");
     Ok(())
 }
@@ -1336,10 +2612,65 @@ fn syncer_servers_args(
     }
 }
 
+/// A launched microservice, a child process or a thread when the node is
+/// embedded
+pub enum Launched {
+    Process(process::Child),
+    #[cfg(feature = "embedded")]
+    Thread(ServiceThread),
+}
+
+impl Launched {
+    /// The exit of the service if it ended: Ok if it terminated, the failure
+    /// if it crashed
+    pub fn try_exit(&mut self) -> io::Result<Option<Result<(), String>>> {
+        match self {
+            Launched::Process(child) => Ok(child.try_wait()?.map(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(status.to_string())
+                }
+            })),
+            #[cfg(feature = "embedded")]
+            Launched::Thread(thread) => Ok(thread.try_exit()),
+        }
+    }
+
+    /// Kill the service process, a service thread can not be killed and ends
+    /// with the embedding application
+    pub fn kill(&mut self) -> io::Result<()> {
+        match self {
+            Launched::Process(child) => child.kill(),
+            #[cfg(feature = "embedded")]
+            Launched::Thread(_) => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Launched {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Launched::Process(child) => write!(f, "PID {}", child.id()),
+            #[cfg(feature = "embedded")]
+            Launched::Thread(thread) => write!(f, "{}", thread),
+        }
+    }
+}
+
 pub fn launch(
     name: &str,
     args: impl IntoIterator<Item = impl AsRef<OsStr>>,
-) -> io::Result<process::Child> {
+) -> io::Result<Launched> {
+    #[cfg(feature = "embedded")]
+    if let Some(shared_args) = embedded::shared_args() {
+        let args = shared_args.into_iter().chain(
+            args.into_iter()
+                .map(|arg| arg.as_ref().to_string_lossy().to_string()),
+        );
+        return embedded::spawn_service(name, args.collect()).map(Launched::Thread);
+    }
+
     let app = Opts::command();
     let mut bin_path = std::env::current_exe().map_err(|err| {
         error!("Unable to detect binary directory: {}", err);
@@ -1394,7 +2725,7 @@ pub fn launch(
     cmd.args(args);
 
     debug!("Executing `{:?}`", cmd);
-    cmd.spawn().map_err(|err| {
+    cmd.spawn().map(Launched::Process).map_err(|err| {
         error!("Error launching {}: {}", name, err);
         err
     })
@@ -0,0 +1,184 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, SystemTime};
+
+use farcaster_core::swap::SwapId;
+
+use crate::swapd::StateReport;
+use crate::ServiceId;
+
+/// Number of blocks under which a cancel or punish deadline is imminent, the
+/// node then refuses to shut down unless forced
+pub const IMMINENT_DEADLINE_BLOCKS: i64 = 6;
+/// Delay after which the services are terminated even if some swaps did not
+/// confirm their checkpoint
+pub const CHECKPOINT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay after which farcasterd exits even if some services did not terminate
+pub const TERMINATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A shutdown requested by a client. The running swaps are checkpointed
+/// first, then the services are terminated and farcasterd exits once they all
+/// stopped.
+pub struct Shutdown {
+    /// The client that requested the shutdown, replied to once the services
+    /// are terminated
+    pub client: ServiceId,
+    /// The deadlines of the running swaps, reported to the client
+    pub deadlines: Vec<SwapDeadline>,
+    /// Swaps whose checkpoint is not confirmed yet
+    pub checkpointing: HashSet<SwapId>,
+    pub started: SystemTime,
+    /// Set once the services are requested to terminate
+    pub terminating: Option<SystemTime>,
+}
+
+impl Shutdown {
+    /// Whether the swaps did not confirm their checkpoint in time
+    pub fn checkpoint_timed_out(&self) -> bool {
+        self.terminating.is_none() && elapsed(self.started, CHECKPOINT_TIMEOUT)
+    }
+
+    /// Whether the services did not terminate in time
+    pub fn termination_timed_out(&self) -> bool {
+        self.terminating.map_or(false, |terminating| {
+            elapsed(terminating, TERMINATION_TIMEOUT)
+        })
+    }
+}
+
+fn elapsed(since: SystemTime, timeout: Duration) -> bool {
+    SystemTime::now()
+        .duration_since(since)
+        .map_or(false, |elapsed| elapsed > timeout)
+}
+
+/// The cancel and punish deadlines of a running swap, from its latest state
+/// report
+#[derive(Clone, Debug)]
+pub struct SwapDeadline {
+    pub swap_id: SwapId,
+    /// None if swapd did not report its state yet
+    pub report: Option<StateReport>,
+}
+
+impl SwapDeadline {
+    /// Whether the cancel or the punish transaction may soon be broadcasted.
+    /// The deadlines are no longer relevant once the buy or the refund is
+    /// seen.
+    pub fn is_imminent(&self) -> bool {
+        let report = match &self.report {
+            Some(report) if !report.buy_seen && !report.refund_seen => report,
+            _ => return false,
+        };
+        let imminent =
+            |blocks: Option<i64>| blocks.map_or(false, |blocks| blocks <= IMMINENT_DEADLINE_BLOCKS);
+        if report.canceled {
+            imminent(report.blocks_until_punish_possible)
+        } else {
+            imminent(report.blocks_until_cancel_possible)
+        }
+    }
+}
+
+impl Display for SwapDeadline {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let report = match &self.report {
+            Some(report) => report,
+            None => return write!(f, "{}: no state reported yet", self.swap_id),
+        };
+        write!(f, "{} ({})", self.swap_id, report.state)?;
+        let deadlines = [
+            ("cancel", report.blocks_until_cancel_possible),
+            ("punish", report.blocks_until_punish_possible),
+        ];
+        let mut deadlines = deadlines
+            .iter()
+            .filter_map(|(tx, blocks)| blocks.map(|blocks| (tx, blocks)))
+            .peekable();
+        if deadlines.peek().is_none() {
+            return write!(f, ": no upcoming deadline");
+        }
+        for (tx, blocks) in deadlines {
+            if blocks > 0 {
+                write!(f, ", {} possible in {} blocks", tx, blocks)?;
+            } else {
+                write!(f, ", {} possible now", tx)?;
+            }
+        }
+        if self.is_imminent() {
+            write!(f, " (imminent)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use farcaster_core::Uuid;
+
+    #[test]
+    fn imminent_deadlines() {
+        let report = StateReport {
+            state: "Bob Accordant Lock".to_string(),
+            arb_block_height: 0,
+            acc_block_height: 0,
+            arb_locked: true,
+            acc_locked: true,
+            canceled: false,
+            buy_seen: false,
+            refund_seen: false,
+            overfunded: false,
+            arb_lock_confirmations: Some(10),
+            acc_lock_confirmations: Some(10),
+            cancel_confirmations: None,
+            blocks_until_cancel_possible: Some(20),
+            blocks_until_punish_possible: None,
+            blocks_until_safe_buy: None,
+            blocks_until_safe_monero_buy_sweep: None,
+        };
+        let deadline = |report: StateReport| SwapDeadline {
+            swap_id: SwapId(Uuid::new()),
+            report: Some(report),
+        };
+        assert!(!deadline(report.clone()).is_imminent());
+        assert!(deadline(StateReport {
+            blocks_until_cancel_possible: Some(3),
+            ..report.clone()
+        })
+        .is_imminent());
+        // the deadlines no longer matter once the buy is seen
+        assert!(!deadline(StateReport {
+            blocks_until_cancel_possible: Some(3),
+            buy_seen: true,
+            ..report.clone()
+        })
+        .is_imminent());
+        // once canceled only the punish deadline is checked
+        assert!(!deadline(StateReport {
+            canceled: true,
+            blocks_until_cancel_possible: Some(-5),
+            blocks_until_punish_possible: Some(30),
+            ..report.clone()
+        })
+        .is_imminent());
+        assert!(deadline(StateReport {
+            canceled: true,
+            blocks_until_cancel_possible: Some(-5),
+            blocks_until_punish_possible: Some(0),
+            ..report
+        })
+        .is_imminent());
+        assert!(!SwapDeadline {
+            swap_id: SwapId(Uuid::new()),
+            report: None,
+        }
+        .is_imminent());
+    }
+}
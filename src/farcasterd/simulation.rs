@@ -0,0 +1,247 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::convert::TryInto;
+
+use farcaster_core::bitcoin::fee::SatPerVByte;
+use farcaster_core::blockchain::FeeStrategy;
+use farcaster_core::swap::btcxmr::DealParameters;
+
+use crate::bus::info::{BlockWindow, SimulationReport};
+use crate::config::{Config, ParsedSwapConfig};
+use crate::swapd::{lock_fee, TemporalSafety, SWEEP_MONERO_THRESHOLD};
+use crate::syncerd::FeeEstimations;
+use crate::Error;
+
+/// Weight of the unsigned cancel transaction, one input spending the lock and
+/// one P2WSH output. The fee strategy is applied on the unsigned weight, see
+/// farcaster_core's `Fee` implementation.
+const CANCEL_TX_WEIGHT: u64 = 376;
+
+/// Weight of the unsigned buy, refund, and punish transactions, one input and
+/// one P2WPKH output
+const SPENDING_TX_WEIGHT: u64 = 328;
+
+/// Dust limit of a P2WPKH output
+const DUST_LIMIT: u64 = 294;
+
+/// Smallest Monero amount accepted when making a deal
+const MIN_ACCORDANT_PICO: u64 = 1_000_000_000;
+
+/// Swap configuration of the blockchains and network of the deal
+pub fn swap_config(
+    config: &Config,
+    deal_parameters: &DealParameters,
+) -> Result<ParsedSwapConfig, Error> {
+    Ok(config.get_swap_config(
+        deal_parameters.arbitrating_blockchain.try_into()?,
+        deal_parameters.accordant_blockchain.try_into()?,
+        deal_parameters.network,
+    )?)
+}
+
+pub fn temporal_safety(
+    deal_parameters: &DealParameters,
+    swap_config: &ParsedSwapConfig,
+) -> TemporalSafety {
+    TemporalSafety {
+        cancel_timelock: deal_parameters.cancel_timelock.as_u32(),
+        punish_timelock: deal_parameters.punish_timelock.as_u32(),
+        btc_finality_thr: swap_config.arbitrating.finality.into(),
+        race_thr: swap_config.arbitrating.safety.into(),
+        xmr_finality_thr: swap_config.accordant.finality.into(),
+        sweep_monero_thr: SWEEP_MONERO_THRESHOLD,
+    }
+}
+
+/// Fee rate used by the pre-signed transactions, the low priority rate of a
+/// range as in swapd's wallet
+fn strategy_sat_per_vbyte(fee_strategy: &FeeStrategy<SatPerVByte>) -> u64 {
+    match fee_strategy {
+        FeeStrategy::Fixed(rate) => rate.as_native_unit().as_sat(),
+        FeeStrategy::Range { min_inc, .. } => min_inc.as_native_unit().as_sat(),
+    }
+}
+
+pub fn simulate(
+    deal_parameters: &DealParameters,
+    temporal_safety: &TemporalSafety,
+    fee_estimations: &FeeEstimations,
+) -> SimulationReport {
+    let FeeEstimations::BitcoinFeeEstimation {
+        high_priority_sats_per_kvbyte,
+        low_priority_sats_per_kvbyte,
+    } = *fee_estimations;
+    let TemporalSafety {
+        cancel_timelock,
+        punish_timelock,
+        race_thr,
+        btc_finality_thr,
+        ..
+    } = *temporal_safety;
+    let mut incompatibilities = vec![];
+
+    let rate = strategy_sat_per_vbyte(&deal_parameters.fee_strategy);
+    let lock_fee = lock_fee(high_priority_sats_per_kvbyte);
+    let cancel_fee = bitcoin::Amount::from_sat(rate * CANCEL_TX_WEIGHT);
+    let spending_fee = bitcoin::Amount::from_sat(rate * SPENDING_TX_WEIGHT);
+    if rate * 1000 < low_priority_sats_per_kvbyte {
+        incompatibilities.push(format!(
+            "Fee strategy of {} sat/vB is below the current low priority estimate of {} sat/vB, the cancel and refund transactions may not confirm in time",
+            rate,
+            low_priority_sats_per_kvbyte / 1000
+        ));
+    }
+
+    if cancel_timelock <= btc_finality_thr {
+        incompatibilities.push(format!(
+            "Cancel timelock {} must be greater than the bitcoin finality {}",
+            cancel_timelock, btc_finality_thr
+        ));
+    }
+    if punish_timelock <= cancel_timelock {
+        incompatibilities.push(format!(
+            "Punish timelock {} must be greater than the cancel timelock {}",
+            punish_timelock, cancel_timelock
+        ));
+    }
+    if race_thr <= btc_finality_thr {
+        incompatibilities.push(format!(
+            "Bitcoin safety {} must be greater than the bitcoin finality {}",
+            race_thr, btc_finality_thr
+        ));
+    }
+    if cancel_timelock <= race_thr {
+        incompatibilities.push(format!(
+            "Cancel timelock {} must be greater than the bitcoin safety {}",
+            cancel_timelock, race_thr
+        ));
+    }
+    let safe_buy_window = BlockWindow {
+        from: btc_finality_thr,
+        to: cancel_timelock.saturating_sub(race_thr),
+    };
+    if safe_buy_window.is_empty() {
+        incompatibilities.push(format!(
+            "No block window to safely buy: the lock is final after {} confirmations but buy is unsafe after {}",
+            safe_buy_window.from, safe_buy_window.to
+        ));
+    }
+    let safe_refund_window = BlockWindow {
+        from: btc_finality_thr,
+        to: punish_timelock.saturating_sub(race_thr),
+    };
+    if safe_refund_window.is_empty() {
+        incompatibilities.push(format!(
+            "No block window to safely refund: the cancel is final after {} confirmations but refund is unsafe after {}",
+            safe_refund_window.from, safe_refund_window.to
+        ));
+    }
+
+    // the locked amount pays for the buy, or for the cancel and then the
+    // refund or the punish transaction
+    let min_arbitrating_amount = std::cmp::max(spending_fee, cancel_fee + spending_fee)
+        + bitcoin::Amount::from_sat(DUST_LIMIT);
+    if deal_parameters.arbitrating_amount < min_arbitrating_amount {
+        incompatibilities.push(format!(
+            "Bitcoin amount {} is below the minimum of {} after fees",
+            deal_parameters.arbitrating_amount, min_arbitrating_amount
+        ));
+    }
+    let arbitrating_sat = deal_parameters.arbitrating_amount.as_sat().max(1) as u128;
+    let min_accordant_pico = (deal_parameters.accordant_amount.as_pico() as u128
+        * min_arbitrating_amount.as_sat() as u128
+        / arbitrating_sat) as u64;
+    let min_accordant_amount =
+        monero::Amount::from_pico(min_accordant_pico.max(MIN_ACCORDANT_PICO));
+    if deal_parameters.accordant_amount < min_accordant_amount {
+        incompatibilities.push(format!(
+            "Monero amount {} is below the minimum of {}",
+            deal_parameters.accordant_amount, min_accordant_amount
+        ));
+    }
+
+    SimulationReport {
+        high_priority_sats_per_kvbyte,
+        low_priority_sats_per_kvbyte,
+        lock_fee,
+        buy_fee: spending_fee,
+        cancel_fee,
+        refund_fee: spending_fee,
+        punish_fee: spending_fee,
+        safe_buy_window,
+        cancel_valid_after: cancel_timelock,
+        safe_refund_window,
+        punish_valid_after: punish_timelock,
+        min_arbitrating_amount,
+        min_accordant_amount,
+        incompatibilities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use farcaster_core::bitcoin::timelock::CSVTimelock;
+    use farcaster_core::blockchain::{Blockchain, Network};
+    use farcaster_core::role::SwapRole;
+    use farcaster_core::Uuid;
+    use std::str::FromStr;
+
+    fn deal_parameters(cancel: u32, punish: u32) -> DealParameters {
+        DealParameters {
+            uuid: Uuid::new().into(),
+            network: Network::Testnet,
+            arbitrating_blockchain: Blockchain::Bitcoin,
+            accordant_blockchain: Blockchain::Monero,
+            arbitrating_amount: bitcoin::Amount::from_sat(100_000),
+            accordant_amount: monero::Amount::from_pico(1_000_000_000_000),
+            cancel_timelock: CSVTimelock::new(cancel),
+            punish_timelock: CSVTimelock::new(punish),
+            fee_strategy: FeeStrategy::from_str("1 satoshi/vByte").unwrap(),
+            maker_role: SwapRole::Bob,
+        }
+    }
+
+    fn temporal_safety(cancel: u32, punish: u32, finality: u32) -> TemporalSafety {
+        TemporalSafety {
+            cancel_timelock: cancel,
+            punish_timelock: punish,
+            race_thr: 3,
+            btc_finality_thr: finality,
+            xmr_finality_thr: 1,
+            sweep_monero_thr: SWEEP_MONERO_THRESHOLD,
+        }
+    }
+
+    #[test]
+    fn simulate_swap() {
+        let fees = FeeEstimations::BitcoinFeeEstimation {
+            high_priority_sats_per_kvbyte: 2000,
+            low_priority_sats_per_kvbyte: 1000,
+        };
+        let report = simulate(&deal_parameters(10, 20), &temporal_safety(10, 20, 1), &fees);
+        assert!(report.incompatibilities.is_empty());
+        assert_eq!(report.safe_buy_window, BlockWindow { from: 1, to: 7 });
+        assert_eq!(report.safe_refund_window, BlockWindow { from: 1, to: 17 });
+        assert_eq!(report.cancel_fee, bitcoin::Amount::from_sat(376));
+        assert_eq!(report.lock_fee, bitcoin::Amount::from_sat(244));
+
+        // passes `valid_params` but leaves no block to buy safely
+        assert!(temporal_safety(4, 6, 2).valid_params().is_ok());
+        let report = simulate(&deal_parameters(4, 6), &temporal_safety(4, 6, 2), &fees);
+        assert!(report.safe_buy_window.is_empty());
+        assert_eq!(report.incompatibilities.len(), 1);
+
+        // the fee strategy is below the current estimate
+        let fees = FeeEstimations::BitcoinFeeEstimation {
+            high_priority_sats_per_kvbyte: 20000,
+            low_priority_sats_per_kvbyte: 10000,
+        };
+        let report = simulate(&deal_parameters(10, 20), &temporal_safety(10, 20, 1), &fees);
+        assert_eq!(report.incompatibilities.len(), 1);
+    }
+}
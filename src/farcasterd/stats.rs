@@ -24,6 +24,7 @@ pub struct Stats {
     refund: u64,
     punish: u64,
     abort: u64,
+    timeout: u64,
     initialized: u64,
     awaiting_funding_btc: HashSet<SwapId>,
     awaiting_funding_xmr: HashSet<SwapId>,
@@ -40,6 +41,7 @@ impl Stats {
             Outcome::FailureRefund => self.refund += 1,
             Outcome::FailurePunish => self.punish += 1,
             Outcome::FailureAbort => self.abort += 1,
+            Outcome::FailureTimeout => self.timeout += 1,
         };
     }
 
@@ -109,6 +111,7 @@ impl Stats {
             refund,
             punish,
             abort,
+            timeout,
             initialized,
             awaiting_funding_btc,
             awaiting_funding_xmr,
@@ -117,14 +120,15 @@ impl Stats {
             funding_canceled_xmr,
             funding_canceled_btc,
         } = self;
-        let total = success + refund + punish + abort;
+        let total = success + refund + punish + abort + timeout;
         let rate = *success as f64 / (total as f64);
         info!(
-            "Swapped({}) | Refunded({}) / Punished({}) | Aborted({}) | Initialized({}) / AwaitingFundingXMR({}) / AwaitingFundingBTC({}) / FundedXMR({}) / FundedBTC({}) / FundingCanceledXMR({}) / FundingCanceledBTC({})",
+            "Swapped({}) | Refunded({}) / Punished({}) | Aborted({}) / TimedOut({}) | Initialized({}) / AwaitingFundingXMR({}) / AwaitingFundingBTC({}) / FundedXMR({}) / FundedBTC({}) / FundingCanceledXMR({}) / FundingCanceledBTC({})",
             success.label(),
             refund.label(),
             punish.label(),
             abort.label(),
+            timeout.label(),
             initialized.label(),
             awaiting_funding_xmr.len().label(),
             awaiting_funding_btc.len().label(),
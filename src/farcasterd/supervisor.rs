@@ -0,0 +1,324 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+use farcaster_core::swap::SwapId;
+
+use crate::farcasterd::runtime::{launch, Launched};
+use crate::peerd::Backoff;
+use crate::{Error, LogStyle, ServiceId};
+
+/// Delay before restarting a crashed service for the first time, doubled after
+/// each consecutive crash
+const RESTART_INITIAL_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay before restarting a crashed service
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(60);
+/// Uptime after which a restarted service is considered stable again and the
+/// restart delay is reset
+const STABLE_UPTIME: Duration = Duration::from_secs(300);
+
+/// Child processes launched by farcasterd. Crashed syncers, walletd,
+/// databased and grpcd are launched again with the same arguments after an
+/// exponential backoff, crashed swapd are reported to be restored from their
+/// checkpoint.
+#[derive(Default)]
+pub struct Supervisor {
+    children: HashMap<ServiceId, Supervised>,
+    /// Swaps whose swapd is terminated to be restored from their checkpoint
+    restores: HashSet<SwapId>,
+    /// Number of crashes per service, by binary name
+    crashes: BTreeMap<String, u64>,
+}
+
+struct Supervised {
+    name: String,
+    args: Vec<String>,
+    // None while waiting to be restarted
+    child: Option<Launched>,
+    started: SystemTime,
+    restart_at: Option<SystemTime>,
+    backoff: Backoff,
+    restarts: u64,
+}
+
+/// A supervised service that exited
+pub struct Exit {
+    pub service: ServiceId,
+    /// Whether the service exited with a failure or was killed by a signal
+    pub crashed: bool,
+}
+
+impl Supervisor {
+    /// Launch the service and supervise it
+    pub fn launch(
+        &mut self,
+        service: ServiceId,
+        name: &str,
+        args: Vec<String>,
+    ) -> Result<(), Error> {
+        let child = launch(name, &args)?;
+        debug!("New instance of {} launched with {}", name, child);
+        self.children.insert(
+            service,
+            Supervised {
+                name: name.to_string(),
+                args,
+                child: Some(child),
+                started: SystemTime::now(),
+                restart_at: None,
+                backoff: Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY, None),
+                restarts: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Collect the supervised services that exited since the last call. The
+    /// crashed services, but swapd, are scheduled for a restart.
+    pub fn reap(&mut self) -> Vec<Exit> {
+        let now = SystemTime::now();
+        let mut exits = vec![];
+        for (service, supervised) in self.children.iter_mut() {
+            let exit = match supervised.child.as_mut().map(|child| child.try_exit()) {
+                Some(Ok(Some(exit))) => exit,
+                Some(Err(err)) => {
+                    warn!("Failed to check the status of {}: {}", service, err);
+                    continue;
+                }
+                _ => continue,
+            };
+            supervised.child = None;
+            let crashed = exit.is_err();
+            if let Err(failure) = exit {
+                *self.crashes.entry(supervised.name.clone()).or_insert(0) += 1;
+                if !matches!(service, ServiceId::Swap(_)) {
+                    if now
+                        .duration_since(supervised.started)
+                        .map_or(false, |uptime| uptime > STABLE_UPTIME)
+                    {
+                        supervised.backoff =
+                            Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY, None);
+                    }
+                    let delay = supervised.backoff.next_delay().unwrap_or(RESTART_MAX_DELAY);
+                    error!(
+                        "{} crashed with {}, restarting it in {:.1} seconds",
+                        service,
+                        failure,
+                        delay.as_secs_f32()
+                    );
+                    supervised.restart_at = Some(now + delay);
+                } else {
+                    error!("{} crashed with {}", service, failure);
+                }
+            }
+            exits.push(Exit {
+                service: service.clone(),
+                crashed,
+            });
+        }
+        // only the services waiting for a restart are kept
+        self.children
+            .retain(|_, supervised| supervised.child.is_some() || supervised.restart_at.is_some());
+        exits
+    }
+
+    /// Launch again the crashed services whose restart delay elapsed, returns
+    /// the restarted services
+    pub fn restart_due(&mut self) -> Vec<ServiceId> {
+        let now = SystemTime::now();
+        let mut restarted = vec![];
+        for (service, supervised) in self.children.iter_mut() {
+            if !matches!(supervised.restart_at, Some(restart_at) if restart_at <= now) {
+                continue;
+            }
+            match launch(&supervised.name, &supervised.args) {
+                Ok(child) => {
+                    info!("Restarted {} with {}", service.label(), child);
+                    supervised.child = Some(child);
+                    supervised.started = now;
+                    supervised.restart_at = None;
+                    supervised.restarts += 1;
+                    restarted.push(service.clone());
+                }
+                Err(err) => {
+                    let delay = supervised.backoff.next_delay().unwrap_or(RESTART_MAX_DELAY);
+                    error!(
+                        "Failed to restart {}: {}, retrying in {:.1} seconds",
+                        service,
+                        err,
+                        delay.as_secs_f32()
+                    );
+                    supervised.restart_at = Some(now + delay);
+                }
+            }
+        }
+        restarted
+    }
+
+    /// Whether the service was restarted after a crash
+    pub fn is_restarted(&self, service: &ServiceId) -> bool {
+        self.children
+            .get(service)
+            .map_or(false, |supervised| supervised.restarts > 0)
+    }
+
+    /// The supervised services that are running
+    pub fn running(&self) -> Vec<ServiceId> {
+        self.children
+            .iter()
+            .filter(|(_, supervised)| supervised.child.is_some())
+            .map(|(service, _)| service.clone())
+            .collect()
+    }
+
+    /// Restore the swap from its checkpoint once its swapd exits
+    pub fn restore_on_exit(&mut self, swap_id: SwapId) {
+        self.restores.insert(swap_id);
+    }
+
+    /// Whether the swap was set to be restored once its swapd exits
+    pub fn take_restore(&mut self, swap_id: &SwapId) -> bool {
+        self.restores.remove(swap_id)
+    }
+
+    /// Number of crashes per service, by binary name
+    pub fn crashes(&self) -> BTreeMap<String, u64> {
+        self.crashes.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use farcaster_core::blockchain::{Blockchain, Network};
+    use farcaster_core::Uuid;
+    use std::process::Command;
+
+    fn supervised(name: &str, command: &str, started: SystemTime, attempts: u32) -> Supervised {
+        let mut backoff = Backoff::new(RESTART_INITIAL_DELAY, RESTART_MAX_DELAY, None);
+        for _ in 0..attempts {
+            backoff.next_delay();
+        }
+        Supervised {
+            name: name.to_string(),
+            args: vec![],
+            child: Some(Launched::Process(Command::new(command).spawn().unwrap())),
+            started,
+            restart_at: None,
+            backoff,
+            restarts: 0,
+        }
+    }
+
+    /// Reap until the expected number of services exited
+    fn reap_exits(supervisor: &mut Supervisor, count: usize) -> Vec<Exit> {
+        let mut exits = vec![];
+        for _ in 0..100 {
+            exits.extend(supervisor.reap());
+            if exits.len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        exits
+    }
+
+    #[test]
+    fn reap_exited_services() {
+        let now = SystemTime::now();
+        let syncer = ServiceId::Syncer(Blockchain::Bitcoin, Network::Testnet);
+        let swap = ServiceId::Swap(SwapId(Uuid::new()));
+        let mut supervisor = Supervisor::default();
+        supervisor
+            .children
+            .insert(syncer.clone(), supervised("syncerd", "false", now, 0));
+        supervisor
+            .children
+            .insert(swap.clone(), supervised("swapd", "false", now, 0));
+        supervisor
+            .children
+            .insert(ServiceId::Wallet, supervised("walletd", "true", now, 0));
+
+        let exits = reap_exits(&mut supervisor, 3);
+        assert_eq!(exits.len(), 3);
+        let crashed = |service: &ServiceId| {
+            exits
+                .iter()
+                .find(|exit| exit.service == *service)
+                .map(|exit| exit.crashed)
+        };
+        assert_eq!(crashed(&syncer), Some(true));
+        assert_eq!(crashed(&swap), Some(true));
+        assert_eq!(crashed(&ServiceId::Wallet), Some(false));
+        assert_eq!(supervisor.crashes().get("syncerd"), Some(&1));
+        assert_eq!(supervisor.crashes().get("swapd"), Some(&1));
+        assert_eq!(supervisor.crashes().get("walletd"), None);
+        // only the crashed syncer waits for a restart, swapd is restored
+        assert!(supervisor.children[&syncer].restart_at.is_some());
+        assert!(!supervisor.children.contains_key(&swap));
+        assert!(!supervisor.children.contains_key(&ServiceId::Wallet));
+        assert!(supervisor.running().is_empty());
+    }
+
+    #[test]
+    fn restart_backoff_reset_after_stable_uptime() {
+        let now = SystemTime::now();
+        let stable = ServiceId::Syncer(Blockchain::Bitcoin, Network::Testnet);
+        let unstable = ServiceId::Syncer(Blockchain::Monero, Network::Testnet);
+        let mut supervisor = Supervisor::default();
+        supervisor.children.insert(
+            stable.clone(),
+            supervised("syncerd", "false", now - STABLE_UPTIME * 2, 3),
+        );
+        supervisor
+            .children
+            .insert(unstable.clone(), supervised("syncerd", "false", now, 3));
+
+        assert_eq!(reap_exits(&mut supervisor, 2).len(), 2);
+        assert_eq!(supervisor.children[&stable].backoff.attempts(), 1);
+        assert_eq!(supervisor.children[&unstable].backoff.attempts(), 4);
+        assert!(
+            supervisor.children[&stable].restart_at.unwrap()
+                <= SystemTime::now() + RESTART_INITIAL_DELAY
+        );
+    }
+
+    #[test]
+    fn restart_only_when_due() {
+        let now = SystemTime::now();
+        let due = ServiceId::Syncer(Blockchain::Bitcoin, Network::Testnet);
+        let later = ServiceId::Syncer(Blockchain::Monero, Network::Testnet);
+        let mut supervisor = Supervisor::default();
+        for (service, restart_at) in [(&due, now), (&later, now + RESTART_MAX_DELAY)] {
+            let mut waiting = supervised("not-a-farcaster-service", "true", now, 0);
+            waiting.child = None;
+            waiting.restart_at = Some(restart_at);
+            supervisor.children.insert(service.clone(), waiting);
+        }
+
+        // the binary does not exist, the due restart fails and is retried later
+        assert!(supervisor.restart_due().is_empty());
+        assert!(supervisor.children[&due].restart_at.unwrap() > now);
+        assert_eq!(supervisor.children[&due].backoff.attempts(), 1);
+        assert_eq!(
+            supervisor.children[&later].restart_at,
+            Some(now + RESTART_MAX_DELAY)
+        );
+        assert_eq!(supervisor.children[&later].backoff.attempts(), 0);
+        assert!(!supervisor.is_restarted(&due));
+    }
+
+    #[test]
+    fn restore_taken_once() {
+        let swap_id = SwapId(Uuid::new());
+        let mut supervisor = Supervisor::default();
+        supervisor.restore_on_exit(swap_id);
+        assert!(supervisor.take_restore(&swap_id));
+        assert!(!supervisor.take_restore(&swap_id));
+    }
+}
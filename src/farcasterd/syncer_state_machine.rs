@@ -6,6 +6,7 @@
 
 use bitcoin::hashes::{hex::ToHex, Hash};
 use farcaster_core::blockchain::{Blockchain, Network};
+use farcaster_core::swap::btcxmr::DealParameters;
 
 use crate::{
     bus::ctl::CtlMsg,
@@ -15,13 +16,15 @@ use crate::{
     error::Error,
     event::{Event, StateMachine, StateMachineExecutor},
     syncerd::{
-        Event as SyncerEvent, GetAddressBalance, Health, HealthCheck, SweepAddress,
-        SweepAddressAddendum, Task, TaskAborted, TaskId,
+        syncer_state::NOTHING_TO_SWEEP, Abort, Boolean, Event as SyncerEvent, GetAddressBalance,
+        Health, HealthCheck, SweepAddress, SweepAddressAddendum, Task, TaskAborted, TaskId,
+        TaskTarget, WatchEstimateFee, WatchHeight,
     },
     ServiceId,
 };
 
 use super::runtime::{syncer_up, Runtime};
+use super::simulation;
 
 /// State machine for making a syncer request from and to a client.
 /// State machine automaton:
@@ -65,12 +68,22 @@ pub struct AwaitingSyncer {
     syncer: ServiceId,
     syncer_task: Task,
     syncer_task_id: TaskId,
+    simulation: Option<DealParameters>,
+    batch: Option<Vec<bitcoin::Address>>,
+    block_height: bool,
 }
 
 pub struct AwaitingSyncerRequest {
     source: ServiceId,
     syncer: ServiceId,
     syncer_task_id: TaskId,
+    /// Deal parameters to simulate once the fee estimation is received
+    simulation: Option<DealParameters>,
+    /// Source addresses of a batch sweep issued by farcasterd, removed from
+    /// the queue once swept
+    batch: Option<Vec<bitcoin::Address>>,
+    /// Whether the block height is retrieved to expire the deals
+    block_height: bool,
 }
 
 impl StateMachine<Runtime, Error> for SyncerStateMachine {
@@ -110,6 +123,29 @@ impl SyncerStateMachine {
         }
     }
 
+    pub fn is_batch_sweep(&self) -> bool {
+        match self {
+            SyncerStateMachine::AwaitingSyncer(AwaitingSyncer { batch, .. }) => batch.is_some(),
+            SyncerStateMachine::AwaitingSyncerRequest(AwaitingSyncerRequest { batch, .. }) => {
+                batch.is_some()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_block_height(&self) -> bool {
+        match self {
+            SyncerStateMachine::AwaitingSyncer(AwaitingSyncer { block_height, .. }) => {
+                *block_height
+            }
+            SyncerStateMachine::AwaitingSyncerRequest(AwaitingSyncerRequest {
+                block_height,
+                ..
+            }) => *block_height,
+            _ => false,
+        }
+    }
+
     pub fn syncer(&self) -> Option<ServiceId> {
         match self {
             SyncerStateMachine::AwaitingSyncer(AwaitingSyncer { syncer, .. }) => {
@@ -150,6 +186,23 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                 SweepAddressAddendum::Bitcoin(addendum) => {
                     (Blockchain::Bitcoin, addendum.source_address.network.into())
                 }
+                SweepAddressAddendum::BitcoinBatch(addendum) => match addendum.sweeps.first() {
+                    Some(sweep) => (Blockchain::Bitcoin, sweep.source_address.network.into()),
+                    None => {
+                        debug!("No queued sweeps to batch");
+                        return Ok(None);
+                    }
+                },
+            };
+            let batch = match &sweep_address {
+                SweepAddressAddendum::BitcoinBatch(addendum) => Some(
+                    addendum
+                        .sweeps
+                        .iter()
+                        .map(|sweep| sweep.source_address.clone())
+                        .collect(),
+                ),
+                _ => None,
             };
 
             let syncer_task_id = TaskId(runtime.syncer_task_counter);
@@ -165,6 +218,7 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
             if let Some(service_id) = syncer_up(
                 &mut runtime.spawning_services,
                 &mut runtime.registered_services,
+                &mut runtime.supervisor,
                 blockchain,
                 network,
                 &runtime.config,
@@ -175,6 +229,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                         source,
                         syncer_task_id,
                         syncer: ServiceId::Syncer(blockchain, network),
+                        simulation: None,
+                        batch,
+                        block_height: false,
                     },
                 )))
             } else {
@@ -183,6 +240,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                     syncer: ServiceId::Syncer(blockchain, network),
                     syncer_task: syncer_task,
                     syncer_task_id,
+                    simulation: None,
+                    batch,
+                    block_height: false,
                 })))
             }
         }
@@ -206,6 +266,7 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
             if let Some(service_id) = syncer_up(
                 &mut runtime.spawning_services,
                 &mut runtime.registered_services,
+                &mut runtime.supervisor,
                 blockchain,
                 network,
                 &runtime.config,
@@ -216,6 +277,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                         source,
                         syncer_task_id,
                         syncer: ServiceId::Syncer(blockchain, network),
+                        simulation: None,
+                        batch: None,
+                        block_height: false,
                     },
                 )))
             } else {
@@ -224,6 +288,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                     syncer: ServiceId::Syncer(blockchain, network),
                     syncer_task: syncer_task,
                     syncer_task_id,
+                    simulation: None,
+                    batch: None,
+                    block_height: false,
                 })))
             }
         }
@@ -236,6 +303,7 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
             match syncer_up(
                 &mut runtime.spawning_services,
                 &mut runtime.registered_services,
+                &mut runtime.supervisor,
                 blockchain,
                 network,
                 &runtime.config,
@@ -247,6 +315,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                             source,
                             syncer_task_id,
                             syncer: ServiceId::Syncer(blockchain, network),
+                            simulation: None,
+                            batch: None,
+                            block_height: false,
                         },
                     )))
                 }
@@ -255,6 +326,9 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
                     syncer: ServiceId::Syncer(blockchain, network),
                     syncer_task: syncer_task,
                     syncer_task_id,
+                    simulation: None,
+                    batch: None,
+                    block_height: false,
                 }))),
                 Err(err) => {
                     event.complete_ctl(CtlMsg::HealthResult(Health::ConfigUnavailable(
@@ -265,6 +339,103 @@ fn attempt_transition_to_awaiting_syncer_or_awaiting_syncer_request(
             }
         }
 
+        BusMsg::Ctl(CtlMsg::Simulate(deal_parameters)) => {
+            let syncer_task_id = TaskId(runtime.syncer_task_counter);
+            runtime.syncer_task_counter += 1;
+            let syncer_task = Task::WatchEstimateFee(WatchEstimateFee {
+                id: syncer_task_id,
+                lifetime: u64::MAX,
+            });
+            let network = deal_parameters.network;
+            let syncer = ServiceId::Syncer(Blockchain::Bitcoin, network);
+            match syncer_up(
+                &mut runtime.spawning_services,
+                &mut runtime.registered_services,
+                &mut runtime.supervisor,
+                Blockchain::Bitcoin,
+                network,
+                &runtime.config,
+            ) {
+                Ok(Some(service_id)) => {
+                    event.complete_sync_service(service_id, SyncMsg::Task(syncer_task))?;
+                    Ok(Some(SyncerStateMachine::AwaitingSyncerRequest(
+                        AwaitingSyncerRequest {
+                            source,
+                            syncer_task_id,
+                            syncer,
+                            simulation: Some(deal_parameters),
+                            batch: None,
+                            block_height: false,
+                        },
+                    )))
+                }
+                Ok(None) => Ok(Some(SyncerStateMachine::AwaitingSyncer(AwaitingSyncer {
+                    source,
+                    syncer,
+                    syncer_task,
+                    syncer_task_id,
+                    simulation: Some(deal_parameters),
+                    batch: None,
+                    block_height: false,
+                }))),
+                Err(err) => {
+                    event.complete_client_ctl(CtlMsg::Failure(Failure {
+                        code: FailureCode::Unknown,
+                        info: format!("Cannot estimate fees on {}: {}", network, err),
+                    }))?;
+                    Ok(None)
+                }
+            }
+        }
+
+        BusMsg::Ctl(CtlMsg::GetBlockHeight(network)) => {
+            let syncer_task_id = TaskId(runtime.syncer_task_counter);
+            runtime.syncer_task_counter += 1;
+            let syncer_task = Task::WatchHeight(WatchHeight {
+                id: syncer_task_id,
+                lifetime: u64::MAX,
+            });
+            let syncer = ServiceId::Syncer(Blockchain::Bitcoin, network);
+            match syncer_up(
+                &mut runtime.spawning_services,
+                &mut runtime.registered_services,
+                &mut runtime.supervisor,
+                Blockchain::Bitcoin,
+                network,
+                &runtime.config,
+            ) {
+                Ok(Some(service_id)) => {
+                    event.complete_sync_service(service_id, SyncMsg::Task(syncer_task))?;
+                    Ok(Some(SyncerStateMachine::AwaitingSyncerRequest(
+                        AwaitingSyncerRequest {
+                            source,
+                            syncer_task_id,
+                            syncer,
+                            simulation: None,
+                            batch: None,
+                            block_height: true,
+                        },
+                    )))
+                }
+                Ok(None) => Ok(Some(SyncerStateMachine::AwaitingSyncer(AwaitingSyncer {
+                    source,
+                    syncer,
+                    syncer_task,
+                    syncer_task_id,
+                    simulation: None,
+                    batch: None,
+                    block_height: true,
+                }))),
+                Err(err) => {
+                    warn!(
+                        "Cannot retrieve the block height on {} to expire deals: {}",
+                        network, err
+                    );
+                    Ok(None)
+                }
+            }
+        }
+
         req => {
             warn!(
                 "Request {} from {} invalid for state start - invalidating.",
@@ -285,6 +456,9 @@ fn attempt_transition_to_awaiting_syncer_request(
         syncer,
         syncer_task,
         syncer_task_id,
+        simulation,
+        batch,
+        block_height,
     } = awaiting_syncer;
     match (event.request.clone(), event.source.clone()) {
         (BusMsg::Ctl(CtlMsg::Hello), syncer_id) if syncer == syncer_id => {
@@ -294,6 +468,9 @@ fn attempt_transition_to_awaiting_syncer_request(
                     source,
                     syncer_task_id,
                     syncer,
+                    simulation,
+                    batch,
+                    block_height,
                 },
             )))
         }
@@ -315,6 +492,9 @@ fn attempt_transition_to_awaiting_syncer_request(
                 syncer,
                 syncer_task,
                 syncer_task_id,
+                simulation,
+                batch,
+                block_height,
             })))
         }
     }
@@ -329,8 +509,29 @@ fn attempt_transition_to_end(
         syncer_task_id,
         source,
         syncer,
+        simulation,
+        batch,
+        block_height,
     } = awaiting_syncer_request;
     match (event.request.clone(), event.source.clone()) {
+        (BusMsg::Sync(SyncMsg::Event(SyncerEvent::SweepSuccess(success))), syncer_id)
+            if syncer == syncer_id && success.id == syncer_task_id && batch.is_some() =>
+        {
+            for txid in success
+                .txids
+                .iter()
+                .filter_map(|txid| bitcoin::Txid::from_slice(txid).ok())
+            {
+                info!("Swept the queued addresses in batch transaction {}", txid);
+            }
+            event.send_ctl_service(
+                ServiceId::Database,
+                CtlMsg::RemoveQueuedSweeps(batch.unwrap_or_default()),
+            )?;
+            runtime.clean_up_after_syncer_usage(event.endpoints)?;
+            Ok(None)
+        }
+
         (BusMsg::Sync(SyncMsg::Event(SyncerEvent::SweepSuccess(success))), syncer_id)
             if syncer == syncer_id && success.id == syncer_task_id =>
         {
@@ -378,6 +579,30 @@ fn attempt_transition_to_end(
             Ok(None)
         }
 
+        (
+            BusMsg::Sync(SyncMsg::Event(SyncerEvent::TaskAborted(TaskAborted {
+                id, error, ..
+            }))),
+            syncer_id,
+        ) if syncer == syncer_id && id.len() == 1 && id[0] == syncer_task_id && batch.is_some() => {
+            if error.as_deref() == Some(NOTHING_TO_SWEEP) {
+                // the queued addresses are all empty, already swept
+                info!("Nothing left to sweep on the queued addresses, dropping them");
+                event.send_ctl_service(
+                    ServiceId::Database,
+                    CtlMsg::RemoveQueuedSweeps(batch.unwrap_or_default()),
+                )?;
+            } else {
+                // the queued sweeps are kept and retried with the next batch
+                warn!(
+                    "Nothing swept by the batch sweep{}",
+                    error.map_or("".to_string(), |e| format!(": {}", e))
+                );
+            }
+            runtime.clean_up_after_syncer_usage(event.endpoints)?;
+            Ok(None)
+        }
+
         (
             BusMsg::Sync(SyncMsg::Event(SyncerEvent::TaskAborted(TaskAborted {
                 id, error, ..
@@ -405,6 +630,63 @@ fn attempt_transition_to_end(
             runtime.clean_up_after_syncer_usage(event.endpoints)?;
             Ok(None)
         }
+
+        (BusMsg::Sync(SyncMsg::Event(SyncerEvent::HeightChanged(res))), syncer_id)
+            if syncer == syncer_id && res.id == syncer_task_id && block_height =>
+        {
+            // the height is watched, the current height is enough
+            event.send_sync_service(
+                syncer.clone(),
+                SyncMsg::Task(Task::Abort(Abort {
+                    task_target: TaskTarget::TaskId(syncer_task_id),
+                    respond: Boolean::False,
+                })),
+            )?;
+            if let ServiceId::Syncer(_, network) = syncer {
+                runtime.block_heights.insert(network, res.height);
+            }
+            runtime.revoke_expired_deals(event.endpoints)?;
+            runtime.clean_up_after_syncer_usage(event.endpoints)?;
+            Ok(None)
+        }
+
+        (BusMsg::Sync(SyncMsg::Event(SyncerEvent::FeeEstimation(res))), syncer_id)
+            if syncer == syncer_id && res.id == syncer_task_id =>
+        {
+            // the fee estimation is watched, one estimate is enough
+            event.send_sync_service(
+                syncer,
+                SyncMsg::Task(Task::Abort(Abort {
+                    task_target: TaskTarget::TaskId(syncer_task_id),
+                    respond: Boolean::False,
+                })),
+            )?;
+            if let Some(deal_parameters) = simulation {
+                match simulation::swap_config(&runtime.config, &deal_parameters) {
+                    Ok(swap_config) => {
+                        let temporal_safety =
+                            simulation::temporal_safety(&deal_parameters, &swap_config);
+                        let report = simulation::simulate(
+                            &deal_parameters,
+                            &temporal_safety,
+                            &res.fee_estimations,
+                        );
+                        event.send_client_info(source, InfoMsg::SimulationReport(report))?;
+                    }
+                    Err(err) => {
+                        event.send_client_ctl(
+                            source,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: format!("Failed to simulate the swap: {}", err),
+                            }),
+                        )?;
+                    }
+                }
+            }
+            runtime.clean_up_after_syncer_usage(event.endpoints)?;
+            Ok(None)
+        }
         (req, source) => {
             if let BusMsg::Ctl(CtlMsg::Hello) = req {
                 trace!(
@@ -423,6 +705,9 @@ fn attempt_transition_to_end(
                     syncer_task_id,
                     source,
                     syncer,
+                    simulation,
+                    batch,
+                    block_height,
                 },
             )))
         }
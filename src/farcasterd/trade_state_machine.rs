@@ -6,12 +6,18 @@
 
 use crate::bus::ctl::{
     BitcoinFundingInfo, CtlMsg, FundingInfo, InitMakerSwap, InitTakerSwap, MoneroFundingInfo,
-    ProtoDeal, PubDeal, SwapKeys, WrappedKeyManager,
+    PeerdUnreachable, ProtoDeal, PubDeal, SwapKeys, WrappedKeyManager,
 };
 use crate::bus::info::{DealInfo, InfoMsg, MadeDeal, TookDeal};
-use crate::bus::p2p::{Commit, PeerMsg};
-use crate::bus::{CheckpointEntry, DealStatus, DealStatusPair, Failure, FailureCode};
+use crate::bus::p2p::{Commit, DealRejected, PeerMsg};
+use crate::bus::{
+    AdvertisedDeal, CheckpointEntry, DealExpiry, DealRange, DealStatus, DealStatusPair, EventKind,
+    Failure, FailureCode, MakerAddresses, OpenDeal,
+};
+use crate::farcasterd::auto_funding::auto_fund;
 use crate::farcasterd::runtime::{launch_swapd, syncer_up, Runtime};
+use crate::farcasterd::webhooks::WebhookEvent;
+use crate::syncerd::{validate_sweep_split, SweepDestination};
 use crate::LogStyle;
 use crate::{
     bus::{BusMsg, Outcome},
@@ -19,13 +25,16 @@ use crate::{
     event::{Event, StateMachine, StateMachineExecutor},
     ServiceId,
 };
-use farcaster_core::blockchain::Blockchain;
+use farcaster_core::blockchain::{Blockchain, Network};
 use farcaster_core::role::TradeRole;
-use farcaster_core::swap::{btcxmr::Deal, SwapId};
-use internet2::addr::{NodeAddr, NodeId};
+use farcaster_core::swap::{
+    btcxmr::{Deal, DealParameters},
+    SwapId,
+};
+use internet2::addr::{InetSocketAddr, NodeAddr, NodeId};
 use microservices::esb::Handler;
+use std::collections::HashMap;
 use std::convert::TryInto;
-use std::str::FromStr;
 
 /// State machine for launching a swap and cleaning up once done.
 ///
@@ -74,16 +83,18 @@ pub enum TradeStateMachine {
     StartRestore,
 
     /// TakerConnect state - transitions to TakeDeal once ConnectSuccess is
-    /// received from peerd or None if ConnectFailed is received. On
-    /// ConnectSuccess sends CreateSwapKeys to walletd and TookDeal back to
-    /// cli. On Connect Failed sends Failure back to cli.
+    /// received from peerd. On ConnectFailed the next maker's address is
+    /// tried, or None once all addresses failed. On ConnectSuccess sends
+    /// CreateSwapKeys to walletd and TookDeal back to cli. Once all addresses
+    /// failed sends Failure back to cli.
     #[display("Taker Connect")]
     TakerConnect(TakerConnect),
 
     /// MakeDeal state - transitions to TakerCommit once TakerCommit is
     /// received from a counterpary or None if RevokeDeal is received from the
-    /// user. Transition to TakerCommit triggers sending CreateSwapKeys to
-    /// walletd and sending SetDealStatus to databased.
+    /// user or the deal expired. Transition to TakerCommit triggers sending
+    /// CreateSwapKeys to walletd and sending SetDealStatus to databased. A
+    /// TakerCommit received after the expiry is replied with DealNotFound.
     #[display("Make Deal")]
     MakeDeal(MakeDeal),
 
@@ -122,6 +133,10 @@ pub struct MakeDeal {
     deal: Deal,
     arb_addr: bitcoin::Address,
     acc_addr: monero::Address,
+    acc_split: Vec<SweepDestination>,
+    alternate_addrs: Vec<InetSocketAddr>,
+    expiry: Option<DealExpiry>,
+    range: Option<DealRange>,
 }
 
 pub struct TakerCommit {
@@ -130,19 +145,26 @@ pub struct TakerCommit {
     commit: Commit,
     target_bitcoin_address: bitcoin::Address,
     target_monero_address: monero::Address,
+    target_monero_split: Vec<SweepDestination>,
 }
 
 pub struct TakerConnect {
     deal: Deal,
     arb_addr: bitcoin::Address,
     acc_addr: monero::Address,
+    acc_split: Vec<SweepDestination>,
     source: ServiceId,
+    // maker's address peerd is currently connecting to
+    peer_addr: NodeAddr,
+    // maker's addresses left to try if the connection fails
+    alternate_addrs: Vec<InetSocketAddr>,
 }
 
 pub struct TakeDeal {
     deal: Deal,
     arb_addr: bitcoin::Address,
     acc_addr: monero::Address,
+    acc_split: Vec<SweepDestination>,
     peerd: ServiceId,
 }
 
@@ -158,6 +180,7 @@ pub struct SwapdLaunched {
     key_manager: WrappedKeyManager,
     target_bitcoin_address: bitcoin::Address,
     target_monero_address: monero::Address,
+    target_monero_split: Vec<SweepDestination>,
 }
 
 pub struct RestoringSwapd {
@@ -282,6 +305,24 @@ impl TradeStateMachine {
         }
     }
 
+    /// Whether the taken deal is the open deal or a child deal of the open
+    /// range deal
+    pub fn open_deal_taken_by(&self, taken: &Deal) -> bool {
+        if let TradeStateMachine::MakeDeal(MakeDeal { deal, range, .. }) = self {
+            is_taken_from(deal, range, taken)
+        } else {
+            false
+        }
+    }
+
+    pub fn open_deal_expiry(&self) -> Option<DealExpiry> {
+        if let TradeStateMachine::MakeDeal(MakeDeal { expiry, .. }) = self {
+            *expiry
+        } else {
+            None
+        }
+    }
+
     pub fn consumed_deal(&self) -> Option<Deal> {
         match self {
             TradeStateMachine::TakeDeal(TakeDeal { deal, .. }) => Some(deal.clone()),
@@ -294,6 +335,13 @@ impl TradeStateMachine {
         }
     }
 
+    /// The network of the deal made or taken
+    pub fn network(&self) -> Option<Network> {
+        self.open_deal()
+            .or_else(|| self.consumed_deal())
+            .map(|deal| deal.parameters.network)
+    }
+
     pub fn swap_id(&self) -> Option<SwapId> {
         match self {
             TradeStateMachine::SwapdLaunched(SwapdLaunched { swap_id, .. }) => Some(*swap_id),
@@ -354,11 +402,12 @@ impl TradeStateMachine {
         }
     }
 
-    pub fn awaiting_connect_from(&self) -> Option<NodeAddr> {
+    pub fn awaiting_connect_from(
+        &self,
+        maker_addresses: &HashMap<SwapId, Vec<InetSocketAddr>>,
+    ) -> Option<NodeAddr> {
         match self {
-            TradeStateMachine::TakerConnect(taker_connect) => {
-                Some(node_addr_from_deal(&taker_connect.deal))
-            }
+            TradeStateMachine::TakerConnect(taker_connect) => Some(taker_connect.peer_addr),
             TradeStateMachine::RestoringSwapd(RestoringSwapd {
                 deal,
                 trade_role,
@@ -366,7 +415,7 @@ impl TradeStateMachine {
                 ..
             }) => {
                 if *trade_role == TradeRole::Taker && *expect_connection {
-                    Some(node_addr_from_deal(&deal))
+                    Some(maker_node_addr(maker_addresses, deal))
                 } else {
                     None
                 }
@@ -381,7 +430,7 @@ impl TradeStateMachine {
                 // is only possible to a listening peer as a taker, its address
                 // thus has to match the address of the deal.
                 if *trade_role == TradeRole::Taker && peerd.is_none() {
-                    Some(node_addr_from_deal(deal))
+                    Some(maker_node_addr(maker_addresses, deal))
                 } else {
                     None
                 }
@@ -431,9 +480,59 @@ fn attempt_transition_to_make_deal(
             deal_parameters,
             arbitrating_addr,
             accordant_addr,
+            accordant_split,
             public_addr,
-            ..
+            alternate_addrs,
+            expiry,
+            range,
         })) => {
+            if let Some(range) = range {
+                if range.min.as_sat() == 0
+                    || range.min > range.remaining
+                    || range.remaining > deal_parameters.arbitrating_amount
+                {
+                    event.complete_client_ctl(CtlMsg::Failure(Failure {
+                        code: FailureCode::Unknown,
+                        info: format!(
+                            "Invalid range {} for a deal of {}",
+                            range, deal_parameters.arbitrating_amount
+                        ),
+                    }))?;
+                    return Ok(None);
+                }
+            }
+            if let Some(expiry) = expiry {
+                if runtime.is_expired(&expiry, deal_parameters.network) {
+                    event.complete_client_ctl(CtlMsg::Failure(Failure {
+                        code: FailureCode::Unknown,
+                        info: format!("Deal expiry at {} already passed", expiry),
+                    }))?;
+                    return Ok(None);
+                }
+            }
+            if let Err(err) = runtime.check_swap_limits(&deal_parameters, None) {
+                warn!("Cannot make deal: {}", err);
+                event.complete_client_ctl(CtlMsg::Failure(Failure {
+                    code: FailureCode::Unknown,
+                    info: format!("Cannot make deal: {}", err),
+                }))?;
+                return Ok(None);
+            }
+            let accordant_split = match resolve_sweep_split(
+                runtime,
+                &deal_parameters,
+                accordant_addr,
+                accordant_split,
+            ) {
+                Err(err) => {
+                    event.complete_client_ctl(CtlMsg::Failure(Failure {
+                        code: FailureCode::Unknown,
+                        info: err.to_string(),
+                    }))?;
+                    return Ok(None);
+                }
+                Ok(split) => split,
+            };
             // start a listener on the bind_addr
             let bind_addr = match runtime.config.get_bind_addr() {
                 Err(err) => {
@@ -469,18 +568,54 @@ fn attempt_transition_to_make_deal(
                             status: DealStatus::Open,
                         }),
                     )?;
+                    event.send_ctl_service(
+                        ServiceId::Database,
+                        CtlMsg::SetOpenDeal(OpenDeal {
+                            deal: deal.clone(),
+                            arbitrating_addr: arbitrating_addr.clone(),
+                            accordant_addr,
+                            accordant_split: accordant_split.clone(),
+                            alternate_addrs: alternate_addrs.clone(),
+                            expiry,
+                            range,
+                            market_maker: runtime.is_market_maker_deal(&deal),
+                        }),
+                    )?;
+                    if let Some(expiry) = expiry {
+                        info!("Deal {} expires at {}", deal.id().label(), expiry);
+                    }
+                    runtime.log_event(
+                        event.endpoints,
+                        EventKind::DealMade,
+                        None,
+                        format!("Deal {} made", deal.id()),
+                    )?;
+                    let msg = match range {
+                        Some(range) => format!("{} Takers can take from {}.", msg, range),
+                        None => msg,
+                    };
+                    // takers learn the alternate addresses from the shared deal
+                    let advertised = AdvertisedDeal {
+                        deal: deal.clone(),
+                        alternate_addrs: alternate_addrs.clone(),
+                    };
                     event.complete_client_info(InfoMsg::MadeDeal(MadeDeal {
                         message: msg,
                         deal_info: DealInfo {
-                            deal: deal.to_string(),
+                            deal: advertised.to_string(),
                             details: deal.clone(),
                         },
+                        alternate_addrs: alternate_addrs.clone(),
                     }))?;
                     runtime.deals.insert(deal.clone());
                     Ok(Some(TradeStateMachine::MakeDeal(MakeDeal {
                         deal,
                         arb_addr: arbitrating_addr,
                         acc_addr: accordant_addr,
+                        acc_split: accordant_split,
+                        alternate_addrs,
+                        expiry,
+                        range,
                     })))
                 }
             }
@@ -504,6 +639,8 @@ fn attempt_transition_to_taker_connect_or_take_deal(
             deal,
             bitcoin_address: arb_addr,
             monero_address: acc_addr,
+            monero_split,
+            alternate_addrs,
         })) => {
             if runtime.consumed_deals_contains(&deal) || runtime.deals.contains(&deal) {
                 let msg = format!(
@@ -517,54 +654,46 @@ fn attempt_transition_to_taker_connect_or_take_deal(
                 }))?;
                 return Ok(None);
             }
-
-            let peer_node_addr = node_addr_from_deal(&deal);
-            // connect to the remote peer
-            match runtime.connect_peer(&peer_node_addr) {
-                Err(err) => {
-                    warn!(
-                        "Error connecting to remote peer {}, failed to take deal.",
-                        err
-                    );
-                    event.complete_client_ctl(CtlMsg::Failure(Failure {
-                        code: FailureCode::Unknown,
-                        info: err.to_string(),
-                    }))?;
-                    Ok(None)
-                }
-                Ok((connected, peer_service_id)) => {
-                    if connected {
-                        let deal_registered = "Deal registered".to_string();
-                        info!(
-                            "{}: {:#}",
-                            deal_registered.bright_green_bold(),
-                            &deal.id().bright_yellow_bold()
-                        );
-                        event.send_ctl_service(
-                            ServiceId::Wallet,
-                            CtlMsg::CreateSwapKeys(deal.clone(), runtime.wallet_token.clone()),
-                        )?;
-                        event.complete_client_info(InfoMsg::TookDeal(TookDeal {
-                            deal_id: deal.id(),
-                            message: deal_registered,
+            if let Err(err) =
+                runtime.check_swap_limits(&deal.parameters, Some(NodeId::from(deal.node_id)))
+            {
+                warn!("Cannot take deal: {}", err);
+                event.complete_client_ctl(CtlMsg::Failure(Failure {
+                    code: FailureCode::Unknown,
+                    info: format!("Cannot take deal: {}", err),
+                }))?;
+                return Ok(None);
+            }
+            let acc_split =
+                match resolve_sweep_split(runtime, &deal.parameters, acc_addr, monero_split) {
+                    Err(err) => {
+                        event.complete_client_ctl(CtlMsg::Failure(Failure {
+                            code: FailureCode::Unknown,
+                            info: err.to_string(),
                         }))?;
-                        runtime.deals.insert(deal.clone());
-                        Ok(Some(TradeStateMachine::TakeDeal(TakeDeal {
-                            deal,
-                            arb_addr,
-                            acc_addr,
-                            peerd: peer_service_id,
-                        })))
-                    } else {
-                        Ok(Some(TradeStateMachine::TakerConnect(TakerConnect {
-                            deal,
-                            arb_addr,
-                            acc_addr,
-                            source: event.source,
-                        })))
+                        return Ok(None);
                     }
-                }
-            }
+                    Ok(split) => split,
+                };
+
+            // try the deal's address first, then the alternate ones in order
+            let mut peer_addrs = vec![deal.peer_address];
+            peer_addrs.extend(alternate_addrs);
+            runtime
+                .maker_addresses
+                .insert(deal.id().into(), peer_addrs.clone());
+            let source = event.source.clone();
+            connect_to_maker(
+                &mut event,
+                runtime,
+                deal,
+                arb_addr,
+                acc_addr,
+                acc_split,
+                source,
+                peer_addrs,
+                s!("no address to connect to"),
+            )
         }
         req => {
             warn!(
@@ -576,6 +705,31 @@ fn attempt_transition_to_taker_connect_or_take_deal(
     }
 }
 
+/// The split of the swept Monero, the node's default for the network if none
+/// is given, checked against the swap's Monero address and amount
+fn resolve_sweep_split(
+    runtime: &Runtime,
+    deal_parameters: &DealParameters,
+    target: monero::Address,
+    split: Vec<SweepDestination>,
+) -> Result<Vec<SweepDestination>, Error> {
+    let split = if split.is_empty() {
+        runtime
+            .config
+            .get_swap_config(
+                deal_parameters.arbitrating_blockchain.try_into()?,
+                deal_parameters.accordant_blockchain.try_into()?,
+                deal_parameters.network,
+            )?
+            .accordant
+            .sweep_split
+    } else {
+        split
+    };
+    validate_sweep_split(&split, target, deal_parameters.accordant_amount)?;
+    Ok(split)
+}
+
 fn attempt_transition_to_restoring_swapd(
     mut event: Event,
     runtime: &mut Runtime,
@@ -610,8 +764,7 @@ fn attempt_transition_to_restoring_swapd(
 
             // We only try to re-establish a connection if we are the Taker
             let expect_connection = if trade_role == TradeRole::Taker {
-                let peer_node_addr = node_addr_from_deal(&deal);
-                if let Err(err) = runtime.connect_peer(&peer_node_addr) {
+                if let Err(err) = runtime.connect_maker(&deal) {
                     warn!("failed to reconnect to peer on restore: {}", err);
                     false
                 } else {
@@ -653,6 +806,7 @@ fn attempt_transition_to_restoring_swapd(
             let arbitrating_syncer_up = syncer_up(
                 &mut runtime.spawning_services,
                 &mut runtime.registered_services,
+                &mut runtime.supervisor,
                 deal.parameters.arbitrating_blockchain,
                 deal.parameters.network,
                 &runtime.config,
@@ -660,12 +814,20 @@ fn attempt_transition_to_restoring_swapd(
             let accordant_syncer_up = syncer_up(
                 &mut runtime.spawning_services,
                 &mut runtime.registered_services,
+                &mut runtime.supervisor,
                 deal.parameters.accordant_blockchain,
                 deal.parameters.network,
                 &runtime.config,
             )?;
 
-            launch_swapd(trade_role, deal.clone(), swap_id, swap_config)?;
+            launch_swapd(
+                &mut runtime.supervisor,
+                trade_role,
+                deal.clone(),
+                swap_id,
+                swap_config,
+                runtime.config.is_batch_sweep_enable(),
+            )?;
             event.complete_client_info(InfoMsg::String("Restoring checkpoint.".to_string()))?;
 
             Ok(Some(TradeStateMachine::RestoringSwapd(RestoringSwapd {
@@ -700,47 +862,185 @@ fn attempt_transition_to_taker_committed(
         deal,
         arb_addr,
         acc_addr,
+        acc_split,
+        alternate_addrs,
+        expiry,
+        range,
     } = make_deal;
     match (event.request.clone(), event.source.clone()) {
-        (BusMsg::P2p(PeerMsg::TakerCommit(taker_commit)), ServiceId::Peer(..)) => {
-            if deal == taker_commit.deal {
-                let source = event.source.clone();
-                let swap_id = taker_commit.swap_id();
-                info!(
-                    "{} | Received TakerCommit for swap - requesting walletd to create swap keys.",
-                    swap_id.swap_id(),
+        (BusMsg::P2p(PeerMsg::TakerCommit(taker_commit)), ServiceId::Peer(..))
+            if is_taken_from(&deal, &range, &taker_commit.deal)
+                && expiry.map_or(false, |expiry| {
+                    runtime.is_expired(&expiry, deal.parameters.network)
+                }) =>
+        {
+            info!(
+                "{} | Deal {} expired, replying with deal not found to the late taker",
+                taker_commit.swap_id().swap_id(),
+                deal.id().label()
+            );
+            event.send_msg_service(
+                event.source.clone(),
+                PeerMsg::DealNotFound(taker_commit.swap_id()),
+            )?;
+            end_open_deal(&mut event, deal)?;
+            Ok(None)
+        }
+        (BusMsg::P2p(PeerMsg::TakerCommit(taker_commit)), ServiceId::Peer(_, node_addr))
+            if is_taken_from(&deal, &range, &taker_commit.deal) =>
+        {
+            let taken = taker_commit.deal.clone();
+            let amount = taken.parameters.arbitrating_amount;
+            let checked = match range {
+                // a range deal is taken as a whole only if none of it is taken yet
+                Some(range) if taken == deal && range.remaining != amount => Err(Error::Farcaster(
+                    "Part of the range deal is already taken".to_string(),
+                )),
+                Some(range) if taken != deal => range.check(amount),
+                _ => Ok(()),
+            }
+            .and_then(|_| runtime.check_swap_limits(&taken.parameters, Some(node_addr.id)));
+            if let Err(err) = checked {
+                // the deal stays open, it can be taken once a running swap ends
+                warn!(
+                    "{} | Rejecting the taker of deal {}: {}",
+                    taker_commit.swap_id().swap_id(),
+                    deal.id().label(),
+                    err
                 );
-                event.send_ctl_service(
-                    ServiceId::Wallet,
-                    CtlMsg::CreateSwapKeys(deal.clone(), runtime.wallet_token.clone()),
-                )?;
-                event.complete_ctl_service(
-                    ServiceId::Database,
-                    CtlMsg::SetDealStatus(DealStatusPair {
-                        deal: deal.clone(),
-                        status: DealStatus::InProgress,
+                event.send_msg_service(
+                    event.source.clone(),
+                    PeerMsg::DealRejected(DealRejected {
+                        swap_id: taker_commit.swap_id(),
+                        reason: err.to_string(),
                     }),
                 )?;
-                Ok(Some(TradeStateMachine::TakerCommit(TakerCommit {
-                    peerd: source,
+                return Ok(Some(TradeStateMachine::MakeDeal(MakeDeal {
                     deal,
-                    commit: taker_commit.commit,
-                    target_bitcoin_address: arb_addr,
-                    target_monero_address: acc_addr,
-                })))
+                    arb_addr,
+                    acc_addr,
+                    acc_split,
+                    alternate_addrs,
+                    expiry,
+                    range,
+                })));
+            }
+            let source = event.source.clone();
+            let swap_id = taker_commit.swap_id();
+            info!(
+                "{} | Received TakerCommit for swap - requesting walletd to create swap keys.",
+                swap_id.swap_id(),
+            );
+            event.send_ctl_service(
+                ServiceId::Wallet,
+                CtlMsg::CreateSwapKeys(taken.clone(), runtime.wallet_token.clone()),
+            )?;
+            // the liquidity left in a range deal stays open if it can still be
+            // taken, the range deal is exhausted otherwise
+            let remaining = match range {
+                Some(range) if taken != deal => Some(DealRange {
+                    min: range.min,
+                    remaining: range.remaining - amount,
+                }),
+                _ => None,
+            }
+            .filter(|range| range.remaining >= range.min);
+            if taken == deal {
+                event
+                    .send_ctl_service(ServiceId::Database, CtlMsg::RemoveOpenDeal(deal.clone()))?;
             } else {
-                error!("Received invalid TakerCommit for deal {}.", deal.id());
+                info!(
+                    "{} | Deal {} of {} taken from range deal {}",
+                    swap_id.swap_id(),
+                    taken.id().label(),
+                    amount,
+                    deal.id().label()
+                );
+                runtime.deals.insert(taken.clone());
+                if let Some(range) = remaining {
+                    event.send_ctl_service(
+                        ServiceId::Database,
+                        CtlMsg::SetOpenDeal(OpenDeal {
+                            deal: deal.clone(),
+                            arbitrating_addr: arb_addr.clone(),
+                            accordant_addr: acc_addr,
+                            accordant_split: acc_split.clone(),
+                            alternate_addrs: alternate_addrs.clone(),
+                            expiry,
+                            range: Some(range),
+                            market_maker: runtime.is_market_maker_deal(&deal),
+                        }),
+                    )?;
+                } else {
+                    info!("Range deal {} exhausted", deal.id().label());
+                    runtime.exhausted_ranges.insert(deal.clone());
+                    event.send_ctl_service(
+                        ServiceId::Database,
+                        CtlMsg::RemoveOpenDeal(deal.clone()),
+                    )?;
+                    event.send_ctl_service(
+                        ServiceId::Database,
+                        CtlMsg::SetDealStatus(DealStatusPair {
+                            deal: deal.clone(),
+                            status: DealStatus::Exhausted,
+                        }),
+                    )?;
+                }
+            }
+            event.complete_ctl_service(
+                ServiceId::Database,
+                CtlMsg::SetDealStatus(DealStatusPair {
+                    deal: taken.clone(),
+                    status: DealStatus::InProgress,
+                }),
+            )?;
+            let taker_committed = TradeStateMachine::TakerCommit(TakerCommit {
+                peerd: source,
+                deal: taken,
+                commit: taker_commit.commit,
+                target_bitcoin_address: arb_addr.clone(),
+                target_monero_address: acc_addr,
+                target_monero_split: acc_split.clone(),
+            });
+            if let Some(range) = remaining {
+                // spawn the child swap next to the range deal kept open
+                runtime.trade_state_machines.push(taker_committed);
                 Ok(Some(TradeStateMachine::MakeDeal(MakeDeal {
                     deal,
                     arb_addr,
                     acc_addr,
+                    acc_split,
+                    alternate_addrs,
+                    expiry,
+                    range: Some(range),
                 })))
+            } else {
+                Ok(Some(taker_committed))
             }
         }
+        (BusMsg::P2p(PeerMsg::TakerCommit(..)), ServiceId::Peer(..)) => {
+            error!("Received invalid TakerCommit for deal {}.", deal.id());
+            Ok(Some(TradeStateMachine::MakeDeal(MakeDeal {
+                deal,
+                arb_addr,
+                acc_addr,
+                acc_split,
+                alternate_addrs,
+                expiry,
+                range,
+            })))
+        }
         (BusMsg::Ctl(CtlMsg::RevokeDeal(revoke_deal)), _) => {
             debug!("attempting to revoke {}", deal);
             if revoke_deal == deal {
                 info!("Revoked deal {}", deal.label());
+                runtime.log_event(
+                    event.endpoints,
+                    EventKind::DealRevoked,
+                    None,
+                    format!("Deal {} revoked", deal.id()),
+                )?;
+                end_open_deal(&mut event, deal)?;
                 event.complete_client_info(InfoMsg::String(
                     "Successfully revoked deal.".to_string(),
                 ))?;
@@ -753,6 +1053,10 @@ fn attempt_transition_to_taker_committed(
                     deal,
                     arb_addr,
                     acc_addr,
+                    acc_split,
+                    alternate_addrs,
+                    expiry,
+                    range,
                 })))
             }
         }
@@ -773,11 +1077,35 @@ fn attempt_transition_to_taker_committed(
                 deal,
                 arb_addr,
                 acc_addr,
+                acc_split,
+                alternate_addrs,
+                expiry,
+                range,
             })))
         }
     }
 }
 
+/// Whether the taken deal is the made deal or a child deal of the made range
+/// deal
+fn is_taken_from(deal: &Deal, range: &Option<DealRange>, taken: &Deal) -> bool {
+    taken == deal || (range.is_some() && DealRange::is_child(deal, taken))
+}
+
+/// Remove a made deal that is revoked or expired from the persisted open deals
+/// and mark it as ended
+fn end_open_deal(event: &mut Event, deal: Deal) -> Result<(), Error> {
+    event.send_ctl_service(ServiceId::Database, CtlMsg::RemoveOpenDeal(deal.clone()))?;
+    event.send_ctl_service(
+        ServiceId::Database,
+        CtlMsg::SetDealStatus(DealStatusPair {
+            deal,
+            status: DealStatus::Ended(Outcome::FailureAbort),
+        }),
+    )?;
+    Ok(())
+}
+
 fn attempt_transition_from_taker_commit_to_swapd_launched(
     event: Event,
     runtime: &mut Runtime,
@@ -789,11 +1117,14 @@ fn attempt_transition_from_taker_commit_to_swapd_launched(
         commit,
         target_bitcoin_address,
         target_monero_address,
+        target_monero_split,
     } = taker_commit;
     match event.request {
         BusMsg::Ctl(CtlMsg::SwapKeys(swap_keys)) => {
             let swap_id = commit.swap_id();
             info!("{} | Creating new swap.", swap_id.swap_id());
+            let deal_str = deal.to_string();
+            let deal_id = deal.id();
             let tsm = transition_to_swapd_launched_tsm(
                 runtime,
                 ConsumedDealRole::Maker(commit),
@@ -802,8 +1133,23 @@ fn attempt_transition_from_taker_commit_to_swapd_launched(
                 deal,
                 target_bitcoin_address,
                 target_monero_address,
+                target_monero_split,
                 swap_id,
             )?;
+            runtime.log_event(
+                event.endpoints,
+                EventKind::DealTaken,
+                Some(swap_id),
+                format!("Deal {} taken as {}", deal_id, TradeRole::Maker),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
+                swap_id,
+                WebhookEvent::DealTaken {
+                    deal: deal_str,
+                    trade_role: TradeRole::Maker.to_string(),
+                },
+            )?;
             Ok(Some(tsm))
         }
         req => {
@@ -818,6 +1164,7 @@ fn attempt_transition_from_taker_commit_to_swapd_launched(
                 commit,
                 target_bitcoin_address,
                 target_monero_address,
+                target_monero_split,
             })))
         }
     }
@@ -832,55 +1179,44 @@ fn attempt_transition_to_take_deal(
         deal,
         arb_addr,
         acc_addr,
+        acc_split,
         source,
+        peer_addr,
+        alternate_addrs,
     } = taker_connect;
     match event.request {
-        BusMsg::Ctl(CtlMsg::ConnectSuccess)
-            if Some(node_addr_from_deal(&deal)) == event.source.node_addr() =>
-        {
+        BusMsg::Ctl(CtlMsg::ConnectSuccess) if Some(peer_addr) == event.source.node_addr() => {
             runtime.handle_new_connection(event.source.clone());
-            let deal_registered = "Deal registered".to_string();
-            info!(
-                "{}: {:#}",
-                deal_registered.bright_green_bold(),
-                &deal.id().bright_yellow_bold()
-            );
-            event.send_ctl_service(
-                ServiceId::Wallet,
-                CtlMsg::CreateSwapKeys(deal.clone(), runtime.wallet_token.clone()),
-            )?;
-            event.send_client_info(
-                source,
-                InfoMsg::TookDeal(TookDeal {
-                    deal_id: deal.id(),
-                    message: deal_registered,
-                }),
-            )?;
-            runtime.deals.insert(deal.clone());
-            Ok(Some(TradeStateMachine::TakeDeal(TakeDeal {
-                deal,
-                arb_addr,
-                acc_addr,
-                peerd: event.source,
-            })))
+            let peerd = event.source.clone();
+            take_deal(
+                &mut event, runtime, deal, arb_addr, acc_addr, acc_split, source, peerd,
+            )
         }
-        BusMsg::Ctl(CtlMsg::ConnectFailed)
-            if Some(node_addr_from_deal(&deal)) == event.source.node_addr() =>
+        BusMsg::Ctl(CtlMsg::ConnectFailed(ref reason))
+            if Some(peer_addr) == event.source.node_addr() =>
         {
             warn!(
-                "{} | Connection to the remote peer {} failed, cannot  take the deal.",
+                "{} | Connection to the remote peer {} failed: {}",
                 deal.id(),
-                event.source
+                event.source,
+                reason
+            );
+            let failure = format!(
+                "Could not connect to remote peer {}: {}",
+                event.source, reason
             );
             runtime.handle_failed_connection(event.endpoints, event.source.clone())?;
-            event.send_client_ctl(
+            connect_to_maker(
+                &mut event,
+                runtime,
+                deal,
+                arb_addr,
+                acc_addr,
+                acc_split,
                 source,
-                CtlMsg::Failure(Failure {
-                    info: format!("Could not connect to remote peer {}.", event.source),
-                    code: FailureCode::Unknown,
-                }),
-            )?;
-            Ok(None)
+                alternate_addrs,
+                failure,
+            )
         }
         req => {
             if let BusMsg::Ctl(CtlMsg::Hello) = req {
@@ -899,12 +1235,136 @@ fn attempt_transition_to_take_deal(
                 deal,
                 arb_addr,
                 acc_addr,
+                acc_split,
                 source,
+                peer_addr,
+                alternate_addrs,
             })))
         }
     }
 }
 
+/// Connect to the first of the maker's addresses that does not fail right
+/// away. If a connection to the maker already exists the deal is taken
+/// immediately, otherwise the state machine waits in TakerConnect for the
+/// outcome of the connection. The client is sent a Failure with the last
+/// error once all the addresses failed.
+#[allow(clippy::too_many_arguments)]
+fn connect_to_maker(
+    event: &mut Event,
+    runtime: &mut Runtime,
+    deal: Deal,
+    arb_addr: bitcoin::Address,
+    acc_addr: monero::Address,
+    acc_split: Vec<SweepDestination>,
+    source: ServiceId,
+    mut peer_addrs: Vec<InetSocketAddr>,
+    mut last_error: String,
+) -> Result<Option<TradeStateMachine>, Error> {
+    while !peer_addrs.is_empty() {
+        let peer_addr = NodeAddr {
+            id: NodeId::from(deal.node_id),
+            addr: peer_addrs.remove(0),
+        };
+        match runtime.connect_peer(&peer_addr) {
+            Err(err) => {
+                warn!(
+                    "{} | Error connecting to remote peer {}: {}",
+                    deal.id(),
+                    peer_addr,
+                    err
+                );
+                last_error = err.to_string();
+            }
+            Ok((true, peerd)) => {
+                return take_deal(
+                    event, runtime, deal, arb_addr, acc_addr, acc_split, source, peerd,
+                );
+            }
+            Ok((false, _)) => {
+                return Ok(Some(TradeStateMachine::TakerConnect(TakerConnect {
+                    deal,
+                    arb_addr,
+                    acc_addr,
+                    acc_split,
+                    source,
+                    peer_addr,
+                    alternate_addrs: peer_addrs,
+                })));
+            }
+        }
+    }
+    warn!(
+        "{} | Could not connect to the maker, cannot take the deal.",
+        deal.id()
+    );
+    runtime.maker_addresses.remove(&deal.id().into());
+    event.send_client_ctl(
+        source,
+        CtlMsg::Failure(Failure {
+            info: last_error,
+            code: FailureCode::Unknown,
+        }),
+    )?;
+    Ok(None)
+}
+
+/// Take the deal once connected to the maker through peerd.
+#[allow(clippy::too_many_arguments)]
+fn take_deal(
+    event: &mut Event,
+    runtime: &mut Runtime,
+    deal: Deal,
+    arb_addr: bitcoin::Address,
+    acc_addr: monero::Address,
+    acc_split: Vec<SweepDestination>,
+    source: ServiceId,
+    peerd: ServiceId,
+) -> Result<Option<TradeStateMachine>, Error> {
+    let deal_registered = "Deal registered".to_string();
+    info!(
+        "{}: {:#}",
+        deal_registered.bright_green_bold(),
+        &deal.id().bright_yellow_bold()
+    );
+    event.send_ctl_service(
+        ServiceId::Wallet,
+        CtlMsg::CreateSwapKeys(deal.clone(), runtime.wallet_token.clone()),
+    )?;
+    event.send_client_info(
+        source,
+        InfoMsg::TookDeal(TookDeal {
+            deal_id: deal.id(),
+            message: deal_registered,
+        }),
+    )?;
+    // reconnects go through the address the deal is taken through first
+    let swap_id: SwapId = deal.id().into();
+    if let Some(addrs) = runtime.maker_addresses.get_mut(&swap_id) {
+        if let Some(pos) = peerd
+            .node_addr()
+            .and_then(|node_addr| addrs.iter().position(|addr| *addr == node_addr.addr))
+        {
+            addrs[..=pos].rotate_right(1);
+        }
+        event.send_ctl_service(
+            ServiceId::Database,
+            CtlMsg::SetMakerAddresses(MakerAddresses {
+                swap_id,
+                addrs: addrs.clone(),
+            }),
+        )?;
+    }
+    runtime.deals.insert(deal.clone());
+    Ok(Some(TradeStateMachine::TakeDeal(TakeDeal {
+        deal,
+        arb_addr,
+        acc_addr,
+        acc_split,
+        peerd,
+    })))
+}
+
 fn attempt_transition_from_take_deal_to_swapd_launched(
     event: Event,
     runtime: &mut Runtime,
@@ -914,12 +1374,15 @@ fn attempt_transition_from_take_deal_to_swapd_launched(
         deal,
         arb_addr,
         acc_addr,
+        acc_split,
         peerd,
     } = take_deal;
     match event.request {
         BusMsg::Ctl(CtlMsg::SwapKeys(swap_keys)) => {
             let swap_id: SwapId = deal.id().into(); // The deal id is now used to track a swap
             info!("{} | Creating new swap.", swap_id.swap_id());
+            let deal_str = deal.to_string();
+            let deal_id = deal.id();
             let tsm = transition_to_swapd_launched_tsm(
                 runtime,
                 ConsumedDealRole::Taker,
@@ -928,8 +1391,23 @@ fn attempt_transition_from_take_deal_to_swapd_launched(
                 deal,
                 arb_addr,
                 acc_addr,
+                acc_split,
                 swap_id,
             )?;
+            runtime.log_event(
+                event.endpoints,
+                EventKind::DealTaken,
+                Some(swap_id),
+                format!("Deal {} taken as {}", deal_id, TradeRole::Taker),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
+                swap_id,
+                WebhookEvent::DealTaken {
+                    deal: deal_str,
+                    trade_role: TradeRole::Taker.to_string(),
+                },
+            )?;
             Ok(Some(tsm))
         }
         req => {
@@ -949,6 +1427,7 @@ fn attempt_transition_from_take_deal_to_swapd_launched(
                 deal,
                 arb_addr,
                 acc_addr,
+                acc_split,
                 peerd,
             })))
         }
@@ -963,6 +1442,7 @@ fn transition_to_swapd_launched_tsm(
     deal: Deal,
     target_bitcoin_address: bitcoin::Address,
     target_monero_address: monero::Address,
+    target_monero_split: Vec<SweepDestination>,
     swap_id: SwapId,
 ) -> Result<TradeStateMachine, Error> {
     let swap_config = runtime.config.get_swap_config(
@@ -974,6 +1454,7 @@ fn transition_to_swapd_launched_tsm(
     let arbitrating_syncer_up = syncer_up(
         &mut runtime.spawning_services,
         &mut runtime.registered_services,
+        &mut runtime.supervisor,
         deal.parameters.arbitrating_blockchain,
         deal.parameters.network,
         &runtime.config,
@@ -981,6 +1462,7 @@ fn transition_to_swapd_launched_tsm(
     let accordant_syncer_up = syncer_up(
         &mut runtime.spawning_services,
         &mut runtime.registered_services,
+        &mut runtime.supervisor,
         deal.parameters.accordant_blockchain,
         deal.parameters.network,
         &runtime.config,
@@ -992,10 +1474,12 @@ fn transition_to_swapd_launched_tsm(
 
     runtime.stats.incr_initiated();
     launch_swapd(
+        &mut runtime.supervisor,
         consumed_deal_role.clone().into(),
         deal.clone(),
         swap_id,
         swap_config,
+        runtime.config.is_batch_sweep_enable(),
     )?;
 
     Ok(TradeStateMachine::SwapdLaunched(SwapdLaunched {
@@ -1008,6 +1492,7 @@ fn transition_to_swapd_launched_tsm(
         key_manager,
         target_bitcoin_address,
         target_monero_address,
+        target_monero_split,
         consumed_deal_role,
         peerd_reconnected: false,
     }))
@@ -1029,6 +1514,7 @@ fn attempt_transition_from_swapd_launched_to_swapd_running(
         mut peerd_reconnected,
         target_bitcoin_address,
         target_monero_address,
+        target_monero_split,
         key_manager,
     } = swapd_launched;
     match (event.request.clone(), event.source.clone()) {
@@ -1038,6 +1524,12 @@ fn attempt_transition_from_swapd_launched_to_swapd_running(
             accordant_syncer_up = Some(source);
         }
         (BusMsg::Ctl(CtlMsg::Hello), source) if ServiceId::Swap(swap_id) == source => {
+            runtime.log_event(
+                event.endpoints,
+                EventKind::SwapLaunched,
+                Some(swap_id),
+                "Swap launched",
+            )?;
             swapd_up = true;
         }
         (BusMsg::Ctl(CtlMsg::Hello), source)
@@ -1090,6 +1582,7 @@ fn attempt_transition_from_swapd_launched_to_swapd_running(
                 key_manager,
                 target_bitcoin_address,
                 target_monero_address,
+                target_monero_split,
                 commit: commit.clone(),
             }),
             ConsumedDealRole::Taker => CtlMsg::TakeSwap(InitTakerSwap {
@@ -1099,6 +1592,7 @@ fn attempt_transition_from_swapd_launched_to_swapd_running(
                 key_manager,
                 target_bitcoin_address,
                 target_monero_address,
+                target_monero_split,
             }),
         };
         if peerd_reconnected {
@@ -1138,6 +1632,7 @@ fn attempt_transition_from_swapd_launched_to_swapd_running(
             key_manager,
             target_bitcoin_address,
             target_monero_address,
+            target_monero_split,
             arbitrating_syncer_up,
             accordant_syncer_up,
             swapd_up,
@@ -1171,6 +1666,12 @@ fn attempt_transition_from_restoring_swapd_to_swapd_running(
             accordant_syncer_up = Some(source);
         }
         (BusMsg::Ctl(CtlMsg::Hello), source) if ServiceId::Swap(swap_id) == source => {
+            runtime.log_event(
+                event.endpoints,
+                EventKind::SwapLaunched,
+                Some(swap_id),
+                "Swap restored from checkpoint",
+            )?;
             swapd_up = true;
         }
         (BusMsg::Ctl(CtlMsg::Hello), source)
@@ -1179,20 +1680,25 @@ fn attempt_transition_from_restoring_swapd_to_swapd_running(
             arbitrating_syncer_up = Some(source);
         }
         (BusMsg::Ctl(CtlMsg::ConnectSuccess), source)
-            if Some(node_addr_from_deal(&deal)) == source.node_addr()
+            if Some(maker_node_addr(&runtime.maker_addresses, &deal)) == source.node_addr()
                 && trade_role == TradeRole::Taker =>
         {
             runtime.handle_new_connection(event.source.clone());
+            runtime.maker_connected(&swap_id);
 
             info!("{} | Peerd connected for restored swap", swap_id.swap_id());
             peerd = Some(event.source.clone());
         }
-        (BusMsg::Ctl(CtlMsg::ConnectFailed), source)
-            if Some(node_addr_from_deal(&deal)) == source.node_addr()
+        (BusMsg::Ctl(CtlMsg::ConnectFailed(_)), source)
+            if Some(maker_node_addr(&runtime.maker_addresses, &deal)) == source.node_addr()
                 && trade_role == TradeRole::Taker =>
         {
             runtime.handle_failed_connection(event.endpoints, source.clone())?;
-            expect_connection = false;
+            match runtime.reconnect_maker(&deal) {
+                Some((true, connected_peerd)) => peerd = Some(connected_peerd),
+                Some((false, _)) => {}
+                None => expect_connection = false,
+            }
         }
         (BusMsg::Ctl(CtlMsg::Hello), source) if trade_role == TradeRole::Maker => {
             if let Ok(bind_addr) = runtime.config.get_bind_addr() {
@@ -1325,194 +1831,111 @@ fn attempt_transition_to_end(
             })))
         }
 
-        (BusMsg::Ctl(CtlMsg::FundingInfo(info)), _) => match info {
-            FundingInfo::Bitcoin(BitcoinFundingInfo {
+        // auto-funding deferred until the swap reported its state
+        (BusMsg::Ctl(CtlMsg::Progress(_)), _) if runtime.auto_funding_deferred.remove(&swap_id) => {
+            let auto_funded = match &funding_info {
+                Some(info) if !auto_funded => auto_fund(runtime, &mut event, &deal, info)?,
+                _ => auto_funded,
+            };
+            Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
+                peerd,
+                deal,
                 swap_id,
-                ref address,
-                amount,
-            }) => {
-                runtime
-                    .stats
-                    .incr_awaiting_funding(&Blockchain::Bitcoin, swap_id);
-                let network = address.network.into();
-                if let Some(auto_fund_config) = runtime.config.get_auto_funding_config(network) {
-                    info!("{} | Attempting to auto-fund Bitcoin", swap_id.swap_id());
-                    debug!("{} | Auto funding config: {:#?}", swap_id, auto_fund_config);
-
-                    use bitcoincore_rpc::{Auth, Client, Error, RpcApi};
-                    use std::path::PathBuf;
-
-                    let host = auto_fund_config.bitcoin_rpc;
-                    let bitcoin_rpc = match auto_fund_config.bitcoin_cookie_path {
-                            Some(cookie) => {
-                                let path = PathBuf::from_str(&shellexpand::tilde(&cookie)).unwrap();
-                                debug!("{} | bitcoin-rpc connecting with cookie auth",
-                                       swap_id.swap_id());
-                                Client::new(&host, Auth::CookieFile(path))
-                            }
-                            None => {
-                                match (auto_fund_config.bitcoin_rpc_user, auto_fund_config.bitcoin_rpc_pass) {
-                                    (Some(rpc_user), Some(rpc_pass)) => {
-                                        debug!("{} | bitcoin-rpc connecting with userpass auth",
-                                               swap_id.swap_id());
-                                        Client::new(&host, Auth::UserPass(rpc_user, rpc_pass))
-                                    }
-                                    _ => {
-                                        error!(
-                                            "{} | Couldn't instantiate Bitcoin RPC - provide either `bitcoin_cookie_path` or `bitcoin_rpc_user` AND `bitcoin_rpc_pass` configuration parameters",
-                                            swap_id.swap_id()
-                                        );
-
-                                        Err(Error::InvalidCookieFile)}
-                                }
-                            }
-                        }.unwrap();
-
-                    match bitcoin_rpc
-                        .send_to_address(&address, amount, None, None, None, None, None, None)
-                    {
-                        Ok(txid) => {
-                            info!(
-                                "{} | Auto-funded Bitcoin with txid: {}",
-                                swap_id.swap_id(),
-                                txid.tx_hash()
-                            );
-                            Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                                peerd,
-                                deal,
-                                swap_id,
-                                arbitrating_syncer,
-                                accordant_syncer,
-                                funding_info: Some(info),
-                                auto_funded: true,
-                                clients_awaiting_connect_result,
-                                trade_role,
-                                expected_counterparty_node_id,
-                            })))
-                        }
-                        Err(err) => {
-                            warn!("{}", err);
-                            error!(
-                                    "{} | Auto-funding Bitcoin transaction failed, pushing to cli, use `swap-cli needs-funding Bitcoin` to retrieve address and amount",
-                                    swap_id.swap_id()
-                                );
-                            Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                                peerd,
-                                deal,
-                                swap_id,
-                                arbitrating_syncer,
-                                accordant_syncer,
-                                funding_info: Some(info),
-                                auto_funded: false,
-                                clients_awaiting_connect_result,
-                                trade_role,
-                                expected_counterparty_node_id,
-                            })))
-                        }
-                    }
-                } else {
-                    Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                        peerd,
-                        deal,
-                        swap_id,
-                        arbitrating_syncer,
-                        accordant_syncer,
-                        funding_info: Some(info.clone()),
-                        auto_funded: false,
-                        clients_awaiting_connect_result,
-                        trade_role,
-                        expected_counterparty_node_id,
-                    })))
-                }
-            }
-            FundingInfo::Monero(MoneroFundingInfo {
+                arbitrating_syncer,
+                accordant_syncer,
+                funding_info,
+                auto_funded,
+                clients_awaiting_connect_result,
+                trade_role,
+                expected_counterparty_node_id,
+            })))
+        }
+
+        // swapd updates the funding info once a payment falls short, the
+        // missing amount is then only reported
+        (BusMsg::Ctl(CtlMsg::FundingInfo(info)), _) if funding_info.is_some() => {
+            let required = match &info {
+                FundingInfo::Bitcoin(info) => format!("{} to {}", info.amount, info.address),
+                FundingInfo::Monero(info) => format!("{} to {}", info.amount, info.address),
+            };
+            runtime.log_event(
+                event.endpoints,
+                EventKind::Funding,
+                Some(swap_id),
+                format!("Funding still required: {}", required),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
                 swap_id,
-                address,
-                amount,
-            }) => {
-                runtime
-                    .stats
-                    .incr_awaiting_funding(&Blockchain::Monero, swap_id);
-                let network = address.network.into();
-                if let Some(auto_fund_config) = runtime.config.get_auto_funding_config(network) {
-                    info!("{} | Attempting to auto-fund Monero", swap_id.swap_id());
-                    debug!("{} | Auto funding config: {:#?}", swap_id, auto_fund_config);
-                    use tokio::runtime::Builder;
-                    let rt = Builder::new_multi_thread()
-                        .worker_threads(1)
-                        .enable_all()
-                        .build()
-                        .unwrap();
-                    rt.block_on(async {
-                        let host = auto_fund_config.monero_rpc_wallet;
-                        let wallet = monero_rpc::RpcClientBuilder::new()
-                            .build(host)
-                            .expect("client builder failed, cannot recover from bad configuration")
-                            .wallet();
-                        let options = monero_rpc::TransferOptions::default();
-
-                        let mut auto_funded = false;
-                        for retries in (0..10).rev() {
-                            match wallet
-                                .transfer(
-                                    [(address, amount)].iter().cloned().collect(),
-                                    monero_rpc::TransferPriority::Default,
-                                    options.clone(),
-                                )
-                                .await
-                            {
-                                Ok(tx) => {
-                                    info!(
-                                        "{} | Auto-funded Monero with txid: {}",
-                                        &swap_id.swap_id(),
-                                        tx.tx_hash.tx_hash()
-                                    );
-                                    auto_funded = true;
-                                    break;
-                                }
-                                Err(err) => {
-                                    if (err.to_string().contains("not enough") && err.to_string().contains("money")) || retries == 0 {
-                                        warn!("{}", err);
-                                        error!("{} | Auto-funding Monero transaction failed, pushing to cli, use `swap-cli needs-funding Monero` to retrieve address and amount", &swap_id.swap_id());
-                                        break;
-                                    } else {
-                                        warn!("{} | Auto-funding Monero transaction failed with {}, retrying, {} retries left", &swap_id.swap_id(), err, retries);
-                                    }
-                                }
-                            }
-                        }
-                        Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                             peerd,
-                             deal,
-                             swap_id,
-                             arbitrating_syncer,
-                             accordant_syncer,
-                             funding_info: Some(info),
-                             auto_funded,
-                             clients_awaiting_connect_result,
-                             trade_role,
-                             expected_counterparty_node_id,
-                         })))
-                    })
-                } else {
-                    Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                        peerd,
-                        deal,
-                        swap_id,
-                        arbitrating_syncer,
-                        accordant_syncer,
-                        funding_info: Some(info),
-                        auto_funded: false,
-                        clients_awaiting_connect_result,
-                        trade_role,
-                        expected_counterparty_node_id,
-                    })))
-                }
-            }
-        },
+                WebhookEvent::FundingRequired(info.clone()),
+            )?;
+            Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
+                peerd,
+                deal,
+                swap_id,
+                arbitrating_syncer,
+                accordant_syncer,
+                funding_info: Some(info),
+                auto_funded,
+                clients_awaiting_connect_result,
+                trade_role,
+                expected_counterparty_node_id,
+            })))
+        }
+
+        (BusMsg::Ctl(CtlMsg::FundingInfo(info)), _) => {
+            let (blockchain, required) = match &info {
+                FundingInfo::Bitcoin(info) => (
+                    Blockchain::Bitcoin,
+                    format!("{} to {}", info.amount, info.address),
+                ),
+                FundingInfo::Monero(info) => (
+                    Blockchain::Monero,
+                    format!("{} to {}", info.amount, info.address),
+                ),
+            };
+            runtime.stats.incr_awaiting_funding(&blockchain, swap_id);
+            runtime.log_event(
+                event.endpoints,
+                EventKind::Funding,
+                Some(swap_id),
+                format!("{} funding required: {}", blockchain, required),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
+                swap_id,
+                WebhookEvent::FundingRequired(info.clone()),
+            )?;
+            let auto_funded = auto_fund(runtime, &mut event, &deal, &info)?;
+            Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
+                peerd,
+                deal,
+                swap_id,
+                arbitrating_syncer,
+                accordant_syncer,
+                funding_info: Some(info),
+                auto_funded,
+                clients_awaiting_connect_result,
+                trade_role,
+                expected_counterparty_node_id,
+            })))
+        }
 
         (BusMsg::Ctl(CtlMsg::FundingCompleted(blockchain)), _) => {
             runtime.stats.incr_funded(&blockchain, &swap_id);
+            runtime.log_event(
+                event.endpoints,
+                EventKind::Funding,
+                Some(swap_id),
+                format!("{} funding completed", blockchain),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
+                swap_id,
+                WebhookEvent::Funded {
+                    blockchain: blockchain.to_string(),
+                },
+            )?;
             info!(
                 "{} | Your {} funding completed",
                 swap_id.swap_id(),
@@ -1564,8 +1987,7 @@ fn attempt_transition_to_end(
                     ),
                 }))?;
             } else {
-                let peer_node_addr = node_addr_from_deal(&deal);
-                match runtime.connect_peer(&peer_node_addr) {
+                match runtime.connect_maker(&deal) {
                     Err(err) => {
                         event.complete_client_ctl(CtlMsg::Failure(Failure {
                             code: FailureCode::Unknown,
@@ -1596,12 +2018,13 @@ fn attempt_transition_to_end(
 
         // A ConnectSuccess event can only come from a peerd connecting to a listener (maker)
         (BusMsg::Ctl(CtlMsg::ConnectSuccess), source)
-            if source.node_addr() == Some(node_addr_from_deal(&deal)) =>
+            if source.node_addr() == Some(maker_node_addr(&runtime.maker_addresses, &deal)) =>
         {
             for client in clients_awaiting_connect_result.drain(..) {
                 event.send_client_ctl(client, CtlMsg::ConnectSuccess)?;
             }
             runtime.handle_new_connection(source.clone());
+            runtime.maker_connected(&swap_id);
             Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
                 peerd: Some(source),
                 deal,
@@ -1617,42 +2040,61 @@ fn attempt_transition_to_end(
         }
 
         // A ConnectFailed event can only come from a peerd connecting to a listener (maker)
-        (BusMsg::Ctl(CtlMsg::ConnectFailed), source)
-            if source.node_addr() == Some(node_addr_from_deal(&deal)) =>
+        (BusMsg::Ctl(CtlMsg::ConnectFailed(reason)), source)
+            if source.node_addr() == Some(maker_node_addr(&runtime.maker_addresses, &deal)) =>
         {
-            for client in clients_awaiting_connect_result.drain(..) {
-                event.send_client_ctl(
-                    client,
-                    CtlMsg::Failure(Failure {
-                        code: FailureCode::Unknown,
-                        info: format!("Failed to connect to remote peer: {}", source),
-                    }),
-                )?;
-            }
             runtime.handle_failed_connection(event.endpoints, source.clone())?;
+            let (peerd, clients_awaiting_connect_result) = match runtime.reconnect_maker(&deal) {
+                Some((false, peerd)) => (peerd, clients_awaiting_connect_result),
+                Some((true, peerd)) => {
+                    for client in clients_awaiting_connect_result {
+                        event.send_client_ctl(client, CtlMsg::ConnectSuccess)?;
+                    }
+                    (peerd, vec![])
+                }
+                None => {
+                    for client in clients_awaiting_connect_result {
+                        event.send_client_ctl(
+                            client,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: format!(
+                                    "Failed to connect to remote peer {}: {}",
+                                    source, reason
+                                ),
+                            }),
+                        )?;
+                    }
+                    (source, vec![])
+                }
+            };
             Ok(Some(TradeStateMachine::SwapdRunning(SwapdRunning {
-                peerd: Some(source),
+                peerd: Some(peerd),
                 deal,
                 swap_id,
                 arbitrating_syncer,
                 accordant_syncer,
                 funding_info,
                 auto_funded,
-                clients_awaiting_connect_result: vec![],
+                clients_awaiting_connect_result,
                 trade_role,
                 expected_counterparty_node_id,
             })))
         }
 
-        (BusMsg::Ctl(CtlMsg::PeerdUnreachable(peerd_service)), source)
-            if ServiceId::Swap(swap_id) == source =>
-        {
+        (
+            BusMsg::Ctl(CtlMsg::PeerdUnreachable(PeerdUnreachable {
+                peerd: peerd_service,
+                reason,
+            })),
+            source,
+        ) if ServiceId::Swap(swap_id) == source => {
             if runtime.registered_services.contains(&peerd_service) {
                 warn!(
-                    "Peerd {} was reported to be unreachable, attempting to
+                    "Peerd {} was reported to be unreachable ({}), attempting to
                     terminate to kick-off re-connect procedure, if we are
                     taker and the swap is still running.",
-                    peerd_service
+                    peerd_service, reason
                 );
                 runtime.handle_failed_connection(event.endpoints, peerd_service.clone())?;
                 event.complete_ctl_service(peerd_service, CtlMsg::Terminate)?;
@@ -1683,6 +2125,19 @@ fn attempt_transition_to_end(
             )?;
             runtime.clean_up_after_swap(&swap_id, event.endpoints)?;
             runtime.stats.incr_outcome(&outcome);
+            runtime.log_event(
+                event.endpoints,
+                EventKind::SwapEnded,
+                Some(swap_id),
+                format!("Swap ended: {}", outcome),
+            )?;
+            runtime.notify_webhooks(
+                event.endpoints,
+                swap_id,
+                WebhookEvent::Outcome {
+                    outcome: outcome.clone(),
+                },
+            )?;
             match outcome {
                 Outcome::SuccessSwap => {
                     debug!("Success on swap {}", swap_id);
@@ -1696,6 +2151,9 @@ fn attempt_transition_to_end(
                 Outcome::FailureAbort => {
                     warn!("Aborted swap {}", swap_id);
                 }
+                Outcome::FailureTimeout => {
+                    warn!("Timed out swap {}", swap_id);
+                }
             }
             runtime.stats.success_rate();
             Ok(None)
@@ -1730,9 +2188,18 @@ fn attempt_transition_to_end(
     }
 }
 
-fn node_addr_from_deal(deal: &Deal) -> NodeAddr {
+/// Address of the maker of a taken deal: the address the deal was taken
+/// through if known, the deal's address otherwise
+fn maker_node_addr(
+    maker_addresses: &HashMap<SwapId, Vec<InetSocketAddr>>,
+    deal: &Deal,
+) -> NodeAddr {
+    let swap_id: SwapId = deal.id().into();
     NodeAddr {
         id: NodeId::from(deal.node_id.clone()), // node_id is bitcoin::Pubkey
-        addr: deal.peer_address,                // peer_address is InetSocketAddr
+        addr: maker_addresses
+            .get(&swap_id)
+            .and_then(|addrs| addrs.first().copied())
+            .unwrap_or(deal.peer_address),
     }
 }
@@ -0,0 +1,267 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::Duration;
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use farcaster_core::swap::SwapId;
+use farcaster_core::Uuid;
+use internet2::session::LocalSession;
+use internet2::{zeromq::ZmqSocketType, SendRecvMessage, TypedEnum};
+use microservices::ZMQ_CONTEXT;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::bus::ctl::{CtlMsg, FundingInfo};
+use crate::bus::{BusMsg, Outcome, StateTransition, WebhookDelivery};
+use crate::config::{WebhookConfig, WEBHOOK_MAX_ATTEMPTS};
+use crate::farcasterd::auto_funding::now;
+use crate::peerd::Backoff;
+use crate::{Error, ServiceId};
+
+/// Delay before retrying a failed delivery for the first time, doubled after
+/// each failed attempt
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(5);
+/// Maximum delay between two delivery attempts
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10 * 60);
+/// Timeout of a single delivery attempt
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Swap events notified to the webhooks, serialized in the `event` and `data`
+/// fields of the notification
+#[derive(Clone, Debug, Serialize)]
+#[serde(
+    crate = "serde_crate",
+    tag = "event",
+    content = "data",
+    rename_all = "snake_case"
+)]
+pub enum WebhookEvent {
+    /// A deal is taken and its swap launched
+    DealTaken { deal: String, trade_role: String },
+    /// The swap waits for the funding of its address
+    FundingRequired(FundingInfo),
+    /// The funding of the swap completed
+    Funded { blockchain: String },
+    /// The lock transaction on the blockchain is final
+    Locked { blockchain: String },
+    /// The swap entered a new state
+    StateTransition(StateTransition),
+    /// The swap ended
+    Outcome { outcome: Outcome },
+}
+
+#[derive(Serialize)]
+#[serde(crate = "serde_crate")]
+struct Notification<'a> {
+    id: Uuid,
+    swap_id: String,
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// Webhooks notified of the swap events. The notifications are persisted by
+/// databased and handed to the delivery thread, which removes them from
+/// databased once delivered or once their attempts are exhausted.
+pub struct Webhooks {
+    webhooks: Vec<WebhookConfig>,
+    tx: UnboundedSender<WebhookDelivery>,
+}
+
+impl Webhooks {
+    /// The webhooks and the receiver of their deliveries, to be run by
+    /// [`run_delivery`]
+    pub fn new(webhooks: Vec<WebhookConfig>) -> (Self, UnboundedReceiver<WebhookDelivery>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Webhooks { webhooks, tx }, rx)
+    }
+
+    /// Whether any webhook is configured
+    pub fn is_enabled(&self) -> bool {
+        !self.webhooks.is_empty()
+    }
+
+    /// A notification of the swap event for each webhook
+    pub fn notifications(
+        &self,
+        swap_id: SwapId,
+        event: &WebhookEvent,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        self.webhooks
+            .iter()
+            .map(|webhook| {
+                let id = Uuid::new();
+                let body = serde_json::to_string(&Notification {
+                    id,
+                    swap_id: swap_id.to_string(),
+                    timestamp: now(),
+                    event,
+                })
+                .map_err(|err| Error::Farcaster(err.to_string()))?;
+                Ok(WebhookDelivery {
+                    id,
+                    url: webhook.url.clone(),
+                    body,
+                })
+            })
+            .collect()
+    }
+
+    /// Hand the notification to the delivery thread
+    pub fn deliver(&self, delivery: WebhookDelivery) {
+        if let Err(err) = self.tx.send(delivery) {
+            error!("Failed to queue the webhook notification {}", err.0);
+        }
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the notification body with the webhook secret
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(body.as_bytes());
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).to_hex()
+}
+
+/// Deliver the notifications in a new thread, retrying the failed deliveries
+/// with an exponential backoff. Each delivery is reported over the farcasterd
+/// bridge once done to be removed from databased.
+pub fn run_delivery(
+    webhooks: Vec<WebhookConfig>,
+    mut rx: UnboundedReceiver<WebhookDelivery>,
+) -> Result<(), Error> {
+    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
+    tx.connect("inproc://farcasterdbridge")?;
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|err| Error::Farcaster(err.to_string()))?;
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    std::thread::spawn(move || {
+        let mut session = LocalSession::with_zmq_socket(ZmqSocketType::Push, tx);
+        let farcasterd_address: Vec<u8> = ServiceId::Farcasterd.into();
+        rt.block_on(async move {
+            let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+            loop {
+                tokio::select! {
+                    delivery = rx.recv() => {
+                        let delivery = match delivery {
+                            Some(delivery) => delivery,
+                            None => break,
+                        };
+                        match webhooks.iter().find(|webhook| webhook.url == delivery.url) {
+                            Some(webhook) => {
+                                tokio::spawn(deliver(
+                                    client.clone(),
+                                    webhook.clone(),
+                                    delivery,
+                                    done_tx.clone(),
+                                ));
+                            }
+                            None => {
+                                warn!(
+                                    "Webhook of notification {} is no longer configured, dropping it",
+                                    delivery
+                                );
+                                let _ = done_tx.send(delivery.id);
+                            }
+                        }
+                    }
+                    Some(id) = done_rx.recv() => {
+                        let request = BusMsg::Ctl(CtlMsg::RemoveWebhookDelivery(id));
+                        if let Err(err) = session.send_routed_message(
+                            &farcasterd_address,
+                            &farcasterd_address,
+                            &farcasterd_address,
+                            &request.serialize(),
+                        ) {
+                            error!("Failed to send {} over the bridge: {}", request, err);
+                        }
+                    }
+                }
+            }
+        });
+    });
+    Ok(())
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    webhook: WebhookConfig,
+    delivery: WebhookDelivery,
+    done: UnboundedSender<Uuid>,
+) {
+    let max_attempts = webhook.max_attempts.unwrap_or(WEBHOOK_MAX_ATTEMPTS).max(1);
+    let mut backoff = Backoff::new(RETRY_INITIAL_DELAY, RETRY_MAX_DELAY, Some(max_attempts - 1));
+    loop {
+        match post(&client, &webhook, &delivery).await {
+            Ok(()) => {
+                debug!("Delivered the webhook notification {}", delivery);
+                break;
+            }
+            Err(err) => match backoff.next_delay() {
+                Some(delay) => {
+                    warn!(
+                        "Failed to deliver the webhook notification {}: {}, retrying in {:.1} seconds",
+                        delivery,
+                        err,
+                        delay.as_secs_f32()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                None => {
+                    error!(
+                        "Giving up the webhook notification {} after {} attempts: {}",
+                        delivery, max_attempts, err
+                    );
+                    break;
+                }
+            },
+        }
+    }
+    let _ = done.send(delivery.id);
+}
+
+async fn post(
+    client: &reqwest::Client,
+    webhook: &WebhookConfig,
+    delivery: &WebhookDelivery,
+) -> Result<(), String> {
+    let mut request = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Farcaster-Delivery", delivery.id.to_string());
+    if let Some(secret) = &webhook.secret {
+        request = request.header("X-Farcaster-Signature", sign(secret, &delivery.body));
+    }
+    let response = request
+        .body(delivery.body.clone())
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("responded with {}", response.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -5,6 +5,8 @@
 // https://opensource.org/licenses/MIT.
 
 use crate::bus::bridge::BridgeMsg;
+use crate::bus::ctl::parse_broadcast_label;
+use crate::bus::ctl::ForceBroadcast;
 use crate::bus::ctl::FundingInfo;
 use crate::bus::ctl::ProtoDeal;
 use crate::bus::ctl::PubDeal;
@@ -12,16 +14,24 @@ use crate::bus::info::Address;
 use crate::bus::info::DealStatusSelector;
 use crate::bus::info::ProgressEvent;
 use crate::bus::AddressSecretKey;
+use crate::bus::AdvertisedDeal;
+use crate::bus::ChatRecord;
+use crate::bus::EventFilter;
+use crate::bus::EventKind;
 use crate::bus::Failure;
+use crate::bus::FailureCode;
 use crate::bus::HealthCheckSelector;
 use crate::bus::OptionDetails;
 use crate::bus::Outcome;
+use crate::bus::{DealExpiry, DealRange};
 use crate::grpcd::runtime::farcaster::NetworkSelector;
-use crate::service::Endpoints;
+use crate::service::{terminate_service, Endpoints};
 use crate::swapd::StateReport;
 use crate::syncerd::SweepAddressAddendum;
 use crate::syncerd::SweepBitcoinAddress;
+use crate::syncerd::SweepDestination;
 use crate::syncerd::SweepMoneroAddress;
+use amplify::Wrapper;
 use farcaster_core::bitcoin::fee::SatPerVByte;
 use farcaster_core::bitcoin::timelock::CSVTimelock;
 use farcaster_core::blockchain::Blockchain;
@@ -39,12 +49,14 @@ use internet2::SendRecvMessage;
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::sync::Arc;
 use tokio::runtime::Builder;
 use tokio::sync::oneshot::error::RecvError;
 use tokio::sync::Mutex;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
 use uuid::Uuid;
 
 use crate::bus::{ctl::CtlMsg, info::InfoMsg, info::SwapInfo};
@@ -174,6 +186,25 @@ impl From<Outcome> for farcaster::Outcome {
             Outcome::FailureRefund => farcaster::Outcome::FailureRefund,
             Outcome::FailurePunish => farcaster::Outcome::FailurePunish,
             Outcome::FailureAbort => farcaster::Outcome::FailureAbort,
+            Outcome::FailureTimeout => farcaster::Outcome::FailureTimeout,
+        }
+    }
+}
+
+impl From<crate::bus::StateTimestamp> for farcaster::StateTimestamp {
+    fn from(t: crate::bus::StateTimestamp) -> farcaster::StateTimestamp {
+        farcaster::StateTimestamp {
+            state: t.state,
+            timestamp: t.timestamp,
+        }
+    }
+}
+
+impl From<crate::bus::info::BlockWindow> for farcaster::BlockWindow {
+    fn from(w: crate::bus::info::BlockWindow) -> farcaster::BlockWindow {
+        farcaster::BlockWindow {
+            from: w.from,
+            to: w.to,
         }
     }
 }
@@ -257,6 +288,35 @@ impl From<farcaster::NetworkSelector> for HealthCheckSelector {
     }
 }
 
+impl From<ChatRecord> for farcaster::ChatMessage {
+    fn from(record: ChatRecord) -> farcaster::ChatMessage {
+        let direction = match record.direction {
+            crate::bus::ChatDirection::Sent => farcaster::ChatDirection::Sent,
+            crate::bus::ChatDirection::Received => farcaster::ChatDirection::Received,
+        };
+        farcaster::ChatMessage {
+            swap_id: record.swap_id.to_string(),
+            timestamp: record.timestamp,
+            direction: direction.into(),
+            text: record.text,
+        }
+    }
+}
+
+impl From<crate::bus::NodeEvent> for farcaster::NodeEvent {
+    fn from(event: crate::bus::NodeEvent) -> farcaster::NodeEvent {
+        farcaster::NodeEvent {
+            id: event.id,
+            timestamp: event.timestamp,
+            kind: event.kind.to_string(),
+            swap_id: event
+                .swap_id
+                .map_or_else(String::new, |swap_id| swap_id.to_string()),
+            details: event.details,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq, PartialOrd, Hash, Display)]
 #[display(Debug)]
 pub struct IdCounter(u64);
@@ -268,6 +328,7 @@ impl IdCounter {
     }
 }
 
+#[derive(Clone)]
 pub struct FarcasterService {
     tokio_tx_request: tokio::sync::mpsc::Sender<(u64, BusMsg)>,
     pending_requests: Arc<Mutex<HashMap<u64, tokio::sync::oneshot::Sender<BusMsg>>>>,
@@ -293,6 +354,97 @@ impl FarcasterService {
         drop(pending_requests);
         Ok(oneshot_rx)
     }
+
+    async fn chat_records(&self, swap_id: SwapId) -> Result<Vec<ChatRecord>, Status> {
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Info {
+                request: InfoMsg::GetChatMessages(swap_id),
+                service_id: ServiceId::Database,
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::ChatMessageList(records))) => Ok(records.into_inner()),
+            Err(error) => Err(Status::internal(error.to_string())),
+            Ok(BusMsg::Ctl(CtlMsg::Failure(Failure { info, .. }))) => Err(Status::internal(info)),
+            _ => Err(Status::internal("received unexpected internal response")),
+        }
+    }
+
+    async fn events(&self, filter: EventFilter) -> Result<Vec<crate::bus::NodeEvent>, Status> {
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Info {
+                request: InfoMsg::GetEvents(filter),
+                service_id: ServiceId::Database,
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::EventList(events))) => Ok(events.into_inner()),
+            Err(error) => Err(Status::internal(error.to_string())),
+            Ok(BusMsg::Ctl(CtlMsg::Failure(Failure { info, .. }))) => Err(Status::internal(info)),
+            _ => Err(Status::internal("received unexpected internal response")),
+        }
+    }
+
+    /// Swap info of an ended swap, built from the outcome stored by databased
+    async fn ended_swap_info(
+        &self,
+        id: u32,
+        swap_id: SwapId,
+    ) -> Result<GrpcResponse<SwapInfoResponse>, Status> {
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Info {
+                request: InfoMsg::GetOutcomeRecord(swap_id),
+                service_id: ServiceId::Database,
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::OutcomeRecord(record))) => {
+                let reply = SwapInfoResponse {
+                    id,
+                    connection: "".to_string(),
+                    connected: false,
+                    uptime: 0,
+                    since: record.timeline.first().map(|t| t.timestamp).unwrap_or(0),
+                    trade_role: farcaster::TradeRole::from(record.local_trade_role).into(),
+                    swap_role: farcaster::SwapRole::from(
+                        record.deal.swap_role(&record.local_trade_role),
+                    )
+                    .into(),
+                    deal: Some(record.deal.into()),
+                    connected_counterparty_node_id: "".to_string(),
+                    state: format!("Swap End: {}", record.outcome),
+                    timeline: record.timeline.into_iter().map(|t| t.into()).collect(),
+                    outcome: Some(OutcomeInfo {
+                        outcome: farcaster::Outcome::from(record.outcome).into(),
+                        reason: record.reason.map(|r| r.to_string()).unwrap_or_default(),
+                        final_state: record.final_state,
+                    }),
+                };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+}
+
+fn parse_alternate_addrs(addrs: Vec<String>) -> Result<Vec<InetSocketAddr>, Status> {
+    addrs
+        .iter()
+        .map(|addr| {
+            InetSocketAddr::from_str(addr)
+                .map_err(|_| Status::invalid_argument(format!("alternate address {}", addr)))
+        })
+        .collect()
+}
+
+fn parse_sweep_split(split: Vec<String>) -> Result<Vec<SweepDestination>, Status> {
+    split
+        .iter()
+        .map(|destination| {
+            SweepDestination::from_str(destination)
+                .map_err(|err| Status::invalid_argument(err.to_string()))
+        })
+        .collect()
 }
 
 fn process_error_response<T>(msg: Result<BusMsg, RecvError>) -> Result<GrpcResponse<T>, Status> {
@@ -306,6 +458,11 @@ fn process_error_response<T>(msg: Result<BusMsg, RecvError>) -> Result<GrpcRespo
 
 #[tonic::async_trait]
 impl Farcaster for FarcasterService {
+    type MessagesStream =
+        Pin<Box<dyn Stream<Item = Result<farcaster::ChatMessage, Status>> + Send + 'static>>;
+    type EventsStream =
+        Pin<Box<dyn Stream<Item = Result<farcaster::NodeEvent, Status>> + Send + 'static>>;
+
     async fn info(
         &self,
         request: GrpcRequest<InfoRequest>,
@@ -331,6 +488,7 @@ impl Farcaster for FarcasterService {
                     peers: info.peers.iter().map(|peer| peer.to_string()).collect(),
                     swaps: info.swaps.iter().map(|swap| swap.to_string()).collect(),
                     deals: info.deals.iter().map(|deal| deal.to_string()).collect(),
+                    crashes: info.crashes.into_iter().collect(),
                 };
                 Ok(GrpcResponse::new(reply))
             }
@@ -361,6 +519,73 @@ impl Farcaster for FarcasterService {
         }
     }
 
+    async fn simulate(
+        &self,
+        request: GrpcRequest<SimulateRequest>,
+    ) -> Result<GrpcResponse<SimulateResponse>, Status> {
+        debug!("Received a grpc simulate request: {:?}", request);
+        let SimulateRequest {
+            id,
+            network: grpc_network,
+            arbitrating_amount: int_arb_amount,
+            accordant_amount: int_acc_amount,
+            cancel_timelock: int_cancel_timelock,
+            punish_timelock: int_punish_timelock,
+            fee_strategy: str_fee_strategy,
+            maker_role: grpc_swap_role,
+        } = request.into_inner();
+
+        let network: Network = farcaster::Network::from_i32(grpc_network)
+            .ok_or(Status::invalid_argument("network"))?
+            .into();
+        let maker_role: SwapRole = farcaster::SwapRole::from_i32(grpc_swap_role)
+            .ok_or(Status::invalid_argument("maker role"))?
+            .into();
+        let fee_strategy: FeeStrategy<SatPerVByte> = FeeStrategy::from_str(&str_fee_strategy)
+            .map_err(|_| Status::invalid_argument("fee strategy"))?;
+        let deal_parameters = DealParameters {
+            uuid: Uuid::new_v4().into(),
+            network,
+            arbitrating_blockchain: Blockchain::Bitcoin,
+            accordant_blockchain: Blockchain::Monero,
+            arbitrating_amount: bitcoin::Amount::from_sat(int_arb_amount),
+            accordant_amount: monero::Amount::from_pico(int_acc_amount),
+            cancel_timelock: CSVTimelock::new(int_cancel_timelock),
+            punish_timelock: CSVTimelock::new(int_punish_timelock),
+            fee_strategy,
+            maker_role,
+        };
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Ctl {
+                request: CtlMsg::Simulate(deal_parameters),
+                service_id: ServiceId::Farcasterd,
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::SimulationReport(report))) => {
+                let reply = SimulateResponse {
+                    id,
+                    high_priority_sats_per_kvbyte: report.high_priority_sats_per_kvbyte,
+                    low_priority_sats_per_kvbyte: report.low_priority_sats_per_kvbyte,
+                    lock_fee: report.lock_fee.as_sat(),
+                    buy_fee: report.buy_fee.as_sat(),
+                    cancel_fee: report.cancel_fee.as_sat(),
+                    refund_fee: report.refund_fee.as_sat(),
+                    punish_fee: report.punish_fee.as_sat(),
+                    safe_buy_window: Some(report.safe_buy_window.into()),
+                    cancel_valid_after: report.cancel_valid_after,
+                    safe_refund_window: Some(report.safe_refund_window.into()),
+                    punish_valid_after: report.punish_valid_after,
+                    min_arbitrating_amount: report.min_arbitrating_amount.as_sat(),
+                    min_accordant_amount: report.min_accordant_amount.as_pico(),
+                    incompatibilities: report.incompatibilities,
+                };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
     async fn swap_info(
         &self,
         request: GrpcRequest<SwapInfoRequest>,
@@ -390,6 +615,7 @@ impl Farcaster for FarcasterService {
                 local_trade_role,
                 local_swap_role,
                 connected_counterparty_node_id,
+                timeline,
             }))) => {
                 let reply = SwapInfoResponse {
                     id,
@@ -404,9 +630,16 @@ impl Farcaster for FarcasterService {
                         .map(|n| n.to_string())
                         .unwrap_or("".to_string()),
                     state: state.to_string(),
+                    timeline: timeline.into_iter().map(|t| t.into()).collect(),
+                    outcome: None,
                 };
                 Ok(GrpcResponse::new(reply))
             }
+            // The swap is not running anymore, reply with its outcome if it ended
+            Ok(BusMsg::Ctl(CtlMsg::Failure(Failure {
+                code: FailureCode::TargetServiceNotFound,
+                ..
+            }))) => self.ended_swap_info(id, swap_id).await,
             res => process_error_response(res),
         }
     }
@@ -429,7 +662,7 @@ impl Farcaster for FarcasterService {
                 .ok_or(Status::invalid_argument("network_selector"))?;
         let oneshot_rx = self
             .process_request(BusMsg::Bridge(BridgeMsg::Info {
-                request: InfoMsg::ListDeals(deal_selector),
+                request: InfoMsg::ListDeals(deal_selector, network_selector.into()),
                 service_id: ServiceId::Farcasterd,
             }))
             .await?;
@@ -437,14 +670,7 @@ impl Farcaster for FarcasterService {
             Ok(BusMsg::Info(InfoMsg::DealList(mut deals))) => {
                 let reply = ListDealsResponse {
                     id,
-                    deals: deals
-                        .drain(..)
-                        .filter(|d| {
-                            network_selector == NetworkSelector::AllNetworks
-                                || Some(d.details.parameters.network) == network_selector.into()
-                        })
-                        .map(|d| DealInfo::from(d.details))
-                        .collect(),
+                    deals: deals.drain(..).map(|d| DealInfo::from(d.details)).collect(),
                 };
                 Ok(GrpcResponse::new(reply))
             }
@@ -502,7 +728,7 @@ impl Farcaster for FarcasterService {
         let oneshot_rx = match checkpoint_selector {
             farcaster::CheckpointSelector::AllCheckpoints => {
                 self.process_request(BusMsg::Bridge(BridgeMsg::Info {
-                    request: InfoMsg::RetrieveAllCheckpointInfo,
+                    request: InfoMsg::RetrieveAllCheckpointInfo(network_selector.into()),
                     service_id: ServiceId::Database,
                 }))
                 .await?
@@ -510,7 +736,7 @@ impl Farcaster for FarcasterService {
             farcaster::CheckpointSelector::AvailableForRestore => {
                 let oneshot_rx = self
                     .process_request(BusMsg::Bridge(BridgeMsg::Info {
-                        request: InfoMsg::RetrieveAllCheckpointInfo,
+                        request: InfoMsg::RetrieveAllCheckpointInfo(network_selector.into()),
                         service_id: ServiceId::Database,
                     }))
                     .await?;
@@ -541,10 +767,6 @@ impl Farcaster for FarcasterService {
                     id,
                     checkpoint_entries: checkpoint_entries
                         .iter()
-                        .filter(|entry| {
-                            network_selector == NetworkSelector::AllNetworks
-                                || Some(entry.deal.parameters.network) == network_selector.into()
-                        })
                         .map(|entry| farcaster::CheckpointEntry {
                             swap_id: entry.swap_id.to_string(),
                             deal: Some(entry.deal.clone().into()),
@@ -622,7 +844,7 @@ impl Farcaster for FarcasterService {
 
         let oneshot_rx = self
             .process_request(BusMsg::Bridge(BridgeMsg::Info {
-                request: InfoMsg::GetAddresses(blockchain),
+                request: InfoMsg::GetAddresses(blockchain, network_selector.into()),
                 service_id: ServiceId::Database,
             }))
             .await?;
@@ -632,10 +854,6 @@ impl Farcaster for FarcasterService {
                     id,
                     addresses: addresses
                         .iter()
-                        .filter(|a| {
-                            network_selector == NetworkSelector::AllNetworks
-                                || Some(Network::from(a.address.network)) == network_selector.into()
-                        })
                         .map(|a| AddressSwapIdPair {
                             address: a.address.to_string(),
                             address_swap_id: a.swap_id.map(|c| {
@@ -673,6 +891,10 @@ impl Farcaster for FarcasterService {
             maker_role: grpc_swap_role,
             public_ip_addr: str_public_ip_addr,
             public_port,
+            alternate_addrs: str_alternate_addrs,
+            accordant_split: str_accordant_split,
+            deal_expiry,
+            deal_range,
         } = request.into_inner();
 
         let network: Network = farcaster::Network::from_i32(grpc_network)
@@ -698,6 +920,8 @@ impl Farcaster for FarcasterService {
             .into();
         let public_ip_addr = IpAddr::from_str(&str_public_ip_addr)
             .map_err(|_| Status::invalid_argument("public ip address"))?;
+        let alternate_addrs = parse_alternate_addrs(str_alternate_addrs)?;
+        let accordant_split = parse_sweep_split(str_accordant_split)?;
         let fee_strategy: FeeStrategy<SatPerVByte> = FeeStrategy::from_str(&str_fee_strategy).map_err(|_| Status::invalid_argument("
         fee strategy is required to be formated as a fixed value, e.g. \"100 satoshi/vByte\" or a range, e.g. \"50 satoshi/vByte-150 satoshi/vByte\" "))?;
 
@@ -752,8 +976,24 @@ impl Farcaster for FarcasterService {
         let proto_deal = ProtoDeal {
             deal_parameters,
             public_addr,
+            alternate_addrs,
             arbitrating_addr,
             accordant_addr,
+            accordant_split,
+            expiry: deal_expiry.map(|expiry| match expiry {
+                farcaster::make_request::DealExpiry::ExpiresAt(timestamp) => {
+                    DealExpiry::Timestamp(timestamp)
+                }
+                farcaster::make_request::DealExpiry::ExpiresAtHeight(height) => {
+                    DealExpiry::BlockHeight(height)
+                }
+            }),
+            range: deal_range.map(|range| match range {
+                farcaster::make_request::DealRange::MinArbitratingAmount(min) => DealRange {
+                    min: bitcoin::Amount::from_sat(min),
+                    remaining: arbitrating_amount,
+                },
+            }),
         };
 
         let oneshot_rx = self
@@ -764,9 +1004,17 @@ impl Farcaster for FarcasterService {
             .await?;
         match oneshot_rx.await {
             Ok(BusMsg::Info(InfoMsg::MadeDeal(made_deal))) => {
+                // the encoded deal shared with takers carries the alternate addresses
+                let mut deal_info: DealInfo = made_deal.deal_info.details.into();
+                deal_info.encoded_deal = made_deal.deal_info.deal;
                 let reply = farcaster::MakeResponse {
                     id,
-                    deal: Some(made_deal.deal_info.details.into()),
+                    deal: Some(deal_info),
+                    alternate_addrs: made_deal
+                        .alternate_addrs
+                        .iter()
+                        .map(|addr| addr.to_string())
+                        .collect(),
                 };
                 Ok(GrpcResponse::new(reply))
             }
@@ -825,6 +1073,73 @@ impl Farcaster for FarcasterService {
         }
     }
 
+    async fn swap_txs(
+        &self,
+        request: GrpcRequest<SwapTxsRequest>,
+    ) -> Result<GrpcResponse<SwapTxsResponse>, Status> {
+        debug!("Received a grpc swap txs request: {:?}", request);
+        let SwapTxsRequest {
+            id,
+            swap_id: str_swap_id,
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Info {
+                request: InfoMsg::GetSwapTxs,
+                service_id: ServiceId::Swap(swap_id),
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::SwapTxList(txs))) => {
+                let reply = SwapTxsResponse {
+                    id,
+                    txs: txs
+                        .iter()
+                        .map(|tx| farcaster::SwapTx {
+                            label: tx.label.to_string(),
+                            txid: tx.txid.to_string(),
+                            hex: tx.hex.clone(),
+                        })
+                        .collect(),
+                };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
+    async fn broadcast_tx(
+        &self,
+        request: GrpcRequest<BroadcastTxRequest>,
+    ) -> Result<GrpcResponse<BroadcastTxResponse>, Status> {
+        debug!("Received a grpc broadcast tx request: {:?}", request);
+        let BroadcastTxRequest {
+            id,
+            swap_id: str_swap_id,
+            tx,
+            confirm,
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+        let label = parse_broadcast_label(&tx).map_err(|_| Status::invalid_argument("tx"))?;
+
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Ctl {
+                request: CtlMsg::ForceBroadcast(ForceBroadcast { label, confirm }),
+                service_id: ServiceId::Swap(swap_id),
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::String(info))) => {
+                let reply = BroadcastTxResponse { id, info };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
     async fn progress(
         &self,
         request: GrpcRequest<ProgressRequest>,
@@ -976,6 +1291,188 @@ impl Farcaster for FarcasterService {
         }
     }
 
+    async fn send_message(
+        &self,
+        request: GrpcRequest<SendMessageRequest>,
+    ) -> Result<GrpcResponse<SendMessageResponse>, Status> {
+        debug!("Received a grpc send message request: {:?}", request);
+        let SendMessageRequest {
+            id,
+            swap_id: str_swap_id,
+            message,
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Ctl {
+                request: CtlMsg::SendChatMessage(message),
+                service_id: ServiceId::Swap(swap_id),
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::String(_))) => {
+                let reply = farcaster::SendMessageResponse { id };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
+    async fn funding_psbt(
+        &self,
+        request: GrpcRequest<FundingPsbtRequest>,
+    ) -> Result<GrpcResponse<FundingPsbtResponse>, Status> {
+        debug!("Received a grpc funding psbt request: {:?}", request);
+        let FundingPsbtRequest {
+            id,
+            swap_id: str_swap_id,
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Info {
+                request: InfoMsg::GetFundingPsbt,
+                service_id: ServiceId::Swap(swap_id),
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::FundingPsbt(psbt))) => {
+                let reply = farcaster::FundingPsbtResponse { id, psbt };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
+    async fn import_funding_psbt(
+        &self,
+        request: GrpcRequest<ImportFundingPsbtRequest>,
+    ) -> Result<GrpcResponse<ImportFundingPsbtResponse>, Status> {
+        debug!("Received a grpc import funding psbt request: {:?}", request);
+        let ImportFundingPsbtRequest {
+            id,
+            swap_id: str_swap_id,
+            psbt,
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+
+        let oneshot_rx = self
+            .process_request(BusMsg::Bridge(BridgeMsg::Ctl {
+                request: CtlMsg::ImportFundingPsbt(psbt),
+                service_id: ServiceId::Swap(swap_id),
+            }))
+            .await?;
+        match oneshot_rx.await {
+            Ok(BusMsg::Info(InfoMsg::String(_))) => {
+                let reply = farcaster::ImportFundingPsbtResponse { id };
+                Ok(GrpcResponse::new(reply))
+            }
+            res => process_error_response(res),
+        }
+    }
+
+    async fn messages(
+        &self,
+        request: GrpcRequest<MessagesRequest>,
+    ) -> Result<GrpcResponse<Self::MessagesStream>, Status> {
+        debug!("Received a grpc messages request: {:?}", request);
+        let MessagesRequest {
+            swap_id: str_swap_id,
+            follow,
+            ..
+        } = request.into_inner();
+        let swap_id =
+            SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?;
+
+        // databased has no notifications, new messages are polled while
+        // following the stream
+        let service = self.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut sent = 0;
+            loop {
+                let records = match service.chat_records(swap_id).await {
+                    Ok(records) => records,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                for record in records.into_iter().skip(sent) {
+                    sent += 1;
+                    if tx.send(Ok(record.into())).await.is_err() {
+                        return;
+                    }
+                }
+                if !follow || tx.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        });
+        Ok(GrpcResponse::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn events(
+        &self,
+        request: GrpcRequest<EventsRequest>,
+    ) -> Result<GrpcResponse<Self::EventsStream>, Status> {
+        debug!("Received a grpc events request: {:?}", request);
+        let EventsRequest {
+            since,
+            swap_id: str_swap_id,
+            kind: str_kind,
+            follow,
+            ..
+        } = request.into_inner();
+        let swap_id = if str_swap_id.is_empty() {
+            None
+        } else {
+            Some(SwapId::from_str(&str_swap_id).map_err(|_| Status::invalid_argument("swap id"))?)
+        };
+        let kind = if str_kind.is_empty() {
+            None
+        } else {
+            Some(EventKind::from_str(&str_kind).map_err(|_| Status::invalid_argument("kind"))?)
+        };
+        let mut filter = EventFilter {
+            since: if since == 0 { None } else { Some(since) },
+            after: None,
+            swap_id,
+            kind,
+        };
+
+        // databased has no notifications, new events are polled while
+        // following the stream
+        let service = self.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let events = match service.events(filter.clone()).await {
+                    Ok(events) => events,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                for event in events {
+                    filter.after = Some(event.id);
+                    if tx.send(Ok(event.into())).await.is_err() {
+                        return;
+                    }
+                }
+                if !follow || tx.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        });
+        Ok(GrpcResponse::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
     async fn health_check(
         &self,
         request: GrpcRequest<HealthCheckRequest>,
@@ -1204,6 +1701,7 @@ impl Farcaster for FarcasterService {
                                     source_spend_key: secret_key_info.spend,
                                     source_view_key: secret_key_info.view,
                                     destination_address,
+                                    split: vec![],
                                     minimum_balance: monero::Amount::from_pico(0),
                                     from_height: secret_key_info.creation_height,
                                 },
@@ -1236,13 +1734,34 @@ impl Farcaster for FarcasterService {
             deal: str_deal,
             bitcoin_address: str_bitcoin_address,
             monero_address: str_monero_address,
+            alternate_addrs: str_alternate_addrs,
+            monero_split: str_monero_split,
+            take_amount,
         } = request.into_inner();
 
         let bitcoin_address = bitcoin::Address::from_str(&str_bitcoin_address)
             .map_err(|_| Status::invalid_argument("arbitrating address"))?;
         let monero_address = monero::Address::from_str(&str_monero_address)
             .map_err(|_| Status::invalid_argument("accordant_address"))?;
-        let deal = Deal::from_str(&str_deal).map_err(|_| Status::invalid_argument("deal"))?;
+        let AdvertisedDeal {
+            deal,
+            mut alternate_addrs,
+        } = AdvertisedDeal::from_str(&str_deal).map_err(|_| Status::invalid_argument("deal"))?;
+        for addr in parse_alternate_addrs(str_alternate_addrs)? {
+            if !alternate_addrs.contains(&addr) {
+                alternate_addrs.push(addr);
+            }
+        }
+        let monero_split = parse_sweep_split(str_monero_split)?;
+        // taking part of a range deal is taking a child deal at the same price
+        let deal = match take_amount {
+            Some(farcaster::take_request::TakeAmount::ArbitratingAmount(amount)) => {
+                DealRange::child_parameters(&deal.parameters, bitcoin::Amount::from_sat(amount))
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?
+                    .to_v1(deal.node_id, deal.peer_address)
+            }
+            None => deal,
+        };
 
         let Deal {
             parameters: deal_parameters,
@@ -1296,6 +1815,8 @@ impl Farcaster for FarcasterService {
                     deal,
                     bitcoin_address: bitcoin_address,
                     monero_address: monero_address,
+                    monero_split,
+                    alternate_addrs,
                 }),
                 service_id: ServiceId::Farcasterd,
             }))
@@ -1556,6 +2077,11 @@ impl Runtime {
                 debug!("Received Hello from {}", source);
             }
 
+            CtlMsg::Terminate if source == ServiceId::Farcasterd => {
+                info!("Terminating {}", self.identity);
+                terminate_service();
+            }
+
             req => {
                 if let ServiceId::GrpcdClient(id) = source {
                     self.tx_response
@@ -43,6 +43,8 @@ pub mod cli;
 pub mod client;
 #[cfg(feature = "_rpc")]
 pub mod config;
+#[cfg(feature = "embedded")]
+pub mod embedded;
 pub mod error;
 pub mod event;
 #[cfg(feature = "shell")]
@@ -64,6 +66,8 @@ pub mod swapd;
 pub mod syncerd;
 #[cfg(feature = "node")]
 pub mod walletd;
+#[cfg(feature = "node")]
+pub mod watchtowerd;
 
 #[cfg(feature = "_rpc")]
 pub use crate::config::Config;
@@ -72,4 +76,6 @@ pub use crate::service::ServiceConfig;
 pub use chains::{AccordantBlockchain, ArbitratingBlockchain};
 pub use error::Error;
 #[cfg(feature = "_rpc")]
-pub use service::{CtlServer, Endpoints, LogStyle, Service, ServiceId, TryToServiceId};
+pub use service::{
+    terminate_service, CtlServer, Endpoints, LogStyle, Service, ServiceId, TryToServiceId,
+};
@@ -0,0 +1,74 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::time::Duration;
+
+use bitcoin::secp256k1::rand::{self, Rng};
+
+/// Exponential backoff with jitter used to space reconnect attempts. The delay
+/// doubles after each attempt up to the maximum delay, and the actual wait is
+/// drawn between half of it and the full delay so that many peerds losing
+/// their connection at once do not reconnect in lockstep.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_attempts: Option<u32>,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial_delay: Duration, max_delay: Duration, max_attempts: Option<u32>) -> Self {
+        Backoff {
+            initial_delay,
+            max_delay: max_delay.max(initial_delay),
+            max_attempts,
+            attempt: 0,
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt, or None once the
+    /// maximum number of attempts is reached.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if matches!(self.max_attempts, Some(max) if self.attempt >= max) {
+            return None;
+        }
+        let delay = self.ceiling();
+        self.attempt += 1;
+        let half = delay / 2;
+        let jitter = rand::thread_rng().gen_range(0, half.as_millis() as u64 + 1);
+        Some(half + Duration::from_millis(jitter))
+    }
+
+    /// Number of delays handed out so far
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+
+    fn ceiling(&self) -> Duration {
+        self.initial_delay
+            .checked_mul(2u32.saturating_pow(self.attempt))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Backoff;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_is_capped_and_gives_up() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10), Some(8));
+        let mut ceiling = Duration::from_secs(1);
+        while let Some(delay) = backoff.next_delay() {
+            assert!(delay <= ceiling && delay >= ceiling / 2);
+            ceiling = (ceiling * 2).min(Duration::from_secs(10));
+        }
+        assert_eq!(backoff.attempts(), 8);
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Per-swap channels multiplexed over a single peer connection.
+//!
+//! Every protocol message sent through peerd is assigned to the channel of its
+//! swap and numbered. At most [`CHANNEL_WINDOW`] messages per channel are in
+//! flight at any time; further messages are queued until the counterparty
+//! acknowledges the previous ones with a cumulative [`Receipt`]. Incoming
+//! messages are delivered to the swap in order, duplicates are dropped and
+//! messages arriving ahead of a gap are held back until the gap is filled.
+//!
+//! Sequence numbers are scoped to a connection: on reconnect the inbound state
+//! is reset and the unacknowledged messages are renumbered from zero before
+//! being re-emitted, matching the fresh state of the counterparty's peerd.
+//! A message delivered before the reconnect whose receipt was lost is thus
+//! delivered again, swapd drops the messages it already handled.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use farcaster_core::swap::SwapId;
+use internet2::TypedEnum;
+
+use crate::bus::p2p::{PeerMsg, Receipt};
+
+/// Maximum number of unacknowledged messages in flight per swap channel.
+pub const CHANNEL_WINDOW: usize = 4;
+
+#[derive(Debug, Default)]
+struct SwapChannel {
+    next_seq: u64,
+    in_flight: VecDeque<(u64, PeerMsg)>,
+    queued: VecDeque<PeerMsg>,
+    next_expected: u64,
+    held_back: BTreeMap<u64, PeerMsg>,
+    last_type: Option<internet2::TypeId>,
+}
+
+impl SwapChannel {
+    /// Move queued messages into the in flight window and return them.
+    fn release(&mut self) -> Vec<(u64, PeerMsg)> {
+        let mut ready = vec![];
+        while self.in_flight.len() < CHANNEL_WINDOW {
+            match self.queued.pop_front() {
+                Some(msg) => {
+                    let seq = self.next_seq;
+                    self.next_seq += 1;
+                    self.in_flight.push_back((seq, msg.clone()));
+                    ready.push((seq, msg));
+                }
+                None => break,
+            }
+        }
+        ready
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SwapChannels {
+    channels: HashMap<SwapId, SwapChannel>,
+}
+
+impl SwapChannels {
+    /// Queue an outgoing protocol message on its swap channel and return the
+    /// numbered messages that can be sent right away.
+    pub fn enqueue(&mut self, msg: PeerMsg) -> Vec<(u64, PeerMsg)> {
+        let channel = self.channels.entry(msg.swap_id()).or_default();
+        // a swap re-sending a message supersedes its queued version
+        channel
+            .queued
+            .retain(|queued| queued.get_type() != msg.get_type());
+        channel.queued.push_back(msg);
+        channel.release()
+    }
+
+    /// Process a cumulative acknowledgment and return the queued messages now
+    /// fitting in the window.
+    pub fn ack(&mut self, receipt: &Receipt) -> Vec<(u64, PeerMsg)> {
+        match self.channels.get_mut(&receipt.swap_id) {
+            Some(channel) => {
+                channel.in_flight.retain(|(seq, _)| *seq > receipt.seq);
+                channel.release()
+            }
+            None => vec![],
+        }
+    }
+
+    /// Register an incoming message and return the messages to deliver in
+    /// order, along with the receipt to send back, if any.
+    pub fn receive(
+        &mut self,
+        swap_id: SwapId,
+        seq: u64,
+        msg: PeerMsg,
+    ) -> (Vec<PeerMsg>, Option<Receipt>) {
+        let channel = self.channels.entry(swap_id).or_default();
+        let duplicate = seq < channel.next_expected;
+        // a well behaved sender never has more than the window in flight, so
+        // anything further ahead is dropped rather than held back
+        if seq >= channel.next_expected + CHANNEL_WINDOW as u64 {
+            warn!(
+                "Dropping message {} on channel of swap {}, beyond window starting at {}",
+                seq, swap_id, channel.next_expected
+            );
+            return (vec![], None);
+        }
+        if !duplicate {
+            channel.held_back.insert(seq, msg);
+        }
+        let mut deliver = vec![];
+        while let Some(msg) = channel.held_back.remove(&channel.next_expected) {
+            channel.next_expected += 1;
+            channel.last_type = Some(msg.get_type());
+            deliver.push(msg);
+        }
+        // duplicates are acknowledged again, so a sender that missed our
+        // previous receipt can make progress
+        let receipt = match (channel.next_expected.checked_sub(1), channel.last_type) {
+            (Some(seq), Some(msg_type)) if duplicate || !deliver.is_empty() => Some(Receipt {
+                swap_id,
+                msg_type,
+                seq,
+            }),
+            _ => None,
+        };
+        (deliver, receipt)
+    }
+
+    /// Reset the connection scoped state after a reconnect and return the
+    /// unacknowledged messages, renumbered, to re-emit.
+    pub fn reset_connection(&mut self) -> Vec<(u64, PeerMsg)> {
+        let mut resend = vec![];
+        for channel in self.channels.values_mut() {
+            channel.next_expected = 0;
+            channel.held_back.clear();
+            channel.last_type = None;
+            let in_flight: Vec<PeerMsg> = channel.in_flight.drain(..).map(|(_, msg)| msg).collect();
+            channel.next_seq = 0;
+            for msg in in_flight.into_iter().rev() {
+                channel.queued.push_front(msg);
+            }
+            resend.extend(channel.release());
+        }
+        resend
+    }
+
+    /// Remove all the messages not yet acknowledged by the counterparty.
+    pub fn drain(&mut self) -> Vec<(SwapId, PeerMsg)> {
+        let mut drained = vec![];
+        for (swap_id, channel) in self.channels.iter_mut() {
+            drained.extend(channel.in_flight.drain(..).map(|(_, msg)| (*swap_id, msg)));
+            drained.extend(channel.queued.drain(..).map(|msg| (*swap_id, msg)));
+        }
+        drained
+    }
+
+    pub fn swap_ids(&self) -> Vec<SwapId> {
+        self.channels.keys().copied().collect()
+    }
+
+    /// Whether all the messages sent on the swap channel were acknowledged.
+    pub fn is_acked(&self, swap_id: &SwapId) -> bool {
+        self.channels
+            .get(swap_id)
+            .map(|channel| channel.in_flight.is_empty() && channel.queued.is_empty())
+            .unwrap_or(true)
+    }
+
+    /// Remove the channel of a swap once all the messages sent on it were
+    /// acknowledged, returns whether it was removed.
+    pub fn remove_acked(&mut self, swap_id: &SwapId) -> bool {
+        if self.channels.contains_key(swap_id) && self.is_acked(swap_id) {
+            self.channels.remove(swap_id);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Number of messages either in flight or queued across all channels.
+    pub fn unacked(&self) -> usize {
+        self.channels
+            .values()
+            .map(|channel| channel.in_flight.len() + channel.queued.len())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SwapChannels, CHANNEL_WINDOW};
+    use crate::bus::p2p::{PeerMsg, Receipt};
+    use farcaster_core::{swap::SwapId, Uuid};
+    use internet2::TypedEnum;
+
+    #[test]
+    fn channel_window_and_ordering() {
+        let swap_id = SwapId(Uuid::new());
+        let mut sender = SwapChannels::default();
+        let mut sent = vec![];
+        for _ in 0..CHANNEL_WINDOW + 2 {
+            sent.extend(sender.enqueue(PeerMsg::DealNotFound(swap_id)));
+        }
+        // only the queued version of a message type is superseded
+        assert_eq!(sent.len(), CHANNEL_WINDOW);
+        assert_eq!(sender.unacked(), CHANNEL_WINDOW + 1);
+
+        let mut receiver = SwapChannels::default();
+        let (deliver, receipt) = receiver.receive(swap_id, 1, sent[1].1.clone());
+        assert!(deliver.is_empty() && receipt.is_none());
+        let (deliver, receipt) = receiver.receive(swap_id, 0, sent[0].1.clone());
+        assert_eq!(deliver.len(), 2);
+        assert_eq!(receipt.as_ref().map(|r| r.seq), Some(1));
+        let (deliver, receipt) = receiver.receive(swap_id, 0, sent[0].1.clone());
+        assert!(deliver.is_empty());
+        assert_eq!(receipt.as_ref().map(|r| r.seq), Some(1));
+        let (deliver, receipt) =
+            receiver.receive(swap_id, 2 + CHANNEL_WINDOW as u64, sent[0].1.clone());
+        assert!(deliver.is_empty() && receipt.is_none());
+        assert!(receiver.channels[&swap_id].held_back.is_empty());
+
+        let released = sender.ack(&Receipt {
+            swap_id,
+            msg_type: sent[1].1.get_type(),
+            seq: 1,
+        });
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].0, CHANNEL_WINDOW as u64);
+
+        let resend = sender.reset_connection();
+        let seqs: Vec<u64> = resend.iter().map(|(seq, _)| *seq).collect();
+        assert_eq!(seqs, (0..CHANNEL_WINDOW as u64 - 1).collect::<Vec<u64>>());
+
+        // a channel is only removed once its messages are all acknowledged
+        assert!(!sender.remove_acked(&swap_id));
+        sender.ack(&Receipt {
+            swap_id,
+            msg_type: sent[1].1.get_type(),
+            seq: CHANNEL_WINDOW as u64,
+        });
+        assert!(sender.remove_acked(&swap_id));
+        assert_eq!(sender.count(), 0);
+        assert!(receiver.remove_acked(&swap_id));
+        assert!(!receiver.remove_acked(&swap_id));
+    }
+}
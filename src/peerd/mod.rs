@@ -4,10 +4,13 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+mod backoff;
+mod channel;
 #[cfg(feature = "shell")]
 mod opts;
 mod runtime;
 
+pub use backoff::Backoff;
 #[cfg(feature = "shell")]
 pub use opts::{Opts, PeerKeyOpts};
 pub use runtime::run_from_connect;
@@ -6,8 +6,13 @@
 
 use clap::{ArgGroup, ValueHint};
 use std::net::IpAddr;
+use std::time::Duration;
 
+use super::Backoff;
+
+use crate::bus::p2p::Init;
 use crate::opts::TokenString;
+use farcaster_core::blockchain::Network;
 use internet2::addr::{LocalNode, NodeAddr};
 
 /// Peer network connection daemon; part of Farcaster Node
@@ -59,6 +64,36 @@ pub struct Opts {
     #[clap(short, long, default_value = "9735")]
     pub port: u16,
 
+    /// Timeout in seconds of the initial connection to the remote peer
+    ///
+    /// Only used when connecting to a remote peer.
+    #[clap(long, default_value = "10")]
+    pub connect_timeout: u64,
+
+    /// Delay in seconds before the first reconnect attempt
+    ///
+    /// Only used when connecting to a remote peer. The delay doubles after
+    /// each failed attempt, up to `--reconnect-max-delay`, and is randomized.
+    #[clap(long, default_value = "1")]
+    pub reconnect_initial_delay: u64,
+
+    /// Maximum delay in seconds between two reconnect attempts
+    #[clap(long, default_value = "60")]
+    pub reconnect_max_delay: u64,
+
+    /// Give up reconnecting after this many attempts, retries forever if not
+    /// set
+    #[clap(long)]
+    pub reconnect_max_attempts: Option<u32>,
+
+    /// Network the node is able to swap on, repeat for each network
+    ///
+    /// Announced to the remote peer during the handshake, a deal is rejected
+    /// if either node does not swap on the deal's network. Defaults to all
+    /// networks.
+    #[clap(long = "network")]
+    pub networks: Vec<Network>,
+
     /// Node key configuration
     #[clap(flatten)]
     pub peer_key_opts: PeerKeyOpts,
@@ -77,6 +112,26 @@ impl Opts {
     pub fn process(&mut self) {
         self.shared.process();
     }
+
+    pub fn local_init(&self) -> Init {
+        if self.networks.is_empty() {
+            Init::new(vec![Network::Mainnet, Network::Testnet, Network::Local])
+        } else {
+            Init::new(self.networks.clone())
+        }
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout)
+    }
+
+    pub fn reconnect_backoff(&self) -> Backoff {
+        Backoff::new(
+            Duration::from_secs(self.reconnect_initial_delay),
+            Duration::from_secs(self.reconnect_max_delay),
+            self.reconnect_max_attempts,
+        )
+    }
 }
 
 /// Node key configuration
@@ -7,6 +7,9 @@
 use farcaster_core::swap::SwapId;
 use internet2::addr::LocalNode;
 use microservices::peer::RecvMessage;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Arc;
 use std::thread::spawn;
 use std::time::{Duration, SystemTime};
@@ -16,30 +19,37 @@ use amplify::Bipolar;
 use bitcoin::secp256k1::rand::{self, Rng, RngCore};
 use internet2::{addr::InetSocketAddr, CreateUnmarshaller, Unmarshall, Unmarshaller};
 use internet2::{
-    addr::NodeAddr,
+    addr::{NodeAddr, NodeId},
     presentation, transport,
     zeromq::{Carrier, ZmqSocketType},
     TypedEnum,
 };
 use microservices::esb::{self, Handler};
 use microservices::node::TryService;
-use microservices::peer::{self, PeerConnection, PeerSender, SendMessage};
+use microservices::peer::{self, PeerConnection, PeerReceiver, PeerSender, SendMessage};
 use microservices::ZMQ_CONTEXT;
 
-use crate::bus::p2p::Receipt;
+use super::channel::SwapChannels;
+use super::Backoff;
+use crate::bus::p2p::{DealRejected, Init, Sequenced, TakerCommit};
 use crate::bus::{
-    ctl::CtlMsg,
+    ctl::{CtlMsg, PeerdUnreachable},
     info::{InfoMsg, PeerInfo},
     p2p::PeerMsg,
     BusMsg, ServiceBus,
 };
-use crate::{CtlServer, Endpoints, Error, LogStyle, Service, ServiceConfig, ServiceId};
+use crate::{
+    terminate_service, CtlServer, Endpoints, Error, LogStyle, Service, ServiceConfig, ServiceId,
+};
 
-pub fn start_connect_peer_listener_runtime(
+/// Connect to the remote listening peer and run the handshake. The receiver
+/// runtime is only started once the connection is accepted by the runtime,
+/// so a connection completing after the runtime gave up is simply dropped.
+pub fn connect_peer_listener(
     remote_node_addr: NodeAddr,
     local_node: LocalNode,
-    peerd_service_id: ServiceId,
-) -> Result<(PeerSender, std::sync::mpsc::Sender<()>), Error> {
+    local_init: &Init,
+) -> Result<Connection, Error> {
     let connection = PeerConnection::connect_brontozaur(local_node, remote_node_addr)?;
     debug!("Connected to remote peer: {}", remote_node_addr);
 
@@ -67,26 +77,87 @@ pub fn start_connect_peer_listener_runtime(
         }
     };
 
-    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
-    tx.connect("inproc://bridge")?;
+    peer_sender.send_message(PeerMsg::Init(local_init.clone()))?;
+    let remote_init = match &*peer_receiver.recv_message(&unmarshaller)? {
+        PeerMsg::Init(remote_init) => {
+            debug!("Received init {} from the maker", remote_init);
+            local_init
+                .check_compatibility(remote_init)
+                .map_err(|reason| Error::Farcaster(format!("incompatible maker, {}", reason)))?;
+            remote_init.clone()
+        }
+        _ => {
+            return Err(Error::Farcaster(
+                "incompatible maker, no init received during handshake".to_string(),
+            ));
+        }
+    };
 
+    Ok((peer_receiver, peer_sender, remote_init))
+}
+
+/// Run the handshake with the remote connecting peer, fails if it does not
+/// complete the handshake or is incompatible.
+fn accept_peer_connector(
+    peer_receiver: &mut PeerReceiver,
+    peer_sender: &mut PeerSender,
+    local_init: &Init,
+) -> Result<(NodeId, Init), Error> {
+    // this is hella hacky, but it serves the purpose of keeping peerd's service
+    // id constant across reconnects: <REMOTE_NODE_ID>:<REMOTE_ADDR> for taker,
+    // <REMOTE_NODE_ID>:<LOCAL_ADDR> for maker
+    // TODO: It is privacy/security critical that once the
+    // connection is encrypted, this should be replaced by a proper handshake.
+    let unmarshaller: Unmarshaller<PeerMsg> = PeerMsg::create_unmarshaller();
+    let id = match &*peer_receiver.recv_message(&unmarshaller)? {
+        PeerMsg::Identity(id) => {
+            debug!("Received the following local node id from the taker {}", id);
+            *id
+        }
+        _ => {
+            return Err(Error::Farcaster(
+                "no identity received during handshake".to_string(),
+            ));
+        }
+    };
+    peer_sender.send_message(PeerMsg::Pong(vec![0]))?;
+    let remote_init = match &*peer_receiver.recv_message(&unmarshaller)? {
+        PeerMsg::Init(remote_init) => {
+            debug!("Received init {} from the taker", remote_init);
+            Some(remote_init.clone())
+        }
+        _ => None,
+    };
+    // always answer with our init so the taker can report why it is rejected
+    peer_sender.send_message(PeerMsg::Init(local_init.clone()))?;
+    let remote_init = match remote_init {
+        Some(remote_init) => {
+            local_init
+                .check_compatibility(&remote_init)
+                .map_err(|reason| Error::Farcaster(format!("incompatible taker, {}", reason)))?;
+            remote_init
+        }
+        None => {
+            return Err(Error::Farcaster(
+                "incompatible taker, no init received during handshake".to_string(),
+            ));
+        }
+    };
+    Ok((id, remote_init))
+}
+
+/// Start the thread listening for messages from the remote peer, returns the
+/// flag used to detect the thread terminated
+fn start_peer_receiver_runtime(
+    peer_receiver: PeerReceiver,
+    peerd_service_id: ServiceId,
+) -> Result<Sender<()>, Error> {
     let (thread_flag_tx, _thread_flag_rx) = std::sync::mpsc::channel();
 
     debug!("Starting thread listening for messages from the remote peer");
     let bridge_handler = PeerReceiverRuntime {
+        bridge: bridge_controller(&peerd_service_id)?,
         internal_identity: peerd_service_id,
-        bridge: esb::Controller::with(
-            map! {
-                ServiceBus::Bridge => esb::BusConfig {
-                    carrier: Carrier::Socket(tx),
-                    router: None,
-                    queued: true,
-                    api_type: ZmqSocketType::Rep,
-                    topic: None,
-                }
-            },
-            BridgeHandler,
-        )?,
         _thread_flag_rx,
         awaiting_pong: false,
     };
@@ -107,7 +178,37 @@ pub fn start_connect_peer_listener_runtime(
         }
     });
 
-    Ok((peer_sender, thread_flag_tx))
+    Ok(thread_flag_tx)
+}
+
+/// Address of the bridge between the runtime and the peer receiver threads,
+/// unique per peerd for several peerds to run in the same process when the
+/// node is embedded
+fn bridge_addr(identity: &ServiceId) -> String {
+    match identity {
+        ServiceId::Peer(id, _) => format!("inproc://peerdbridge-{:x}", id),
+        _ => "inproc://bridge".to_string(),
+    }
+}
+
+/// Create a controller pushing messages to the main runtime over the bridge
+fn bridge_controller(
+    identity: &ServiceId,
+) -> Result<esb::Controller<ServiceBus, BusMsg, BridgeHandler>, Error> {
+    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
+    tx.connect(&bridge_addr(identity))?;
+    Ok(esb::Controller::with(
+        map! {
+            ServiceBus::Bridge => esb::BusConfig {
+                carrier: Carrier::Socket(tx),
+                router: None,
+                queued: true,
+                api_type: ZmqSocketType::Rep,
+                topic: None,
+            }
+        },
+        BridgeHandler,
+    )?)
 }
 
 pub fn run_from_connect(
@@ -115,13 +216,16 @@ pub fn run_from_connect(
     remote_node_addr: NodeAddr,
     local_socket: Option<InetSocketAddr>,
     local_node: LocalNode,
+    local_init: Init,
+    connect_timeout: Duration,
+    reconnect_backoff: Backoff,
 ) -> Result<(), Error> {
     debug!("Opening bridge between runtime and peer receiver threads");
+    let id = Uuid::new_v4().as_u128();
     let rx = ZMQ_CONTEXT.socket(zmq::PULL)?;
-    rx.bind("inproc://bridge")?;
+    rx.bind(&bridge_addr(&ServiceId::Peer(id, remote_node_addr)))?;
 
     let (thread_flag_tx, _thread_flag_rx) = std::sync::mpsc::channel();
-    let id = Uuid::new_v4().as_u128();
 
     debug!(
         "Starting main service runtime with identity: {}",
@@ -132,6 +236,8 @@ pub fn run_from_connect(
         remote_node_addr: Some(remote_node_addr),
         local_socket,
         local_node,
+        local_init,
+        remote_init: None,
         peer_sender: None, // As connector we create the sender on is_ready
         forked_from_listener: false,
         started: SystemTime::now(),
@@ -139,7 +245,12 @@ pub fn run_from_connect(
         messages_received: 0,
         awaited_pong: None,
         thread_flag_tx,
-        unchecked_msg_cache: empty!(),
+        swap_channels: SwapChannels::default(),
+        released_swaps: none!(),
+        connect_timeout,
+        connecting: None,
+        reconnect_backoff,
+        reconnecting: None,
     };
     let mut service = Service::service(config, runtime)?;
     service.add_bridge_service_bus(rx)?;
@@ -154,61 +265,37 @@ pub fn run_from_listener(
     remote_node_addr: Option<NodeAddr>,
     local_socket: Option<InetSocketAddr>,
     local_node: LocalNode,
+    local_init: Init,
 ) -> Result<(), Error> {
     debug!("Splitting connection into receiver and sender parts");
     let (mut peer_receiver, mut peer_sender) = connection.split();
 
-    // this is hella hacky, but it serves the purpose of keeping peerd's service
-    // id constant across reconnects: <REMOTE_NODE_ID>:<REMOTE_ADDR> for taker,
-    // <REMOTE_NODE_ID>:<LOCAL_ADDR> for maker
-    // TODO: It is privacy/security critical that once the
-    // connection is encrypted, this should be replaced by a proper handshake.
-    let unmarshaller: Unmarshaller<PeerMsg> = PeerMsg::create_unmarshaller();
-    let msg: &PeerMsg = &*peer_receiver
-        .recv_message(&unmarshaller)
-        .expect("failed to receive identity message from taker");
-    let id = match msg {
-        PeerMsg::Identity(id) => {
-            debug!("Received the following local node id from the taker {}", id);
-            Some(id)
-        }
-        _ => None,
-    };
-    peer_sender
-        .send_message(PeerMsg::Pong(vec![0]))
-        .expect("Failed to send handshake pong");
+    let (remote_id, remote_init) =
+        accept_peer_connector(&mut peer_receiver, &mut peer_sender, &local_init).map_err(
+            |err| {
+                warn!("Closing the connection of the taker: {}", err);
+                err
+            },
+        )?;
     let peerd_id = Uuid::new_v4().as_u128();
     let internal_identity = ServiceId::Peer(
         peerd_id,
         NodeAddr {
-            id: *id.expect("remote id should always be some in maker's case"),
+            id: remote_id,
             addr: local_socket.expect("Checked for listener"),
         },
     );
 
     debug!("Opening bridge between runtime and peer receiver threads");
     let rx = ZMQ_CONTEXT.socket(zmq::PULL)?;
-    rx.bind("inproc://bridge")?;
-    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
-    tx.connect("inproc://bridge")?;
+    rx.bind(&bridge_addr(&internal_identity))?;
 
     let (thread_flag_tx, _thread_flag_rx) = std::sync::mpsc::channel();
 
     debug!("Starting thread listening for messages from the remote peer");
     let bridge_handler = PeerReceiverRuntime {
         internal_identity: internal_identity.clone(),
-        bridge: esb::Controller::with(
-            map! {
-                ServiceBus::Bridge => esb::BusConfig {
-                    carrier: Carrier::Socket(tx),
-                    router: None,
-                    queued: true,
-                    api_type: ZmqSocketType::Rep,
-                    topic: None,
-                }
-            },
-            BridgeHandler,
-        )?,
+        bridge: bridge_controller(&internal_identity)?,
         _thread_flag_rx,
         awaiting_pong: false,
     };
@@ -237,6 +324,8 @@ pub fn run_from_listener(
         remote_node_addr,
         local_socket,
         local_node,
+        local_init,
+        remote_init: Some(remote_init),
         peer_sender: Some(peer_sender),
         forked_from_listener: true,
         started: SystemTime::now(),
@@ -244,7 +333,13 @@ pub fn run_from_listener(
         messages_received: 0,
         awaited_pong: None,
         thread_flag_tx,
-        unchecked_msg_cache: empty!(),
+        swap_channels: SwapChannels::default(),
+        released_swaps: none!(),
+        connect_timeout: Duration::from_secs(0),
+        connecting: None,
+        // the listener-forked peerd never reconnects
+        reconnect_backoff: Backoff::new(Duration::from_secs(0), Duration::from_secs(0), Some(0)),
+        reconnecting: None,
     };
     let mut service = Service::service(config, runtime)?;
     service.add_bridge_service_bus(rx)?;
@@ -372,6 +467,9 @@ pub struct Runtime {
     remote_node_addr: Option<NodeAddr>,
     local_socket: Option<InetSocketAddr>,
     local_node: LocalNode,
+    local_init: Init,
+    // init received from the remote peer during the last handshake
+    remote_init: Option<Init>,
 
     peer_sender: Option<PeerSender>,
     // TODO: make this an enum instead with a descriptive distinction of listening and connecting to a listener
@@ -382,11 +480,27 @@ pub struct Runtime {
     messages_received: usize,
     awaited_pong: Option<u16>,
 
-    unchecked_msg_cache: Vec<((SwapId, internet2::TypeId), PeerMsg)>,
+    swap_channels: SwapChannels,
+    // swaps that do not need messages from the counterparty anymore
+    released_swaps: HashSet<SwapId>,
+
+    connect_timeout: Duration,
+    // result of the first connection of the taker, made in the background
+    connecting: Option<Receiver<Result<Connection, String>>>,
+    reconnect_backoff: Backoff,
+    reconnecting: Option<Reconnecting>,
 
     thread_flag_tx: std::sync::mpsc::Sender<()>,
 }
 
+type Connection = (PeerReceiver, PeerSender, Init);
+
+/// Handle on a reconnect running in the background
+struct Reconnecting {
+    result_rx: Receiver<Result<Connection, String>>,
+    cancel: Arc<AtomicBool>,
+}
+
 impl CtlServer for Runtime {}
 
 impl esb::Handler<ServiceBus> for Runtime {
@@ -397,54 +511,52 @@ impl esb::Handler<ServiceBus> for Runtime {
         self.identity.clone()
     }
 
-    fn on_ready(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+    fn on_ready(&mut self, _endpoints: &mut Endpoints) -> Result<(), Error> {
         // log iff taker
         if !self.forked_from_listener {
-            let (peer_sender, thread_flag_tx) = match start_connect_peer_listener_runtime(
-                self.remote_node_addr
-                    .clone()
-                    .expect("Checked for connecter"),
-                self.local_node,
-                self.identity(),
-            ) {
-                Ok(val) => {
-                    debug!(
-                        "{} | Successfully connected to remote peer: {}",
-                        self.identity(),
-                        self.remote_node_addr.expect("Checked for connecter")
-                    );
-                    endpoints.send_to(
-                        ServiceBus::Ctl,
-                        self.identity(),
-                        ServiceId::Farcasterd,
-                        BusMsg::Ctl(CtlMsg::ConnectSuccess),
-                    )?;
-                    val
+            let remote_node_addr = self.remote_node_addr.expect("Checked for connecter");
+            let local_node = self.local_node;
+            let local_init = self.local_init.clone();
+            let (result_tx, result_rx) = std::sync::mpsc::channel();
+            let timeout_tx = result_tx.clone();
+            let identity = self.identity();
+            let mut bridge = bridge_controller(&identity)?;
+            let mut timeout_bridge = bridge_controller(&identity)?;
+            let connect_timeout = self.connect_timeout;
+            self.connecting = Some(result_rx);
+            // connect in the background so a remote peer not answering cannot
+            // hold the taker longer than the timeout, the runtime is notified
+            // over the bridge by whichever of the connection or the timeout
+            // comes first. The connect thread ends on its own with the socket
+            // timeouts, a connection it completes after the timeout fails to
+            // be sent and is closed.
+            spawn(move || {
+                let result = connect_peer_listener(remote_node_addr, local_node, &local_init)
+                    .map_err(|err| err.to_string());
+                if result_tx.send(result).is_ok() {
+                    if let Err(err) = bridge.send_to(
+                        ServiceBus::Bridge,
+                        identity,
+                        BusMsg::P2p(PeerMsg::ConnectDone),
+                    ) {
+                        error!("Error sending over bridge: {}", err);
+                    }
                 }
-                Err(err) => {
-                    error!(
-                        "{} | Failed to connect to remote peer: {}, exiting",
-                        self.identity(),
-                        err
-                    );
-                    endpoints.send_to(
-                        ServiceBus::Ctl,
-                        self.identity(),
-                        ServiceId::Farcasterd,
-                        BusMsg::Ctl(CtlMsg::ConnectFailed),
-                    )?;
-                    return Ok(());
+            });
+            let identity = self.identity();
+            spawn(move || {
+                std::thread::sleep(connect_timeout);
+                let timeout = format!("timed out after {} seconds", connect_timeout.as_secs());
+                if timeout_tx.send(Err(timeout)).is_ok() {
+                    if let Err(err) = timeout_bridge.send_to(
+                        ServiceBus::Bridge,
+                        identity,
+                        BusMsg::P2p(PeerMsg::ConnectDone),
+                    ) {
+                        error!("Error sending over bridge: {}", err);
+                    }
                 }
-            };
-
-            self.peer_sender = Some(peer_sender);
-            self.thread_flag_tx = thread_flag_tx;
-            info!(
-                "{} with the remote peer {}",
-                "Successfully connected".bright_blue_bold(),
-                self.remote_node_addr
-                    .expect("remote node addr is never None if forked from listener")
-            );
+            });
         }
         Ok(())
     }
@@ -498,83 +610,129 @@ impl Runtime {
             self.identity(),
             &message.get_type()
         );
-        self.messages_sent += 1;
-        while let Err(err) = self
-            .peer_sender
-            .as_mut()
-            .expect("should be connected")
-            .send_message(message.clone())
-        {
-            endpoints.send_to(
-                ServiceBus::Ctl,
-                self.identity(),
-                ServiceId::Farcasterd,
-                BusMsg::Ctl(CtlMsg::Disconnected),
-            )?;
-            debug!(
-                "{} | Error sending to remote peer in peerd runtime: {}",
-                self.identity(),
-                err
-            );
-            // If this is the listener-forked peerd, i.e. the maker's peerd, terminate it.
-            if self.forked_from_listener {
-                for (_, cached_msg) in self.unchecked_msg_cache.drain(..) {
-                    // Draining cached messages to the various running swaps
-                    endpoints.send_to(
-                        ServiceBus::Ctl,
-                        self.identity.clone(),
-                        ServiceId::Swap(cached_msg.swap_id()),
-                        BusMsg::Ctl(CtlMsg::FailedPeerMessage(cached_msg)),
-                    )?;
-                }
+        if let PeerMsg::TakerCommit(taker_commit) = &message {
+            if let Err(reason) = self.check_deal_network(taker_commit) {
+                warn!(
+                    "{} | Not taking the deal with the remote peer: {}",
+                    taker_commit.swap_id().swap_id(),
+                    reason
+                );
+                // answer as the maker would for a deal it cannot swap
+                let swap_id = taker_commit.swap_id();
                 endpoints.send_to(
-                    ServiceBus::Ctl,
+                    ServiceBus::Msg,
                     self.identity(),
-                    ServiceId::Farcasterd,
-                    BusMsg::Ctl(CtlMsg::PeerdTerminated),
+                    ServiceId::Swap(swap_id),
+                    BusMsg::P2p(PeerMsg::DealRejected(DealRejected { swap_id, reason })),
                 )?;
-                warn!(
-                    "Waiting for farcaster to terminate peerd instance {}",
-                    self.identity().label()
-                );
-                // Return here, Farcaster is supposed to terminate us
                 return Ok(());
             }
-
-            // This blocks until reconnected successfully
-            while let Err(err) = self.reconnect_peer(endpoints) {
+        }
+        if message.is_protocol() {
+            let swap_id = message.swap_id();
+            let label = message.label();
+            let ready = self.swap_channels.enqueue(message);
+            if ready.is_empty() {
                 info!(
-                    "{} | Failed to reconnect: {}, retrying.",
-                    self.identity(),
-                    err
+                    "{} | Queued the {} protocol message until the previous ones are acknowledged",
+                    swap_id.swap_id(),
+                    label
                 );
-                std::thread::sleep(std::time::Duration::from_secs(1));
             }
+            self.send_sequenced(endpoints, ready)?;
+        } else {
+            self.send_to_peer(endpoints, message)?;
         }
+        Ok(())
+    }
 
-        if message.is_protocol() {
-            // First remove any dangling messages with the same type and origin
-            self.unchecked_msg_cache
-                .iter()
-                .position(|(key, _)| {
-                    if key.0 == message.swap_id() && key.1 == message.get_type() {
-                        true
-                    } else {
-                        false
-                    }
-                })
-                .map(|pos| self.unchecked_msg_cache.remove(pos));
-            // Then push the message to the back of the vector
-            self.unchecked_msg_cache
-                .push(((message.swap_id(), message.get_type()), message.clone()));
-            let swap_id = message.swap_id();
+    /// send numbered protocol messages of swap channels over the peer connection
+    fn send_sequenced(
+        &mut self,
+        endpoints: &mut Endpoints,
+        messages: Vec<(u64, PeerMsg)>,
+    ) -> Result<(), Error> {
+        for (seq, message) in messages {
+            let sequenced =
+                Sequenced::with(seq, &message).map_err(|err| Error::Farcaster(err.to_string()))?;
+            self.send_to_peer(endpoints, PeerMsg::Sequenced(sequenced))?;
             info!(
-                "{} | Sent the {} protocol message",
-                swap_id.swap_id(),
-                message.label()
+                "{} | Sent the {} protocol message (seq {})",
+                message.swap_id().swap_id(),
+                message.label(),
+                seq
             );
         }
+        Ok(())
+    }
 
+    fn send_to_peer(&mut self, endpoints: &mut Endpoints, message: PeerMsg) -> Result<(), Error> {
+        let peer_sender = match self.peer_sender.as_mut() {
+            Some(peer_sender) => peer_sender,
+            None => {
+                // Sequenced messages stay in their swap channel and are
+                // re-emitted once reconnected
+                debug!(
+                    "{} | Not connected, not sending {} to the remote peer",
+                    self.identity(),
+                    message
+                );
+                return Ok(());
+            }
+        };
+        self.messages_sent += 1;
+        if let Err(err) = peer_sender.send_message(message) {
+            debug!(
+                "{} | Error sending to remote peer in peerd runtime: {}",
+                self.identity(),
+                err
+            );
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                ServiceId::Farcasterd,
+                BusMsg::Ctl(CtlMsg::Disconnected),
+            )?;
+            self.handle_disconnect(endpoints)?;
+        }
+        Ok(())
+    }
+
+    fn handle_disconnect(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        self.peer_sender = None;
+        // If this is the listener-forked peerd, i.e. the maker's peerd, terminate it.
+        if self.forked_from_listener {
+            self.drain_swap_channels(endpoints)?;
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity(),
+                ServiceId::Farcasterd,
+                BusMsg::Ctl(CtlMsg::PeerdTerminated),
+            )?;
+            warn!(
+                "Waiting for farcaster to terminate peerd instance {}",
+                self.identity().label()
+            );
+            // Return here, Farcaster is supposed to terminate us
+            return Ok(());
+        }
+        self.start_reconnect(endpoints)
+    }
+
+    /// return all unacknowledged messages to their swaps
+    fn drain_swap_channels(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        for (swap_id, cached_msg) in self.swap_channels.drain() {
+            debug!(
+                "{} | Returning cache message {} back to swap",
+                swap_id, cached_msg
+            );
+            endpoints.send_to(
+                ServiceBus::Ctl,
+                self.identity.clone(),
+                ServiceId::Swap(swap_id),
+                BusMsg::Ctl(CtlMsg::FailedPeerMessage(cached_msg)),
+            )?;
+        }
         Ok(())
     }
 
@@ -586,15 +744,7 @@ impl Runtime {
     ) -> Result<(), Error> {
         match request {
             CtlMsg::Terminate if source == ServiceId::Farcasterd => {
-                for (_, cached_msg) in self.unchecked_msg_cache.drain(..) {
-                    // Draining cached messages to the various running swaps
-                    endpoints.send_to(
-                        ServiceBus::Ctl,
-                        self.identity.clone(),
-                        ServiceId::Swap(cached_msg.swap_id()),
-                        BusMsg::Ctl(CtlMsg::FailedPeerMessage(cached_msg)),
-                    )?;
-                }
+                self.drain_swap_channels(endpoints)?;
                 // FIXME: if persist pid logging beyond debugging, make this idiomatic across all services
                 info!(
                     "Terminating {} with PID {}",
@@ -602,7 +752,22 @@ impl Runtime {
                     std::process::id()
                 );
 
-                std::process::exit(0);
+                terminate_service();
+            }
+
+            CtlMsg::ReleasePeer(swap_id) => {
+                debug!(
+                    "{} | Swap does not need the counterparty anymore",
+                    swap_id.swap_id()
+                );
+                self.released_swaps.insert(swap_id);
+                self.prune_released_channels();
+                if self.reconnecting.is_some() {
+                    if let Some(reason) = self.give_up_reason() {
+                        self.give_up(endpoints, reason)?;
+                    }
+                }
+                Ok(())
             }
 
             _ => {
@@ -646,6 +811,8 @@ impl Runtime {
                     messages_received: self.messages_received,
                     forked_from_listener: self.forked_from_listener,
                     awaits_pong: self.awaited_pong.is_some(),
+                    swap_channels: self.swap_channels.count(),
+                    unacked_messages: self.swap_channels.unacked(),
                 };
                 self.send_client_info(endpoints, source, InfoMsg::PeerInfo(info))?;
             }
@@ -662,68 +829,225 @@ impl Runtime {
         Ok(())
     }
 
-    fn reconnect_peer(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
-        // flag_rx on the old receiver thread goes out of scope, thus making
-        // the send fail as soon as the old receiver thread exited.
-        while self.thread_flag_tx.send(()).is_ok() {
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-        let mut attempt = 0;
-        loop {
-            match start_connect_peer_listener_runtime(
-                self.remote_node_addr
-                    .clone()
-                    .expect("Checked for connnecter"),
-                self.local_node,
-                self.identity(),
-            ) {
-                Err(err) => {
-                    attempt += 1;
-                    trace!("{} | Failed to reconnect: {}", self.identity(), err);
-                    warn!(
-                        "{} | Reconnect failed attempting again in {} seconds",
-                        self.identity(),
-                        attempt
-                    );
-                    std::thread::sleep(std::time::Duration::from_secs(attempt));
-                }
-                Ok((peer_sender, thread_flag_tx)) => {
-                    info!(
-                        "{} | Reconnect success after {} attempts",
-                        self.identity(),
-                        attempt
-                    );
-                    self.peer_sender = Some(peer_sender);
-                    self.thread_flag_tx = thread_flag_tx;
-                    break;
-                }
+    /// Remove the channels of the released swaps once their messages are
+    /// acknowledged, the released swaps are kept to tell them apart from new
+    /// swaps.
+    fn prune_released_channels(&mut self) {
+        for swap_id in self.released_swaps.iter() {
+            if self.swap_channels.remove_acked(swap_id) {
+                debug!("{} | Removed the swap channel", swap_id.swap_id());
             }
         }
-        for (_, cached_msg) in self.unchecked_msg_cache.iter() {
-            info!(
-                "{} | re-emitting cached message after reconnect: {}",
-                cached_msg.swap_id(),
-                cached_msg
-            );
-            self.peer_sender
-                .as_mut()
-                .expect("should be connected")
-                .send_message(cached_msg.clone())?;
+    }
+
+    /// Reason to stop reconnecting, if none of the swaps running over this
+    /// connection need the counterparty anymore.
+    fn give_up_reason(&self) -> Option<String> {
+        let swap_ids = self.swap_channels.swap_ids();
+        // the channels of the released swaps may already be removed
+        if !self.released_swaps.is_empty()
+            && swap_ids.iter().all(|swap_id| {
+                self.released_swaps.contains(swap_id) && self.swap_channels.is_acked(swap_id)
+            })
+        {
+            Some("none of the swaps over this connection need the counterparty anymore".into())
+        } else {
+            None
+        }
+    }
+
+    fn give_up(&mut self, endpoints: &mut Endpoints, reason: String) -> Result<(), Error> {
+        warn!("{} | Not reconnecting: {}", self.identity(), reason);
+        if let Some(reconnecting) = self.reconnecting.take() {
+            reconnecting.cancel.store(true, Ordering::SeqCst);
         }
+        self.drain_swap_channels(endpoints)?;
         endpoints.send_to(
             ServiceBus::Ctl,
             self.identity(),
             ServiceId::Farcasterd,
-            BusMsg::Ctl(CtlMsg::Reconnected),
+            BusMsg::Ctl(CtlMsg::PeerdUnreachable(PeerdUnreachable {
+                peerd: self.identity(),
+                reason,
+            })),
         )?;
         Ok(())
     }
 
+    /// Reconnect to the remote peer in the background, spacing the attempts
+    /// with the configured backoff. The runtime keeps answering bus requests
+    /// and is notified over the bridge once done.
+    fn start_reconnect(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        if self.reconnecting.is_some() {
+            return Ok(());
+        }
+        if let Some(reason) = self.give_up_reason() {
+            return self.give_up(endpoints, reason);
+        }
+        let remote_node_addr = self.remote_node_addr.expect("Checked for connecter");
+        let local_node = self.local_node;
+        let local_init = self.local_init.clone();
+        let identity = self.identity();
+        let mut backoff = self.reconnect_backoff.clone();
+        let mut bridge = bridge_controller(&identity)?;
+        // flag_rx on the old receiver thread goes out of scope, thus making
+        // the send fail as soon as the old receiver thread exited.
+        let old_thread_flag_tx =
+            std::mem::replace(&mut self.thread_flag_tx, std::sync::mpsc::channel().0);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (result_tx, result_rx) = std::sync::mpsc::channel();
+        self.reconnecting = Some(Reconnecting {
+            result_rx,
+            cancel: Arc::clone(&cancel),
+        });
+
+        spawn(move || {
+            while old_thread_flag_tx.send(()).is_ok() {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            let result = loop {
+                let delay = match backoff.next_delay() {
+                    Some(delay) => delay,
+                    None => {
+                        break Err(format!(
+                            "gave up after {} reconnect attempts",
+                            backoff.attempts()
+                        ))
+                    }
+                };
+                warn!(
+                    "{} | Reconnecting in {:.1} seconds (attempt {})",
+                    identity,
+                    delay.as_secs_f32(),
+                    backoff.attempts()
+                );
+                std::thread::sleep(delay);
+                if cancel.load(Ordering::SeqCst) {
+                    return;
+                }
+                match connect_peer_listener(remote_node_addr, local_node, &local_init) {
+                    Ok(connection) => {
+                        info!(
+                            "{} | Reconnect success after {} attempts",
+                            identity,
+                            backoff.attempts()
+                        );
+                        break Ok(connection);
+                    }
+                    Err(err) => trace!("{} | Failed to reconnect: {}", identity, err),
+                }
+            };
+            // the runtime dropped the receiver if it gave up in the meantime
+            if result_tx.send(result).is_ok() {
+                if let Err(err) = bridge.send_to(
+                    ServiceBus::Bridge,
+                    identity,
+                    BusMsg::P2p(PeerMsg::ReconnectDone),
+                ) {
+                    error!("Error sending over bridge: {}", err);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Complete the first connection of the taker with the result of the
+    /// background connect, or its timeout
+    fn handle_connect_done(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        // dropping the receiver closes a connection completed after the
+        // timeout, and the notification of the later result is ignored
+        let result_rx = match self.connecting.take() {
+            Some(result_rx) => result_rx,
+            None => return Ok(()),
+        };
+        let result = result_rx
+            .try_recv()
+            .unwrap_or_else(|err| Err(err.to_string()))
+            .and_then(|(peer_receiver, peer_sender, remote_init)| {
+                start_peer_receiver_runtime(peer_receiver, self.identity())
+                    .map(|thread_flag_tx| (peer_sender, thread_flag_tx, remote_init))
+                    .map_err(|err| err.to_string())
+            });
+        let (peer_sender, thread_flag_tx, remote_init) = match result {
+            Ok(val) => {
+                debug!(
+                    "{} | Successfully connected to remote peer: {}",
+                    self.identity(),
+                    self.remote_node_addr.expect("Checked for connecter")
+                );
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Farcasterd,
+                    BusMsg::Ctl(CtlMsg::ConnectSuccess),
+                )?;
+                val
+            }
+            Err(err) => {
+                error!(
+                    "{} | Failed to connect to remote peer: {}, exiting",
+                    self.identity(),
+                    err
+                );
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Farcasterd,
+                    BusMsg::Ctl(CtlMsg::ConnectFailed(err)),
+                )?;
+                return Ok(());
+            }
+        };
+
+        self.peer_sender = Some(peer_sender);
+        self.thread_flag_tx = thread_flag_tx;
+        self.remote_init = Some(remote_init);
+        info!(
+            "{} with the remote peer {}",
+            "Successfully connected".bright_blue_bold(),
+            self.remote_node_addr
+                .expect("remote node addr is never None if forked from listener")
+        );
+        // messages sequenced while connecting are emitted now
+        let resend = self.swap_channels.reset_connection();
+        self.send_sequenced(endpoints, resend)
+    }
+
+    fn handle_reconnect_done(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        let reconnecting = match self.reconnecting.take() {
+            Some(reconnecting) => reconnecting,
+            None => return Ok(()),
+        };
+        match reconnecting.result_rx.try_recv() {
+            Ok(Ok((peer_receiver, peer_sender, remote_init))) => {
+                let thread_flag_tx = start_peer_receiver_runtime(peer_receiver, self.identity())?;
+                self.peer_sender = Some(peer_sender);
+                self.thread_flag_tx = thread_flag_tx;
+                self.remote_init = Some(remote_init);
+                endpoints.send_to(
+                    ServiceBus::Ctl,
+                    self.identity(),
+                    ServiceId::Farcasterd,
+                    BusMsg::Ctl(CtlMsg::Reconnected),
+                )?;
+                let resend = self.swap_channels.reset_connection();
+                self.send_sequenced(endpoints, resend)?;
+            }
+            Ok(Err(reason)) => self.give_up(endpoints, reason)?,
+            Err(err) => error!(
+                "{} | Reconnect finished without a result: {}",
+                self.identity(),
+                err
+            ),
+        }
+        Ok(())
+    }
+
     /// receive messages arriving over the bridge
     fn handle_bridge(
         &mut self,
         endpoints: &mut Endpoints,
-        source: ServiceId,
+        _source: ServiceId,
         request: PeerMsg,
     ) -> Result<(), Error> {
         debug!("{} | BRIDGE RPC request: {}", self.identity(), request);
@@ -731,11 +1055,11 @@ impl Runtime {
         self.messages_received += 1;
 
         match &request {
-            PeerMsg::PingPeer => self.ping()?,
+            PeerMsg::PingPeer => self.ping(endpoints)?,
 
             PeerMsg::Ping(pong_size) => {
                 debug!("{} | receiving ping, ponging back", self.identity().label());
-                self.pong(*pong_size)?
+                self.pong(endpoints, *pong_size)?
             }
 
             PeerMsg::Pong(noise) => {
@@ -763,130 +1087,127 @@ impl Runtime {
                     ServiceId::Farcasterd,
                     BusMsg::Ctl(CtlMsg::Disconnected),
                 )?;
-                // If this is the listener-forked peerd, i.e. the maker's peerd, terminate it.
-                if self.forked_from_listener {
-                    endpoints.send_to(
-                        ServiceBus::Ctl,
-                        self.identity(),
-                        ServiceId::Farcasterd,
-                        BusMsg::Ctl(CtlMsg::PeerdTerminated),
-                    )?;
-                    for ((swap_id, _), cached_msg) in self.unchecked_msg_cache.drain(..) {
-                        // Draining cached messages to the various running swaps
-                        debug!(
-                            "{} | Returning cache message {} back to swap",
-                            swap_id, cached_msg
-                        );
-                        endpoints.send_to(
-                            ServiceBus::Ctl,
-                            self.identity.clone(),
-                            ServiceId::Swap(swap_id),
-                            BusMsg::Ctl(CtlMsg::FailedPeerMessage(cached_msg)),
-                        )?;
-                    }
-                    warn!(
-                        "Waiting for farcaster to terminate peerd instance {}",
-                        self.identity().label()
-                    );
-                } else {
-                    // This blocks until reconnected successfully
-                    while let Err(err) = self.reconnect_peer(endpoints) {
-                        info!(
-                            "{} | Failed to reconnect: {}, retrying.",
-                            self.identity(),
-                            err
-                        );
-                        std::thread::sleep(std::time::Duration::from_secs(1));
-                    }
-                }
+                self.handle_disconnect(endpoints)?;
             }
 
+            PeerMsg::ConnectDone => self.handle_connect_done(endpoints)?,
+
+            PeerMsg::ReconnectDone => self.handle_reconnect_done(endpoints)?,
+
             PeerMsg::MsgReceipt(receipt) => {
                 debug!("{} | received receipt: {:?}", request.swap_id(), receipt);
-
-                self.unchecked_msg_cache
-                    .iter()
-                    .position(|(key, _)| {
-                        if key.0 == receipt.swap_id && key.1 == receipt.msg_type {
-                            true
-                        } else {
-                            false
-                        }
-                    })
-                    .map(|pos| self.unchecked_msg_cache.remove(pos));
+                let ready = self.swap_channels.ack(receipt);
+                self.send_sequenced(endpoints, ready)?;
+                self.prune_released_channels();
             }
 
-            // swap initiation message
-            PeerMsg::TakerCommit(_) => {
-                let swap_id = request.swap_id();
-                let msg_type = request.get_type();
-                debug!(
-                    "{} | PID {} | Received the {} protocol message, forwarding to farcasterd",
-                    swap_id.swap_id(),
-                    std::process::id(),
-                    request.label()
-                );
-                endpoints.send_to(
-                    ServiceBus::Msg,
-                    self.identity(),
-                    ServiceId::Farcasterd,
-                    BusMsg::P2p(request),
-                )?;
-
+            PeerMsg::Sequenced(sequenced) => {
+                let msg = sequenced
+                    .msg()
+                    .map_err(|err| Error::Farcaster(err.to_string()))?;
+                if !msg.is_protocol() || msg.swap_id() != sequenced.swap_id {
+                    warn!(
+                        "{} | Ignoring sequenced message {} not matching its swap channel",
+                        sequenced.swap_id.swap_id(),
+                        msg
+                    );
+                    return Ok(());
+                }
+                let (deliver, receipt) =
+                    self.swap_channels
+                        .receive(sequenced.swap_id, sequenced.seq, msg);
+                if deliver.is_empty() {
+                    debug!(
+                        "{} | Holding back or dropping message with seq {}",
+                        sequenced.swap_id.swap_id(),
+                        sequenced.seq
+                    );
+                }
+                for msg in deliver {
+                    self.forward_protocol_msg(endpoints, msg)?;
+                }
                 // send a receipt back to the remote peer
-                self.handle_msg(
-                    endpoints,
-                    source,
-                    PeerMsg::MsgReceipt(Receipt { swap_id, msg_type }),
-                )?;
+                if let Some(receipt) = receipt {
+                    self.send_to_peer(endpoints, PeerMsg::MsgReceipt(receipt))?;
+                }
             }
 
+            // chat messages are sent unsequenced, they do not hold back the
+            // protocol messages of their swap
+            PeerMsg::ChatMessage(_) => self.forward_protocol_msg(endpoints, request)?,
+
             msg => {
-                debug_assert!(msg.is_protocol());
-                let swap_id = msg.swap_id();
-                debug!(
-                    "{} | PID {} | Received the {} protocol message, forwarding to swapd",
+                warn!(
+                    "{} | Ignoring message {} received outside of a swap channel",
+                    self.identity(),
+                    msg
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// forward an in-order protocol message to the service handling it
+    fn forward_protocol_msg(
+        &mut self,
+        endpoints: &mut Endpoints,
+        msg: PeerMsg,
+    ) -> Result<(), Error> {
+        let swap_id = msg.swap_id();
+        if let PeerMsg::TakerCommit(taker_commit) = &msg {
+            if let Err(reason) = self.check_deal_network(taker_commit) {
+                warn!(
+                    "{} | Rejecting the deal taken by the remote peer: {}",
                     swap_id.swap_id(),
-                    std::process::id(),
-                    msg.label()
+                    reason
                 );
-                endpoints.send_to(
-                    ServiceBus::Msg,
+                return self.handle_msg(
+                    endpoints,
                     self.identity(),
-                    ServiceId::Swap(swap_id),
-                    BusMsg::P2p(request.clone()),
-                )?;
-
-                // send a receipt back to the remote peer
-                self.peer_sender
-                    .as_mut()
-                    .expect("should be connected")
-                    .send_message(PeerMsg::MsgReceipt(Receipt {
-                        swap_id: request.swap_id(),
-                        msg_type: request.get_type(),
-                    }))?;
+                    PeerMsg::DealRejected(DealRejected { swap_id, reason }),
+                );
             }
         }
+        // swap initiation message
+        let (service, name) = if let PeerMsg::TakerCommit(_) = msg {
+            (ServiceId::Farcasterd, "farcasterd")
+        } else {
+            (ServiceId::Swap(swap_id), "swapd")
+        };
+        debug!(
+            "{} | PID {} | Received the {} protocol message, forwarding to {}",
+            swap_id.swap_id(),
+            std::process::id(),
+            msg.label(),
+            name
+        );
+        endpoints.send_to(ServiceBus::Msg, self.identity(), service, BusMsg::P2p(msg))?;
         Ok(())
     }
 
-    fn ping(&mut self) -> Result<(), Error> {
+    /// Check both nodes swap on the network of the deal being taken
+    fn check_deal_network(&self, taker_commit: &TakerCommit) -> Result<(), String> {
+        match &self.remote_init {
+            Some(remote_init) => self
+                .local_init
+                .check_network(remote_init, taker_commit.deal.parameters.network),
+            None => Err("no init received from the remote peer".to_string()),
+        }
+    }
+
+    fn ping(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
         trace!("{} | Sending ping to the remote peer", self.identity());
         let mut rng = rand::thread_rng();
         let len: u16 = rng.gen_range(4, 32);
         let mut noise = vec![0u8; len as usize];
         rng.fill_bytes(&mut noise);
         let pong_size = rng.gen_range(4, 32);
-        self.messages_sent += 1;
-        self.peer_sender
-            .as_mut()
-            .expect("should be connected")
-            .send_message(PeerMsg::Ping(pong_size))?;
+        self.send_to_peer(endpoints, PeerMsg::Ping(pong_size))?;
         self.awaited_pong = Some(pong_size);
         Ok(())
     }
 
-    fn pong(&mut self, pong_size: u16) -> Result<(), Error> {
+    fn pong(&mut self, endpoints: &mut Endpoints, pong_size: u16) -> Result<(), Error> {
         trace!(
             "{} | Replying with pong to the remote peer",
             self.identity()
@@ -896,11 +1217,6 @@ impl Runtime {
             .iter()
             .map(|_| rng.gen())
             .collect();
-        self.messages_sent += 1;
-        self.peer_sender
-            .as_mut()
-            .expect("should be connected")
-            .send_message(PeerMsg::Pong(noise))?;
-        Ok(())
+        self.send_to_peer(endpoints, PeerMsg::Pong(noise))
     }
 }
@@ -158,6 +158,9 @@ pub enum ServiceId {
 
     #[display("other<{0}>")]
     Other(ClientName),
+
+    #[display("watchtowerd")]
+    Watchtower,
 }
 
 impl ServiceId {
@@ -334,6 +337,16 @@ where
 
 pub type Endpoints = esb::EndpointList<ServiceBus>;
 
+/// Terminate the service: exit its process, or only end its thread when the
+/// node is embedded
+pub fn terminate_service() -> ! {
+    #[cfg(feature = "embedded")]
+    if crate::embedded::is_embedded() {
+        std::panic::resume_unwind(Box::new(crate::embedded::Terminated));
+    }
+    std::process::exit(0)
+}
+
 pub trait TryToServiceId {
     fn try_to_service_id(&self) -> Option<ServiceId>;
 }
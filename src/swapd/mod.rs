@@ -20,3 +20,5 @@ pub use runtime::run;
 pub use runtime::CheckpointSwapd;
 pub use state_report::StateReport;
 pub use swap_state::SwapStateMachine;
+pub use temporal_safety::{TemporalSafety, SWEEP_MONERO_THRESHOLD};
+pub use wallet::lock_fee;
@@ -10,6 +10,8 @@ use farcaster_core::{
 };
 use std::str::FromStr;
 
+use crate::config::{StateTimeout, SwapTimeouts};
+
 /// Swap executor daemon; part of Farcaster Node
 ///
 /// The daemon is controlled through ZMQ ctl socket (see `ctl-socket` argument
@@ -48,6 +50,41 @@ pub struct Opts {
     #[clap(long = "acc-finality")]
     pub accordant_finality: u8,
 
+    /// Seconds after which the swap is aborted if Bob is still awaiting the
+    /// counterparty after the reveal
+    #[clap(long)]
+    pub bob_reveal_timeout_secs: Option<u64>,
+
+    /// Bitcoin blocks after which the swap is aborted if Bob is still awaiting
+    /// the counterparty after the reveal
+    #[clap(long)]
+    pub bob_reveal_timeout_blocks: Option<u32>,
+
+    /// Seconds after which the swap is aborted if Bob is still awaiting the
+    /// funding
+    #[clap(long)]
+    pub bob_fee_estimated_timeout_secs: Option<u64>,
+
+    /// Bitcoin blocks after which the swap is aborted if Bob is still awaiting
+    /// the funding
+    #[clap(long)]
+    pub bob_fee_estimated_timeout_blocks: Option<u32>,
+
+    /// Seconds after which the swap is aborted if Alice is still awaiting the
+    /// counterparty after the reveal
+    #[clap(long)]
+    pub alice_reveal_timeout_secs: Option<u64>,
+
+    /// Bitcoin blocks after which the swap is aborted if Alice is still
+    /// awaiting the counterparty after the reveal
+    #[clap(long)]
+    pub alice_reveal_timeout_blocks: Option<u32>,
+
+    /// Queue the bitcoin sweeps of the swap in farcasterd to be swept in a
+    /// batch with other swaps, instead of sweeping them individually
+    #[clap(long)]
+    pub batch_sweep: bool,
+
     /// These params can be read also from the configuration file, not just
     /// Command-line args or environment variables
     #[clap(flatten)]
@@ -58,4 +95,25 @@ impl Opts {
     pub fn process(&mut self) {
         self.shared.process();
     }
+
+    pub fn timeouts(&self) -> SwapTimeouts {
+        let timeout = |seconds: Option<u64>, blocks: Option<u32>| {
+            if seconds.is_some() || blocks.is_some() {
+                Some(StateTimeout { seconds, blocks })
+            } else {
+                None
+            }
+        };
+        SwapTimeouts {
+            bob_reveal: timeout(self.bob_reveal_timeout_secs, self.bob_reveal_timeout_blocks),
+            bob_fee_estimated: timeout(
+                self.bob_fee_estimated_timeout_secs,
+                self.bob_fee_estimated_timeout_blocks,
+            ),
+            alice_reveal: timeout(
+                self.alice_reveal_timeout_secs,
+                self.alice_reveal_timeout_blocks,
+            ),
+        }
+    }
 }
@@ -8,25 +8,48 @@ use super::{
     swap_state::{SwapStateMachine, SwapStateMachineExecutor},
     syncer_client::{SyncerState, SyncerTasks},
     temporal_safety::TemporalSafety,
+    wallet::{
+        consolidation_fee, export_funding_psbt, import_funding_psbt, lock_fee, missing_funding,
+        FundingOutput,
+    },
     StateReport,
 };
+use crate::config::{StateTimeout, SwapTimeouts};
 use crate::service::{Endpoints, Reporter};
 use crate::swapd::Opts;
-use crate::syncerd::bitcoin_syncer::p2wpkh_signed_tx_fee;
-use crate::syncerd::types::{Event, TransactionConfirmations};
+use crate::syncerd::types::{
+    Event, SweepBitcoinAddress, SweepMoneroAddress, SweepSuccess, TransactionConfirmations,
+};
 use crate::{
-    bus::ctl::{BitcoinFundingInfo, Checkpoint, CtlMsg, FundingInfo, Params},
-    bus::info::{InfoMsg, SwapInfo},
-    bus::p2p::{Commit, PeerMsg, Reveal},
+    bus::ctl::{
+        BitcoinFundingInfo, Checkpoint, CtlMsg, ForceBroadcast, FundingInfo, Params,
+        PeerdUnreachable,
+    },
+    bus::info::{InfoMsg, SwapInfo, SwapTx},
+    bus::p2p::{ChatMessage, Commit, PeerMsg, Reveal, MAX_CHAT_MESSAGES, MAX_CHAT_MESSAGE_LEN},
     bus::sync::SyncMsg,
-    bus::{BusMsg, Outcome, ServiceBus},
-    syncerd::{HeightChanged, TransactionRetrieved, XmrAddressAddendum},
+    bus::{
+        BusMsg, ChatDirection, ChatRecord, EventKind, Failure, FailureCode, NodeEvent, Outcome,
+        OutcomeReason, OutcomeRecord, Progress, QueuedSweep, ServiceBus, StateTimestamp,
+    },
+    syncerd::{
+        AddressTransaction, HeightChanged, SweepAddress, SweepAddressAddendum, Task,
+        TransactionRetrieved, XmrAddressAddendum,
+    },
 };
-use crate::{CtlServer, Error, LogStyle, Service, ServiceConfig, ServiceId};
+use crate::{terminate_service, CtlServer, Error, LogStyle, Service, ServiceConfig, ServiceId};
 
+use std::io;
+use std::mem::Discriminant;
+use std::sync::mpsc::{Sender, TryRecvError};
 use std::time::{Duration, SystemTime};
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
 
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::psbt::serialize::Deserialize;
 use bitcoin::Txid;
 use colored::ColoredString;
 use farcaster_core::{
@@ -40,10 +63,15 @@ use farcaster_core::{
 };
 
 use internet2::addr::{NodeAddr, NodeId};
+use internet2::session::LocalSession;
+use internet2::zeromq::ZmqSocketType;
+use internet2::{SendRecvMessage, TypedEnum};
 use microservices::esb::{self, Handler};
-use strict_encoding::{StrictDecode, StrictEncode};
+use microservices::ZMQ_CONTEXT;
+use strict_encoding::{strict_encode_list, StrictDecode, StrictEncode};
 
 pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
+    let timeouts = opts.timeouts();
     let Opts {
         swap_id,
         deal,
@@ -51,6 +79,7 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
         arbitrating_finality,
         arbitrating_safety,
         accordant_finality,
+        batch_sweep,
         ..
     } = opts;
 
@@ -96,6 +125,7 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
         watched_txs: none!(),
         retrieving_txs: none!(),
         sweeping_addr: none!(),
+        late_funding_sweeps: none!(),
         broadcasting_txs: none!(),
         txids: none!(),
         final_txs: none!(),
@@ -114,12 +144,23 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
         bitcoin_syncer: ServiceId::Syncer(Blockchain::Bitcoin, network),
         monero_syncer: ServiceId::Syncer(Blockchain::Monero, network),
         awaiting_funding: false,
+        overfunded: false,
+        funding_txids: none!(),
+        funding_sweep: None,
+        late_funding_pending: false,
         xmr_addr_addendum: None,
         confirmations: none!(),
     };
 
     let state_report = StateReport::new("Start".to_string(), &temporal_safety, &syncer_state);
 
+    let timeline = vec![StateTimestamp {
+        state: swap_state_machine.to_string(),
+        timestamp: unix_timestamp(),
+    }];
+    let rx = ZMQ_CONTEXT.socket(zmq::PULL)?;
+    rx.bind(&bridge_addr(&swap_id))?;
+    let timer_flag_tx = run_pre_lock_timer(swap_id)?;
     let runtime = Runtime {
         swap_id,
         identity: ServiceId::Swap(swap_id),
@@ -141,9 +182,64 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
         monero_address_creation_height: None,
         swap_state_machine,
         unhandled_peer_message: None, // The last message we received and was not handled by the state machine
+        timeouts,
+        state_entered: StateEntered::now(0),
+        outcome_reason: None,
+        timeline,
+        batch_sweep,
+        checkpointed: None,
+        chat_messages: 0,
+        handled_peer_msgs: none!(),
+        _timer_flag_tx: timer_flag_tx,
     };
-    let broker = false;
-    Service::run(config, runtime, broker)
+    let mut service = Service::service(config, runtime)?;
+    service.add_bridge_service_bus(rx)?;
+    service.run_loop()?;
+    unreachable!()
+}
+
+/// Digest of a peer message, identifying it whatever connection delivered it
+fn peer_msg_digest(msg: &PeerMsg) -> Result<sha256::Hash, Error> {
+    Ok(sha256::Hash::hash(&msg.strict_serialize()?))
+}
+
+/// Interval in seconds between two checks of the pre-lock timeouts
+const PRE_LOCK_TIMEOUT_CHECK_INTERVAL: u64 = 5;
+
+/// Address of the bridge between the runtime and its timer thread, unique per
+/// swapd for several swapds to run in the same process when the node is
+/// embedded
+fn bridge_addr(swap_id: &SwapId) -> String {
+    format!("inproc://swapdbridge-{}", swap_id)
+}
+
+/// Periodically trigger the check of the pre-lock timeouts over the bridge,
+/// until the returned flag is dropped with the runtime
+fn run_pre_lock_timer(swap_id: SwapId) -> Result<Sender<()>, Error> {
+    let tx = ZMQ_CONTEXT.socket(zmq::PUSH)?;
+    tx.connect(&bridge_addr(&swap_id))?;
+    let (timer_flag_tx, timer_flag_rx) = std::sync::mpsc::channel::<()>();
+    let swapd_address: Vec<u8> = ServiceId::Swap(swap_id).into();
+    std::thread::spawn(move || {
+        let mut session = LocalSession::with_zmq_socket(ZmqSocketType::Push, tx);
+        let request = BusMsg::Ctl(CtlMsg::CheckPreLockTimeout);
+        loop {
+            std::thread::sleep(Duration::from_secs(PRE_LOCK_TIMEOUT_CHECK_INTERVAL));
+            if let Err(TryRecvError::Disconnected) = timer_flag_rx.try_recv() {
+                break;
+            }
+            trace!("sending request over swapd bridge: {}", request);
+            if let Err(err) = session.send_routed_message(
+                &swapd_address,
+                &swapd_address,
+                &swapd_address,
+                &request.serialize(),
+            ) {
+                error!("Failed to send {} over the bridge: {}", request, err);
+            }
+        }
+    });
+    Ok(timer_flag_tx)
 }
 
 // FIXME: State enum should carry over the data that is accumulated over time,
@@ -166,9 +262,97 @@ pub struct Runtime {
     pub monero_address_creation_height: Option<u64>,
     pub swap_state_machine: SwapStateMachine,
     pub unhandled_peer_message: Option<PeerMsg>,
+    pub timeouts: SwapTimeouts,
+    pub state_entered: StateEntered, // When the swap entered its current state, used by the pre-lock timeouts
+    pub outcome_reason: Option<OutcomeReason>, // What leads the swap to fail, reported with the outcome
+    pub timeline: Vec<StateTimestamp>, // The states entered by the swap, reported with the outcome
+    pub batch_sweep: bool, // Whether bitcoin sweeps are queued in farcasterd to be batched
+    pub checkpointed: Option<(Discriminant<SwapStateMachine>, Option<PeerMsg>)>, // The state and pending peer message of the latest checkpoint, checkpointed again on shutdown
+    pub chat_messages: u64, // Chat messages stored since the launch, the ones over the cap are dropped
+    pub handled_peer_msgs: HashSet<sha256::Hash>, // Digests of the peer messages handled by the state machine, a message re-emitted after a reconnect is handled once
+    _timer_flag_tx: Sender<()>, // Stops the timer thread once the runtime is dropped
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Wall-clock time and Bitcoin height at which the swap entered its current
+/// state
+#[derive(Debug, Clone, Copy)]
+pub struct StateEntered {
+    pub time: SystemTime,
+    pub bitcoin_height: u64,
+}
+
+impl StateEntered {
+    fn now(bitcoin_height: u64) -> Self {
+        StateEntered {
+            time: SystemTime::now(),
+            bitcoin_height,
+        }
+    }
+}
+
+/// Unix timestamp and Bitcoin height, as persisted in the checkpoints
+impl From<StateEntered> for (u64, u64) {
+    fn from(state_entered: StateEntered) -> Self {
+        let timestamp = state_entered
+            .time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        (timestamp, state_entered.bitcoin_height)
+    }
+}
+
+impl From<(u64, u64)> for StateEntered {
+    fn from((timestamp, bitcoin_height): (u64, u64)) -> Self {
+        StateEntered {
+            time: SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp),
+            bitcoin_height,
+        }
+    }
+}
+
+/// How long the swap is stalled in its state if it exceeds the timeout of the
+/// state, given the seconds and blocks elapsed since the state was entered
+pub fn stalled_for(timeout: StateTimeout, elapsed: u64, blocks: u64) -> Option<String> {
+    match timeout {
+        StateTimeout {
+            seconds: Some(seconds),
+            ..
+        } if elapsed >= seconds => Some(format!("{} seconds", elapsed)),
+        StateTimeout {
+            blocks: Some(max_blocks),
+            ..
+        } if blocks >= max_blocks as u64 => Some(format!("{} blocks", blocks)),
+        _ => None,
+    }
+}
+
+/// Marks a versioned swapd checkpoint. Unversioned checkpoints start with the
+/// variant tag of the swap state, which never takes this value.
+const CHECKPOINT_VERSION_MARKER: u8 = 0xff;
+
+/// Version of the swapd checkpoint encoding. Version 0 is the unversioned
+/// encoding, it lacks the fee rate and the received payments of the Bob Fee
+/// Estimated state, the late funding tracking, the pending sweep of the
+/// sweeping states, the timeline, the time the state was entered and the Monero
+/// sweep split of the wallets.
+pub const CHECKPOINT_VERSION: u16 = 1;
+
+/// Decoding of the swap states and wallets whose encoding changed between
+/// checkpoint versions, given the version of the checkpoint being decoded.
+/// Their strict decoding reads the current version.
+pub trait CheckpointDecode: Sized {
+    fn checkpoint_decode<D: io::Read>(d: D, version: u16) -> Result<Self, strict_encoding::Error>;
 }
 
-#[derive(Debug, Clone, Display, StrictEncode, StrictDecode)]
+#[derive(Debug, Clone, Display)]
 #[display("checkpoint-swapd")]
 pub struct CheckpointSwapd {
     pub state: SwapStateMachine,
@@ -183,6 +367,87 @@ pub struct CheckpointSwapd {
     pub connected_counterparty_node_id: Option<NodeId>,
     pub deal: Deal,
     pub monero_address_creation_height: Option<u64>,
+    pub funding_txids: Vec<Txid>,
+    pub funding_sweep: Option<SweepBitcoinAddress>,
+    pub late_funding_pending: bool,
+    pub pending_sweep: Option<SweepAddress>,
+    pub timeline: Vec<StateTimestamp>,
+    pub state_entered: Option<(u64, u64)>,
+}
+
+impl CheckpointSwapd {
+    fn strict_decode_fields<D: io::Read>(
+        mut d: D,
+        version: u16,
+    ) -> Result<Self, strict_encoding::Error> {
+        let mut checkpoint = CheckpointSwapd {
+            state: SwapStateMachine::checkpoint_decode(&mut d, version)?,
+            pending_msg: StrictDecode::strict_decode(&mut d)?,
+            enquirer: StrictDecode::strict_decode(&mut d)?,
+            xmr_addr_addendum: StrictDecode::strict_decode(&mut d)?,
+            temporal_safety: StrictDecode::strict_decode(&mut d)?,
+            txs: StrictDecode::strict_decode(&mut d)?,
+            txids: StrictDecode::strict_decode(&mut d)?,
+            pending_broadcasts: StrictDecode::strict_decode(&mut d)?,
+            local_trade_role: StrictDecode::strict_decode(&mut d)?,
+            connected_counterparty_node_id: StrictDecode::strict_decode(&mut d)?,
+            deal: StrictDecode::strict_decode(&mut d)?,
+            monero_address_creation_height: StrictDecode::strict_decode(&mut d)?,
+            funding_txids: vec![],
+            funding_sweep: None,
+            late_funding_pending: false,
+            pending_sweep: None,
+            timeline: vec![],
+            state_entered: None,
+        };
+        // version 0 checkpoints do not track late funding, sweeps, the
+        // timeline nor when the state was entered
+        if version > 0 {
+            checkpoint.funding_txids = StrictDecode::strict_decode(&mut d)?;
+            checkpoint.funding_sweep = StrictDecode::strict_decode(&mut d)?;
+            checkpoint.late_funding_pending = StrictDecode::strict_decode(&mut d)?;
+            checkpoint.pending_sweep = StrictDecode::strict_decode(&mut d)?;
+            checkpoint.timeline = StrictDecode::strict_decode(&mut d)?;
+            checkpoint.state_entered = StrictDecode::strict_decode(&mut d)?;
+        }
+        Ok(checkpoint)
+    }
+}
+
+impl StrictEncode for CheckpointSwapd {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        Ok(
+            strict_encode_list!(e; CHECKPOINT_VERSION_MARKER, CHECKPOINT_VERSION, self.state,
+            self.pending_msg, self.enquirer, self.xmr_addr_addendum, self.temporal_safety,
+            self.txs, self.txids, self.pending_broadcasts, self.local_trade_role,
+            self.connected_counterparty_node_id, self.deal, self.monero_address_creation_height,
+            self.funding_txids, self.funding_sweep, self.late_funding_pending,
+            self.pending_sweep, self.timeline, self.state_entered),
+        )
+    }
+}
+
+impl StrictDecode for CheckpointSwapd {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let first = u8::strict_decode(&mut d)?;
+        let version = if first == CHECKPOINT_VERSION_MARKER {
+            u16::strict_decode(&mut d)?
+        } else {
+            0
+        };
+        if version > CHECKPOINT_VERSION {
+            return Err(strict_encoding::Error::DataIntegrityError(format!(
+                "unsupported swapd checkpoint version {}",
+                version
+            )));
+        }
+        if version == 0 {
+            // the first byte is the variant tag of the swap state
+            Self::strict_decode_fields(io::Read::chain(&[first][..], d), version)
+        } else {
+            Self::strict_decode_fields(d, version)
+        }
+    }
 }
 
 impl CtlServer for Runtime {}
@@ -225,6 +490,11 @@ impl esb::Handler<ServiceBus> for Runtime {
                 self.handle_sync(endpoints, source, req)?;
                 self.report_potential_state_change(endpoints)
             }
+            // Bridge with the timer thread, only accept the pre-lock timeout checks
+            (ServiceBus::Bridge, BusMsg::Ctl(CtlMsg::CheckPreLockTimeout)) => {
+                self.check_pre_lock_timeout(endpoints)?;
+                self.report_potential_state_change(endpoints)
+            }
             // All other pairs are not supported
             (bus, req) => Err(Error::NotSupported(bus, req.to_string())),
         }
@@ -262,13 +532,64 @@ impl Runtime {
                 ServiceBus::Ctl,
                 self.identity(),
                 ServiceId::Farcasterd,
-                BusMsg::Ctl(CtlMsg::PeerdUnreachable(self.peer_service.clone())),
+                BusMsg::Ctl(CtlMsg::PeerdUnreachable(PeerdUnreachable {
+                    peerd: self.peer_service.clone(),
+                    reason: format!("could not send message {}: {}", msg, error),
+                })),
             )?;
             self.pending_peer_request.push(msg);
         }
         Ok(())
     }
 
+    fn store_chat_record(
+        &mut self,
+        endpoints: &mut Endpoints,
+        direction: ChatDirection,
+        text: String,
+    ) -> Result<(), Error> {
+        self.chat_messages += 1;
+        endpoints.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            ServiceId::Database,
+            BusMsg::Ctl(CtlMsg::SetChatRecord(ChatRecord {
+                swap_id: self.swap_id(),
+                timestamp: unix_timestamp(),
+                direction,
+                text,
+            })),
+        )?;
+        Ok(())
+    }
+
+    /// Let peerd know it does not have to keep the connection alive for this
+    /// swap anymore, once the counterparty has nothing left to send us.
+    fn release_peer_if_not_needed(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        if self.swap_state_machine.needs_counterparty()
+            || matches!(
+                self.peer_service,
+                ServiceId::Loopback | ServiceId::Peer(0, _)
+            )
+        {
+            return Ok(());
+        }
+        self.log_debug(format!(
+            "Counterparty not needed anymore in state {}, releasing {}",
+            self.swap_state_machine, self.peer_service
+        ));
+        // peerd might already be gone, in which case there is nothing to release
+        if let Err(err) = endpoints.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            self.peer_service.clone(),
+            BusMsg::Ctl(CtlMsg::ReleasePeer(self.swap_id())),
+        ) {
+            self.log_debug(format!("Could not release {}: {}", self.peer_service, err));
+        }
+        Ok(())
+    }
+
     pub fn swap_id(&self) -> SwapId {
         match self.identity {
             ServiceId::Swap(swap_id) => swap_id,
@@ -289,6 +610,16 @@ impl Runtime {
             tx_label.label(),
             tx.txid().tx_hash()
         ));
+        endpoints.send_to(
+            ServiceBus::Ctl,
+            self.identity(),
+            ServiceId::Database,
+            BusMsg::Ctl(CtlMsg::LogEvent(NodeEvent::new(
+                EventKind::TxBroadcast,
+                Some(self.swap_id()),
+                format!("Broadcasting {} tx {}", tx_label, tx.txid()),
+            ))),
+        )?;
         let task = self.syncer_state.broadcast(tx);
         Ok(endpoints.send_to(
             ServiceBus::Sync,
@@ -336,10 +667,42 @@ impl Runtime {
                     "Ping/Pong must remain in peerd, not supported in swapd".to_string(),
                 ));
             }
+
+            // chat messages are stored, they do not affect the swap
+            PeerMsg::ChatMessage(ChatMessage { text, .. }) => {
+                if text.len() > MAX_CHAT_MESSAGE_LEN {
+                    self.log_warn("Ignoring chat message from the counterparty: too long");
+                    return Ok(());
+                }
+                if self.chat_messages >= MAX_CHAT_MESSAGES {
+                    debug!(
+                        "{} | Dropping chat message from the counterparty: too many messages",
+                        self.swap_id.swap_id()
+                    );
+                    return Ok(());
+                }
+                // the text is untrusted, control characters are escaped
+                self.log_info(format!("Counterparty says: {}", text.escape_debug()));
+                return self.store_chat_record(endpoints, ChatDirection::Received, text);
+            }
             _ => {}
         }
 
-        self.execute_state_machine(endpoints, BusMsg::P2p(request), source)?;
+        // the sequence numbers of the swap channel are scoped to the peerd
+        // connection, a message delivered again over a new connection after
+        // its receipt was lost is dropped here
+        if self.handled_peer_msgs.contains(&peer_msg_digest(&request)?) {
+            self.log_debug(format!(
+                "Dropping the peer message {}, already handled",
+                request
+            ));
+            return Ok(());
+        }
+
+        if let Err(err) = self.execute_state_machine(endpoints, BusMsg::P2p(request), source) {
+            self.set_outcome_reason_if_unknown(OutcomeReason::InvalidMessage);
+            return Err(err);
+        }
 
         Ok(())
     }
@@ -359,7 +722,7 @@ impl Runtime {
             }
             CtlMsg::Terminate if source == ServiceId::Farcasterd => {
                 self.log_info(format!("Terminating {}", self.identity()).label());
-                std::process::exit(0);
+                terminate_service();
             }
 
             CtlMsg::Disconnected => {
@@ -370,6 +733,12 @@ impl Runtime {
                 self.connected = true;
             }
 
+            CtlMsg::PeerdUnreachable(PeerdUnreachable { peerd, reason }) => {
+                self.log_warn(format!("Peer {} is unreachable: {}", peerd, reason));
+                self.connected = false;
+                self.set_outcome_reason_if_unknown(OutcomeReason::PeerUnreachable);
+            }
+
             // Set the reconnected service id. This can happen if this is a
             // maker launched swap after restoration and the taker reconnects,
             // after a manual connect call, or a new connection with the same
@@ -378,12 +747,109 @@ impl Runtime {
                 self.log_info(format!("Peer {} reconnected", service_id));
                 self.peer_service = service_id.clone();
                 self.connected = true;
+                if self.outcome_reason == Some(OutcomeReason::PeerUnreachable) {
+                    self.outcome_reason = None;
+                }
+                self.release_peer_if_not_needed(endpoints)?;
                 for msg in self.pending_peer_request.clone().iter() {
                     self.send_peer(endpoints, msg.clone())?;
                 }
                 self.pending_peer_request.clear();
             }
 
+            CtlMsg::SendChatMessage(text) => {
+                let failure = if text.len() > MAX_CHAT_MESSAGE_LEN {
+                    Some(format!(
+                        "Chat message too long, at most {} bytes",
+                        MAX_CHAT_MESSAGE_LEN
+                    ))
+                } else if self.chat_messages >= MAX_CHAT_MESSAGES {
+                    Some(format!(
+                        "Too many chat messages, at most {} per swap",
+                        MAX_CHAT_MESSAGES
+                    ))
+                } else if matches!(
+                    self.peer_service,
+                    ServiceId::Loopback | ServiceId::Peer(0, _)
+                ) {
+                    Some("Swap is not connected to the counterparty yet".to_string())
+                } else {
+                    None
+                };
+                if let Some(info) = failure {
+                    self.send_client_ctl(
+                        endpoints,
+                        source,
+                        CtlMsg::Failure(Failure {
+                            code: FailureCode::Unknown,
+                            info,
+                        }),
+                    )?;
+                    return Ok(());
+                }
+                self.send_peer(
+                    endpoints,
+                    PeerMsg::ChatMessage(ChatMessage {
+                        swap_id: self.swap_id(),
+                        text: text.clone(),
+                    }),
+                )?;
+                self.store_chat_record(endpoints, ChatDirection::Sent, text)?;
+                self.send_client_info(
+                    endpoints,
+                    source,
+                    InfoMsg::String("Message sent".to_string()),
+                )?;
+            }
+
+            CtlMsg::ImportFundingPsbt(psbt) => {
+                let res = match self.swap_state_machine.funding_request() {
+                    Some((address, amount)) => import_funding_psbt(&psbt, &address, amount),
+                    None => Err(Error::Farcaster(
+                        "Swap is not awaiting Bitcoin funding".to_string(),
+                    )),
+                };
+                match res {
+                    Ok(tx) => {
+                        let txid = tx.txid();
+                        self.broadcast(tx, TxLabel::Funding, endpoints)?;
+                        self.send_client_info(
+                            endpoints,
+                            source,
+                            InfoMsg::String(format!("Funding transaction {} broadcasted", txid)),
+                        )?;
+                    }
+                    Err(err) => {
+                        self.send_client_ctl(
+                            endpoints,
+                            source,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: err.to_string(),
+                            }),
+                        )?;
+                    }
+                }
+            }
+
+            CtlMsg::ForceBroadcast(ForceBroadcast { label, confirm }) => {
+                match self.force_broadcast(endpoints, label, confirm) {
+                    Ok(msg) => {
+                        self.send_client_info(endpoints, source, InfoMsg::String(msg))?;
+                    }
+                    Err(err) => {
+                        self.send_client_ctl(
+                            endpoints,
+                            source,
+                            CtlMsg::Failure(Failure {
+                                code: FailureCode::Unknown,
+                                info: err.to_string(),
+                            }),
+                        )?;
+                    }
+                }
+            }
+
             CtlMsg::FailedPeerMessage(msg) => {
                 self.log_warn(format!(
                     "Sending the peer message {} failed. Adding to pending peer requests",
@@ -392,6 +858,41 @@ impl Runtime {
                 self.pending_peer_request.push(msg);
             }
 
+            // Checkpoint the current state on shutdown, a swap that did not
+            // reach its first checkpoint is not restorable and is left as is
+            CtlMsg::CheckpointSwap if source == ServiceId::Farcasterd => {
+                if let Some((checkpointed, pending_msg)) = self.checkpointed.clone() {
+                    // the pending peer message is only resent if the swap is
+                    // still in the state it was checkpointed with
+                    let pending_msg =
+                        if checkpointed == std::mem::discriminant(&self.swap_state_machine) {
+                            pending_msg
+                        } else {
+                            None
+                        };
+                    self.log_info("Checkpointing swap for shutdown");
+                    self.checkpoint_state(endpoints, pending_msg, self.swap_state_machine.clone())?;
+                }
+                self.send_ctl(
+                    endpoints,
+                    ServiceId::Farcasterd,
+                    BusMsg::Ctl(CtlMsg::SwapCheckpointed),
+                )?;
+            }
+
+            CtlMsg::ReportState if source == ServiceId::Farcasterd => {
+                let report = StateReport::new(
+                    self.swap_state_machine.to_string(),
+                    &self.temporal_safety,
+                    &self.syncer_state,
+                );
+                self.send_ctl(
+                    endpoints,
+                    ServiceId::Farcasterd,
+                    BusMsg::Ctl(CtlMsg::Progress(Progress::StateUpdate(report))),
+                )?;
+            }
+
             CtlMsg::Checkpoint(Checkpoint { swap_id: _, state }) => {
                 let CheckpointSwapd {
                     pending_msg,
@@ -403,11 +904,33 @@ impl Runtime {
                     xmr_addr_addendum,
                     local_trade_role,
                     monero_address_creation_height,
-                    state,
+                    funding_txids,
+                    funding_sweep,
+                    late_funding_pending,
+                    pending_sweep,
+                    timeline,
+                    state_entered,
+                    mut state,
                     ..
                 } = state;
                 self.log_info("Restoring swap");
+                self.checkpointed = Some((std::mem::discriminant(&state), pending_msg.clone()));
+                // the sweep of a state waiting to sweep is sent under this
+                // runtime's task ids
+                if let SwapStateMachine::BobBuyFinal(sweep) | SwapStateMachine::AliceRefund(sweep) =
+                    &mut state
+                {
+                    *sweep = self.syncer_state.resume_sweep(sweep.clone());
+                }
                 self.swap_state_machine = state;
+                self.enter_state();
+                // the timeline of a version 0 checkpoint starts at the restore
+                if !timeline.is_empty() {
+                    self.timeline = timeline;
+                }
+                if let Some(state_entered) = state_entered {
+                    self.state_entered = state_entered.into();
+                }
                 self.enquirer = enquirer;
                 self.temporal_safety = temporal_safety;
                 self.monero_address_creation_height = monero_address_creation_height;
@@ -432,6 +955,43 @@ impl Runtime {
                     )?;
                 }
 
+                // keep watching the funding address for the funding or late
+                // funding, the payments funding the swap are reported again
+                self.syncer_state.funding_txids = funding_txids.into_iter().collect();
+                self.syncer_state.late_funding_pending = late_funding_pending;
+                self.syncer_state.awaiting_funding = matches!(
+                    self.swap_state_machine,
+                    SwapStateMachine::BobFeeEstimated(_)
+                );
+                if let Some(sweep) = funding_sweep {
+                    self.log_trace("Watching the funding address for late funding");
+                    let task = self
+                        .syncer_state
+                        .watch_addr_btc(sweep.source_address.clone(), TxLabel::Funding);
+                    self.syncer_state.funding_sweep = Some(sweep);
+                    endpoints.send_to(
+                        ServiceBus::Sync,
+                        self.identity(),
+                        self.syncer_state.bitcoin_syncer(),
+                        BusMsg::Sync(SyncMsg::Task(task)),
+                    )?;
+                }
+
+                if let Some(sweep) = pending_sweep {
+                    self.log_trace("Sweeping again");
+                    let syncer = match sweep.addendum {
+                        SweepAddressAddendum::Monero(_) => self.syncer_state.monero_syncer(),
+                        _ => self.syncer_state.bitcoin_syncer(),
+                    };
+                    let task = Task::SweepAddress(self.syncer_state.resume_sweep(sweep));
+                    endpoints.send_to(
+                        ServiceBus::Sync,
+                        self.identity(),
+                        syncer,
+                        BusMsg::Sync(SyncMsg::Task(task)),
+                    )?;
+                }
+
                 self.log_trace("Broadcasting txs pending broadcast");
                 for tx in pending_broadcasts.iter() {
                     let task = self.syncer_state.broadcast(tx.clone());
@@ -502,10 +1062,42 @@ impl Runtime {
                     local_trade_role: self.local_trade_role,
                     local_swap_role: self.deal.swap_role(&self.local_trade_role),
                     connected_counterparty_node_id: get_node_id(&self.peer_service),
+                    timeline: self.timeline.clone(),
                 };
                 self.send_client_info(endpoints, source, InfoMsg::SwapInfo(info))?;
             }
 
+            InfoMsg::GetFundingPsbt => {
+                let res = match self.swap_state_machine.funding_request() {
+                    Some((address, amount)) => export_funding_psbt(&address, amount),
+                    None => Err(Error::Farcaster(
+                        "Swap is not awaiting Bitcoin funding".to_string(),
+                    )),
+                };
+                let msg = match res {
+                    Ok(psbt) => InfoMsg::FundingPsbt(psbt),
+                    Err(err) => InfoMsg::Failure(Failure {
+                        code: FailureCode::Unknown,
+                        info: err.to_string(),
+                    }),
+                };
+                self.send_client_info(endpoints, source, msg)?;
+            }
+
+            InfoMsg::GetSwapTxs => {
+                let mut txs: Vec<SwapTx> = self
+                    .txs
+                    .iter()
+                    .map(|(label, tx)| SwapTx {
+                        label: *label,
+                        txid: tx.txid(),
+                        hex: bitcoin::consensus::encode::serialize_hex(tx),
+                    })
+                    .collect();
+                txs.sort_by_key(|tx| tx.label.to_string());
+                self.send_client_info(endpoints, source, InfoMsg::SwapTxList(txs.into()))?;
+            }
+
             req => {
                 self.log_error(format!(
                     "BusMsg {} is not supported by the INFO interface",
@@ -628,12 +1220,50 @@ impl Runtime {
                         self.syncer_state.transaction_broadcasted(event)
                     }
 
+                    // payments to the funding address once funding completed or
+                    // after the swap was aborted
+                    Event::AddressTransaction(AddressTransaction {
+                        id,
+                        amount,
+                        tx,
+                        incoming: true,
+                        ..
+                    }) if !self.syncer_state.awaiting_funding
+                        && self.syncer_state.tasks.watched_addrs.get(id)
+                            == Some(&TxLabel::Funding) =>
+                    {
+                        let tx = bitcoin::Transaction::deserialize(
+                            &tx.iter().flatten().copied().collect::<Vec<u8>>(),
+                        )?;
+                        if !self.syncer_state.funding_txids.contains(&tx.txid()) {
+                            self.syncer_state.overfunded = true;
+                            self.syncer_state.late_funding_pending = true;
+                            let msg = format!(
+                                "Received {} on the funding address in tx {} after the swap was funded or aborted, it will be swept to the target bitcoin address",
+                                bitcoin::Amount::from_sat(*amount),
+                                tx.txid(),
+                            );
+                            self.log_warn(&msg);
+                            self.report_progress_message(endpoints, msg)?;
+                        }
+                    }
+
                     Event::AddressTransaction(_) => {}
 
                     Event::TaskAborted(event) => {
                         self.log_debug(event);
                     }
 
+                    Event::SweepSuccess(SweepSuccess { id, txids })
+                        if self.syncer_state.tasks.late_funding_sweeps.remove(id) =>
+                    {
+                        for txid in txids.iter().filter_map(|txid| Txid::from_slice(txid).ok()) {
+                            let msg = format!("Late funding swept in tx {}", txid);
+                            self.log_info(&msg);
+                            self.report_progress_message(endpoints, msg)?;
+                        }
+                    }
+
                     Event::SweepSuccess(event) => {
                         self.log_debug(event);
                     }
@@ -653,6 +1283,32 @@ impl Runtime {
 
                     Event::HealthResult(_) => self.log_debug("ignoring health result in swapd"),
                 };
+                if self.syncer_state.late_funding_pending && self.can_sweep_late_funding() {
+                    if self.batch_sweep {
+                        if let Some(sweep) = self.syncer_state.funding_sweep.clone() {
+                            self.syncer_state.late_funding_pending = false;
+                            self.log_info("Queueing the late funding sweep for the next batch");
+                            endpoints.send_to(
+                                ServiceBus::Ctl,
+                                self.identity(),
+                                ServiceId::Farcasterd,
+                                BusMsg::Ctl(CtlMsg::QueueSweep(QueuedSweep {
+                                    swap_id: self.swap_id,
+                                    source_address: sweep.source_address,
+                                    destination_address: sweep.destination_address,
+                                })),
+                            )?;
+                        }
+                    } else if let Some(task) = self.syncer_state.sweep_late_funding_btc() {
+                        self.log_info("Sweeping late funding to the target bitcoin address");
+                        endpoints.send_to(
+                            ServiceBus::Sync,
+                            self.identity(),
+                            self.syncer_state.bitcoin_syncer(),
+                            BusMsg::Sync(SyncMsg::Task(task)),
+                        )?;
+                    }
+                }
             }
             _ => {}
         }
@@ -663,6 +1319,157 @@ impl Runtime {
 }
 
 impl Runtime {
+    /// Broadcast a pre-signed cancel, refund, or punish transaction on request
+    /// of the operator, regardless of the state of the swap. Without
+    /// confirmation, only reports what would be broadcasted.
+    fn force_broadcast(
+        &mut self,
+        endpoints: &mut Endpoints,
+        label: TxLabel,
+        confirm: bool,
+    ) -> Result<String, Error> {
+        if !matches!(label, TxLabel::Cancel | TxLabel::Refund | TxLabel::Punish) {
+            return Err(Error::Farcaster(format!(
+                "The {} transaction cannot be force-broadcasted",
+                label
+            )));
+        }
+        // the transaction is kept, the automatic logic may still use it
+        let tx = self.txs.get(&label).cloned().ok_or_else(|| {
+            Error::Farcaster(format!("The swap holds no signed {} transaction", label))
+        })?;
+        let txid = tx.txid();
+        let warning = self.force_broadcast_warning(label);
+        if !confirm {
+            return Ok(format!(
+                "Would broadcast the {} transaction {}. {}Re-run with --confirm to broadcast it.",
+                label,
+                txid,
+                warning
+                    .map(|w| format!("Warning: {}. ", w))
+                    .unwrap_or_default()
+            ));
+        }
+        if let Some(warning) = &warning {
+            self.log_warn(format!(
+                "Force-broadcasting the {} transaction: {}",
+                label, warning
+            ));
+        }
+        self.broadcast(tx, label, endpoints)?;
+        Ok(format!(
+            "Broadcasting the {} transaction {}{}",
+            label,
+            txid,
+            warning
+                .map(|w| format!(". Warning: {}", w))
+                .unwrap_or_default()
+        ))
+    }
+
+    /// Why broadcasting the transaction now may fail or race with the
+    /// counterparty, according to the temporal safety parameters
+    fn force_broadcast_warning(&self, label: TxLabel) -> Option<String> {
+        let temporal_safety = &self.temporal_safety;
+        let lock_confs = self.syncer_state.get_confs(TxLabel::Lock);
+        let cancel_confs = self.syncer_state.get_confs(TxLabel::Cancel);
+        match (label, lock_confs, cancel_confs) {
+            (TxLabel::Cancel, None, _) => Some(
+                "the lock transaction is not mined, the cancel transaction will be rejected"
+                    .to_string(),
+            ),
+            (TxLabel::Cancel, Some(confs), _) if !temporal_safety.valid_cancel(confs) => {
+                Some(format!(
+                    "the cancel timelock expires in {} blocks, the cancel transaction will be rejected until then",
+                    temporal_safety.blocks_until_cancel(confs)
+                ))
+            }
+            (TxLabel::Cancel, ..) if self.syncer_state.get_confs(TxLabel::Buy).is_some() => Some(
+                "the buy transaction is already seen, the cancel transaction conflicts with it"
+                    .to_string(),
+            ),
+            (TxLabel::Refund, _, None) | (TxLabel::Punish, _, None) => Some(format!(
+                "the cancel transaction is not mined, the {} transaction will be rejected",
+                label
+            )),
+            (TxLabel::Refund, _, Some(confs))
+                if !temporal_safety.final_tx(confs, Blockchain::Bitcoin) =>
+            {
+                Some(
+                    "the cancel transaction is not final, the refund may be invalidated by a reorganization"
+                        .to_string(),
+                )
+            }
+            (TxLabel::Refund, _, Some(confs)) if !temporal_safety.safe_refund(confs) => {
+                Some(format!(
+                    "the refund races with the counterparty's punish transaction, valid in {} blocks",
+                    temporal_safety.blocks_until_punish_after_cancel(confs).max(0)
+                ))
+            }
+            (TxLabel::Punish, _, Some(confs)) if !temporal_safety.valid_punish(confs) => {
+                Some(format!(
+                    "the punish timelock expires in {} blocks, the punish transaction will be rejected until then",
+                    temporal_safety.blocks_until_punish_after_cancel(confs)
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reset the time the current state was entered and add it to the timeline
+    fn enter_state(&mut self) {
+        self.state_entered = StateEntered::now(self.syncer_state.height(Blockchain::Bitcoin));
+        self.timeline.push(StateTimestamp {
+            state: self.swap_state_machine.to_string(),
+            timestamp: unix_timestamp(),
+        });
+    }
+
+    /// Set the reason of a failure outcome, unless a reason is already known
+    pub fn set_outcome_reason_if_unknown(&mut self, reason: OutcomeReason) {
+        if self.outcome_reason.is_none() {
+            self.outcome_reason = Some(reason);
+        }
+    }
+
+    /// Abort the swap if it is stalled in a state before the arbitrating lock
+    /// for longer than the configured timeout of the state
+    fn check_pre_lock_timeout(&mut self, endpoints: &mut Endpoints) -> Result<(), Error> {
+        let timeout = match self.swap_state_machine {
+            SwapStateMachine::BobReveal(_) => self.timeouts.bob_reveal,
+            SwapStateMachine::BobFeeEstimated(_) => self.timeouts.bob_fee_estimated,
+            SwapStateMachine::AliceReveal(_) => self.timeouts.alice_reveal,
+            _ => None,
+        };
+        let timeout = match timeout {
+            Some(timeout) if self.outcome_reason != Some(OutcomeReason::Timeout) => timeout,
+            _ => return Ok(()),
+        };
+        // the height may not be known yet when entering the state
+        let height = self.syncer_state.height(Blockchain::Bitcoin);
+        if self.state_entered.bitcoin_height == 0 {
+            self.state_entered.bitcoin_height = height;
+        }
+        let elapsed = SystemTime::now()
+            .duration_since(self.state_entered.time)
+            .unwrap_or_else(|_| Duration::from_secs(0))
+            .as_secs();
+        let blocks = height.saturating_sub(self.state_entered.bitcoin_height);
+        let stalled_for = match stalled_for(timeout, elapsed, blocks) {
+            Some(stalled_for) => stalled_for,
+            None => return Ok(()),
+        };
+        self.outcome_reason = Some(OutcomeReason::Timeout);
+        let msg = format!(
+            "Swap stalled in state {} for {}, aborting the swap",
+            self.swap_state_machine, stalled_for
+        );
+        self.log_warn(&msg);
+        self.report_progress_message(endpoints, msg)?;
+        let identity = self.identity();
+        self.execute_state_machine(endpoints, BusMsg::Ctl(CtlMsg::AbortSwap), identity)
+    }
+
     fn execute_state_machine(
         &mut self,
         endpoints: &mut Endpoints,
@@ -676,7 +1483,20 @@ impl Runtime {
             msg.clone(),
             self.swap_state_machine.clone(),
         )? {
+            let needed_counterparty = self.swap_state_machine.needs_counterparty();
+            let final_state = self.swap_state_machine.to_string();
+            let new_state =
+                std::mem::discriminant(&ssm) != std::mem::discriminant(&self.swap_state_machine);
             self.swap_state_machine = ssm;
+            if let BusMsg::P2p(peer_msg) = &msg {
+                self.handled_peer_msgs.insert(peer_msg_digest(peer_msg)?);
+            }
+            if new_state {
+                self.enter_state();
+            }
+            if needed_counterparty {
+                self.release_peer_if_not_needed(endpoints)?;
+            }
             // On SwapEnd, report immediately to ensure the progress message goes out before the swap is terminated, then let farcasterd know of the outcome.
             if let SwapStateMachine::SwapEnd(outcome) = &self.swap_state_machine {
                 let outcome = outcome.clone(); // so we don't borrow self anymore
@@ -684,7 +1504,23 @@ impl Runtime {
                 self.send_ctl(
                     endpoints,
                     ServiceId::Farcasterd,
-                    BusMsg::Ctl(CtlMsg::SwapOutcome(outcome)),
+                    BusMsg::Ctl(CtlMsg::SwapOutcome(outcome.clone())),
+                )?;
+                let record = OutcomeRecord {
+                    swap_id: self.swap_id(),
+                    deal: self.deal.clone(),
+                    local_trade_role: self.local_trade_role,
+                    reason: self
+                        .outcome_reason
+                        .filter(|_| outcome != Outcome::SuccessSwap),
+                    outcome,
+                    final_state,
+                    timeline: self.timeline.clone(),
+                };
+                self.send_ctl(
+                    endpoints,
+                    ServiceId::Database,
+                    BusMsg::Ctl(CtlMsg::SetOutcomeRecord(record)),
                 )?;
             }
             if matches!(self.swap_state_machine, SwapStateMachine::SwapEnd(_)) {
@@ -738,24 +1574,53 @@ impl Runtime {
         Ok(())
     }
 
+    /// Sweeping the funding address must not spend the funding output, it is
+    /// only safe once the lock transaction is mined or if the swap was aborted
+    /// before locking.
+    fn can_sweep_late_funding(&self) -> bool {
+        matches!(
+            self.swap_state_machine,
+            SwapStateMachine::BobAbortAwaitingBitcoinSweep
+        ) || self
+            .syncer_state
+            .get_confs(TxLabel::Lock)
+            .map_or(false, |confs| confs > 0)
+    }
+
+    /// Sends the funding info to the enquirer and returns the amount the
+    /// swap requires on the funding address. `funding_outputs` are the
+    /// payments already received, the funding info then asks for the amount
+    /// still missing, merging fee included.
     pub fn ask_bob_to_fund(
         &mut self,
         sat_per_kvb: u64,
         address: bitcoin::Address,
+        funding_outputs: &[FundingOutput],
         endpoints: &mut Endpoints,
     ) -> Result<bitcoin::Amount, Error> {
         let swap_id = self.swap_id();
-        let vsize = 94;
-        let nr_inputs = 1;
-        let total_fees =
-            bitcoin::Amount::from_sat(p2wpkh_signed_tx_fee(sat_per_kvb, vsize, nr_inputs));
-        let amount = self.deal.parameters.arbitrating_amount + total_fees;
-        self.log_info(format!(
-            "Send {} to {}, this includes {} for the Lock transaction network fees",
-            amount.bright_green_bold(),
-            address.addr(),
-            total_fees.label(),
-        ));
+        let total_fees = lock_fee(sat_per_kvb);
+        let required_funding_amount = self.deal.parameters.arbitrating_amount + total_fees;
+        let amount = match missing_funding(required_funding_amount, sat_per_kvb, funding_outputs) {
+            Some(amount) => amount,
+            None => return Ok(required_funding_amount),
+        };
+        if funding_outputs.is_empty() {
+            self.log_info(format!(
+                "Send {} to {}, this includes {} for the Lock transaction network fees",
+                amount.bright_green_bold(),
+                address.addr(),
+                total_fees.label(),
+            ));
+        } else {
+            self.log_info(format!(
+                "Send {} more to {}, this includes {} for merging {} payments",
+                amount.bright_green_bold(),
+                address.addr(),
+                consolidation_fee(sat_per_kvb, funding_outputs.len() + 1).label(),
+                funding_outputs.len() + 1,
+            ));
+        }
         let req = BusMsg::Ctl(CtlMsg::FundingInfo(FundingInfo::Bitcoin(
             BitcoinFundingInfo {
                 swap_id,
@@ -768,7 +1633,7 @@ impl Runtime {
         if let Some(enquirer) = self.enquirer.clone() {
             endpoints.send_to(ServiceBus::Ctl, self.identity(), enquirer, req)?;
         }
-        Ok(amount)
+        Ok(required_funding_amount)
     }
 
     pub fn taker_commit(
@@ -848,6 +1713,38 @@ impl Runtime {
         pending_msg: Option<PeerMsg>,
         next_state: SwapStateMachine,
     ) -> Result<(), Error> {
+        self.checkpointed = Some((std::mem::discriminant(&next_state), pending_msg.clone()));
+        // the sweeping states wait for the sweep sent on entering them, it is
+        // sent again on restore
+        let pending_sweep = if matches!(
+            next_state,
+            SwapStateMachine::BobBuySweeping
+                | SwapStateMachine::AliceRefundSweeping
+                | SwapStateMachine::BobAbortAwaitingBitcoinSweep
+        ) {
+            self.syncer_state.pending_sweep()
+        } else {
+            None
+        };
+        // the state checkpointed on a transition is not entered yet
+        let mut timeline = self.timeline.clone();
+        if timeline
+            .last()
+            .map_or(true, |entered| entered.state != next_state.to_string())
+        {
+            timeline.push(StateTimestamp {
+                state: next_state.to_string(),
+                timestamp: unix_timestamp(),
+            });
+        }
+        // the pre-lock timeouts run from the state entry across restores
+        let state_entered = if std::mem::discriminant(&next_state)
+            == std::mem::discriminant(&self.swap_state_machine)
+        {
+            self.state_entered
+        } else {
+            StateEntered::now(self.syncer_state.height(Blockchain::Bitcoin))
+        };
         endpoints.send_to(
             ServiceBus::Ctl,
             self.identity(),
@@ -867,13 +1764,19 @@ impl Runtime {
                     connected_counterparty_node_id: get_node_id(&self.peer_service),
                     deal: self.deal.clone(),
                     monero_address_creation_height: self.monero_address_creation_height.clone(),
+                    funding_txids: self.syncer_state.funding_txids.iter().copied().collect(),
+                    funding_sweep: self.syncer_state.funding_sweep.clone(),
+                    late_funding_pending: self.syncer_state.late_funding_pending,
+                    pending_sweep,
+                    timeline,
+                    state_entered: Some(state_entered.into()),
                 },
             })),
         )?;
         Ok(())
     }
 
-    pub fn log_monero_maturity(&self, address: monero::Address) {
+    pub fn log_monero_maturity(&self, sweep: &SweepMoneroAddress) {
         let acc_confs_needs = self
             .syncer_state
             .get_confs(TxLabel::AccLock)
@@ -885,6 +1788,14 @@ impl Runtime {
             })
             .unwrap_or(self.temporal_safety.sweep_monero_thr);
         let sweep_block = self.syncer_state.height(Blockchain::Monero) + acc_confs_needs as u64;
+        let addresses = sweep
+            .split
+            .iter()
+            .map(|destination| destination.address)
+            .chain(std::iter::once(sweep.destination_address))
+            .map(|address| address.addr().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
         self.log_info(format!(
             "Tx {} needs {} more confirmations to spending maturity, and has {} confirmations.\n\
                 {} reaches your address {} after block {}",
@@ -892,7 +1803,7 @@ impl Runtime {
             acc_confs_needs.bright_green_bold(),
             self.syncer_state.get_confs(TxLabel::AccLock).unwrap_or(0),
             Blockchain::Monero.label(),
-            address.addr(),
+            addresses,
             sweep_block.bright_blue_bold(),
         ));
     }
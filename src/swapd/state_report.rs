@@ -61,7 +61,7 @@ impl StateReport {
             ),
             buy_seen: syncer_state.get_confs(TxLabel::Buy).is_some(),
             refund_seen: syncer_state.get_confs(TxLabel::Refund).is_some(),
-            overfunded: false, // FIXME
+            overfunded: syncer_state.overfunded,
             arb_lock_confirmations: syncer_state.get_confs(TxLabel::Lock),
             acc_lock_confirmations: syncer_state.get_confs(TxLabel::AccLock),
             cancel_confirmations: syncer_state.get_confs(TxLabel::Cancel),
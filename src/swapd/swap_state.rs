@@ -4,6 +4,8 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
+use std::io;
+
 use bitcoin::psbt::serialize::Deserialize;
 use farcaster_core::{
     blockchain::Blockchain, role::SwapRole, swap::btcxmr::message::BuyProcedureSignature,
@@ -19,8 +21,8 @@ use crate::{
 use crate::{
     bus::{
         ctl::{CtlMsg, InitMakerSwap, InitTakerSwap},
-        p2p::{Commit, PeerMsg, TakerCommit},
-        BusMsg, Failure, FailureCode,
+        p2p::{Commit, DealRejected, PeerMsg, TakerCommit},
+        BusMsg, Failure, FailureCode, OutcomeReason, QueuedSweep,
     },
     event::{Event, StateMachine},
     service::Reporter,
@@ -35,7 +37,10 @@ use crate::{
     swapd::{
         runtime::aggregate_xmr_spend_view,
         syncer_client::{log_tx_created, log_tx_seen},
-        wallet::{HandleBuyProcedureSignatureRes, HandleRefundProcedureSignaturesRes},
+        wallet::{
+            consolidation_fee, lock_fee_rate, missing_funding, BobState, ConsolidatedFunding,
+            FundingOutput, HandleBuyProcedureSignatureRes, HandleRefundProcedureSignaturesRes,
+        },
     },
     syncerd::{
         Abort, Boolean, SweepSuccess, Task, TaskTarget, TransactionConfirmations,
@@ -50,7 +55,7 @@ use crate::{
 use crate::{swapd::wallet::HandleCoreArbitratingSetupRes, syncerd::types::Event as SyncEvent};
 
 use super::{
-    runtime::{validate_reveal, Runtime},
+    runtime::{validate_reveal, CheckpointDecode, Runtime, CHECKPOINT_VERSION},
     wallet::Wallet,
 };
 
@@ -355,12 +360,63 @@ pub struct AliceReveal {
     wallet: Wallet,
 }
 
-#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+#[derive(Clone, Debug, StrictEncode)]
 pub struct BobFeeEstimated {
     local_params: Params,
     required_funding_amount: bitcoin::Amount,
     remote_params: Params,
     wallet: Wallet,
+    sat_per_kvb: u64,
+    funding_outputs: Vec<FundingOutput>,
+}
+
+impl StrictDecode for BobFeeEstimated {
+    fn strict_decode<D: io::Read>(d: D) -> Result<Self, strict_encoding::Error> {
+        Self::checkpoint_decode(d, CHECKPOINT_VERSION)
+    }
+}
+
+impl CheckpointDecode for BobFeeEstimated {
+    fn checkpoint_decode<D: io::Read>(
+        mut d: D,
+        version: u16,
+    ) -> Result<Self, strict_encoding::Error> {
+        let local_params = Params::strict_decode(&mut d)?;
+        let required_funding_amount = bitcoin::Amount::strict_decode(&mut d)?;
+        let remote_params = Params::strict_decode(&mut d)?;
+        let wallet = Wallet::checkpoint_decode(&mut d, version)?;
+        if version > 0 {
+            return Ok(BobFeeEstimated {
+                local_params,
+                required_funding_amount,
+                remote_params,
+                wallet,
+                sat_per_kvb: StrictDecode::strict_decode(&mut d)?,
+                funding_outputs: StrictDecode::strict_decode(&mut d)?,
+            });
+        }
+        // version 0 checkpoints predate the merging of several payments, the
+        // fee rate is recovered from the lock fee added to the swap amount
+        let arbitrating_amount = match &wallet {
+            Wallet::Bob(BobState { deal, .. }) => deal.parameters.arbitrating_amount,
+            Wallet::Alice(_) => {
+                return Err(strict_encoding::Error::DataIntegrityError(
+                    "Bob Fee Estimated state holds Alice's wallet".to_string(),
+                ))
+            }
+        };
+        let lock_fee = required_funding_amount
+            .checked_sub(arbitrating_amount)
+            .unwrap_or(bitcoin::Amount::ZERO);
+        Ok(BobFeeEstimated {
+            local_params,
+            required_funding_amount,
+            remote_params,
+            wallet,
+            sat_per_kvb: lock_fee_rate(lock_fee),
+            funding_outputs: vec![],
+        })
+    }
 }
 
 #[derive(Clone, Debug, StrictEncode, StrictDecode)]
@@ -417,6 +473,153 @@ pub struct AliceCanceled {
     wallet: Wallet,
 }
 
+/// Decode the fields of a swap state in order, its wallet with the version of
+/// the checkpoint
+macro_rules! impl_checkpoint_decode {
+    ($name:ident { $($field:ident),* }) => {
+        impl CheckpointDecode for $name {
+            fn checkpoint_decode<D: io::Read>(
+                mut d: D,
+                version: u16,
+            ) -> Result<Self, strict_encoding::Error> {
+                Ok($name {
+                    $($field: checkpoint_field!($field, d, version)),*
+                })
+            }
+        }
+    };
+}
+
+macro_rules! checkpoint_field {
+    (wallet, $d:ident, $version:ident) => {
+        Wallet::checkpoint_decode(&mut $d, $version)?
+    };
+    ($field:ident, $d:ident, $version:ident) => {
+        StrictDecode::strict_decode(&mut $d)?
+    };
+}
+
+impl_checkpoint_decode!(BobInitMaker {
+    local_params,
+    remote_commit,
+    wallet
+});
+impl_checkpoint_decode!(AliceInitMaker {
+    local_params,
+    remote_commit,
+    wallet
+});
+impl_checkpoint_decode!(BobInitTaker {
+    local_params,
+    wallet
+});
+impl_checkpoint_decode!(AliceInitTaker {
+    local_params,
+    wallet
+});
+impl_checkpoint_decode!(BobTakerMakerCommit {
+    local_params,
+    remote_commit,
+    wallet
+});
+impl_checkpoint_decode!(AliceTakerMakerCommit {
+    local_params,
+    remote_commit,
+    wallet
+});
+impl_checkpoint_decode!(BobReveal {
+    local_params,
+    remote_params,
+    wallet
+});
+impl_checkpoint_decode!(AliceReveal {
+    local_params,
+    remote_params,
+    wallet
+});
+impl_checkpoint_decode!(BobFunded {
+    local_params,
+    remote_params,
+    wallet
+});
+impl_checkpoint_decode!(AliceCoreArbitratingSetup {
+    local_params,
+    remote_params,
+    wallet
+});
+impl_checkpoint_decode!(BobRefundProcedureSignatures {
+    local_params,
+    remote_params,
+    wallet,
+    buy_procedure_signature
+});
+impl_checkpoint_decode!(AliceArbitratingLockFinal {
+    wallet,
+    funding_info,
+    required_funding_amount
+});
+impl_checkpoint_decode!(BobAccordantLock {
+    local_params,
+    remote_params,
+    wallet,
+    buy_procedure_signature
+});
+impl_checkpoint_decode!(AliceAccordantLock { wallet });
+impl_checkpoint_decode!(BobAccordantLockFinal {
+    local_params,
+    remote_params,
+    wallet
+});
+impl_checkpoint_decode!(AliceCanceled { wallet });
+
+/// Mirrors the derived strict decoding, the variants are tagged by their order
+impl CheckpointDecode for SwapStateMachine {
+    fn checkpoint_decode<D: io::Read>(
+        mut d: D,
+        version: u16,
+    ) -> Result<Self, strict_encoding::Error> {
+        use SwapStateMachine::*;
+        Ok(match u8::strict_decode(&mut d)? {
+            0 => StartTaker(StrictDecode::strict_decode(&mut d)?),
+            1 => StartMaker(StrictDecode::strict_decode(&mut d)?),
+            2 => BobInitMaker(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            3 => AliceInitMaker(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            4 => BobInitTaker(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            5 => AliceInitTaker(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            6 => BobTakerMakerCommit(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            7 => AliceTakerMakerCommit(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            8 => BobReveal(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            9 => BobFeeEstimated(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            10 => BobFunded(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            11 => {
+                BobRefundProcedureSignatures(CheckpointDecode::checkpoint_decode(&mut d, version)?)
+            }
+            12 => BobAccordantLock(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            13 => BobAccordantLockFinal(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            14 => BobBuyFinal(StrictDecode::strict_decode(&mut d)?),
+            15 => BobBuySweeping,
+            16 => BobCanceled,
+            17 => BobCancelFinal,
+            18 => BobAbortAwaitingBitcoinSweep,
+            19 => AliceReveal(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            20 => AliceCoreArbitratingSetup(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            21 => AliceArbitratingLockFinal(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            22 => AliceAccordantLock(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            23 => AliceBuyProcedureSignature,
+            24 => AliceCanceled(CheckpointDecode::checkpoint_decode(&mut d, version)?),
+            25 => AliceRefund(StrictDecode::strict_decode(&mut d)?),
+            26 => AliceRefundSweeping,
+            27 => SwapEnd(StrictDecode::strict_decode(&mut d)?),
+            tag => {
+                return Err(strict_encoding::Error::EnumValueNotKnown(
+                    "SwapStateMachine",
+                    tag as usize,
+                ))
+            }
+        })
+    }
+}
+
 impl StateMachine<Runtime, Error> for SwapStateMachine {
     fn next(self, event: Event, runtime: &mut Runtime) -> Result<Option<Self>, Error> {
         runtime.log_debug(format!(
@@ -543,6 +746,49 @@ impl StateMachine<Runtime, Error> for SwapStateMachine {
     }
 }
 
+impl SwapStateMachine {
+    /// Whether the swap may still exchange messages with the counterparty in
+    /// this state. Once past the point where the last protocol message is
+    /// sent or received, or on the cancel and abort paths, the swap completes
+    /// on-chain only.
+    pub fn needs_counterparty(&self) -> bool {
+        !matches!(
+            self,
+            SwapStateMachine::BobAccordantLockFinal(_)
+                | SwapStateMachine::BobBuyFinal(_)
+                | SwapStateMachine::BobBuySweeping
+                | SwapStateMachine::BobCanceled
+                | SwapStateMachine::BobCancelFinal
+                | SwapStateMachine::BobAbortAwaitingBitcoinSweep
+                | SwapStateMachine::AliceBuyProcedureSignature
+                | SwapStateMachine::AliceCanceled(_)
+                | SwapStateMachine::AliceRefund(_)
+                | SwapStateMachine::AliceRefundSweeping
+                | SwapStateMachine::SwapEnd(_)
+        )
+    }
+
+    /// Funding address and amount still to be sent to it while Bob awaits
+    /// the funding of the swap. Once some payments are received, the amount
+    /// covers the fee for merging them with one more payment.
+    pub fn funding_request(&self) -> Option<(bitcoin::Address, bitcoin::Amount)> {
+        if let SwapStateMachine::BobFeeEstimated(BobFeeEstimated {
+            wallet,
+            required_funding_amount,
+            sat_per_kvb,
+            funding_outputs,
+            ..
+        }) = self
+        {
+            let address = wallet.funding_address()?;
+            missing_funding(*required_funding_amount, *sat_per_kvb, funding_outputs)
+                .map(|missing| (address, missing))
+        } else {
+            None
+        }
+    }
+}
+
 pub struct SwapStateMachineExecutor {}
 impl SwapStateMachineExecutor {
     pub fn execute(
@@ -597,6 +843,7 @@ fn attempt_transition_to_init_taker(
             ref key_manager,
             ref target_bitcoin_address,
             target_monero_address,
+            ref target_monero_split,
         })) => {
             if ServiceId::Swap(swap_id) != runtime.identity {
                 runtime.log_error(format!(
@@ -624,6 +871,7 @@ fn attempt_transition_to_init_taker(
                 runtime.deal.clone(),
                 target_bitcoin_address.clone(),
                 target_monero_address,
+                target_monero_split.clone(),
                 key_manager.0.clone(),
                 swap_id,
             )?;
@@ -675,6 +923,7 @@ fn attempt_transition_to_init_maker(
             swap_id,
             target_bitcoin_address,
             target_monero_address,
+            target_monero_split,
             commit: remote_commit,
         })) => {
             // start watching block height changes
@@ -689,6 +938,7 @@ fn attempt_transition_to_init_maker(
                 runtime.deal.clone(),
                 target_bitcoin_address,
                 target_monero_address,
+                target_monero_split,
                 key_manager.0,
                 swap_id,
                 remote_commit.clone(),
@@ -749,6 +999,14 @@ fn try_bob_init_taker_to_bob_taker_maker_commit(
             // just cancel the swap, no additional logic required
             handle_bob_abort_swap(event, runtime, wallet)
         }
+        BusMsg::P2p(PeerMsg::DealRejected(DealRejected { ref reason, .. })) => {
+            runtime.log_error(format!(
+                "Taken deal {} was rejected: {}, aborting this swap.",
+                runtime.deal.id().swap_id(),
+                reason
+            ));
+            handle_bob_abort_swap(event, runtime, wallet)
+        }
         BusMsg::P2p(PeerMsg::MakerCommit(remote_commit)) => {
             runtime.log_debug("Received remote maker commitment");
             let reveal =
@@ -787,6 +1045,14 @@ fn try_alice_init_taker_to_alice_taker_maker_commit(
             // just cancel the swap, no additional logic required
             handle_abort_swap(event, runtime)
         }
+        BusMsg::P2p(PeerMsg::DealRejected(DealRejected { ref reason, .. })) => {
+            runtime.log_error(format!(
+                "Taken deal {} was rejected: {}, aborting this swap.",
+                runtime.deal.id().swap_id(),
+                reason
+            ));
+            handle_abort_swap(event, runtime)
+        }
         BusMsg::P2p(PeerMsg::MakerCommit(remote_commit)) => {
             runtime.log_debug("Received remote maker commitment");
             let reveal =
@@ -867,7 +1133,7 @@ fn try_bob_reveal_to_bob_fee_estimated(
     let BobReveal {
         local_params,
         remote_params,
-        wallet,
+        mut wallet,
     } = bob_reveal;
     match &event.request {
         BusMsg::Sync(SyncMsg::Event(SyncEvent::FeeEstimation(FeeEstimation {
@@ -879,14 +1145,16 @@ fn try_bob_reveal_to_bob_fee_estimated(
             ..
         }))) => {
             // FIXME handle low priority as well
-            runtime.log_info(format!("Fee: {} sat/kvB", high_priority_sats_per_kvbyte));
+            let sat_per_kvb = *high_priority_sats_per_kvbyte;
+            runtime.log_info(format!("Fee: {} sat/kvB", sat_per_kvb));
             runtime.log_debug("Sending funding info to farcasterd");
             let funding_address = wallet
                 .funding_address()
                 .expect("Am Bob, so have funding address");
             let required_funding_amount = runtime.ask_bob_to_fund(
-                *high_priority_sats_per_kvbyte,
+                sat_per_kvb,
                 funding_address.clone(),
+                &[],
                 event.endpoints,
             )?;
 
@@ -894,6 +1162,11 @@ fn try_bob_reveal_to_bob_fee_estimated(
                 "Watch arbitrating funding {}",
                 funding_address.clone()
             ));
+            // keep the sweep of the funding address at hand for late funding
+            runtime.syncer_state.funding_sweep = Some(
+                wallet
+                    .process_get_sweep_bitcoin_address(funding_address.clone(), runtime.swap_id)?,
+            );
             let watch_addr_task = runtime
                 .syncer_state
                 .watch_addr_btc(funding_address.clone(), TxLabel::Funding);
@@ -906,6 +1179,8 @@ fn try_bob_reveal_to_bob_fee_estimated(
                 remote_params,
                 wallet,
                 required_funding_amount,
+                sat_per_kvb,
+                funding_outputs: vec![],
             })))
         }
         BusMsg::Ctl(CtlMsg::AbortSwap) => handle_bob_abort_swap(event, runtime, wallet),
@@ -923,11 +1198,12 @@ fn try_bob_fee_estimated_to_bob_funded(
         remote_params,
         mut wallet,
         required_funding_amount,
+        sat_per_kvb,
+        mut funding_outputs,
     } = bob_reveal;
     match &event.request {
         BusMsg::Sync(SyncMsg::Event(SyncEvent::AddressTransaction(AddressTransaction {
             id,
-            amount,
             tx,
             ..
         }))) if runtime.syncer_state.tasks.watched_addrs.get(&id) == Some(&TxLabel::Funding)
@@ -941,23 +1217,88 @@ fn try_bob_fee_estimated_to_bob_funded(
                 &tx.txid().tx_hash()
             ));
             log_tx_seen(runtime.swap_id, &TxLabel::Funding, &tx.txid());
-            runtime.syncer_state.awaiting_funding = false;
-            // If the bitcoin amount does not match the expected funding amount, abort the swap
-            let amount = bitcoin::Amount::from_sat(*amount);
-            // Abort the swap in case of bad funding amount
-            if amount != required_funding_amount {
-                // incorrect funding, start aborting procedure
-                let msg = format!("Incorrect amount funded. Required: {}, Funded: {}. Do not fund this swap anymore, will abort and atttempt to sweep the Bitcoin to the provided address.", amount, required_funding_amount);
-                runtime.log_error(&msg);
-                runtime.report_progress_message(event.endpoints, msg)?;
-                return handle_bob_abort_swap(event, runtime, wallet);
+            runtime.syncer_state.funding_txids.insert(tx.txid());
+            let funding_address = wallet
+                .funding_address()
+                .expect("Am Bob, so have funding address");
+            let txid = tx.txid();
+            for (vout, output) in tx.output.iter().enumerate() {
+                let outpoint = bitcoin::OutPoint::new(txid, vout as u32);
+                if output.script_pubkey == funding_address.script_pubkey()
+                    && !funding_outputs.iter().any(|o| o.outpoint == outpoint)
+                {
+                    funding_outputs.push(FundingOutput {
+                        outpoint,
+                        amount: bitcoin::Amount::from_sat(output.value),
+                    });
+                }
+            }
+            let received = funding_outputs
+                .iter()
+                .fold(bitcoin::Amount::ZERO, |acc, output| acc + output.amount);
+
+            // A single payment of the exact amount is spent as is by the lock
+            // transaction, otherwise all the payments are first merged into a
+            // single output and the excess is refunded
+            let tx = if funding_outputs.len() == 1 && received == required_funding_amount {
+                tx
             } else {
-                // funding completed, amount is correct
-                event.send_ctl_service(
-                    ServiceId::Farcasterd,
-                    CtlMsg::FundingCompleted(Blockchain::Bitcoin),
+                let required =
+                    required_funding_amount + consolidation_fee(sat_per_kvb, funding_outputs.len());
+                if received < required {
+                    let msg = format!(
+                        "Received {} on the funding address, {} is still missing (includes the fee for merging {} payments)",
+                        received,
+                        required - received,
+                        funding_outputs.len(),
+                    );
+                    runtime.log_info(&msg);
+                    runtime.report_progress_message(event.endpoints, msg)?;
+                    // the funding info now asks for the missing amount
+                    runtime.ask_bob_to_fund(
+                        sat_per_kvb,
+                        funding_address,
+                        &funding_outputs,
+                        event.endpoints,
+                    )?;
+                    return Ok(Some(SwapStateMachine::BobFeeEstimated(BobFeeEstimated {
+                        local_params,
+                        remote_params,
+                        wallet,
+                        required_funding_amount,
+                        sat_per_kvb,
+                        funding_outputs,
+                    })));
+                }
+                let ConsolidatedFunding { tx, refunded } = wallet.consolidate_funding(
+                    &funding_outputs,
+                    required_funding_amount,
+                    sat_per_kvb,
+                    runtime.swap_id,
                 )?;
-            }
+                if let Some(refunded) = refunded {
+                    runtime.syncer_state.overfunded = true;
+                    let msg = format!(
+                        "Swap overfunded by {}, refunding it to the target bitcoin address",
+                        refunded
+                    );
+                    runtime.log_warn(&msg);
+                    runtime.report_progress_message(event.endpoints, msg)?;
+                }
+                runtime.log_info(format!(
+                    "Merging {} funding payments into a single funding output",
+                    funding_outputs.len()
+                ));
+                runtime.syncer_state.funding_txids.insert(tx.txid());
+                runtime.broadcast(tx.clone(), TxLabel::Funding, event.endpoints)?;
+                tx
+            };
+            runtime.syncer_state.awaiting_funding = false;
+            // funding completed, amount is correct
+            event.send_ctl_service(
+                ServiceId::Farcasterd,
+                CtlMsg::FundingCompleted(Blockchain::Bitcoin),
+            )?;
 
             // process tx with wallet
             wallet.process_funding_tx(Tx::Funding(tx), runtime.swap_id)?;
@@ -1248,7 +1589,7 @@ fn try_bob_accordant_lock_final_to_bob_buy_final(
             } else {
                 return Ok(None);
             };
-            runtime.log_monero_maturity(sweep_xmr.destination_address);
+            runtime.log_monero_maturity(&sweep_xmr);
             Ok(Some(SwapStateMachine::BobBuyFinal(sweep_address)))
         }
         _ => handle_bob_swap_interrupt_after_lock(event, runtime),
@@ -1375,7 +1716,7 @@ fn try_awaiting_sweep_to_swap_end(
                 CtlMsg::FundingCanceled(Blockchain::Bitcoin),
             )?;
             runtime.log_info("Aborted swap.");
-            Ok(Some(SwapStateMachine::SwapEnd(Outcome::FailureAbort)))
+            Ok(Some(SwapStateMachine::SwapEnd(abort_outcome(runtime))))
         }
 
         BusMsg::Sync(SyncMsg::Event(SyncEvent::SweepSuccess(SweepSuccess { id, .. })))
@@ -1386,7 +1727,7 @@ fn try_awaiting_sweep_to_swap_end(
                 CtlMsg::FundingCanceled(Blockchain::Bitcoin),
             )?;
             runtime.log_info("Aborted swap.");
-            Ok(Some(SwapStateMachine::SwapEnd(Outcome::FailureAbort)))
+            Ok(Some(SwapStateMachine::SwapEnd(abort_outcome(runtime))))
         }
         _ => Ok(None),
     }
@@ -1579,6 +1920,7 @@ fn try_alice_arbitrating_lock_final_to_alice_accordant_lock(
             && runtime.syncer_state.awaiting_funding =>
         {
             runtime.log_warn("Alice, the swap may be cancelled soon. Do not fund anymore");
+            runtime.set_outcome_reason_if_unknown(OutcomeReason::FundingCanceled);
             event.complete_ctl_service(
                 ServiceId::Farcasterd,
                 CtlMsg::FundingCanceled(Blockchain::Monero),
@@ -1907,7 +2249,7 @@ fn try_alice_canceled_to_alice_refund_or_alice_punish(
                 } else {
                     return Ok(None);
                 };
-                runtime.log_monero_maturity(sweep_xmr.destination_address);
+                runtime.log_monero_maturity(&sweep_xmr);
                 runtime.log_warn(
                     "Peerd might crash, just ignore it, counterparty closed \
                         connection but you don't need it anymore!",
@@ -2193,6 +2535,7 @@ fn handle_alice_swap_interrupt_after_lock(
         ))) if runtime.syncer_state.tasks.watched_txs.get(&id) == Some(&TxLabel::Cancel) => {
             runtime.log_warn("This swap was canceled. Do not fund anymore.");
             if runtime.syncer_state.awaiting_funding {
+                runtime.set_outcome_reason_if_unknown(OutcomeReason::FundingCanceled);
                 event.send_ctl_service(
                     ServiceId::Farcasterd,
                     CtlMsg::FundingCanceled(Blockchain::Monero),
@@ -2238,9 +2581,33 @@ fn handle_abort_swap(
     event: Event,
     runtime: &mut Runtime,
 ) -> Result<Option<SwapStateMachine>, Error> {
-    event.complete_client_info(InfoMsg::String("Aborted swap".to_string()))?;
+    set_abort_reason(&event, runtime);
+    // a timeout abort is triggered by swapd itself, there is no client to reply to
+    if event.source != runtime.identity() {
+        event.complete_client_info(InfoMsg::String("Aborted swap".to_string()))?;
+    }
     runtime.log_info("Aborted swap.");
-    Ok(Some(SwapStateMachine::SwapEnd(Outcome::FailureAbort)))
+    Ok(Some(SwapStateMachine::SwapEnd(abort_outcome(runtime))))
+}
+
+fn set_abort_reason(event: &Event, runtime: &mut Runtime) {
+    match event.request {
+        BusMsg::P2p(PeerMsg::DealNotFound(_) | PeerMsg::DealRejected(_)) => {
+            runtime.outcome_reason = Some(OutcomeReason::CounterpartyAbort);
+        }
+        BusMsg::Ctl(CtlMsg::AbortSwap) if event.source != runtime.identity() => {
+            runtime.outcome_reason = Some(OutcomeReason::UserAbort);
+        }
+        _ => {}
+    }
+}
+
+fn abort_outcome(runtime: &Runtime) -> Outcome {
+    if runtime.outcome_reason == Some(OutcomeReason::Timeout) {
+        Outcome::FailureTimeout
+    } else {
+        Outcome::FailureAbort
+    }
 }
 
 fn handle_abort_impossible(
@@ -2261,11 +2628,46 @@ fn handle_bob_abort_swap(
     runtime: &mut Runtime,
     mut wallet: Wallet,
 ) -> Result<Option<SwapStateMachine>, Error> {
+    set_abort_reason(&event, runtime);
+    // payments received from now on are late funding
+    runtime.syncer_state.awaiting_funding = false;
     let funding_address = wallet
         .funding_address()
         .expect("Am Bob, so have funding address");
     let sweep_btc =
         wallet.process_get_sweep_bitcoin_address(funding_address, runtime.swap_id.clone())?;
+    if runtime.batch_sweep {
+        // the funding is swept with the next batch, the swap can end now. An
+        // address never paid to is not queued, it would be retried forever.
+        let reply = if runtime.syncer_state.funding_txids.is_empty() {
+            runtime.log_info("Nothing received on the funding address, there is nothing to sweep");
+            "Aborting swap, nothing to sweep."
+        } else {
+            runtime.log_info(format!(
+                "Queueing the sweep of source (funding) address: {} to destination address: {} for the next batch",
+                sweep_btc.source_address.addr(),
+                sweep_btc.destination_address.addr()
+            ));
+            event.send_ctl_service(
+                ServiceId::Farcasterd,
+                CtlMsg::QueueSweep(QueuedSweep {
+                    swap_id: runtime.swap_id,
+                    source_address: sweep_btc.source_address,
+                    destination_address: sweep_btc.destination_address,
+                }),
+            )?;
+            "Aborting swap, funds will be swept with the next batch."
+        };
+        event.send_client_ctl(
+            ServiceId::Farcasterd,
+            CtlMsg::FundingCanceled(Blockchain::Bitcoin),
+        )?;
+        if event.source != runtime.identity() {
+            event.complete_client_info(InfoMsg::String(reply.to_string()))?;
+        }
+        runtime.log_info("Aborted swap.");
+        return Ok(Some(SwapStateMachine::SwapEnd(abort_outcome(runtime))));
+    }
     runtime.log_info(format!(
         "Sweeping source (funding) address: {} to destination address: {}",
         sweep_btc.source_address.addr(),
@@ -2273,8 +2675,287 @@ fn handle_bob_abort_swap(
     ));
     let task = runtime.syncer_state.sweep_btc(sweep_btc, false);
     event.send_sync_service(runtime.syncer_state.bitcoin_syncer(), SyncMsg::Task(task))?;
-    event.complete_client_info(InfoMsg::String(
-        "Aborting swap, checking if funds can be sweeped.".to_string(),
-    ))?;
+    if event.source != runtime.identity() {
+        event.complete_client_info(InfoMsg::String(
+            "Aborting swap, checking if funds can be sweeped.".to_string(),
+        ))?;
+    }
     Ok(Some(SwapStateMachine::BobAbortAwaitingBitcoinSweep))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::secp256k1::Secp256k1;
+    use farcaster_core::{
+        bitcoin::BitcoinSegwitV0,
+        blockchain::FeePriority,
+        consensus,
+        monero::Monero,
+        role::TradeRole,
+        swap::btcxmr::{Alice, Deal, KeyManager},
+        Uuid,
+    };
+    use strict_encoding::{strict_deserialize, strict_serialize};
+
+    use super::*;
+    use crate::bus::StateTimestamp;
+    use crate::config::StateTimeout;
+    use crate::swapd::{
+        runtime::{stalled_for, CheckpointSwapd, StateEntered},
+        syncer_client::{SyncerState, SyncerTasks},
+        wallet::AliceState,
+        TemporalSafety,
+    };
+    use crate::syncerd::{SweepAddressAddendum, SweepDestination, SweepMoneroAddress};
+
+    fn alice_core_arbitrating_setup() -> SwapStateMachine {
+        let deal = Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap();
+        let secret = bitcoin::PrivateKey::from_slice(&[1; 32], bitcoin::Network::Testnet).unwrap();
+        let target_bitcoin_address = bitcoin::Address::p2wpkh(
+            &bitcoin::PublicKey::from_private_key(&Secp256k1::new(), &secret),
+            bitcoin::Network::Testnet,
+        )
+        .unwrap();
+        let monero_key =
+            monero::PublicKey::from_private_key(&monero::PrivateKey::from_slice(&[1; 32]).unwrap());
+        let target_monero_address =
+            monero::Address::standard(monero::Network::Stagenet, monero_key, monero_key);
+        let mut key_manager = KeyManager::new([2; 32], 1).unwrap();
+        let alice = Alice::new(
+            BitcoinSegwitV0::new(),
+            Monero,
+            target_bitcoin_address.clone(),
+            FeePriority::Low,
+        );
+        let parameters = alice.generate_parameters(&mut key_manager, &deal).unwrap();
+        let wallet = Wallet::Alice(AliceState::new(
+            alice,
+            TradeRole::Maker,
+            parameters.clone(),
+            key_manager,
+            deal,
+            None,
+            target_bitcoin_address,
+            target_monero_address,
+            vec![],
+        ));
+        SwapStateMachine::AliceCoreArbitratingSetup(AliceCoreArbitratingSetup {
+            local_params: Params::Alice(parameters.clone()),
+            remote_params: Params::Bob(parameters),
+            wallet,
+        })
+    }
+
+    fn temporal_safety() -> TemporalSafety {
+        TemporalSafety {
+            cancel_timelock: 4,
+            punish_timelock: 5,
+            race_thr: 3,
+            btc_finality_thr: 1,
+            xmr_finality_thr: 1,
+            sweep_monero_thr: 10,
+        }
+    }
+
+    fn syncer_state() -> SyncerState {
+        let network = farcaster_core::blockchain::Network::Testnet;
+        SyncerState {
+            swap_id: Uuid::new().into(),
+            tasks: SyncerTasks {
+                counter: 0,
+                watched_addrs: none!(),
+                watched_txs: none!(),
+                retrieving_txs: none!(),
+                sweeping_addr: none!(),
+                late_funding_sweeps: none!(),
+                broadcasting_txs: none!(),
+                txids: none!(),
+                final_txs: none!(),
+                tasks: none!(),
+            },
+            monero_height: 0,
+            bitcoin_height: 0,
+            confirmation_bound: 50000,
+            lock_tx_confs: None,
+            cancel_tx_confs: None,
+            buy_tx_confs: None,
+            network,
+            bitcoin_syncer: ServiceId::Syncer(Blockchain::Bitcoin, network),
+            monero_syncer: ServiceId::Syncer(Blockchain::Monero, network),
+            awaiting_funding: false,
+            overfunded: false,
+            funding_txids: none!(),
+            funding_sweep: None,
+            late_funding_pending: false,
+            xmr_addr_addendum: None,
+            confirmations: none!(),
+        }
+    }
+
+    #[test]
+    fn decode_unversioned_checkpoint() {
+        let state = alice_core_arbitrating_setup();
+        let deal = match &state {
+            SwapStateMachine::AliceCoreArbitratingSetup(AliceCoreArbitratingSetup {
+                wallet: Wallet::Alice(AliceState { deal, .. }),
+                ..
+            }) => deal.clone(),
+            _ => unreachable!(),
+        };
+        let temporal_safety = temporal_safety();
+
+        // the baseline encoding: no version, the wallet ends with the monero
+        // target address, the checkpoint with the monero address creation height
+        let state_bytes = strict_serialize(&state).unwrap();
+        let split =
+            consensus::serialize(&strict_serialize(&Vec::<SweepDestination>::new()).unwrap());
+        assert!(state_bytes.ends_with(&split));
+        let mut encoded = state_bytes[..state_bytes.len() - split.len()].to_vec();
+        encoded.extend(strict_serialize(&Option::<PeerMsg>::None).unwrap());
+        encoded.extend(strict_serialize(&Option::<ServiceId>::None).unwrap());
+        encoded
+            .extend(strict_serialize(&Option::<crate::syncerd::XmrAddressAddendum>::None).unwrap());
+        encoded.extend(strict_serialize(&temporal_safety).unwrap());
+        encoded.extend(strict_serialize(&Vec::<(TxLabel, bitcoin::Transaction)>::new()).unwrap());
+        encoded.extend(strict_serialize(&Vec::<(TxLabel, bitcoin::Txid)>::new()).unwrap());
+        encoded.extend(strict_serialize(&Vec::<bitcoin::Transaction>::new()).unwrap());
+        encoded.extend(strict_serialize(&TradeRole::Maker).unwrap());
+        encoded.extend(strict_serialize(&Option::<internet2::addr::NodeId>::None).unwrap());
+        encoded.extend(strict_serialize(&deal).unwrap());
+        encoded.extend(strict_serialize(&Some(7u64)).unwrap());
+
+        let checkpoint: CheckpointSwapd = strict_deserialize(&encoded).unwrap();
+        assert_eq!(strict_serialize(&checkpoint.state).unwrap(), state_bytes);
+        assert_eq!(checkpoint.monero_address_creation_height, Some(7));
+        assert!(checkpoint.funding_txids.is_empty());
+        assert!(!checkpoint.late_funding_pending);
+        assert!(checkpoint.timeline.is_empty());
+        assert_eq!(checkpoint.state_entered, None);
+
+        // and the current encoding round-trips
+        let decoded: CheckpointSwapd =
+            strict_deserialize(strict_serialize(&checkpoint).unwrap()).unwrap();
+        assert_eq!(strict_serialize(&decoded.state).unwrap(), state_bytes);
+
+        // the versioned decoding reads the tags of the derived encoding
+        for state in [
+            SwapStateMachine::StartTaker(SwapRole::Bob),
+            SwapStateMachine::BobBuySweeping,
+            SwapStateMachine::AliceRefundSweeping,
+            SwapStateMachine::SwapEnd(Outcome::FailureAbort),
+            state,
+        ] {
+            let encoded = strict_serialize(&state).unwrap();
+            let decoded =
+                SwapStateMachine::checkpoint_decode(&encoded[..], CHECKPOINT_VERSION).unwrap();
+            assert_eq!(strict_serialize(&decoded).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn sweep_resumed_on_restore() {
+        let mut sweeping = syncer_state();
+        let key = monero::PrivateKey::from_slice(&[1; 32]).unwrap();
+        let public_key = monero::PublicKey::from_private_key(&key);
+        let sweep_xmr = SweepMoneroAddress {
+            source_spend_key: key,
+            source_view_key: key,
+            destination_address: monero::Address::standard(
+                monero::Network::Stagenet,
+                public_key,
+                public_key,
+            ),
+            split: vec![],
+            minimum_balance: monero::Amount::from_pico(1),
+            from_height: Some(100),
+        };
+        assert!(sweeping.pending_sweep().is_none());
+        sweeping.estimate_fee_btc();
+        let task = sweeping.sweep_xmr(sweep_xmr.clone(), true);
+        let sweep = sweeping.pending_sweep().unwrap();
+        assert_eq!(Task::SweepAddress(sweep.clone()), task);
+
+        // the sweep is checkpointed with the sweeping state
+        let state = alice_core_arbitrating_setup();
+        let deal = match &state {
+            SwapStateMachine::AliceCoreArbitratingSetup(AliceCoreArbitratingSetup {
+                wallet: Wallet::Alice(AliceState { deal, .. }),
+                ..
+            }) => deal.clone(),
+            _ => unreachable!(),
+        };
+        let checkpoint = CheckpointSwapd {
+            state: SwapStateMachine::BobBuySweeping,
+            pending_msg: None,
+            enquirer: None,
+            xmr_addr_addendum: None,
+            temporal_safety: temporal_safety(),
+            txs: vec![],
+            txids: vec![],
+            pending_broadcasts: vec![],
+            local_trade_role: TradeRole::Taker,
+            connected_counterparty_node_id: None,
+            deal,
+            monero_address_creation_height: None,
+            funding_txids: vec![],
+            funding_sweep: None,
+            late_funding_pending: false,
+            pending_sweep: Some(sweep.clone()),
+            timeline: vec![StateTimestamp {
+                state: SwapStateMachine::BobBuySweeping.to_string(),
+                timestamp: 1_600_000_000,
+            }],
+            state_entered: Some((1_600_000_000, 2_000)),
+        };
+        let checkpoint: CheckpointSwapd =
+            strict_deserialize(strict_serialize(&checkpoint).unwrap()).unwrap();
+        assert!(matches!(checkpoint.state, SwapStateMachine::BobBuySweeping));
+        assert_eq!(checkpoint.pending_sweep, Some(sweep.clone()));
+        assert_eq!(checkpoint.timeline.len(), 1);
+        assert_eq!(checkpoint.timeline[0].timestamp, 1_600_000_000);
+        assert_eq!(checkpoint.state_entered, Some((1_600_000_000, 2_000)));
+
+        // and sent again by the restored swap under its own task id
+        let mut restored = syncer_state();
+        restored.estimate_fee_btc();
+        let resumed = restored.resume_sweep(checkpoint.pending_sweep.unwrap());
+        assert_eq!(restored.tasks.sweeping_addr, Some(resumed.id));
+        assert_eq!(restored.pending_sweep(), Some(resumed.clone()));
+        assert_eq!(resumed.addendum, SweepAddressAddendum::Monero(sweep_xmr));
+        assert_eq!(resumed.retry, sweep.retry);
+    }
+
+    #[test]
+    fn pre_lock_timeout() {
+        let timeout = StateTimeout {
+            seconds: Some(600),
+            blocks: Some(3),
+        };
+        assert_eq!(stalled_for(timeout, 599, 2), None);
+        assert_eq!(
+            stalled_for(timeout, 600, 0),
+            Some("600 seconds".to_string())
+        );
+        assert_eq!(stalled_for(timeout, 10, 3), Some("3 blocks".to_string()));
+        let seconds_only = StateTimeout {
+            seconds: Some(600),
+            blocks: None,
+        };
+        assert_eq!(stalled_for(seconds_only, 10, 100), None);
+        assert_eq!(
+            stalled_for(StateTimeout::default(), u64::MAX, u64::MAX),
+            None
+        );
+
+        // the time the state was entered survives the checkpoint unchanged
+        let entered = StateEntered::from((1_600_000_000, 2_000));
+        assert_eq!(<(u64, u64)>::from(entered), (1_600_000_000, 2_000));
+        let elapsed = std::time::SystemTime::now()
+            .duration_since(entered.time)
+            .unwrap()
+            .as_secs();
+        assert!(stalled_for(seconds_only, elapsed, 0).is_some());
+    }
+}
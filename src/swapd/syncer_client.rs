@@ -34,6 +34,8 @@ pub struct SyncerTasks {
     pub retrieving_txs: HashMap<TaskId, (TxLabel, Task)>,
     pub broadcasting_txs: HashSet<TaskId>,
     pub sweeping_addr: Option<TaskId>,
+    // sweeps of late funding, tracked apart from the swap's own sweep
+    pub late_funding_sweeps: HashSet<TaskId>,
     // external address: needed to subscribe for buy (bob) or refund (alice) address_txs
     pub txids: HashMap<TxLabel, Txid>,
     pub tasks: HashMap<TaskId, Task>,
@@ -61,6 +63,14 @@ pub struct SyncerState {
     pub xmr_addr_addendum: Option<XmrAddressAddendum>,
     pub confirmations: HashMap<TxLabel, Option<u32>>,
     pub awaiting_funding: bool,
+    pub overfunded: bool,
+    // transactions funding the swap, any other payment to the funding address
+    // once funding completed is late funding
+    pub funding_txids: HashSet<Txid>,
+    pub funding_sweep: Option<SweepBitcoinAddress>,
+    // late funding waits for the lock transaction to spend the funding output
+    // before sweeping the funding address
+    pub late_funding_pending: bool,
 }
 impl SyncerState {
     pub fn task_lifetime(&self, blockchain: Blockchain) -> u64 {
@@ -326,6 +336,52 @@ impl SyncerState {
         task
     }
 
+    /// Sweep late payments to the funding address, returns None if Bob's
+    /// funding address is unknown.
+    pub fn sweep_late_funding_btc(&mut self) -> Option<Task> {
+        let addendum = self.funding_sweep.clone()?;
+        let id = self.tasks.new_taskid();
+        self.tasks.late_funding_sweeps.insert(id);
+        self.late_funding_pending = false;
+        let lifetime = self.task_lifetime(Blockchain::Bitcoin);
+        let sweep_task = SweepAddress {
+            id,
+            lifetime,
+            addendum: SweepAddressAddendum::Bitcoin(addendum),
+            retry: true,
+        };
+        let task = Task::SweepAddress(sweep_task);
+        self.tasks.tasks.insert(id, task.clone());
+        Some(task)
+    }
+
+    /// The swap's sweep sent to the syncer, if not completed yet
+    pub fn pending_sweep(&self) -> Option<SweepAddress> {
+        match self.tasks.tasks.get(&self.tasks.sweeping_addr?)? {
+            Task::SweepAddress(sweep) => Some(sweep.clone()),
+            _ => None,
+        }
+    }
+
+    /// Register the sweep of a restored swap again, under a new task id
+    pub fn resume_sweep(&mut self, sweep: SweepAddress) -> SweepAddress {
+        let blockchain = match sweep.addendum {
+            SweepAddressAddendum::Monero(_) => Blockchain::Monero,
+            _ => Blockchain::Bitcoin,
+        };
+        let id = self.tasks.new_taskid();
+        self.tasks.sweeping_addr = Some(id);
+        let sweep = SweepAddress {
+            id,
+            lifetime: self.task_lifetime(blockchain),
+            ..sweep
+        };
+        self.tasks
+            .tasks
+            .insert(id, Task::SweepAddress(sweep.clone()));
+        sweep
+    }
+
     pub fn sweep_xmr(&mut self, addendum: SweepMoneroAddress, retry: bool) -> Task {
         let id = self.tasks.new_taskid();
         self.tasks.sweeping_addr = Some(id);
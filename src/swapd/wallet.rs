@@ -47,7 +47,12 @@ use crate::{
         p2p::{Commit, Reveal},
         AddressSecretKey, BitcoinSecretKeyInfo, BusMsg, MoneroSecretKeyInfo, ServiceBus,
     },
-    syncerd::{SweepBitcoinAddress, SweepMoneroAddress},
+    error::SyncerError,
+    swapd::runtime::{CheckpointDecode, CHECKPOINT_VERSION},
+    syncerd::{
+        bitcoin_syncer::{p2wpkh_signed_tx_fee, sign_p2wpkh_inputs},
+        SweepBitcoinAddress, SweepDestination, SweepMoneroAddress,
+    },
     Endpoints, Error, LogStyle, ServiceId,
 };
 
@@ -69,6 +74,21 @@ pub struct HandleBuyProcedureSignatureRes {
     pub buy_tx: bitcoin::Transaction,
 }
 
+/// A payment received on Bob's funding address.
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+pub struct FundingOutput {
+    pub outpoint: bitcoin::OutPoint,
+    pub amount: bitcoin::Amount,
+}
+
+/// Transaction merging several payments received on the funding address into
+/// the single output spent by the lock transaction.
+pub struct ConsolidatedFunding {
+    pub tx: bitcoin::Transaction,
+    /// Overfunded amount sent back to Bob's target address, if above dust
+    pub refunded: Option<bitcoin::Amount>,
+}
+
 #[derive(Clone, Display, Debug, StrictEncode, StrictDecode)]
 pub enum Wallet {
     #[display("Alice's wallet")]
@@ -92,6 +112,7 @@ pub struct AliceState {
     pub adaptor_refund: Option<EncryptedSignature>,
     pub target_bitcoin_address: bitcoin::Address,
     pub target_monero_address: monero::Address,
+    pub target_monero_split: Vec<SweepDestination>,
 }
 
 impl Encodable for AliceState {
@@ -118,12 +139,24 @@ impl Encodable for AliceState {
             .target_monero_address
             .as_canonical_bytes()
             .consensus_encode(writer)?;
+        len += strict_encoding::strict_serialize(&self.target_monero_split)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+            .consensus_encode(writer)?;
         Ok(len)
     }
 }
 
 impl Decodable for AliceState {
     fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Self::consensus_decode_version(d, CHECKPOINT_VERSION)
+    }
+}
+
+impl AliceState {
+    fn consensus_decode_version<D: io::Read>(
+        d: &mut D,
+        version: u16,
+    ) -> Result<Self, consensus::Error> {
         Ok(AliceState {
             alice: Decodable::consensus_decode(d)?,
             local_trade_role: Decodable::consensus_decode(d)?,
@@ -144,12 +177,26 @@ impl Decodable for AliceState {
             target_monero_address: monero::Address::from_canonical_bytes(
                 farcaster_core::unwrap_vec_ref!(d).as_ref(),
             )?,
+            target_monero_split: decode_target_monero_split(d, version)?,
         })
     }
 }
 
 impl_strict_encoding!(AliceState);
 
+/// Version 0 checkpoints predate the Monero sweep split, the whole amount goes
+/// to the target address
+fn decode_target_monero_split<D: io::Read>(
+    d: &mut D,
+    version: u16,
+) -> Result<Vec<SweepDestination>, consensus::Error> {
+    if version == 0 {
+        return Ok(vec![]);
+    }
+    strict_encoding::strict_deserialize(farcaster_core::unwrap_vec_ref!(d))
+        .map_err(consensus::Error::new)
+}
+
 impl AliceState {
     pub fn new(
         alice: Alice,
@@ -160,6 +207,7 @@ impl AliceState {
         remote_commit: Option<CommitBobParameters>,
         target_bitcoin_address: bitcoin::Address,
         target_monero_address: monero::Address,
+        target_monero_split: Vec<SweepDestination>,
     ) -> Self {
         Self {
             alice,
@@ -175,6 +223,7 @@ impl AliceState {
             adaptor_refund: None,
             target_bitcoin_address,
             target_monero_address,
+            target_monero_split,
         }
     }
 }
@@ -194,6 +243,7 @@ pub struct BobState {
     pub adaptor_buy: Option<BuyProcedureSignature>,
     pub target_bitcoin_address: bitcoin::Address,
     pub target_monero_address: monero::Address,
+    pub target_monero_split: Vec<SweepDestination>,
 }
 
 impl Encodable for BobState {
@@ -217,12 +267,24 @@ impl Encodable for BobState {
             .target_monero_address
             .as_canonical_bytes()
             .consensus_encode(writer)?;
+        len += strict_encoding::strict_serialize(&self.target_monero_split)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+            .consensus_encode(writer)?;
         Ok(len)
     }
 }
 
 impl Decodable for BobState {
     fn consensus_decode<D: io::Read>(d: &mut D) -> Result<Self, consensus::Error> {
+        Self::consensus_decode_version(d, CHECKPOINT_VERSION)
+    }
+}
+
+impl BobState {
+    fn consensus_decode_version<D: io::Read>(
+        d: &mut D,
+        version: u16,
+    ) -> Result<Self, consensus::Error> {
         Ok(BobState {
             bob: Decodable::consensus_decode(d)?,
             local_trade_role: Decodable::consensus_decode(d)?,
@@ -241,6 +303,7 @@ impl Decodable for BobState {
             target_monero_address: monero::Address::from_canonical_bytes(
                 farcaster_core::unwrap_vec_ref!(d).as_ref(),
             )?,
+            target_monero_split: decode_target_monero_split(d, version)?,
         })
     }
 }
@@ -256,6 +319,7 @@ impl BobState {
         remote_commit: Option<CommitAliceParameters>,
         target_bitcoin_address: bitcoin::Address,
         target_monero_address: monero::Address,
+        target_monero_split: Vec<SweepDestination>,
     ) -> Self {
         Self {
             bob,
@@ -271,12 +335,35 @@ impl BobState {
             adaptor_buy: None,
             target_bitcoin_address,
             target_monero_address,
+            target_monero_split,
         }
     }
 }
 
 impl_strict_encoding!(BobState);
 
+impl CheckpointDecode for Wallet {
+    fn checkpoint_decode<D: io::Read>(
+        mut d: D,
+        version: u16,
+    ) -> Result<Self, strict_encoding::Error> {
+        let decode_err =
+            |err: consensus::Error| strict_encoding::Error::DataIntegrityError(err.to_string());
+        match u8::strict_decode(&mut d)? {
+            0 => Ok(Wallet::Alice(
+                AliceState::consensus_decode_version(&mut d, version).map_err(decode_err)?,
+            )),
+            1 => Ok(Wallet::Bob(
+                BobState::consensus_decode_version(&mut d, version).map_err(decode_err)?,
+            )),
+            tag => Err(strict_encoding::Error::EnumValueNotKnown(
+                "Wallet",
+                tag as usize,
+            )),
+        }
+    }
+}
+
 impl Wallet {
     pub fn local_params(&self) -> bus::ctl::Params {
         match self {
@@ -301,6 +388,7 @@ impl Wallet {
         deal: Deal,
         target_bitcoin_address: bitcoin::Address,
         target_monero_address: monero::Address,
+        target_monero_split: Vec<SweepDestination>,
         mut key_manager: KeyManager,
         swap_id: SwapId,
     ) -> Result<Self, Error> {
@@ -326,14 +414,17 @@ impl Wallet {
                     ServiceBus::Ctl,
                     ServiceId::Swap(swap_id),
                     ServiceId::Database,
-                    BusMsg::Ctl(CtlMsg::SetAddressSecretKey(AddressSecretKey::Bitcoin {
-                        address: funding_addr.clone(),
-                        secret_key_info: BitcoinSecretKeyInfo {
-                            swap_id: Some(swap_id),
-                            secret_key: key_manager
-                                .get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?,
+                    BusMsg::Ctl(CtlMsg::SetAddressSecretKey(
+                        AddressSecretKey::Bitcoin {
+                            address: funding_addr.clone(),
+                            secret_key_info: BitcoinSecretKeyInfo {
+                                swap_id: Some(swap_id),
+                                secret_key: key_manager
+                                    .get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?,
+                            },
                         },
-                    })),
+                        deal_parameters.network,
+                    )),
                 )?;
                 info!("{} | Loading {}", swap_id.swap_id(), "Wallet::Bob".label());
                 let local_wallet = BobState::new(
@@ -346,6 +437,7 @@ impl Wallet {
                     None,
                     target_bitcoin_address,
                     target_monero_address,
+                    target_monero_split,
                 );
                 Ok(Wallet::Bob(local_wallet))
             }
@@ -366,17 +458,20 @@ impl Wallet {
                     None,
                     target_bitcoin_address,
                     target_monero_address,
+                    target_monero_split,
                 );
                 Ok(Wallet::Alice(local_wallet))
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_maker(
         endpoints: &mut Endpoints,
         deal: Deal,
         target_bitcoin_address: bitcoin::Address,
         target_monero_address: monero::Address,
+        target_monero_split: Vec<SweepDestination>,
         mut key_manager: KeyManager,
         swap_id: SwapId,
         remote_commit: Commit,
@@ -400,14 +495,17 @@ impl Wallet {
                     ServiceBus::Ctl,
                     ServiceId::Swap(swap_id),
                     ServiceId::Database,
-                    BusMsg::Ctl(CtlMsg::SetAddressSecretKey(AddressSecretKey::Bitcoin {
-                        address: funding_addr.clone(),
-                        secret_key_info: BitcoinSecretKeyInfo {
-                            swap_id: Some(swap_id),
-                            secret_key: key_manager
-                                .get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?,
+                    BusMsg::Ctl(CtlMsg::SetAddressSecretKey(
+                        AddressSecretKey::Bitcoin {
+                            address: funding_addr.clone(),
+                            secret_key_info: BitcoinSecretKeyInfo {
+                                swap_id: Some(swap_id),
+                                secret_key: key_manager
+                                    .get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?,
+                            },
                         },
-                    })),
+                        deal_parameters.network,
+                    )),
                 )?;
                 info!("{} | Loading {}", swap_id.swap_id(), "Wallet::Bob".label());
                 let local_wallet =
@@ -422,6 +520,7 @@ impl Wallet {
                             Some(remote_commit),
                             target_bitcoin_address,
                             target_monero_address,
+                            target_monero_split,
                         )
                     } else {
                         error!("{} | Not Commit::Alice", swap_id.swap_id());
@@ -454,6 +553,7 @@ impl Wallet {
                         Some(bob_commit),
                         target_bitcoin_address,
                         target_monero_address,
+                        target_monero_split,
                     )
                 } else {
                     error!("{} | Not Commit::Bob", swap_id.swap_id());
@@ -492,6 +592,88 @@ impl Wallet {
         }
     }
 
+    /// Spend all the funding outputs into a single output of `funding_amount`
+    /// on the funding address, the excess minus the fee is refunded to Bob.
+    pub fn consolidate_funding(
+        &mut self,
+        outputs: &[FundingOutput],
+        funding_amount: bitcoin::Amount,
+        sat_per_kvb: u64,
+        swap_id: SwapId,
+    ) -> Result<ConsolidatedFunding, Error> {
+        if let Wallet::Bob(BobState {
+            key_manager,
+            funding_tx,
+            target_bitcoin_address,
+            ..
+        }) = self
+        {
+            let funding_address = funding_tx.get_address()?;
+            let received = outputs
+                .iter()
+                .fold(bitcoin::Amount::ZERO, |acc, output| acc + output.amount);
+            let fee = consolidation_fee(sat_per_kvb, outputs.len());
+            let excess = received.checked_sub(funding_amount + fee).ok_or_else(|| {
+                Error::Farcaster(format!(
+                    "Received {} on the funding address, {} required",
+                    received,
+                    funding_amount + fee
+                ))
+            })?;
+            let mut tx_outputs = vec![bitcoin::TxOut {
+                value: funding_amount.as_sat(),
+                script_pubkey: funding_address.script_pubkey(),
+            }];
+            // below the dust value of the refund output the excess is left to
+            // the fee
+            let refund_script = target_bitcoin_address.script_pubkey();
+            let refunded = if excess >= refund_script.dust_value() {
+                tx_outputs.push(bitcoin::TxOut {
+                    value: excess.as_sat(),
+                    script_pubkey: refund_script,
+                });
+                Some(excess)
+            } else {
+                None
+            };
+            let unsigned_tx = bitcoin::Transaction {
+                version: 2,
+                lock_time: 0,
+                input: outputs
+                    .iter()
+                    .map(|output| bitcoin::TxIn {
+                        previous_output: output.outpoint,
+                        script_sig: bitcoin::Script::default(),
+                        sequence: (1 << 31) as u32,
+                        witness: bitcoin::Witness::new(),
+                    })
+                    .collect(),
+                output: tx_outputs,
+            };
+            let mut psbt =
+                bitcoin::util::psbt::PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+                    .map_err(|_| Error::Syncer(SyncerError::InvalidPsbt))?;
+            let sk = bitcoin::PrivateKey::new(
+                key_manager.get_or_derive_bitcoin_key(ArbitratingKeyId::Lock)?,
+                funding_address.network,
+            );
+            let values: Vec<u64> = outputs
+                .iter()
+                .map(|output| output.amount.as_sat())
+                .collect();
+            sign_p2wpkh_inputs(&mut psbt, &sk, &funding_address, &values)?;
+            Ok(ConsolidatedFunding {
+                tx: psbt.extract_tx(),
+                refunded,
+            })
+        } else {
+            error!("{} | consolidating funding requires a bob wallet", swap_id);
+            Err(Error::Farcaster(
+                "Consolidating funding requires a Bob wallet".to_string(),
+            ))
+        }
+    }
+
     pub fn process_buy_tx(
         &mut self,
         buy_tx: bitcoin::Transaction,
@@ -507,6 +689,7 @@ impl Wallet {
             adaptor_buy: Some(adaptor_buy),
             deal,
             target_monero_address,
+            target_monero_split,
             ..
         }) = self
         {
@@ -566,21 +749,25 @@ impl Wallet {
                 ServiceBus::Ctl,
                 ServiceId::Swap(swap_id),
                 ServiceId::Database,
-                BusMsg::Ctl(CtlMsg::SetAddressSecretKey(AddressSecretKey::Monero {
-                    address: corresponding_address,
-                    secret_key_info: MoneroSecretKeyInfo {
-                        swap_id: Some(swap_id),
-                        view: keypair.view.as_bytes().try_into().unwrap(),
-                        spend: keypair.spend.as_bytes().try_into().unwrap(),
-                        creation_height: monero_address_creation_height,
+                BusMsg::Ctl(CtlMsg::SetAddressSecretKey(
+                    AddressSecretKey::Monero {
+                        address: corresponding_address,
+                        secret_key_info: MoneroSecretKeyInfo {
+                            swap_id: Some(swap_id),
+                            view: keypair.view.as_bytes().try_into().unwrap(),
+                            spend: keypair.spend.as_bytes().try_into().unwrap(),
+                            creation_height: monero_address_creation_height,
+                        },
                     },
-                })),
+                    deal.parameters.network,
+                )),
             )?;
 
             let sweep_keys = SweepMoneroAddress {
                 source_view_key: view,
                 source_spend_key: spend,
                 destination_address: target_monero_address.clone(),
+                split: target_monero_split.clone(),
                 minimum_balance: deal.parameters.accordant_amount,
                 from_height: monero_address_creation_height,
             };
@@ -605,6 +792,7 @@ impl Wallet {
             adaptor_refund: Some(adaptor_refund),
             deal,
             target_monero_address,
+            target_monero_split,
             ..
         }) = self
         {
@@ -666,21 +854,25 @@ impl Wallet {
                 ServiceBus::Ctl,
                 ServiceId::Swap(swap_id),
                 ServiceId::Database,
-                BusMsg::Ctl(CtlMsg::SetAddressSecretKey(AddressSecretKey::Monero {
-                    address: corresponding_address,
-                    secret_key_info: MoneroSecretKeyInfo {
-                        swap_id: Some(swap_id),
-                        view: keypair.view.as_bytes().try_into().unwrap(),
-                        spend: keypair.spend.as_bytes().try_into().unwrap(),
-                        creation_height: monero_address_creation_height,
+                BusMsg::Ctl(CtlMsg::SetAddressSecretKey(
+                    AddressSecretKey::Monero {
+                        address: corresponding_address,
+                        secret_key_info: MoneroSecretKeyInfo {
+                            swap_id: Some(swap_id),
+                            view: keypair.view.as_bytes().try_into().unwrap(),
+                            spend: keypair.spend.as_bytes().try_into().unwrap(),
+                            creation_height: monero_address_creation_height,
+                        },
                     },
-                })),
+                    deal.parameters.network,
+                )),
             )?;
 
             let sweep_keys = SweepMoneroAddress {
                 source_view_key: view,
                 source_spend_key: spend,
                 destination_address: target_monero_address.clone(),
+                split: target_monero_split.clone(),
                 minimum_balance: deal.parameters.accordant_amount,
                 from_height: monero_address_creation_height,
             };
@@ -1218,3 +1410,192 @@ pub fn create_funding(
 pub fn funding_update(funding: &mut FundingTx, tx: bitcoin::Transaction) -> Result<(), Error> {
     funding.update(tx).map_err(Into::into)
 }
+
+/// Virtual size of the unsigned lock transaction, spending the single funding
+/// output.
+pub const LOCK_TX_VSIZE: usize = 94;
+
+/// Fee of the lock transaction at `sat_per_kvb`, added to the swap amount
+/// sent to the funding address.
+pub fn lock_fee(sat_per_kvb: u64) -> bitcoin::Amount {
+    bitcoin::Amount::from_sat(p2wpkh_signed_tx_fee(sat_per_kvb, LOCK_TX_VSIZE, 1))
+}
+
+/// Fee rate of a lock transaction paying `fee`, the inverse of `lock_fee`.
+pub fn lock_fee_rate(fee: bitcoin::Amount) -> u64 {
+    // the fee at 1 sat/vB is the signed virtual size
+    fee.as_sat() * 1000 / lock_fee(1000).as_sat()
+}
+
+/// Fee of the transaction consolidating `nr_inputs` payments received on the
+/// funding address, assuming P2WPKH inputs and two outputs.
+pub fn consolidation_fee(sat_per_kvb: u64, nr_inputs: usize) -> bitcoin::Amount {
+    // tx overhead: 11 vB, unsigned P2WPKH input: 41 vB, P2WPKH output: 31 vB
+    let unsigned_vsize = 11 + 41 * nr_inputs + 31 * 2;
+    bitcoin::Amount::from_sat(p2wpkh_signed_tx_fee(sat_per_kvb, unsigned_vsize, nr_inputs))
+}
+
+/// Amount still to be sent to the funding address after receiving `outputs`,
+/// or None if the swap is funded. Once some payments are received, the amount
+/// covers the fee for merging them with one more payment.
+pub fn missing_funding(
+    required_funding_amount: bitcoin::Amount,
+    sat_per_kvb: u64,
+    outputs: &[FundingOutput],
+) -> Option<bitcoin::Amount> {
+    if outputs.is_empty() {
+        return Some(required_funding_amount);
+    }
+    let received = outputs
+        .iter()
+        .fold(bitcoin::Amount::ZERO, |acc, output| acc + output.amount);
+    let required = required_funding_amount + consolidation_fee(sat_per_kvb, outputs.len() + 1);
+    required
+        .checked_sub(received)
+        .filter(|missing| *missing > bitcoin::Amount::ZERO)
+}
+
+/// Unsigned base64 encoded psbt paying `amount` to the funding address, inputs
+/// and change are added by the external wallet signing it.
+pub fn export_funding_psbt(
+    funding_address: &bitcoin::Address,
+    amount: bitcoin::Amount,
+) -> Result<String, Error> {
+    let unsigned_tx = bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: vec![],
+        output: vec![bitcoin::TxOut {
+            value: amount.as_sat(),
+            script_pubkey: funding_address.script_pubkey(),
+        }],
+    };
+    let psbt = bitcoin::util::psbt::PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+        .map_err(|_| Error::Syncer(SyncerError::InvalidPsbt))?;
+    Ok(base64::encode(bitcoin::consensus::serialize(&psbt)))
+}
+
+/// Extract the funding transaction from a base64 encoded psbt signed and
+/// finalized by an external wallet, paying at least `amount` to the funding
+/// address.
+pub fn import_funding_psbt(
+    psbt: &str,
+    funding_address: &bitcoin::Address,
+    amount: bitcoin::Amount,
+) -> Result<bitcoin::Transaction, Error> {
+    let bytes = base64::decode(psbt.trim())
+        .map_err(|err| Error::Farcaster(format!("Invalid base64 psbt: {}", err)))?;
+    let psbt: bitcoin::util::psbt::PartiallySignedTransaction =
+        bitcoin::consensus::deserialize(&bytes)?;
+    if psbt.inputs.is_empty()
+        || psbt
+            .inputs
+            .iter()
+            .any(|input| input.final_script_witness.is_none() && input.final_script_sig.is_none())
+    {
+        return Err(Error::Farcaster(
+            "The psbt is not signed and finalized".to_string(),
+        ));
+    }
+    let tx = psbt.extract_tx();
+    let paid = tx
+        .output
+        .iter()
+        .filter(|output| output.script_pubkey == funding_address.script_pubkey())
+        .fold(bitcoin::Amount::ZERO, |acc, output| {
+            acc + bitcoin::Amount::from_sat(output.value)
+        });
+    if paid == bitcoin::Amount::ZERO {
+        return Err(Error::Farcaster(format!(
+            "The psbt does not pay to the funding address {}",
+            funding_address
+        )));
+    }
+    if paid < amount {
+        return Err(Error::Farcaster(format!(
+            "The psbt pays {} to the funding address {}, {} is required",
+            paid, funding_address, amount
+        )));
+    }
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::Secp256k1;
+
+    use super::{
+        consolidation_fee, import_funding_psbt, lock_fee, lock_fee_rate, missing_funding,
+        FundingOutput,
+    };
+
+    #[test]
+    fn lock_fee_rate_inverts_lock_fee() {
+        for sat_per_kvb in [1000, 1500, 12_345, 100_000] {
+            let fee = lock_fee(sat_per_kvb);
+            assert!(lock_fee_rate(fee) >= sat_per_kvb);
+            assert_eq!(lock_fee(lock_fee_rate(fee)), fee);
+        }
+    }
+
+    #[test]
+    fn missing_funding_covers_merging_fee() {
+        let required = bitcoin::Amount::from_sat(100_000);
+        assert_eq!(missing_funding(required, 1000, &[]), Some(required));
+        let output = |vout, sat| FundingOutput {
+            outpoint: bitcoin::OutPoint::new(bitcoin::Txid::default(), vout),
+            amount: bitcoin::Amount::from_sat(sat),
+        };
+        let outputs = vec![output(0, 40_000), output(1, 30_000)];
+        let missing = required + consolidation_fee(1000, 3) - bitcoin::Amount::from_sat(70_000);
+        assert_eq!(missing_funding(required, 1000, &outputs), Some(missing));
+        let outputs = vec![output(0, 60_000), output(1, 60_000)];
+        assert_eq!(missing_funding(required, 1000, &outputs), None);
+    }
+
+    #[test]
+    fn import_funding_psbt_checks_amount() {
+        let address = |key| {
+            let secret =
+                bitcoin::PrivateKey::from_slice(&[key; 32], bitcoin::Network::Testnet).unwrap();
+            bitcoin::Address::p2wpkh(
+                &bitcoin::PublicKey::from_private_key(&Secp256k1::new(), &secret),
+                bitcoin::Network::Testnet,
+            )
+            .unwrap()
+        };
+        let funding_address = address(1);
+        let signed_psbt = |outputs: Vec<(bitcoin::Address, u64)>| {
+            let tx = bitcoin::Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![bitcoin::TxIn::default()],
+                output: outputs
+                    .into_iter()
+                    .map(|(address, value)| bitcoin::TxOut {
+                        value,
+                        script_pubkey: address.script_pubkey(),
+                    })
+                    .collect(),
+            };
+            let mut psbt =
+                bitcoin::util::psbt::PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].final_script_witness = Some(bitcoin::Witness::from_vec(vec![vec![1]]));
+            base64::encode(bitcoin::consensus::serialize(&psbt))
+        };
+        let required = bitcoin::Amount::from_sat(100_000);
+
+        let psbt = signed_psbt(vec![
+            (funding_address.clone(), 100_000),
+            (address(2), 5_000),
+        ]);
+        assert!(import_funding_psbt(&psbt, &funding_address, required).is_ok());
+        // paying more is refunded as overfunding
+        let psbt = signed_psbt(vec![(funding_address.clone(), 120_000)]);
+        assert!(import_funding_psbt(&psbt, &funding_address, required).is_ok());
+        let psbt = signed_psbt(vec![(funding_address.clone(), 99_999)]);
+        assert!(import_funding_psbt(&psbt, &funding_address, required).is_err());
+        let psbt = signed_psbt(vec![(address(2), 100_000)]);
+        assert!(import_funding_psbt(&psbt, &funding_address, required).is_err());
+    }
+}
@@ -13,7 +13,9 @@ use crate::syncerd::runtime::SyncerdTask;
 use crate::syncerd::runtime::Synclet;
 use crate::syncerd::syncer_state::AddressTx;
 use crate::syncerd::syncer_state::SyncerState;
-use crate::syncerd::types::{AddressAddendum, Boolean, SweepAddressAddendum, Task};
+use crate::syncerd::types::{
+    AddressAddendum, Boolean, SweepAddressAddendum, SweepBitcoinAddress, Task,
+};
 use crate::syncerd::BtcAddressAddendum;
 use crate::syncerd::Event;
 use crate::syncerd::FeeEstimations;
@@ -461,6 +463,51 @@ fn p2wpkh_script_code(script: &bitcoin::Script) -> Script {
         .into_script()
 }
 
+/// Sign and finalize all the inputs of the psbt, each input spending a P2WPKH
+/// output of `source_address` with the value given at the same index in
+/// `values`.
+pub fn sign_p2wpkh_inputs(
+    psbt: &mut bitcoin::util::psbt::PartiallySignedTransaction,
+    sk: &bitcoin::PrivateKey,
+    source_address: &bitcoin::Address,
+    values: &[u64],
+) -> Result<(), Error> {
+    for (index, value) in values.iter().enumerate().take(psbt.inputs.len()) {
+        sign_p2wpkh_input(psbt, index, sk, source_address, *value)?;
+    }
+    Ok(())
+}
+
+/// Sign and finalize the input at `index` of the psbt, spending a P2WPKH
+/// output of `source_address` with `value`.
+fn sign_p2wpkh_input(
+    psbt: &mut bitcoin::util::psbt::PartiallySignedTransaction,
+    index: usize,
+    sk: &bitcoin::PrivateKey,
+    source_address: &bitcoin::Address,
+    value: u64,
+) -> Result<(), Error> {
+    let pk = bitcoin::PublicKey::from_private_key(bitcoin::secp256k1::SECP256K1, sk);
+    let input = &mut psbt.inputs[index];
+    input.witness_utxo = Some(bitcoin::TxOut {
+        value,
+        script_pubkey: source_address.script_pubkey(),
+    });
+    let script = p2wpkh_script_code(&source_address.script_pubkey());
+    input.witness_script = Some(script.clone());
+    let txin = TxInRef::new(&psbt.unsigned_tx, index);
+    let sig_hash = signature_hash(txin, &script, value, bitcoin::EcdsaSighashType::All);
+    let message = bitcoin::secp256k1::Message::from_slice(&sig_hash)?;
+    let signature = bitcoin::secp256k1::SECP256K1.sign_ecdsa(&message, &sk.inner);
+    let sig_all = bitcoin::util::ecdsa::EcdsaSig::sighash_all(signature);
+    input.partial_sigs.insert(pk, sig_all);
+    input.final_script_witness = Some(bitcoin::Witness::from_vec(vec![
+        sig_all.to_vec(),
+        pk.to_bytes(),
+    ]));
+    Ok(())
+}
+
 fn sweep_address(
     source_secret_key: bitcoin::secp256k1::SecretKey,
     source_address: bitcoin::Address,
@@ -480,7 +527,6 @@ fn sweep_address(
     }
 
     let sk = bitcoin::PrivateKey::new(source_secret_key, network);
-    let pk = bitcoin::PublicKey::from_private_key(bitcoin::secp256k1::SECP256K1, &sk);
 
     let unspent_txs = client.script_list_unspent(&source_address.script_pubkey())?;
 
@@ -542,30 +588,8 @@ fn sweep_address(
         .map_err(|_| Error::Syncer(SyncerError::InvalidPsbt))?;
     psbt.outputs[0].witness_script = Some(dest_address.script_pubkey());
 
-    // sign the inputs and collect the witness data
-    for (index, input) in psbt.inputs.iter_mut().enumerate() {
-        input.witness_utxo = Some(bitcoin::TxOut {
-            value: unspent_txs[index].value,
-            script_pubkey: source_address.script_pubkey(),
-        });
-        let script = p2wpkh_script_code(&source_address.script_pubkey());
-        input.witness_script = Some(script.clone());
-        let txin = TxInRef::new(&psbt.unsigned_tx, index);
-        let sig_hash = signature_hash(
-            txin,
-            &script,
-            unspent_txs[index].value,
-            bitcoin::EcdsaSighashType::All,
-        );
-        let message = bitcoin::secp256k1::Message::from_slice(&sig_hash)?;
-        let signature = bitcoin::secp256k1::SECP256K1.sign_ecdsa(&message, &sk.inner);
-        let sig_all = bitcoin::util::ecdsa::EcdsaSig::sighash_all(signature);
-        input.partial_sigs.insert(pk, sig_all);
-        input.final_script_witness = Some(bitcoin::Witness::from_vec(vec![
-            sig_all.to_vec(),
-            pk.to_bytes(),
-        ]));
-    }
+    let values: Vec<u64> = unspent_txs.iter().map(|unspent| unspent.value).collect();
+    sign_p2wpkh_inputs(&mut psbt, &sk, &source_address, &values)?;
     let finalized_signed_tx = psbt.extract_tx();
     let tx_hash =
         client.transaction_broadcast_raw(&bitcoin::consensus::serialize(&finalized_signed_tx))?;
@@ -573,6 +597,149 @@ fn sweep_address(
     Ok(vec![tx_hash.to_vec()])
 }
 
+/// An output spent by a batch sweep, with the key able to sign for it
+struct BatchInput {
+    outpoint: bitcoin::OutPoint,
+    value: u64,
+    sk: bitcoin::PrivateKey,
+    source_address: bitcoin::Address,
+    destination_address: bitcoin::Address,
+}
+
+/// Sweep the outputs of several addresses in a single transaction. Each
+/// destination receives the funds of its source addresses minus its share of
+/// the fee, proportional to the number of inputs it spends. Destinations left
+/// with dust are excluded from the batch.
+fn sweep_addresses_batch(
+    sweeps: &[SweepBitcoinAddress],
+    client: &Client,
+    network: bitcoin::Network,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let mut inputs = vec![];
+    for sweep in sweeps.iter() {
+        if sweep.source_address.address_type() != Some(bitcoin::AddressType::P2wpkh) {
+            warn!(
+                "Not batching the sweep of {}, only native segwit v0 addresses are supported",
+                sweep.source_address
+            );
+            continue;
+        }
+        let sk = bitcoin::PrivateKey::new(sweep.source_secret_key, network);
+        for unspent in client.script_list_unspent(&sweep.source_address.script_pubkey())? {
+            inputs.push(BatchInput {
+                outpoint: bitcoin::OutPoint {
+                    txid: unspent.tx_hash,
+                    vout: unspent.tx_pos as u32,
+                },
+                value: unspent.value,
+                sk,
+                source_address: sweep.source_address.clone(),
+                destination_address: sweep.destination_address.clone(),
+            });
+        }
+    }
+
+    // TODO (maybe): make blocks_until_confirmation or fee_btc_per_kvb configurable by user (see FeeStrategy)
+    let blocks_until_confirmation = 2;
+    let fee_sat_per_kvb = (client
+        .estimate_priority_fee(blocks_until_confirmation, blocks_until_confirmation)?
+        .high_fee
+        * 1.0e8)
+        .ceil() as u64;
+
+    loop {
+        if inputs.is_empty() {
+            debug!("No sweepable outputs detected for the batch sweep");
+            return Ok(vec![]);
+        }
+
+        // one output per destination with the total amount and number of inputs
+        let mut destinations: Vec<(bitcoin::Address, u64, usize)> = vec![];
+        for input in inputs.iter() {
+            match destinations
+                .iter_mut()
+                .find(|(address, ..)| *address == input.destination_address)
+            {
+                Some((_, amount, nr_inputs)) => {
+                    *amount += input.value;
+                    *nr_inputs += 1;
+                }
+                None => destinations.push((input.destination_address.clone(), input.value, 1)),
+            }
+        }
+
+        let mut unsigned_tx = bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: inputs
+                .iter()
+                .map(|input| bitcoin::TxIn {
+                    previous_output: input.outpoint,
+                    script_sig: bitcoin::Script::default(),
+                    sequence: (1 << 31) as u32,
+                    witness: bitcoin::Witness::new(),
+                })
+                .collect(),
+            output: destinations
+                .iter()
+                .map(|(address, amount, _)| bitcoin::TxOut {
+                    value: *amount,
+                    script_pubkey: address.script_pubkey(),
+                })
+                .collect(),
+        };
+        let fee = p2wpkh_signed_tx_fee(fee_sat_per_kvb, unsigned_tx.vsize(), inputs.len());
+
+        let mut dust = vec![];
+        for (output, (address, amount, nr_inputs)) in
+            unsigned_tx.output.iter_mut().zip(destinations.iter())
+        {
+            let fee_share = (fee as f64 * *nr_inputs as f64 / inputs.len() as f64).ceil() as u64;
+            // 546 is the dust limit for a p2pkh output, see `sweep_address`
+            match amount.checked_sub(fee_share) {
+                Some(value) if value > 546 => output.value = value,
+                _ => {
+                    warn!(
+                        "Excluding {} from the batch sweep, amount {} is too close to being dust with a fee share of {} ({} satoshi/kvb)",
+                        address, amount, fee_share, fee_sat_per_kvb,
+                    );
+                    dust.push(address.clone());
+                }
+            }
+        }
+        if !dust.is_empty() {
+            inputs.retain(|input| !dust.contains(&input.destination_address));
+            continue;
+        }
+
+        let mut psbt =
+            bitcoin::util::psbt::PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+                .map_err(|_| Error::Syncer(SyncerError::InvalidPsbt))?;
+        for (output, (address, ..)) in psbt.outputs.iter_mut().zip(destinations.iter()) {
+            output.witness_script = Some(address.script_pubkey());
+        }
+        for (index, input) in inputs.iter().enumerate() {
+            sign_p2wpkh_input(
+                &mut psbt,
+                index,
+                &input.sk,
+                &input.source_address,
+                input.value,
+            )?;
+        }
+        let finalized_signed_tx = psbt.extract_tx();
+        let tx_hash = client
+            .transaction_broadcast_raw(&bitcoin::consensus::serialize(&finalized_signed_tx))?;
+        info!(
+            "Batch swept {} outputs to {} destinations in tx {}",
+            inputs.len(),
+            destinations.len(),
+            tx_hash
+        );
+        return Ok(vec![tx_hash.to_vec()]);
+    }
+}
+
 async fn run_syncerd_bridge_event_sender(
     tx: zmq::Socket,
     mut event_rx: TokioReceiver<BridgeEvent>,
@@ -636,6 +803,11 @@ async fn run_syncerd_task_receiver(
                                 let mut state_guard = state.lock().await;
                                 state_guard.sweep_address(task, syncerd_task.source);
                             }
+                            SweepAddressAddendum::BitcoinBatch(batch) => {
+                                debug!("Sweeping {} addresses in a batch", batch.sweeps.len());
+                                let mut state_guard = state.lock().await;
+                                state_guard.sweep_address(task, syncerd_task.source);
+                            }
                             _ => {
                                 error!("Aborting sweep address task - unable to decode sweep address addendum");
                                 let mut state_guard = state.lock().await;
@@ -1102,26 +1274,34 @@ fn sweep_polling(
                     }
                     Ok(client) => {
                         for (id, sweep_address_task) in sweep_addresses.iter() {
-                            if let SweepAddressAddendum::Bitcoin(addendum) =
-                                sweep_address_task.addendum.clone()
-                            {
-                                let sweep_address_txs = sweep_address(
+                            let res = match sweep_address_task.addendum.clone() {
+                                SweepAddressAddendum::Bitcoin(addendum) => Some(sweep_address(
                                     addendum.source_secret_key,
                                     addendum.source_address,
                                     addendum.destination_address,
                                     &client,
                                     network,
-                                )
-                                .unwrap_or_else(|err| {
-                                    warn!("error polling sweep address {:?}, retrying", err);
-                                    vec![]
-                                });
+                                )),
+                                SweepAddressAddendum::BitcoinBatch(batch) => {
+                                    Some(sweep_addresses_batch(&batch.sweeps, &client, network))
+                                }
+                                SweepAddressAddendum::Monero(_) => None,
+                            };
+                            if let Some(res) = res {
+                                // an error is reported instead of nothing to sweep
+                                let (sweep_address_txs, error) = match res {
+                                    Ok(txs) => (txs, None),
+                                    Err(err) => {
+                                        warn!("error polling sweep address {:?}, retrying", err);
+                                        (vec![], Some(format!("Sweep failed: {}", err)))
+                                    }
+                                };
                                 debug!("sweep address transaction: {:?}", sweep_address_txs);
                                 let mut state_guard = state.lock().await;
                                 if !sweep_address_txs.is_empty() {
                                     state_guard.success_sweep(id, sweep_address_txs).await;
                                 } else if !sweep_address_task.retry {
-                                    state_guard.fail_sweep(id).await;
+                                    state_guard.fail_sweep(id, error).await;
                                 }
                                 drop(state_guard);
                             } else {
@@ -15,7 +15,9 @@ use crate::syncerd::runtime::Synclet;
 use crate::syncerd::syncer_state::create_set;
 use crate::syncerd::syncer_state::AddressTx;
 use crate::syncerd::syncer_state::SyncerState;
-use crate::syncerd::types::{AddressAddendum, Boolean, SweepAddressAddendum, Task};
+use crate::syncerd::types::{
+    AddressAddendum, Boolean, SweepAddressAddendum, SweepMoneroAddress, Task,
+};
 use crate::syncerd::TaskTarget;
 use crate::syncerd::TransactionBroadcasted;
 use crate::syncerd::XmrAddressAddendum;
@@ -313,17 +315,20 @@ impl MoneroRpc {
     }
 }
 
+/// Fee margin left when estimating the fee of a split sweep
+const SWEEP_SPLIT_FEE_RESERVE_PICO: u64 = 1_000_000_000;
+
 async fn sweep_address(
-    destination_address: monero::Address,
-    view: monero::PrivateKey,
-    spend: monero::PrivateKey,
-    minimum_balance: monero::Amount,
+    sweep: SweepMoneroAddress,
     network: &monero::Network,
     wallet_mutex: Arc<Mutex<monero_rpc::WalletClient>>,
-    restore_height: Option<u64>,
     wallet_dir_path: Option<PathBuf>,
 ) -> Result<Vec<Vec<u8>>, Error> {
-    let keypair = monero::KeyPair { view, spend };
+    let keypair = monero::KeyPair {
+        view: sweep.source_view_key,
+        spend: sweep.source_spend_key,
+    };
+    let restore_height = sweep.from_height;
     let password = s!(" ");
     let source_address = monero::Address::from_keypair(*network, &keypair);
     let wallet_filename = format!("sweep:{}", source_address);
@@ -358,33 +363,45 @@ async fn sweep_address(
     wallet.refresh(restore_height).await?;
     let balance = wallet.get_balance(account, addrs).await?;
     // only sweep once all the balance is unlocked
-    if balance.unlocked_balance >= minimum_balance {
-        info!(
-            "Sweeping address {} with unlocked balance {} into {}",
-            source_address.addr(),
-            balance.unlocked_balance.bright_white_bold(),
-            destination_address.addr(),
-        );
-        let sweep_args = monero_rpc::SweepAllArgs {
-            address: destination_address,
-            account_index: 0,
-            subaddr_indices: None,
-            priority: monero_rpc::TransferPriority::Default,
-            mixin: 10,
-            ring_size: 11,
-            unlock_time: 0,
-            get_tx_keys: None,
-            below_amount: None,
-            do_not_relay: None,
-            get_tx_hex: None,
-            get_tx_metadata: None,
+    if balance.unlocked_balance >= sweep.minimum_balance {
+        let tx_hashes = if sweep.split.is_empty() {
+            info!(
+                "Sweeping address {} with unlocked balance {} into {}",
+                source_address.addr(),
+                balance.unlocked_balance.bright_white_bold(),
+                sweep.destination_address.addr(),
+            );
+            let sweep_args = monero_rpc::SweepAllArgs {
+                address: sweep.destination_address,
+                account_index: 0,
+                subaddr_indices: None,
+                priority: monero_rpc::TransferPriority::Default,
+                mixin: 10,
+                ring_size: 11,
+                unlock_time: 0,
+                get_tx_keys: None,
+                below_amount: None,
+                do_not_relay: None,
+                get_tx_hex: None,
+                get_tx_metadata: None,
+            };
+            let res = wallet.sweep_all(sweep_args).await?;
+            res.tx_hash_list
+                .iter()
+                .map(|hash| hash.to_string())
+                .collect()
+        } else {
+            info!(
+                "Sweeping address {} with unlocked balance {} into {} destinations",
+                source_address.addr(),
+                balance.unlocked_balance.bright_white_bold(),
+                sweep.split.len() + 1,
+            );
+            vec![sweep_split(&wallet, &sweep, balance.unlocked_balance).await?]
         };
-        let res = wallet.sweep_all(sweep_args).await?;
-        let tx_ids: Vec<Vec<u8>> = res
-            .tx_hash_list
-            .iter()
-            .filter_map(|hash| {
-                let hash_str = hash.to_string();
+        let tx_ids: Vec<Vec<u8>> = tx_hashes
+            .into_iter()
+            .filter_map(|hash_str| {
                 info!("Sweep transaction hash: {}", hash_str.tx_hash());
                 hex::decode(hash_str).ok()
             })
@@ -430,13 +447,54 @@ async fn sweep_address(
     } else {
         debug!(
             "retrying sweep, balance not unlocked yet. Unlocked balance {:?}. Total balance {:?}. Expected balance {:?}.",
-            balance.unlocked_balance, balance.balance, minimum_balance
+            balance.unlocked_balance, balance.balance, sweep.minimum_balance
         );
         trace!("releasing sweep wallet lock");
         Ok(vec![])
     }
 }
 
+/// Pay every destination of the sweep in a single transfer. The fee is first
+/// estimated with a transfer that is not relayed, then deducted from the
+/// balance split between the destinations.
+async fn sweep_split(
+    wallet: &monero_rpc::WalletClient,
+    sweep: &SweepMoneroAddress,
+    balance: monero::Amount,
+) -> Result<String, Error> {
+    let transfer = |available: monero::Amount, do_not_relay: bool| async move {
+        let destinations: HashMap<monero::Address, monero::Amount> =
+            sweep.split_amounts(available)?.into_iter().collect();
+        for (address, amount) in destinations.iter() {
+            debug!("Sweep split of {} to {}", amount, address.addr());
+        }
+        let options = monero_rpc::TransferOptions {
+            account_index: Some(0),
+            subaddr_indices: None,
+            mixin: Some(10),
+            ring_size: Some(11),
+            unlock_time: Some(0),
+            payment_id: None,
+            do_not_relay: Some(do_not_relay),
+        };
+        wallet
+            .transfer(destinations, monero_rpc::TransferPriority::Default, options)
+            .await
+            .map_err(Error::from)
+    };
+    let reserved = balance
+        .checked_sub(monero::Amount::from_pico(SWEEP_SPLIT_FEE_RESERVE_PICO))
+        .ok_or_else(|| {
+            Error::Farcaster(format!("Balance {} is too low to split the sweep", balance))
+        })?;
+    let estimate = transfer(reserved, true).await?;
+    let available = balance.checked_sub(estimate.fee).ok_or_else(|| {
+        Error::Farcaster(format!("Balance {} is too low to pay the fee", balance))
+    })?;
+    let res = transfer(available, false).await?;
+    Ok(res.tx_hash.to_string())
+}
+
 #[derive(Default)]
 pub struct MoneroSyncer {}
 
@@ -775,13 +833,9 @@ fn sweep_polling(
                 if let SweepAddressAddendum::Monero(addendum) = sweep_address_task.addendum.clone()
                 {
                     let sweep_address_txs = sweep_address(
-                        addendum.destination_address,
-                        addendum.source_view_key,
-                        addendum.source_spend_key,
-                        addendum.minimum_balance,
+                        addendum,
                         &network,
                         Arc::clone(&wallet),
-                        addendum.from_height,
                         wallet_dir_path.clone(),
                     )
                     .await
@@ -796,7 +850,7 @@ fn sweep_polling(
                     if !sweep_address_txs.is_empty() {
                         state_guard.success_sweep(id, sweep_address_txs).await;
                     } else if !sweep_address_task.retry {
-                        state_guard.fail_sweep(id).await;
+                        state_guard.fail_sweep(id, None).await;
                     }
                     drop(state_guard);
                 }
@@ -16,7 +16,7 @@ use crate::syncerd::monero_syncer::MoneroSyncer;
 use crate::syncerd::opts::Opts;
 use crate::syncerd::*;
 use crate::CtlServer;
-use crate::{Error, LogStyle, Service, ServiceConfig, ServiceId};
+use crate::{terminate_service, Error, LogStyle, Service, ServiceConfig, ServiceId};
 
 use std::collections::HashSet;
 use std::sync::mpsc::Receiver;
@@ -60,8 +60,11 @@ pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
 
     let tx_event = ZMQ_CONTEXT.socket(zmq::PAIR)?;
     let rx_event = ZMQ_CONTEXT.socket(zmq::PAIR)?;
-    rx_event.bind("inproc://syncerdbridge")?;
-    tx_event.connect("inproc://syncerdbridge")?;
+    // unique per syncer for the syncers to run in the same process when the
+    // node is embedded
+    let bridge = format!("inproc://syncerdbridge-{}-{}", blockchain, network);
+    rx_event.bind(&bridge)?;
+    tx_event.connect(&bridge)?;
 
     let syncer: Box<dyn Synclet> = match blockchain {
         Blockchain::Monero => Box::new(MoneroSyncer::new()),
@@ -151,7 +154,13 @@ impl Runtime {
             (CtlMsg::Terminate, ServiceId::Farcasterd) => {
                 // terminate all runtimes
                 info!("Received terminate on {}", self.identity());
-                std::process::exit(0);
+                self.tx
+                    .send(SyncerdTask {
+                        task: Task::Terminate,
+                        source: source.clone(),
+                    })
+                    .unwrap_or_else(|err| warn!("Failed to terminate the syncer: {}", err));
+                terminate_service();
             }
 
             (req, source) => {
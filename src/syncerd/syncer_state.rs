@@ -16,6 +16,10 @@ use crate::service::LogStyle;
 use crate::syncerd::*;
 use hex;
 
+/// Error of the aborted sweep tasks that found nothing to sweep
+pub const NOTHING_TO_SWEEP: &str =
+    "Sweep failed, did not find any assets associated with the address";
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Hash, Display)]
 #[display(Debug)]
 pub struct InternalId(u32);
@@ -669,17 +673,15 @@ impl SyncerState {
         self.drop_lifetimes();
     }
 
-    pub async fn fail_sweep(&mut self, id: &InternalId) {
+    /// Abort the sweep task with the error, nothing to sweep by default
+    pub async fn fail_sweep(&mut self, id: &InternalId, error: Option<String>) {
         if let Some(sweep_address) = self.sweep_addresses.get(id) {
             send_event(
                 &self.tx_event,
                 &mut vec![(
                     Event::TaskAborted(TaskAborted {
                         id: vec![sweep_address.id],
-                        error: Some(
-                            "Sweep failed, did not find any assets associated with the address"
-                                .to_string(),
-                        ),
+                        error: Some(error.unwrap_or_else(|| NOTHING_TO_SWEEP.to_string())),
                     }),
                     self.tasks_sources
                         .get(id)
@@ -1124,6 +1126,7 @@ async fn syncer_state_sweep_addresses() {
                 "51qzspbPiQ9Z9Wq3hR8HRhPmVcE3URCK8b8A9ypHHzyvhigWTefCapoG1MXVZQQi7B5t4DpJYrHZyaFjHSb5QqLe8YEaBpo"
             )
             .unwrap(),
+            split: vec![],
             minimum_balance: monero::Amount::from_pico(1),
             from_height: None,
         }),
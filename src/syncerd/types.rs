@@ -6,9 +6,12 @@
 
 #[cfg(feature = "serde")]
 use serde_with::DisplayFromStr;
+use std::fmt;
+use std::str::FromStr;
 use strict_encoding::{StrictDecode, StrictEncode};
 
 use crate::bus::{info::Address, AddressSecretKey};
+use crate::Error;
 
 // The strict encoding length limit
 pub const STRICT_ENCODE_MAX_ITEMS: u16 = u16::MAX - 1;
@@ -91,6 +94,7 @@ pub struct SweepAddress {
 pub enum SweepAddressAddendum {
     Monero(SweepMoneroAddress),
     Bitcoin(SweepBitcoinAddress),
+    BitcoinBatch(SweepBitcoinBatch),
 }
 
 #[cfg_attr(feature = "serde", serde_as)]
@@ -107,11 +111,173 @@ pub struct SweepMoneroAddress {
     #[serde_as(as = "DisplayFromStr")]
     pub source_view_key: monero::PrivateKey,
     pub destination_address: monero::Address,
+    /// Destinations paid out of the swept balance, the remainder goes to
+    /// `destination_address`
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub split: Vec<SweepDestination>,
     #[serde(with = "monero::util::amount::serde::as_xmr")]
     pub minimum_balance: monero::Amount,
     pub from_height: Option<u64>,
 }
 
+impl SweepMoneroAddress {
+    /// Amount sent to each destination when sweeping `available`, the swept
+    /// balance minus the transaction fee. Every destination is paid in the
+    /// same transaction, so all outputs unlock together.
+    pub fn split_amounts(
+        &self,
+        available: monero::Amount,
+    ) -> Result<Vec<(monero::Address, monero::Amount)>, Error> {
+        let mut amounts = vec![];
+        let mut remainder = available;
+        for destination in self.split.iter() {
+            let amount = destination.share.amount(available);
+            remainder = remainder.checked_sub(amount).ok_or_else(|| {
+                Error::Farcaster(format!(
+                    "Sweep split exceeds the available balance of {}",
+                    available
+                ))
+            })?;
+            amounts.push((destination.address, amount));
+        }
+        if remainder > monero::Amount::ZERO {
+            amounts.push((self.destination_address, remainder));
+        }
+        Ok(amounts)
+    }
+}
+
+/// A destination of a Monero sweep besides the swap's target address, parsed
+/// from `<address>:<percent>%` or `<address>:<amount in XMR>`
+#[derive(Clone, Debug, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+pub struct SweepDestination {
+    pub address: monero::Address,
+    pub share: SweepShare,
+}
+
+#[derive(Clone, Copy, Debug, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+pub enum SweepShare {
+    /// Percentage of the swept balance, between 1 and 100
+    Percent(u8),
+    /// Fixed amount
+    Amount(monero::Amount),
+}
+
+impl SweepShare {
+    pub fn amount(&self, available: monero::Amount) -> monero::Amount {
+        match self {
+            SweepShare::Percent(percent) => monero::Amount::from_pico(
+                (available.as_pico() as u128 * *percent as u128 / 100) as u64,
+            ),
+            SweepShare::Amount(amount) => *amount,
+        }
+    }
+}
+
+impl fmt::Display for SweepDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.share {
+            SweepShare::Percent(percent) => write!(f, "{}:{}%", self.address, percent),
+            SweepShare::Amount(amount) => write!(
+                f,
+                "{}:{}",
+                self.address,
+                amount.to_string_in(monero::Denomination::Monero)
+            ),
+        }
+    }
+}
+
+impl FromStr for SweepDestination {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (address, share) = s.split_once(':').ok_or_else(|| {
+            Error::Farcaster(format!(
+                "Invalid sweep destination {}, expected <address>:<percent>% or <address>:<amount>",
+                s
+            ))
+        })?;
+        let address = monero::Address::from_str(address)?;
+        let share = match share.strip_suffix('%') {
+            Some(percent) => match percent.parse::<u8>() {
+                Ok(percent) if percent > 0 && percent <= 100 => SweepShare::Percent(percent),
+                _ => {
+                    return Err(Error::Farcaster(format!(
+                        "Invalid sweep percentage {}, expected a value between 1 and 100",
+                        share
+                    )))
+                }
+            },
+            None => SweepShare::Amount(monero::Amount::from_str_in(
+                share.trim_end_matches("XMR").trim(),
+                monero::Denomination::Monero,
+            )?),
+        };
+        Ok(SweepDestination { address, share })
+    }
+}
+
+/// Check a sweep split against the swap's target address and amount: all the
+/// addresses must be on the network, integrated addresses cannot be combined
+/// with other destinations, and the shares must leave part of `amount`
+/// to the target address.
+pub fn validate_sweep_split(
+    split: &[SweepDestination],
+    target: monero::Address,
+    amount: monero::Amount,
+) -> Result<(), Error> {
+    if split.is_empty() {
+        return Ok(());
+    }
+    let mut addresses = std::collections::HashSet::new();
+    addresses.insert(target);
+    for destination in split.iter() {
+        if destination.address.network != target.network {
+            return Err(Error::Farcaster(format!(
+                "Sweep destination {} is not on the {:?} network",
+                destination.address, target.network
+            )));
+        }
+        if !addresses.insert(destination.address) {
+            return Err(Error::Farcaster(format!(
+                "Sweep destination {} is used more than once",
+                destination.address
+            )));
+        }
+    }
+    if let Some(address) = addresses
+        .iter()
+        .find(|address| matches!(address.addr_type, monero::AddressType::Integrated(_)))
+    {
+        return Err(Error::Farcaster(format!(
+            "Integrated address {} cannot be combined with other sweep destinations",
+            address
+        )));
+    }
+    let percent: u32 = split
+        .iter()
+        .filter_map(|destination| match destination.share {
+            SweepShare::Percent(percent) => Some(percent as u32),
+            SweepShare::Amount(_) => None,
+        })
+        .sum();
+    let fixed = split
+        .iter()
+        .filter_map(|destination| match destination.share {
+            SweepShare::Amount(amount) => Some(amount),
+            SweepShare::Percent(_) => None,
+        })
+        .fold(monero::Amount::ZERO, |sum, amount| sum + amount);
+    let shared = SweepShare::Percent(percent.min(100) as u8).amount(amount) + fixed;
+    if percent > 100 || shared >= amount {
+        return Err(Error::Farcaster(format!(
+            "Sweep split of {}% and {} leaves nothing of {} to the target address",
+            percent, fixed, amount
+        )));
+    }
+    Ok(())
+}
+
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
 #[cfg_attr(
     feature = "serde",
@@ -125,6 +291,19 @@ pub struct SweepBitcoinAddress {
     pub destination_address: bitcoin::Address,
 }
 
+/// Sweep several bitcoin addresses in a single transaction, each source
+/// address is paid to its own destination address
+#[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(crate = "serde_crate")
+)]
+#[display(Debug)]
+pub struct SweepBitcoinBatch {
+    pub sweeps: Vec<SweepBitcoinAddress>,
+}
+
 #[derive(Clone, Debug, Display, StrictEncode, StrictDecode, Eq, PartialEq, Hash)]
 #[cfg_attr(
     feature = "serde",
@@ -442,3 +621,49 @@ pub enum Event {
     HealthResult(HealthResult),
     AddressBalance(AddressBalance),
 }
+
+#[test]
+fn sweep_split_amounts() {
+    let address = |n: u8| {
+        let keypair = monero::KeyPair {
+            view: monero::PrivateKey::from_slice(&[n; 32]).unwrap(),
+            spend: monero::PrivateKey::from_slice(&[n + 1; 32]).unwrap(),
+        };
+        monero::Address::from_keypair(monero::Network::Stagenet, &keypair)
+    };
+    let target = address(1);
+    let treasury = SweepDestination::from_str(&format!("{}:10%", address(3))).unwrap();
+    let fees = SweepDestination::from_str(&format!("{}:0.5", address(5))).unwrap();
+    assert_eq!(treasury.share, SweepShare::Percent(10));
+    assert_eq!(SweepDestination::from_str(&fees.to_string()).unwrap(), fees);
+    assert!(SweepDestination::from_str(&format!("{}:101%", address(3))).is_err());
+
+    let sweep = SweepMoneroAddress {
+        source_spend_key: monero::PrivateKey::from_slice(&[7; 32]).unwrap(),
+        source_view_key: monero::PrivateKey::from_slice(&[8; 32]).unwrap(),
+        destination_address: target,
+        split: vec![treasury.clone(), fees.clone()],
+        minimum_balance: monero::Amount::ZERO,
+        from_height: None,
+    };
+    let amounts = sweep
+        .split_amounts(monero::Amount::from_xmr(2.0).unwrap())
+        .unwrap();
+    assert_eq!(
+        amounts,
+        vec![
+            (treasury.address, monero::Amount::from_xmr(0.2).unwrap()),
+            (fees.address, monero::Amount::from_xmr(0.5).unwrap()),
+            (target, monero::Amount::from_xmr(1.3).unwrap()),
+        ]
+    );
+    assert!(sweep
+        .split_amounts(monero::Amount::from_xmr(0.5).unwrap())
+        .is_err());
+
+    let amount = monero::Amount::from_xmr(1.0).unwrap();
+    assert!(validate_sweep_split(&sweep.split, target, amount).is_ok());
+    assert!(validate_sweep_split(&[fees.clone(), fees], target, amount).is_err());
+    let all = SweepDestination::from_str(&format!("{}:100%", address(3))).unwrap();
+    assert!(validate_sweep_split(&[all], target, amount).is_err());
+}
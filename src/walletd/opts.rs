@@ -7,14 +7,32 @@
 use crate::opts::FARCASTER_KEY_FILE;
 use clap::ValueHint;
 use std::path::PathBuf;
-use std::{fs, io::Read};
+use std::{
+    fs,
+    io::{self, Read},
+};
 
 use crate::opts::TokenString;
 use bitcoin::secp256k1::{
     rand::{rngs::ThreadRng, thread_rng},
     PublicKey, Secp256k1, SecretKey,
 };
-use strict_encoding::{StrictDecode, StrictEncode};
+use bitcoin::util::bip32::{self, ChildNumber, ExtendedPrivKey};
+use farcaster_core::blockchain::Network;
+use strict_encoding::{strict_encode_list, StrictDecode, StrictEncode};
+
+/// Marks a versioned key file, the unversioned key files start with the length
+/// of the key file path
+const KEY_FILE_VERSION_MARKER: u16 = 0xffff;
+
+/// Version of the key file layout. The unversioned key files derive the swap
+/// keys of every network from the wallet seed and share one wallet counter,
+/// version 1 gives each network its own seed and wallet counter.
+const KEY_FILE_VERSION: u8 = 1;
+
+/// Purpose index of the derivation path `m/{purpose}'/{network}'` of the seeds
+/// of the test networks
+const NETWORK_SEED_PURPOSE: u32 = 1001;
 
 /// Walletd daemon; part of Farcaster Node
 #[derive(Parser, Clone, PartialEq, Eq, Debug)]
@@ -68,7 +86,7 @@ impl Counter {
 }
 
 /// Hold secret keys and seeds
-#[derive(StrictEncode, StrictDecode, Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct NodeSecrets {
     /// local key file
     pub key_file: String,
@@ -76,8 +94,65 @@ pub struct NodeSecrets {
     pub peerd_secret_key: SecretKey,
     /// seed used for deriving addresses
     pub wallet_seed: [u8; 32],
-    /// wallet last derivation index
+    /// wallet last derivation index of the mainnet swaps
     pub wallet_counter: Counter,
+    /// wallet last derivation index of the testnet swaps
+    pub testnet_wallet_counter: Counter,
+    /// wallet last derivation index of the local swaps
+    pub local_wallet_counter: Counter,
+    /// last derivation index given out by the unversioned key file, the swap
+    /// keys up to it are derived from the wallet seed on every network
+    pub legacy_wallet_counter: Counter,
+}
+
+impl StrictEncode for NodeSecrets {
+    fn strict_encode<E: io::Write>(&self, mut e: E) -> Result<usize, strict_encoding::Error> {
+        Ok(
+            strict_encode_list!(e; KEY_FILE_VERSION_MARKER, KEY_FILE_VERSION, self.key_file,
+            self.peerd_secret_key, self.wallet_seed, self.wallet_counter,
+            self.testnet_wallet_counter, self.local_wallet_counter, self.legacy_wallet_counter),
+        )
+    }
+}
+
+impl StrictDecode for NodeSecrets {
+    fn strict_decode<D: io::Read>(mut d: D) -> Result<Self, strict_encoding::Error> {
+        let first = u16::strict_decode(&mut d)?;
+        if first != KEY_FILE_VERSION_MARKER {
+            // the first bytes are the length of the key file path, all the
+            // swaps used so far derive their keys from the wallet seed
+            let key_file =
+                String::strict_decode(io::Read::chain(&first.to_le_bytes()[..], &mut d))?;
+            let peerd_secret_key = SecretKey::strict_decode(&mut d)?;
+            let wallet_seed = <[u8; 32]>::strict_decode(&mut d)?;
+            let wallet_counter = Counter::strict_decode(&mut d)?;
+            return Ok(Self {
+                key_file,
+                peerd_secret_key,
+                wallet_seed,
+                testnet_wallet_counter: wallet_counter.clone(),
+                local_wallet_counter: wallet_counter.clone(),
+                legacy_wallet_counter: wallet_counter.clone(),
+                wallet_counter,
+            });
+        }
+        let version = u8::strict_decode(&mut d)?;
+        if version > KEY_FILE_VERSION {
+            return Err(strict_encoding::Error::DataIntegrityError(format!(
+                "unsupported key file version {}",
+                version
+            )));
+        }
+        Ok(Self {
+            key_file: StrictDecode::strict_decode(&mut d)?,
+            peerd_secret_key: StrictDecode::strict_decode(&mut d)?,
+            wallet_seed: StrictDecode::strict_decode(&mut d)?,
+            wallet_counter: StrictDecode::strict_decode(&mut d)?,
+            testnet_wallet_counter: StrictDecode::strict_decode(&mut d)?,
+            local_wallet_counter: StrictDecode::strict_decode(&mut d)?,
+            legacy_wallet_counter: StrictDecode::strict_decode(&mut d)?,
+        })
+    }
 }
 
 impl NodeSecrets {
@@ -100,6 +175,9 @@ impl NodeSecrets {
                 peerd_secret_key: peer_private_key,
                 wallet_seed,
                 wallet_counter: Counter(0),
+                testnet_wallet_counter: Counter(0),
+                local_wallet_counter: Counter(0),
+                legacy_wallet_counter: Counter(0),
             };
 
             let key_file_handle = fs::File::create(&key_file).unwrap_or_else(|_| {
@@ -137,8 +215,17 @@ impl NodeSecrets {
         seed_buf
     }
 
-    pub fn increment_wallet_counter(&mut self) -> u32 {
-        self.wallet_counter.increment();
+    pub fn increment_wallet_counter(&mut self, network: Network) -> u32 {
+        let wallet_index = match network {
+            Network::Mainnet => self.wallet_counter.increment(),
+            Network::Testnet => self.testnet_wallet_counter.increment(),
+            Network::Local => self.local_wallet_counter.increment(),
+        };
+        self.save();
+        wallet_index
+    }
+
+    fn save(&self) {
         let key_file_handle = fs::File::create(&self.key_file).unwrap_or_else(|_| {
             panic!(
                 "Unable to create key file '{}'; please check that path exists",
@@ -146,13 +233,42 @@ impl NodeSecrets {
             )
         });
         self.strict_encode(key_file_handle)
-            .expect("Unable to save incremented wallet counter");
-        self.wallet_counter.0
+            .expect("Unable to save node secrets");
     }
 
     pub fn wallet_seed(&self) -> [u8; 32] {
         self.wallet_seed
     }
+
+    /// Seed the keys of the swap at the wallet index on the network are derived
+    /// from. The indexes given out by an unversioned key file keep their wallet
+    /// seed derivation.
+    pub fn swap_seed(&self, network: Network, wallet_index: u32) -> Result<[u8; 32], bip32::Error> {
+        if wallet_index <= self.legacy_wallet_counter.0 {
+            Ok(self.wallet_seed)
+        } else {
+            self.network_seed(network)
+        }
+    }
+
+    /// Seed the swap keys on the network are derived from. The mainnet swaps
+    /// use the wallet seed, the swaps on the test networks use the key derived
+    /// from it at `m/1001'/{network}'` so they never share keys with mainnet
+    /// swaps.
+    pub fn network_seed(&self, network: Network) -> Result<[u8; 32], bip32::Error> {
+        let network_index = match network {
+            Network::Mainnet => return Ok(self.wallet_seed),
+            Network::Testnet => 1,
+            Network::Local => 2,
+        };
+        let path = [
+            ChildNumber::from_hardened_idx(NETWORK_SEED_PURPOSE)?,
+            ChildNumber::from_hardened_idx(network_index)?,
+        ];
+        let master = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &self.wallet_seed)?;
+        let key = master.derive_priv(&Secp256k1::new(), &path)?;
+        Ok(key.private_key.secret_bytes())
+    }
 }
 
 impl KeyOpts {
@@ -160,3 +276,87 @@ impl KeyOpts {
         shared.process_dir(&mut self.key_file);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::hex::ToHex;
+    use bitcoin::util::bip32::DerivationPath;
+    use std::str::FromStr;
+
+    fn node_secrets() -> NodeSecrets {
+        NodeSecrets {
+            key_file: "".to_string(),
+            peerd_secret_key: SecretKey::from_slice(&[1; 32]).unwrap(),
+            wallet_seed: [2; 32],
+            wallet_counter: Counter(0),
+            testnet_wallet_counter: Counter(0),
+            local_wallet_counter: Counter(0),
+            legacy_wallet_counter: Counter(0),
+        }
+    }
+
+    #[test]
+    fn network_seed_derivation() {
+        let node_secrets = node_secrets();
+        assert_eq!(
+            node_secrets.network_seed(Network::Mainnet).unwrap(),
+            node_secrets.wallet_seed()
+        );
+        let master =
+            ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, &node_secrets.wallet_seed)
+                .unwrap();
+        for (network, path) in [
+            (Network::Testnet, "m/1001'/1'"),
+            (Network::Local, "m/1001'/2'"),
+        ] {
+            let key = master
+                .derive_priv(&Secp256k1::new(), &DerivationPath::from_str(path).unwrap())
+                .unwrap();
+            assert_eq!(
+                node_secrets.network_seed(network).unwrap(),
+                key.private_key.secret_bytes()
+            );
+        }
+        // changing the derivation would lose the keys of the running test swaps
+        assert_eq!(
+            node_secrets
+                .network_seed(Network::Testnet)
+                .unwrap()
+                .to_hex(),
+            "ffc8743745a6e82e33a43edb7db28be34eabb96f28f83cb7a300dc3a0658bfb9"
+        );
+    }
+
+    #[test]
+    fn legacy_key_file() {
+        let node_secrets = node_secrets();
+        let mut legacy = node_secrets.key_file.strict_serialize().unwrap();
+        node_secrets
+            .peerd_secret_key
+            .strict_encode(&mut legacy)
+            .unwrap();
+        node_secrets.wallet_seed.strict_encode(&mut legacy).unwrap();
+        Counter(3).strict_encode(&mut legacy).unwrap();
+        let migrated = NodeSecrets::strict_decode(&legacy[..]).unwrap();
+        for counter in [
+            &migrated.wallet_counter,
+            &migrated.testnet_wallet_counter,
+            &migrated.local_wallet_counter,
+            &migrated.legacy_wallet_counter,
+        ] {
+            assert_eq!(*counter, Counter(3));
+        }
+        // the swaps of the unversioned key file keep their keys
+        assert_eq!(
+            migrated.swap_seed(Network::Testnet, 3).unwrap(),
+            node_secrets.wallet_seed
+        );
+        assert_eq!(
+            migrated.swap_seed(Network::Testnet, 4).unwrap(),
+            node_secrets.network_seed(Network::Testnet).unwrap()
+        );
+        let encoded = migrated.strict_serialize().unwrap();
+        assert_eq!(NodeSecrets::strict_deserialize(encoded).unwrap(), migrated);
+    }
+}
@@ -9,7 +9,7 @@ use crate::bus::{
     BusMsg, ServiceBus,
 };
 
-use crate::service::Endpoints;
+use crate::service::{terminate_service, Endpoints};
 use crate::walletd::NodeSecrets;
 use crate::{CtlServer, Error, Service, ServiceConfig, ServiceId};
 
@@ -81,12 +81,22 @@ impl Runtime {
                 debug!("Received Hello from {}", source);
             }
 
+            CtlMsg::Terminate if source == ServiceId::Farcasterd => {
+                info!("Terminating {}", self.identity());
+                terminate_service();
+            }
+
             CtlMsg::CreateSwapKeys(deal, wallet_token) => {
                 if wallet_token != self.wallet_token {
                     return Err(Error::InvalidToken);
                 }
-                let wallet_index = self.node_secrets.increment_wallet_counter();
-                let key_manager = KeyManager::new(self.node_secrets.wallet_seed, wallet_index)?;
+                let network = deal.parameters.network;
+                let wallet_index = self.node_secrets.increment_wallet_counter(network);
+                let seed = self
+                    .node_secrets
+                    .swap_seed(network, wallet_index)
+                    .map_err(|err| Error::Farcaster(err.to_string()))?;
+                let key_manager = KeyManager::new(seed, wallet_index)?;
                 let swap_keys = SwapKeys {
                     key_manager: WrappedKeyManager(key_manager),
                     deal,
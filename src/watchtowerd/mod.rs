@@ -0,0 +1,17 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+#[cfg(feature = "shell")]
+mod opts;
+mod package;
+#[cfg(feature = "shell")]
+mod runtime;
+
+#[cfg(feature = "shell")]
+pub use opts::Opts;
+pub use package::WatchtowerPackage;
+#[cfg(feature = "shell")]
+pub use runtime::run;
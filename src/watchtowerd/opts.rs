@@ -0,0 +1,66 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use farcaster_core::blockchain::{Blockchain, Network};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::syncerd;
+
+/// Watchtower daemon; part of Farcaster Node
+///
+/// Standalone daemon protecting swaps while their node is offline. It watches
+/// the Bitcoin lock of each swap for which a watchtower package is given and
+/// broadcasts the pre-signed cancel, refund, or punish transactions when their
+/// timelock expires.
+#[derive(Parser, Clone, PartialEq, Eq, Debug)]
+#[clap(name = "watchtowerd", bin_name = "watchtowerd", author, version)]
+pub struct Opts {
+    /// These params can be read also from the configuration file, not just
+    /// command-line args or environment variables
+    #[clap(flatten)]
+    pub shared: crate::opts::Opts,
+
+    /// Blockchain networks to use (Mainnet, Testnet, Local)
+    #[clap(
+        short,
+        long,
+        global = true,
+        alias = "chain",
+        default_value = "Testnet",
+        parse(try_from_str = Network::from_str)
+    )]
+    pub network: Network,
+
+    /// Electrum server used to watch and broadcast Bitcoin transactions
+    #[clap(long)]
+    pub electrum_server: String,
+
+    /// Files containing a watchtower package exported with `swap-cli
+    /// watchtower-package`
+    #[clap(long = "package", required = true)]
+    pub packages: Vec<PathBuf>,
+}
+
+impl Opts {
+    pub fn process(&mut self) {
+        self.shared.process();
+    }
+
+    /// Options of the bitcoin syncer run by the watchtower
+    pub fn syncer_opts(&self) -> syncerd::Opts {
+        syncerd::Opts {
+            shared: self.shared.clone(),
+            blockchain: Blockchain::Bitcoin,
+            network: self.network,
+            electrum_server: Some(self.electrum_server.clone()),
+            monero_daemon: None,
+            monero_rpc_wallet: None,
+            monero_lws: None,
+            monero_wallet_dir_path: None,
+        }
+    }
+}
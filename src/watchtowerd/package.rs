@@ -0,0 +1,213 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::io::Cursor;
+
+use bitcoin::Txid;
+use farcaster_core::blockchain::Network;
+use farcaster_core::role::SwapRole;
+use farcaster_core::swap::SwapId;
+use farcaster_core::transaction::TxLabel;
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::swapd::{CheckpointSwapd, TemporalSafety};
+use crate::Error;
+
+/// Everything a watchtower needs to protect a swap while the node is offline:
+/// the pre-signed transactions of the cancel path and the timelocks and
+/// thresholds to broadcast them with.
+#[derive(Clone, Debug, StrictEncode, StrictDecode)]
+pub struct WatchtowerPackage {
+    pub swap_id: SwapId,
+    pub network: Network,
+    pub swap_role: SwapRole,
+    pub temporal_safety: TemporalSafety,
+    /// Cancel and Refund transactions for Bob, Cancel and Punish transactions
+    /// for Alice
+    pub txs: Vec<(TxLabel, bitcoin::Transaction)>,
+}
+
+impl WatchtowerPackage {
+    /// Build the package from the last checkpoint of the swap, fails if the
+    /// pre-signed transactions are not created yet.
+    pub fn from_checkpoint(swap_id: SwapId, checkpoint: &CheckpointSwapd) -> Result<Self, Error> {
+        let swap_role = checkpoint.deal.swap_role(&checkpoint.local_trade_role);
+        let labels = match swap_role {
+            SwapRole::Alice => [TxLabel::Cancel, TxLabel::Punish],
+            SwapRole::Bob => [TxLabel::Cancel, TxLabel::Refund],
+        };
+        let txs = labels
+            .iter()
+            .map(|label| {
+                checkpoint
+                    .txs
+                    .iter()
+                    .find(|(l, _)| l == label)
+                    .cloned()
+                    .ok_or_else(|| {
+                        Error::Farcaster(format!(
+                            "The {} transaction of swap {} is not signed yet",
+                            label, swap_id
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(WatchtowerPackage {
+            swap_id,
+            network: checkpoint.deal.parameters.network,
+            swap_role,
+            temporal_safety: checkpoint.temporal_safety.clone(),
+            txs,
+        })
+    }
+
+    pub fn tx(&self, label: TxLabel) -> Option<&bitcoin::Transaction> {
+        self.txs.iter().find(|(l, _)| *l == label).map(|(_, tx)| tx)
+    }
+
+    /// The transaction broadcast once the cancel transaction is mined
+    pub fn final_label(&self) -> TxLabel {
+        match self.swap_role {
+            SwapRole::Alice => TxLabel::Punish,
+            SwapRole::Bob => TxLabel::Refund,
+        }
+    }
+
+    /// The lock transaction is the one spent by the cancel transaction
+    pub fn lock_txid(&self) -> Result<Txid, Error> {
+        self.tx(TxLabel::Cancel)
+            .and_then(|tx| tx.input.first())
+            .map(|input| input.previous_output.txid)
+            .ok_or_else(|| Error::Farcaster("Invalid watchtower package".to_string()))
+    }
+
+    pub fn encode(&self) -> Result<String, Error> {
+        let mut encoded = vec![];
+        self.strict_encode(&mut encoded)?;
+        Ok(base64::encode(encoded))
+    }
+
+    pub fn decode(package: &str) -> Result<Self, Error> {
+        let raw = base64::decode(package.trim())
+            .map_err(|e| Error::Farcaster(format!("Invalid watchtower package: {}", e)))?;
+        let package = WatchtowerPackage::strict_decode(Cursor::new(raw))?;
+        package.lock_txid()?;
+        package.tx(package.final_label()).ok_or_else(|| {
+            Error::Farcaster(format!(
+                "Watchtower package is missing the {} transaction",
+                package.final_label()
+            ))
+        })?;
+        Ok(package)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::hashes::Hash;
+    use farcaster_core::role::TradeRole;
+    use farcaster_core::swap::btcxmr::Deal;
+    use farcaster_core::Uuid;
+
+    use super::*;
+
+    fn deal() -> Deal {
+        Deal::from_str("Deal:Cke4ftrP5A7MgLMaQZLZUMTC6TfkqUKBu1LQM2fvVdFMNR4gmBqNCsR11111uMFuZTAsNgpdK8DiK11111TB9zym113GTvtvqfD1111114A4TTF4h53Tv4MR6eS9sdDxV5JCH9xZcKejCqKShnphqndeeD11111111111111111111111111111111111111111AfZ113XRBtrLeA3t").unwrap()
+    }
+
+    fn tx(lock_time: u32) -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: 2,
+            lock_time,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::new(Txid::from_inner([7; 32]), 0),
+                ..Default::default()
+            }],
+            output: vec![],
+        }
+    }
+
+    fn checkpoint(
+        swap_role: SwapRole,
+        txs: Vec<(TxLabel, bitcoin::Transaction)>,
+    ) -> CheckpointSwapd {
+        let deal = deal();
+        let local_trade_role = if deal.parameters.maker_role == swap_role {
+            TradeRole::Maker
+        } else {
+            TradeRole::Taker
+        };
+        CheckpointSwapd {
+            state: crate::swapd::SwapStateMachine::BobBuySweeping,
+            pending_msg: None,
+            enquirer: None,
+            xmr_addr_addendum: None,
+            temporal_safety: TemporalSafety {
+                cancel_timelock: 4,
+                punish_timelock: 5,
+                race_thr: 3,
+                btc_finality_thr: 1,
+                xmr_finality_thr: 1,
+                sweep_monero_thr: 10,
+            },
+            txs,
+            txids: vec![],
+            pending_broadcasts: vec![],
+            local_trade_role,
+            connected_counterparty_node_id: None,
+            deal,
+            monero_address_creation_height: None,
+            funding_txids: vec![],
+            funding_sweep: None,
+            late_funding_pending: false,
+            pending_sweep: None,
+            timeline: vec![],
+            state_entered: None,
+        }
+    }
+
+    #[test]
+    fn package_from_checkpoint() {
+        let swap_id = SwapId(Uuid::new());
+        let txs = vec![
+            (TxLabel::Lock, tx(1)),
+            (TxLabel::Cancel, tx(2)),
+            (TxLabel::Refund, tx(3)),
+            (TxLabel::Punish, tx(4)),
+        ];
+
+        // Bob keeps the cancel and refund transactions
+        let package =
+            WatchtowerPackage::from_checkpoint(swap_id, &checkpoint(SwapRole::Bob, txs.clone()))
+                .unwrap();
+        assert_eq!(package.swap_role, SwapRole::Bob);
+        assert_eq!(package.network, deal().parameters.network);
+        assert_eq!(package.txs, vec![txs[1].clone(), txs[2].clone()]);
+        assert_eq!(package.final_label(), TxLabel::Refund);
+        assert_eq!(package.lock_txid().unwrap(), Txid::from_inner([7; 32]));
+
+        // Alice keeps the cancel and punish transactions
+        let package =
+            WatchtowerPackage::from_checkpoint(swap_id, &checkpoint(SwapRole::Alice, txs.clone()))
+                .unwrap();
+        assert_eq!(package.swap_role, SwapRole::Alice);
+        assert_eq!(package.txs, vec![txs[1].clone(), txs[3].clone()]);
+        assert_eq!(package.final_label(), TxLabel::Punish);
+
+        // the package round-trips its encoding
+        let decoded = WatchtowerPackage::decode(&package.encode().unwrap()).unwrap();
+        assert_eq!(decoded.swap_id, swap_id);
+        assert_eq!(decoded.txs, package.txs);
+
+        // the package is not built before the transactions are signed
+        let unsigned = checkpoint(SwapRole::Bob, vec![txs[0].clone(), txs[1].clone()]);
+        assert!(WatchtowerPackage::from_checkpoint(swap_id, &unsigned).is_err());
+        let unsigned = checkpoint(SwapRole::Alice, vec![txs[1].clone(), txs[2].clone()]);
+        assert!(WatchtowerPackage::from_checkpoint(swap_id, &unsigned).is_err());
+    }
+}
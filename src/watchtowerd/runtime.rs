@@ -0,0 +1,515 @@
+// Copyright 2020-2022 Farcaster Devs & LNP/BP Standards Association
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{Receiver, Sender};
+
+use farcaster_core::blockchain::Blockchain;
+use farcaster_core::swap::SwapId;
+use farcaster_core::transaction::TxLabel;
+use microservices::esb::{self, Handler};
+use microservices::ZMQ_CONTEXT;
+
+use crate::bus::{ctl::CtlMsg, sync::SyncMsg, BusMsg, ServiceBus};
+use crate::service::Endpoints;
+use crate::syncerd::bitcoin_syncer::BitcoinSyncer;
+use crate::syncerd::runtime::{SyncerdTask, Synclet};
+use crate::syncerd::{
+    Abort, Boolean, BroadcastTransaction, Event, Task, TaskId, TaskTarget, TransactionBroadcasted,
+    TransactionConfirmations, WatchTransaction,
+};
+use crate::watchtowerd::{Opts, WatchtowerPackage};
+use crate::{Error, LogStyle, Service, ServiceConfig, ServiceId};
+
+pub fn run(config: ServiceConfig, opts: Opts) -> Result<(), Error> {
+    let mut swaps = HashMap::new();
+    for path in &opts.packages {
+        let package = WatchtowerPackage::decode(&std::fs::read_to_string(path)?)?;
+        if package.network != opts.network {
+            return Err(Error::Farcaster(format!(
+                "Watchtower package {} is for {}, watchtowerd runs on {}",
+                path.display(),
+                package.network,
+                opts.network
+            )));
+        }
+        info!(
+            "{} | Loaded watchtower package as {}",
+            package.swap_id.swap_id(),
+            package.swap_role.label()
+        );
+        swaps.insert(package.swap_id, WatchedSwap::new(package));
+    }
+
+    let (tx, rx): (Sender<SyncerdTask>, Receiver<SyncerdTask>) = std::sync::mpsc::channel();
+    let tx_event = ZMQ_CONTEXT.socket(zmq::PAIR)?;
+    let rx_event = ZMQ_CONTEXT.socket(zmq::PAIR)?;
+    rx_event.bind("inproc://watchtowerdbridge")?;
+    tx_event.connect("inproc://watchtowerdbridge")?;
+
+    let mut runtime = Runtime {
+        identity: ServiceId::Watchtower,
+        tx,
+        task_counter: 0,
+        tasks: none!(),
+        swaps,
+    };
+    BitcoinSyncer::new().run(
+        rx,
+        tx_event,
+        runtime.identity().into(),
+        &opts.syncer_opts(),
+        opts.network,
+    )?;
+    runtime.watch_swaps()?;
+
+    // the watchtower runs without farcasterd, it is the broker of its own bus
+    let mut service = Service::broker(config, runtime)?;
+    service.add_bridge_service_bus(rx_event)?;
+    service.run_loop()?;
+    unreachable!()
+}
+
+/// Progress of a swap protected by the watchtower
+pub struct WatchedSwap {
+    package: WatchtowerPackage,
+    /// Transactions successfully broadcasted by the watchtower
+    broadcasted: HashSet<TxLabel>,
+    done: bool,
+}
+
+impl WatchedSwap {
+    fn new(package: WatchtowerPackage) -> Self {
+        WatchedSwap {
+            package,
+            broadcasted: none!(),
+            done: false,
+        }
+    }
+}
+
+pub struct Runtime {
+    identity: ServiceId,
+    tx: Sender<SyncerdTask>,
+    task_counter: u32,
+    tasks: HashMap<TaskId, (SwapId, TxLabel)>,
+    swaps: HashMap<SwapId, WatchedSwap>,
+}
+
+impl esb::Handler<ServiceBus> for Runtime {
+    type Request = BusMsg;
+    type Error = Error;
+
+    fn identity(&self) -> ServiceId {
+        self.identity.clone()
+    }
+
+    fn handle(
+        &mut self,
+        _endpoints: &mut Endpoints,
+        bus: ServiceBus,
+        source: ServiceId,
+        request: BusMsg,
+    ) -> Result<(), Self::Error> {
+        match (bus, request) {
+            (ServiceBus::Ctl, BusMsg::Ctl(CtlMsg::Hello)) => {
+                debug!("Service {} connected", source);
+                Ok(())
+            }
+            // Internal syncer bridge for the events of the bitcoin synclet
+            (ServiceBus::Bridge, BusMsg::Sync(SyncMsg::BridgeEvent(event))) => {
+                self.handle_event(event.event)
+            }
+            (_, request) => Err(Error::NotSupported(bus, request.to_string())),
+        }
+    }
+
+    fn handle_err(&mut self, _: &mut Endpoints, _: esb::Error<ServiceId>) -> Result<(), Error> {
+        // We do nothing and do not propagate error; it's already being reported
+        // with `error!` macro by the controller. If we propagate error here
+        // this will make whole daemon panic
+        Ok(())
+    }
+}
+
+impl Runtime {
+    fn send_task(&mut self, task: Task) -> Result<(), Error> {
+        self.tx
+            .send(SyncerdTask {
+                task,
+                source: self.identity(),
+            })
+            .map_err(|e| Error::Farcaster(e.to_string()))
+    }
+
+    fn new_taskid(&mut self) -> TaskId {
+        self.task_counter += 1;
+        TaskId(self.task_counter)
+    }
+
+    /// Watch the lock and the cancel transactions of every swap, the cancel
+    /// transaction may be broadcasted by the counterparty.
+    fn watch_swaps(&mut self) -> Result<(), Error> {
+        let mut watches = vec![];
+        for (swap_id, swap) in self.swaps.iter() {
+            let cancel_txid = swap
+                .package
+                .tx(TxLabel::Cancel)
+                .map(|tx| tx.txid())
+                .expect("checked when decoding the package");
+            watches.push((*swap_id, TxLabel::Lock, swap.package.lock_txid()?));
+            watches.push((*swap_id, TxLabel::Cancel, cancel_txid));
+        }
+        for (swap_id, label, txid) in watches {
+            self.watch_tx(swap_id, label, txid)?;
+        }
+        Ok(())
+    }
+
+    fn watch_tx(
+        &mut self,
+        swap_id: SwapId,
+        label: TxLabel,
+        txid: bitcoin::Txid,
+    ) -> Result<(), Error> {
+        let id = self.new_taskid();
+        self.tasks.insert(id, (swap_id, label));
+        info!(
+            "{} | Watching {} transaction ({})",
+            swap_id.swap_id(),
+            label.label(),
+            txid.tx_hash()
+        );
+        self.send_task(Task::WatchTransaction(WatchTransaction {
+            id,
+            lifetime: u64::MAX,
+            hash: txid.to_vec(),
+            confirmation_bound: 50000,
+        }))
+    }
+
+    fn broadcast(&mut self, swap_id: SwapId, label: TxLabel) -> Result<(), Error> {
+        let swap = match self.swaps.get_mut(&swap_id) {
+            Some(swap) => swap,
+            None => return Ok(()),
+        };
+        if !swap.broadcasted.insert(label) {
+            return Ok(());
+        }
+        let tx = swap
+            .package
+            .tx(label)
+            .cloned()
+            .expect("checked when decoding the package");
+        info!(
+            "{} | Broadcasting {} transaction ({})",
+            swap_id.swap_id(),
+            label.label(),
+            tx.txid().tx_hash()
+        );
+        let id = self.new_taskid();
+        self.tasks.insert(id, (swap_id, label));
+        self.send_task(Task::BroadcastTransaction(BroadcastTransaction {
+            id,
+            tx: bitcoin::consensus::serialize(&tx),
+            broadcast_after_height: None,
+        }))
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<(), Error> {
+        match event {
+            Event::TransactionConfirmations(TransactionConfirmations {
+                id,
+                confirmations: Some(confs),
+                ..
+            }) => {
+                let (swap_id, label) = match self.tasks.get(&id) {
+                    Some(task) => *task,
+                    None => return Ok(()),
+                };
+                let swap = match self.swaps.get(&swap_id) {
+                    Some(swap) if !swap.done => swap,
+                    _ => return Ok(()),
+                };
+                let temporal_safety = &swap.package.temporal_safety;
+                let final_label = swap.package.final_label();
+                debug!(
+                    "{} | {} transaction has {} confirmations",
+                    swap_id.swap_id(),
+                    label.label(),
+                    confs
+                );
+                match label {
+                    TxLabel::Lock if temporal_safety.valid_cancel(confs) => {
+                        self.broadcast(swap_id, TxLabel::Cancel)?;
+                    }
+                    TxLabel::Cancel
+                        if final_label == TxLabel::Refund
+                            && temporal_safety.final_tx(confs, Blockchain::Bitcoin) =>
+                    {
+                        if !temporal_safety.safe_refund(confs) {
+                            warn!(
+                                "{} | Refund may race with the counterparty's punish transaction",
+                                swap_id.swap_id()
+                            );
+                        }
+                        self.broadcast(swap_id, TxLabel::Refund)?;
+                    }
+                    TxLabel::Cancel
+                        if final_label == TxLabel::Punish
+                            && temporal_safety.valid_punish(confs) =>
+                    {
+                        self.broadcast(swap_id, TxLabel::Punish)?;
+                    }
+                    label
+                        if label == final_label
+                            && temporal_safety.final_tx(confs, Blockchain::Bitcoin) =>
+                    {
+                        info!(
+                            "{} | {} transaction is final, swap is protected",
+                            swap_id.swap_id(),
+                            label.label()
+                        );
+                        self.finish(swap_id)?;
+                    }
+                    _ => {}
+                }
+            }
+
+            Event::TransactionBroadcasted(TransactionBroadcasted { id, tx, error }) => {
+                let (swap_id, label) = match self.tasks.remove(&id) {
+                    Some(task) => task,
+                    None => return Ok(()),
+                };
+                match error {
+                    None => {
+                        let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&tx)?;
+                        // the cancel transaction is already watched
+                        if label != TxLabel::Cancel {
+                            self.watch_tx(swap_id, label, tx.txid())?;
+                        }
+                    }
+                    // The counterparty may have broadcasted the cancel
+                    // transaction already, or spent the lock with the buy
+                    // transaction, keep watching the cancel transaction
+                    Some(err) if label == TxLabel::Cancel => {
+                        warn!(
+                            "{} | Failed to broadcast {} transaction: {}",
+                            swap_id.swap_id(),
+                            label.label(),
+                            err
+                        );
+                    }
+                    // The cancel output is most likely already spent by the
+                    // counterparty's refund or punish transaction
+                    Some(err) => {
+                        warn!(
+                            "{} | Failed to broadcast {} transaction: {}. The cancel output may already be spent, no further action is taken",
+                            swap_id.swap_id(),
+                            label.label(),
+                            err
+                        );
+                        self.finish(swap_id)?;
+                    }
+                }
+            }
+
+            event => {
+                trace!("Ignoring syncer event {}", event);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching the transactions of the swap
+    fn finish(&mut self, swap_id: SwapId) -> Result<(), Error> {
+        if let Some(swap) = self.swaps.get_mut(&swap_id) {
+            swap.done = true;
+        }
+        let ids: Vec<TaskId> = self
+            .tasks
+            .iter()
+            .filter(|(_, (id, _))| *id == swap_id)
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        for id in ids {
+            self.tasks.remove(&id);
+            self.send_task(Task::Abort(Abort {
+                task_target: TaskTarget::TaskId(id),
+                respond: Boolean::False,
+            }))?;
+        }
+        if self.swaps.values().all(|swap| swap.done) {
+            info!("All swaps are settled, watchtowerd has nothing left to watch");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::Hash;
+    use farcaster_core::blockchain::Network;
+    use farcaster_core::role::SwapRole;
+    use farcaster_core::Uuid;
+
+    use super::*;
+    use crate::swapd::TemporalSafety;
+
+    fn package(swap_role: SwapRole) -> WatchtowerPackage {
+        let tx = |lock_time| bitcoin::Transaction {
+            version: 2,
+            lock_time,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::new(bitcoin::Txid::from_inner([7; 32]), 0),
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let final_label = match swap_role {
+            SwapRole::Alice => TxLabel::Punish,
+            SwapRole::Bob => TxLabel::Refund,
+        };
+        WatchtowerPackage {
+            swap_id: SwapId(Uuid::new()),
+            network: Network::Testnet,
+            swap_role,
+            temporal_safety: TemporalSafety {
+                cancel_timelock: 4,
+                punish_timelock: 5,
+                race_thr: 3,
+                btc_finality_thr: 1,
+                xmr_finality_thr: 1,
+                sweep_monero_thr: 10,
+            },
+            txs: vec![(TxLabel::Cancel, tx(1)), (final_label, tx(2))],
+        }
+    }
+
+    fn runtime(package: WatchtowerPackage) -> (Runtime, Receiver<SyncerdTask>) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut runtime = Runtime {
+            identity: ServiceId::Watchtower,
+            tx,
+            task_counter: 0,
+            tasks: none!(),
+            swaps: map! { package.swap_id => WatchedSwap::new(package) },
+        };
+        runtime.watch_swaps().unwrap();
+        assert_eq!(rx.try_iter().count(), 2);
+        (runtime, rx)
+    }
+
+    /// Feed the confirmations of the watched transaction with the label
+    fn confirm(runtime: &mut Runtime, label: TxLabel, confs: u32) {
+        let id = runtime
+            .tasks
+            .iter()
+            .find(|(_, (_, l))| *l == label)
+            .map(|(id, _)| *id)
+            .unwrap();
+        runtime
+            .handle_event(Event::TransactionConfirmations(TransactionConfirmations {
+                id,
+                block: vec![],
+                confirmations: Some(confs),
+                tx: vec![],
+            }))
+            .unwrap();
+    }
+
+    /// The labels of the transactions broadcasted since the last call
+    fn broadcasted(runtime: &Runtime, rx: &Receiver<SyncerdTask>) -> Vec<TxLabel> {
+        let package = &runtime.swaps.values().next().unwrap().package;
+        rx.try_iter()
+            .filter_map(|task| match task.task {
+                Task::BroadcastTransaction(BroadcastTransaction { tx, .. }) => package
+                    .txs
+                    .iter()
+                    .find(|(_, signed)| bitcoin::consensus::serialize(signed) == tx)
+                    .map(|(label, _)| *label),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Report the pending broadcast of the transaction with the label
+    fn broadcast_result(runtime: &mut Runtime, label: TxLabel, error: Option<String>) {
+        let id = runtime
+            .tasks
+            .iter()
+            .filter(|(_, (_, l))| *l == label)
+            .map(|(id, _)| *id)
+            .max()
+            .unwrap();
+        let tx = bitcoin::consensus::serialize(
+            runtime
+                .swaps
+                .values()
+                .next()
+                .unwrap()
+                .package
+                .tx(label)
+                .unwrap(),
+        );
+        runtime
+            .handle_event(Event::TransactionBroadcasted(TransactionBroadcasted {
+                id,
+                tx,
+                error,
+            }))
+            .unwrap();
+    }
+
+    #[test]
+    fn bob_broadcasts_cancel_then_refund() {
+        let (mut runtime, rx) = runtime(package(SwapRole::Bob));
+
+        // cancel is broadcasted once the cancel timelock expires, only once
+        confirm(&mut runtime, TxLabel::Lock, 3);
+        assert!(broadcasted(&runtime, &rx).is_empty());
+        confirm(&mut runtime, TxLabel::Lock, 4);
+        assert_eq!(broadcasted(&runtime, &rx), vec![TxLabel::Cancel]);
+        confirm(&mut runtime, TxLabel::Lock, 5);
+        assert!(broadcasted(&runtime, &rx).is_empty());
+
+        // refund is broadcasted once cancel is final, then watched
+        broadcast_result(&mut runtime, TxLabel::Cancel, None);
+        confirm(&mut runtime, TxLabel::Cancel, 1);
+        assert_eq!(broadcasted(&runtime, &rx), vec![TxLabel::Refund]);
+        broadcast_result(&mut runtime, TxLabel::Refund, None);
+        assert!(matches!(
+            rx.try_recv().unwrap().task,
+            Task::WatchTransaction(_)
+        ));
+
+        // the swap is done once refund is final
+        confirm(&mut runtime, TxLabel::Refund, 1);
+        assert!(runtime.swaps.values().all(|swap| swap.done));
+        assert!(runtime.tasks.is_empty());
+        assert!(rx
+            .try_iter()
+            .all(|task| matches!(task.task, Task::Abort(_))));
+    }
+
+    #[test]
+    fn alice_broadcasts_punish() {
+        let (mut runtime, rx) = runtime(package(SwapRole::Alice));
+
+        // Alice never refunds, she punishes once the punish timelock expires
+        confirm(&mut runtime, TxLabel::Cancel, 1);
+        assert!(broadcasted(&runtime, &rx).is_empty());
+        confirm(&mut runtime, TxLabel::Cancel, 4);
+        assert!(broadcasted(&runtime, &rx).is_empty());
+        confirm(&mut runtime, TxLabel::Cancel, 5);
+        assert_eq!(broadcasted(&runtime, &rx), vec![TxLabel::Punish]);
+
+        // a failed punish means the cancel output is already spent
+        broadcast_result(&mut runtime, TxLabel::Punish, Some("spent".to_string()));
+        assert!(runtime.swaps.values().all(|swap| swap.done));
+        assert!(runtime.tasks.is_empty());
+    }
+}
@@ -87,10 +87,14 @@ async fn grpc_server_functional_test() {
         maker_role: farcaster::SwapRole::Bob.into(),
         public_ip_addr: "127.0.0.1".to_string(),
         public_port: 7067,
+        alternate_addrs: vec![],
+        accordant_split: vec![],
+        deal_expiry: None,
+        deal_range: None,
     };
     let request = tonic::Request::new(make_request.clone());
     let response = farcaster_client_1.make(request).await;
-    let MakeResponse { id, deal } = response.unwrap().into_inner();
+    let MakeResponse { id, deal, .. } = response.unwrap().into_inner();
     assert_eq!(id, 3);
 
     // Test revoke deal
@@ -104,7 +108,7 @@ async fn grpc_server_functional_test() {
     // Test make another deal
     let request = tonic::Request::new(make_request.clone());
     let response = farcaster_client_1.make(request).await;
-    let MakeResponse { id, deal } = response.unwrap().into_inner();
+    let MakeResponse { id, deal, .. } = response.unwrap().into_inner();
     assert_eq!(id, 3);
 
     let (xmr_address, _xmr_address_wallet_name) =
@@ -134,6 +138,9 @@ async fn grpc_server_functional_test() {
         deal: deal.unwrap().encoded_deal,
         bitcoin_address: btc_address.to_string(),
         monero_address: xmr_address.to_string(),
+        alternate_addrs: vec![],
+        monero_split: vec![],
+        take_amount: None,
     };
     let request = tonic::Request::new(take_request.clone());
     let response = farcaster_client_2.take(request).await;
@@ -190,6 +197,9 @@ async fn grpc_server_functional_test() {
         deal: deal.unwrap().encoded_deal,
         bitcoin_address: btc_address.to_string(),
         monero_address: xmr_address.to_string(),
+        alternate_addrs: vec![],
+        monero_split: vec![],
+        take_amount: None,
     };
     let request = tonic::Request::new(take_request.clone());
     let response = farcaster_client_2.take(request).await;
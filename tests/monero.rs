@@ -171,6 +171,7 @@ async fn monero_syncer_sweep_test() {
                 source_spend_key,
                 source_view_key,
                 destination_address,
+                split: vec![],
                 minimum_balance: monero::Amount::from_pico(1000000000000),
                 from_height: None,
             }),